@@ -0,0 +1,162 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use iced::Point;
+
+use counter::{
+    core::{buffer::Buffer, display_map::DisplayMap, document::Document, position::{Cursor, Position}},
+    highlighter::{color_selector::ColorSelector, Highlighter, HighlighterConfig},
+    widgets::textbox_container::line_hit_test,
+};
+
+/// The lines of a screen, the range that is highlighted and drawn.
+const SCREEN_LINES: usize = 60;
+const LINE_HEIGHT: f32 = 18.0;
+const GLYPH_WIDTH: f32 = 8.4;
+
+const SNIPPET: &str = r#"/**
+ * Adds the numbers of the list that are within the range.
+ */
+pub fn sum_within(values: &[i64], range: std::ops::Range<i64>) -> i64 {
+    let mut total = 0;
+    for value in values.iter().filter(|value| range.contains(value)) {
+        // Overflows are the caller's problem
+        total += *value;
+    }
+    println!("The total is {}", total);
+    total
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+"#;
+
+/**
+ * Rust sources of a few hundred lines, of ten thousand lines and of a couple hundred thousand lines.
+ */
+fn sources() -> Vec<(&'static str, String)> {
+    vec![
+        ("small", SNIPPET.repeat(10)),
+        ("medium", SNIPPET.repeat(500)),
+        ("huge", SNIPPET.repeat(10_000)),
+    ]
+}
+
+/**
+ * Returns the line in the middle of the document, where the edits and the screen are.
+ */
+fn middle(document: &Document) -> usize {
+    document.len() / 2
+}
+
+fn viewport(document: &Document) -> std::ops::Range<usize> {
+    let start = middle(document);
+    document.get_line_bytes(start)..document.get_line_bytes(start + SCREEN_LINES)
+}
+
+fn buffer_edits(c: &mut Criterion) {
+    let mut group = c.benchmark_group("buffer_edits");
+    for (size, source) in sources() {
+        let document = Document::recovered(None, &source);
+        let position = Position::new(middle(&document), 4);
+        // Without a highlighter, only the rope is measured
+        let mut buffer = Buffer::new(document, None);
+        group.bench_function(BenchmarkId::new("insert_delete", size), |b| {
+            b.iter(|| {
+                buffer.set_cursor(Cursor(position));
+                buffer.insert("x".to_owned());
+                buffer.set_cursor(Cursor(position));
+                buffer.delete();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn reparse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reparse");
+    group.sample_size(20);
+    for (size, source) in sources() {
+        let mut document = Document::recovered(None, &source);
+        let mut config = HighlighterConfig::rust_config(document.slice_all());
+        let position = Position::new(middle(&document), 4);
+        let end = Position::new(position.line(), position.character() + 1);
+        group.bench_function(BenchmarkId::new("insert_delete", size), |b| {
+            b.iter(|| {
+                let start = document.insert(&position, "x".to_owned());
+                config.insert(start, start, position, position, 1, &document.slice_all());
+                let removed = document.delete(&position, &end).unwrap();
+                config.delete(removed.start, removed.end, position, end, &document.slice_all());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn highlight_viewport(c: &mut Criterion) {
+    let mut group = c.benchmark_group("highlight_viewport");
+    for (size, source) in sources() {
+        let document = Document::recovered(None, &source);
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        let range = viewport(&document);
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| Highlighter::new(&config, range.clone(), &document))
+        });
+    }
+    group.finish();
+}
+
+fn select_colors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("select_colors");
+    for (size, source) in sources() {
+        let document = Document::recovered(None, &source);
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        let captures = Highlighter::new(&config, viewport(&document), &document).unwrap().captures;
+        let start = middle(&document);
+        let lines: Vec<(usize, usize)> = (start..start + SCREEN_LINES)
+            .map(|line| (line, document.get_line(line).map(|text| text.len_bytes()).unwrap_or_default()))
+            .collect();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter_batched(
+                || captures.clone().into_iter(),
+                |mut highlighter| {
+                    let mut item = highlighter.next();
+                    for &(line, len) in &lines {
+                        for byte in 0..len {
+                            ColorSelector.select(&mut highlighter, &mut item, &Position::new(line, byte));
+                        }
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn hit_test(c: &mut Criterion) {
+    let mut group = c.benchmark_group("line_hit_test");
+    for (size, source) in sources() {
+        let document = Document::recovered(None, &source);
+        let display = DisplayMap::new(&document, None);
+        let top = middle(&document) as f32 * LINE_HEIGHT;
+        // A click on every line of the screen, at the start, the middle and past the end of the line
+        let points: Vec<Point> = (0..SCREEN_LINES)
+            .flat_map(|line| [0.0, 200.0, 2000.0].map(|x| Point::new(x, top + line as f32 * LINE_HEIGHT)))
+            .collect();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                for point in &points {
+                    line_hit_test(&display, &document, LINE_HEIGHT, GLYPH_WIDTH, *point);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, buffer_edits, reparse, highlight_viewport, select_colors, hit_test);
+criterion_main!(benches);
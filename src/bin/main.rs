@@ -3,5 +3,9 @@ use iced::{Settings, Application};
 
 pub fn main() -> iced::Result {
     
-    Editor::run(Settings::default())
+    // The window is closed by the editor once the session is saved
+    Editor::run(Settings {
+        exit_on_close_request: false,
+        ..Settings::default()
+    })
 }
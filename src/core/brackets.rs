@@ -0,0 +1,164 @@
+use std::ops::Range;
+
+use tree_sitter::{Node, Tree};
+
+/**
+ * The characters that are closed as they are typed, along with the character that closes them.
+ */
+pub const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+const OPENING: [&str; 3] = ["(", "[", "{"];
+const CLOSING: [&str; 3] = [")", "]", "}"];
+
+/**
+ * Returns the character that closes the character, if it opens a pair.
+ */
+pub fn closing_of(character: char) -> Option<char> {
+    PAIRS.iter().find(|(open, _)| *open == character).map(|(_, close)| *close)
+}
+
+/**
+ * Returns true if the character closes a pair.
+ */
+pub fn is_closing(character: char) -> bool {
+    PAIRS.iter().any(|(_, close)| *close == character)
+}
+
+/**
+ * Returns the bytes of the bracket at the byte, or of the one just before it, along with the bytes of the bracket
+ * that matches it.
+ *
+ * Brackets are tokens of the syntax tree, so the ones inside of strings and comments aren't matched.
+ */
+pub fn matching_bracket(tree: &Tree, byte: usize) -> Option<(Range<usize>, Range<usize>)> {
+    let bracket = bracket_at(tree, byte).or_else(|| bracket_at(tree, byte.checked_sub(1)?))?;
+    let parent = bracket.parent()?;
+    let mut cursor = parent.walk();
+    let mut siblings = parent.children(&mut cursor);
+    let pair = match OPENING.iter().position(|open| *open == bracket.kind()) {
+        Some(index) => siblings.find(|node| node.kind() == CLOSING[index] && node.start_byte() > bracket.start_byte()),
+        None => {
+            let index = CLOSING.iter().position(|close| *close == bracket.kind())?;
+            siblings.filter(|node| node.kind() == OPENING[index] && node.end_byte() <= bracket.start_byte()).last()
+        }
+    }?;
+    // A bracket the parser had to make up isn't in the text
+    if pair.is_missing() {
+        return None
+    }
+    Some((bracket.byte_range(), pair.byte_range()))
+}
+
+/**
+ * Returns the bracket token that starts at the byte, brackets inside of strings and comments aren't tokens.
+ */
+pub fn bracket_at(tree: &Tree, byte: usize) -> Option<Node<'_>> {
+    tree.root_node()
+        .descendant_for_byte_range(byte, byte + 1)
+        .filter(|node| is_bracket(*node) && node.start_byte() == byte)
+}
+
+/**
+ * Returns true if the bracket opens a pair.
+ */
+pub fn is_opening_bracket(bracket: Node) -> bool {
+    OPENING.contains(&bracket.kind())
+}
+
+fn is_bracket(node: Node) -> bool {
+    !node.is_named() && (OPENING.contains(&node.kind()) || CLOSING.contains(&node.kind()))
+}
+
+/**
+ * Returns how many pairs of brackets are around the bracket.
+ */
+pub fn bracket_depth(bracket: Node) -> usize {
+    let Some(mut inner) = bracket.parent() else {
+        return 0
+    };
+    let mut depth = 0;
+    while let Some(outer) = inner.parent() {
+        if encloses(outer, inner) {
+            depth += 1;
+        }
+        inner = outer;
+    }
+    depth
+}
+
+/**
+ * Returns true if the node is between two brackets of its parent.
+ */
+fn encloses(parent: Node, node: Node) -> bool {
+    let mut cursor = parent.walk();
+    let mut opens = false;
+    for child in parent.children(&mut cursor) {
+        if OPENING.contains(&child.kind()) && child.end_byte() <= node.start_byte() {
+            opens = true;
+        }
+        if opens && CLOSING.contains(&child.kind()) && child.start_byte() >= node.end_byte() {
+            return true
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod brackets_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{core::{buffer::Buffer, document::Document, position::Position}, highlighter::HighlighterConfig};
+
+    use super::{bracket_depth, matching_bracket};
+
+    #[test]
+    fn test_matching_bracket() {
+        let source = "fn main() {\n    call(\"(\", [1]); // )\n}\n";
+        let document = Document::recovered(None, source);
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        let tree = config.tree();
+
+        // On the bracket or just after it
+        assert_eq!(matching_bracket(tree, 10), Some((10..11, 37..38)));
+        assert_eq!(matching_bracket(tree, 38), Some((37..38, 10..11)));
+        // The brackets of strings and comments aren't matched
+        assert_eq!(matching_bracket(tree, 22), None);
+        assert_eq!(matching_bracket(tree, 35), None);
+        assert_eq!(matching_bracket(tree, 20), Some((20..21, 29..30)));
+
+        let depth = |byte: usize| bracket_depth(tree.root_node().descendant_for_byte_range(byte, byte + 1).unwrap());
+        assert_eq!(depth(10), 0);
+        assert_eq!(depth(20), 1);
+        assert_eq!(depth(26), 2);
+    }
+
+    #[test]
+    fn test_pairs_are_closed() {
+        let mut buffer = Buffer::new(Document::recovered(None, "let x = y;\n"), None);
+        buffer.cursor.0 = Position::new(0, 8);
+        buffer.selection.set_start(Position::new(0, 8));
+        buffer.selection.set_end(Position::new(0, 9));
+
+        // The selection is surrounded and stays selected
+        buffer.insert("(".to_owned());
+        assert_eq!(buffer.get_string(), "let x = (y);\n");
+        assert_eq!((*buffer.selection.start(), *buffer.selection.end()), (Position::new(0, 9), Position::new(0, 10)));
+
+        // Closed before a closing bracket, the closing character is stepped over
+        buffer.selection.clear(&mut buffer.cursor);
+        buffer.cursor.0 = Position::new(0, 10);
+        buffer.insert("[".to_owned());
+        buffer.insert("\"".to_owned());
+        assert_eq!(buffer.get_string(), "let x = (y[\"\"]);\n");
+        buffer.insert("\"".to_owned());
+        buffer.insert("]".to_owned());
+        buffer.insert(")".to_owned());
+        assert_eq!(buffer.get_string(), "let x = (y[\"\"]);\n");
+        assert_eq!(buffer.cursor.0, Position::new(0, 15));
+
+        // Quotes aren't closed in the middle of a word
+        buffer.cursor.0 = Position::new(0, 5);
+        buffer.insert("'".to_owned());
+        assert_eq!(buffer.get_string(), "let x' = (y[\"\"]);\n");
+    }
+}
@@ -1,54 +1,168 @@
 
 
+use std::{cell::{Ref, RefCell}, collections::HashMap, rc::Rc};
+
 use iced::{widget::text, clipboard, Command};
-use ropey::iter::Lines;
 
-use crate::{highlighter::{HighlighterConfig, Highlighter}, Message, lsp::response::{ClientDiagnostics, Issue}};
+use tree_sitter::{InputEdit, Point};
+use unicode_segmentation::UnicodeSegmentation;
 
-use super::{document::{ByteRange, Document}, document_change::DocumentChange, position::{Cursor, Position}, selection::{Range, Selection}, window::{MoveDirectionX, MoveDirectionY, VirtualWindow}};
+use crate::{highlighter::{HighlighterConfig, Highlighter}, Message, lsp::{encoding::PositionEncoding, response::{ClientDiagnostics, Issue}}};
 
+use crate::lsp::encoding::ChangeRange;
 
-pub struct Buffer {
+use super::{brackets::{closing_of, is_closing, matching_bracket}, clipboard::Clip, comment::CommentTokens, history::{end_of, Edit, History}, display_map::{DisplayMap, VisualRow}, indent::{leading_whitespace, IndentStyle, LineBreak}, motion::{next_word_end, previous_word_start, word_at, WordKind}, folding::{syntax_folds, FoldRange, Folds}, document::{ByteRange, Document, SaveJob}, error::DocumentResult, file_format::{FileFormat, SaveOptions}, document_change::DocumentChange, position::{Cursor, Position}, selection::{enclosing_node, ExpandHistory, Range, Selection}, window::{MoveDirectionX, MoveDirectionY, VirtualWindow}};
+
+
+/**
+ * The parts of a buffer that every pane showing the document shares: an edit made in one pane shows in the others
+ * and can be undone from any of them.
+ */
+struct SharedBuffer {
     document: Document,
-    highlighter: HighlighterConfig,
+    /// `None` for a large file, it isn't parsed.
+    highlighter: Option<HighlighterConfig>,
+    /// The edits that can be undone.
+    history: History,
+    /// The ranges that can be folded, taken from the syntax tree or sent by a language server.
+    folds: Folds,
+    /// How the document is indented, found from its content when it's opened.
+    indent: IndentStyle,
+    /// The diagnostics of the document, keyed by the id of the server that published them.
+    diagnostics: HashMap<usize, ClientDiagnostics>,
+    /// Edits that haven't been sent to the language servers yet.
+    changes: Vec<DocumentChange>,
+    /// Counts the edits and the changes to the folds, the panes that didn't make them build their rows again.
+    revision: usize,
+}
+
+pub struct Buffer {
+    shared: Rc<RefCell<SharedBuffer>>,
+    /// The revision of the shared buffer the rows were built for.
+    revision: usize,
     pub selection: Selection,
+    /// The selections the selection was grown from, it shrinks back through them.
+    expand_history: ExpandHistory,
     pub cursor: Cursor,
     pub window: VirtualWindow,
-    diagnostics: Option<ClientDiagnostics>
+    /// The rows of the screen the lines are drawn on, the window counts its lines in these rows.
+    display: DisplayMap,
 }
 
 impl Buffer {
 
-    pub fn new(buffer: Document, tree: HighlighterConfig) -> Self {
+    pub fn new(buffer: Document, tree: Option<HighlighterConfig>) -> Self {
         let height = text::LineHeight::default().to_absolute(iced::Pixels(14.0));
-        Self { 
+        let mut folds = Folds::default();
+        if let Some(config) = tree.as_ref() {
+            folds.set_ranges(syntax_folds(config.tree()), false);
+        }
+        let display = DisplayMap::new(&buffer, None);
+        let shared = SharedBuffer {
+            indent: IndentStyle::detect(&buffer),
             document: buffer, 
             highlighter: tree, 
+            folds,
+            history: History::default(),
+            diagnostics: HashMap::new(),
+            changes: Vec::new(),
+            revision: 0,
+        };
+        Self { 
+            shared: Rc::new(RefCell::new(shared)),
+            revision: 0,
+            display,
             cursor: Cursor::default(), 
             window: VirtualWindow::new().set_lineheight(height.0),
             selection: Selection::default(),
-            diagnostics: None
+            expand_history: ExpandHistory::default(),
+        }
+    }
+
+    /**
+     * Opens the Rust source along with its syntax tree, for the tests.
+     */
+    #[cfg(test)]
+    pub fn from_source(source: &str) -> Self {
+        let document = Document::recovered(None, source);
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        Self::new(document, Some(config))
+    }
+
+    /**
+     * Returns another view of the same document, for a new pane. It starts where this one is, and shares the
+     * document, its highlighter and its history with it.
+     */
+    pub fn split(&self) -> Self {
+        Self {
+            shared: Rc::clone(&self.shared),
+            revision: self.revision,
+            selection: self.selection,
+            expand_history: ExpandHistory::default(),
+            cursor: self.cursor,
+            window: self.window,
+            display: self.display.clone(),
+        }
+    }
+
+    /**
+     * Whether both buffers are views of the same document.
+     */
+    pub fn shares_document(&self, other: &Buffer) -> bool {
+        Rc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /**
+     * Builds the rows again once the document was edited or folded from another pane, keeping the cursor and the
+     * selection within it. Returns false if nothing changed.
+     */
+    pub fn sync(&mut self) -> bool {
+        let revision = self.shared.borrow().revision;
+        if revision == self.revision {
+            return false
         }
+        self.revision = revision;
+        self.expand_history.clear();
+        self.display = DisplayMap::new(&self.shared.borrow().document, self.display.wrap_width());
+        self.clamp_cursor();
+        self.hide_folded();
+        true
+    }
+
+    /**
+     * Notes a change to the document or to its folds that this buffer already made its rows match.
+     */
+    fn bump_revision(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.revision += 1;
+        self.revision = shared.revision;
     }
 
     pub fn diagnostic_are_in_position(&self, pos: Position) -> Option<Issue>{
-        if let Some(diagnostic) = &self.diagnostics {
-            return diagnostic.diagnostic_in_position(pos)
-        };
-        None
+        self.shared.borrow().diagnostics.values().find_map(|diagnostic| diagnostic.diagnostic_in_position(pos))
     }
 
     /**
      * Adds the diagnostic to the buffer if the paths match.
+     * 
+     * Each server publishes the full list of its diagnostics, so they replace the previous ones of
+     * the same server and are kept alongside the diagnostics of the other servers.
      */
-    pub fn add_diagnostics(&mut self, diagonostic: ClientDiagnostics) {
-        if let Some(uri) = self.document.uri() {
-            if uri == &diagonostic.uri {
-                self.diagnostics = Some(diagonostic)
-            }
+    pub fn add_diagnostics(&mut self, server_id: usize, mut diagonostic: ClientDiagnostics, encoding: PositionEncoding) {
+        let mut shared = self.shared.borrow_mut();
+        if shared.document.uri().is_some_and(|uri| uri == &diagonostic.uri) {
+            diagonostic.resolve(&shared.document, encoding);
+            shared.diagnostics.insert(server_id, diagonostic);
         }
     }
 
+    /**
+     * Removes the diagnostics of a server, used when the server disconnects.
+     */
+    pub fn remove_diagnostics(&mut self, server_id: usize) {
+        self.shared.borrow_mut().diagnostics.remove(&server_id);
+    }
+
     /**
      * Searches for an issues that is within the range of the positions
      * 
@@ -56,29 +170,96 @@ impl Buffer {
      */
     pub fn find_diagnostic(&self, pos: Option<Position>) -> Option<Issue> {
         let pos = pos.unwrap_or(self.cursor.0);
-        if let Some(diagnostics) = &self.diagnostics {
-            let issue = diagnostics
-                .clone()
-                .issues
-                .into_iter()
-                .find(|issue| {
-                    issue.range.pos_in_range(pos)
-                });
-            return issue
+        self.shared.borrow().diagnostics
+            .values()
+            .flat_map(|diagnostics| diagnostics.issues.iter())
+            .find(|issue| {
+                issue.range.pos_in_range(pos)
+            })
+            .cloned()
+    }
+
+    pub fn filename(&self) -> Option<String> {
+        self.shared.borrow().document.filename().cloned()
+    }
+
+    /**
+     * Returns the edits made since the last call, oldest first.
+     */
+    pub fn take_changes(&mut self) -> Vec<DocumentChange> {
+        std::mem::take(&mut self.shared.borrow_mut().changes)
+    }
+
+    fn add_change(&mut self, range: Range, text_range: ChangeRange, byte_range: ByteRange, text: String) -> Option<DocumentChange> {
+        let mut shared = self.shared.borrow_mut();
+        let filename = shared.document.filename()?.clone();
+        let change = DocumentChange::new(range, text_range, byte_range, text, filename, shared.document.version());
+        shared.changes.push(change.clone());
+        Some(change)
+    }
+
+    pub fn set_file(&mut self, filename: &str) {
+        self.shared.borrow_mut().document.set_file(filename);
+    }
+
+    /**
+     * Returns what saving the document writes, after trimming its trailing whitespace or adding a final newline if
+     * the options ask for it.
+     */
+    pub fn save_job(&mut self, workspace: Option<String>, options: SaveOptions) -> DocumentResult<SaveJob> {
+        let edits = self.shared.borrow().document.save_edits(options);
+        if !edits.is_empty() {
+            self.apply_edits(edits, PositionEncoding::Utf32);
+            self.clamp_cursor();
         }
-        None
+        self.shared.borrow_mut().document.save_job(workspace)
+    }
+
+    pub fn saved(&mut self, filename: &str, version: i32) {
+        self.shared.borrow_mut().document.saved(filename, version);
     }
 
-    pub fn filename(&self) -> Option<&String> {
-        self.document.filename()
+    pub fn format(&self) -> FileFormat {
+        self.shared.borrow().document.format()
     }
 
-    pub fn save(&mut self, workspace: Option<String>) {
-        self.document.save(workspace).unwrap();
+    pub fn set_format(&mut self, format: FileFormat) {
+        self.shared.borrow_mut().document.set_format(format);
     }
 
-    pub fn buffer(&self) -> &Document {
-        &self.document
+    pub fn has_final_newline(&self) -> bool {
+        self.shared.borrow().document.has_final_newline()
+    }
+
+    /**
+     * Adds or removes the newline at the end of the document.
+     */
+    pub fn set_final_newline(&mut self, has_final_newline: bool) {
+        let edits = self.shared.borrow().document.final_newline_edits(has_final_newline);
+        self.apply_edits(edits, PositionEncoding::Utf32);
+        self.clamp_cursor();
+    }
+
+    /**
+     * Keeps the cursor and the selection within the document once text was removed around them.
+     */
+    fn clamp_cursor(&mut self) {
+        let clamp = |document: &Document, position: Position| {
+            let line = position.line().min(document.len().saturating_sub(1));
+            // Every line but the last one ends with a newline
+            let has_newline = line + 1 < document.len();
+            let line_len = document.line_len(line) - usize::from(has_newline);
+            Position::new(line, position.character().min(line_len))
+        };
+        let shared = self.shared.borrow();
+        self.cursor = Cursor(clamp(&shared.document, self.cursor.0));
+        let (start, end) = (*self.selection.start(), *self.selection.end());
+        self.selection.set_start(clamp(&shared.document, start));
+        self.selection.set_end(clamp(&shared.document, end));
+    }
+
+    pub fn buffer(&self) -> Ref<'_, Document> {
+        Ref::map(self.shared.borrow(), |shared| &shared.document)
     }
 
     pub fn set_window(&mut self, window: VirtualWindow) {
@@ -86,18 +267,18 @@ impl Buffer {
     }
 
     pub fn len(&self) -> usize {
-        self.document.len()
+        self.shared.borrow().document.len()
     }
     
     pub fn is_saved(&self) -> bool {
-        self.document.is_saved()
+        self.shared.borrow().document.is_saved()
     }
 
     /**
      * Get's the length of characters within the line
      */
     pub fn line_len(&self, line: usize) -> usize {
-        self.document.line_len(line)
+        self.shared.borrow().document.line_len(line)
     }
 
     /** 
@@ -107,120 +288,890 @@ impl Buffer {
         self.cursor = cursor
     }
 
-    pub fn get_highlighter(&self) -> Option<Highlighter> {
-        let start_line = self.window.padded_start_line();
-        let start_line = self.document.get_line_bytes(start_line);
+    /**
+     * Returns the captures of the visible lines, with the brackets colored by their depth if `rainbow_brackets` is set.
+     */
+    pub fn get_highlighter(&self, rainbow_brackets: bool) -> Option<Highlighter> {
+        let rows = self.visible_rows();
+        let start_line = self.display.row(rows.start).line;
+        let shared = self.shared.borrow();
+        let start_line = shared.document.get_line_bytes(start_line);
+
+        let end_line = self.display.row(rows.end).line + 1;
+        let end_line = shared.document.get_line_bytes(end_line);
 
-        let end_line = self.window.padded_end_line();
-        let end_line = self.document.get_line_bytes(end_line);
+        let config = shared.highlighter.as_ref()?;
+        let mut highlighter = Highlighter::new(config, start_line..end_line, &shared.document)?;
+        if rainbow_brackets {
+            highlighter.color_brackets(config);
+        }
+        Some(highlighter)
+    }
 
-        Highlighter::new(&self.highlighter, start_line..end_line, self.buffer())
+    /**
+     * Returns the positions of the bracket at the cursor, or just before it, and of the bracket that matches it.
+     */
+    pub fn matching_brackets(&self) -> Option<(Position, Position)> {
+        let shared = self.shared.borrow();
+        let byte = shared.document.get_byte_pos(&self.cursor.0);
+        let (bracket, pair) = matching_bracket(shared.highlighter.as_ref()?.tree(), byte)?;
+        Some((shared.document.position_of_byte(bracket.start), shared.document.position_of_byte(pair.start)))
+    }
+
+    /**
+     * Moves the cursor to the bracket that matches the one at the cursor, after it if it closes the pair.
+     */
+    pub fn jump_to_matching_bracket(&mut self) {
+        let Some((bracket, pair)) = self.matching_brackets() else {
+            return
+        };
+        self.selection.clear(&mut self.cursor);
+        self.cursor.0 = pair;
+        if pair > bracket {
+            self.cursor.0.set_character(pair.character() + 1);
+        }
+        self.selection.set_start(self.cursor.0);
+        self.selection.set_end(self.cursor.0);
     }
 
-    pub fn lines(&self)  -> Lines<'_>{
-        self.document.lines()
+    /**
+     * Returns the rows that are drawn, the ones in the window along with a few around it.
+     */
+    pub fn visible_rows(&self) -> std::ops::Range<usize> {
+        let end = self.window.padded_end_line().min(self.row_count());
+        self.window.padded_start_line().min(end)..end
+    }
+
+    /**
+     * Returns what the gutter shows for each visible row. Rows that continue a wrapped line have nothing.
+     */
+    pub fn gutter_rows(&self) -> Vec<GutterRow> {
+        self.visible_rows()
+            .map(|row| self.display.row(row))
+            .map(|row| match row.start {
+                0 => GutterRow {
+                    line: Some(row.line),
+                    fold: self.shared.borrow().folds.range_at(row.line).map(|_| self.shared.borrow().folds.is_folded(row.line)),
+                },
+                _ => GutterRow::default(),
+            })
+            .collect()
+    }
+
+    pub fn display(&self) -> &DisplayMap {
+        &self.display
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.display.row_count()
+    }
+
+    /**
+     * Wraps the lines at the number of cells, or stops wrapping them. Large files aren't wrapped.
+     *
+     * Returns whether the rows changed.
+     */
+    pub fn set_wrap_width(&mut self, width: Option<usize>) -> bool {
+        let width = width.filter(|_| !self.is_large());
+        if self.display.wrap_width() == width {
+            return false
+        }
+        self.display = DisplayMap::new(&self.shared.borrow().document, width);
+        self.display.set_hidden(self.shared.borrow().folds.hidden());
+        true
+    }
+
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.shared.borrow().folds.is_folded(line)
+    }
+
+    /**
+     * Folds the range that starts on the line, or unfolds it if it's folded.
+     */
+    pub fn toggle_fold(&mut self, line: usize) {
+        if self.shared.borrow_mut().folds.toggle(line) {
+            self.folds_changed();
+        }
+    }
+
+    /**
+     * Folds the innermost range around the cursor.
+     */
+    pub fn fold_at_cursor(&mut self) {
+        if self.shared.borrow_mut().folds.fold(self.cursor.0.line()) {
+            self.folds_changed();
+        }
+    }
+
+    /**
+     * Unfolds the range on the line of the cursor, or the innermost folded range around it.
+     */
+    pub fn unfold_at_cursor(&mut self) {
+        if self.shared.borrow_mut().folds.unfold(self.cursor.0.line()) {
+            self.folds_changed();
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.shared.borrow_mut().folds.fold_all();
+        self.folds_changed();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.shared.borrow_mut().folds.unfold_all();
+        self.folds_changed();
+    }
+
+    /**
+     * Unfolds the ranges that hide the cursor, once it was moved to a line that is folded away.
+     */
+    pub fn reveal_cursor(&mut self) {
+        if self.shared.borrow_mut().folds.reveal(self.cursor.0.line()) {
+            self.display.set_hidden(self.shared.borrow().folds.hidden());
+            self.bump_revision();
+        }
+    }
+
+    /**
+     * Uses the ranges sent by a language server instead of the ones of the syntax tree.
+     */
+    pub fn set_server_folds(&mut self, ranges: Vec<FoldRange>) {
+        self.shared.borrow_mut().folds.set_ranges(ranges, true);
+        self.folds_changed();
+    }
+
+    fn folds_changed(&mut self) {
+        self.bump_revision();
+        self.hide_folded();
+    }
+
+    /**
+     * Hides the folded lines. A cursor that was folded away is moved to the first line of its fold.
+     */
+    fn hide_folded(&mut self) {
+        self.display.set_hidden(self.shared.borrow().folds.hidden());
+        let line = self.cursor.0.line();
+        let visible = self.display.visible_line(line);
+        if visible != line {
+            self.cursor.0 = Position::new(visible, self.line_len(visible).saturating_sub(1));
+            self.selection.set_start(self.cursor.0);
+            self.selection.set_end(self.cursor.0);
+        }
+    }
+
+    /**
+     * Keeps the rows and the folds in step with an edit. The lines between the first and the last one were
+     * replaced, the highlighter has to be up to date with the edit already.
+     */
+    fn lines_edited(&mut self, first_line: usize, old_last_line: usize) {
+        self.expand_history.clear();
+        {
+            let mut shared = self.shared.borrow_mut();
+            let shared = &mut *shared;
+            let added = shared.document.len() as isize - self.display.line_count() as isize;
+            self.display.edit(&shared.document, first_line, old_last_line);
+            shared.folds.edit(first_line, old_last_line, added);
+            if let Some(highlighter) = shared.highlighter.as_ref().filter(|_| !shared.folds.from_server()) {
+                shared.folds.set_ranges(syntax_folds(highlighter.tree()), false);
+            }
+            self.display.set_hidden(shared.folds.hidden());
+        }
+        self.bump_revision();
+    }
+
+    /**
+     * Whether the file opened in large file mode, without highlighting or language servers.
+     */
+    pub fn is_large(&self) -> bool {
+        self.shared.borrow().highlighter.is_none()
+    }
+
+    /**
+     * Returns the number of cells of the longest row, wrapped lines are never longer than the wrap width.
+     */
+    pub fn longest_line(&self) -> usize {
+        match self.display.wrap_width() {
+            Some(width) => self.shared.borrow().document.longest_line().min(width),
+            None => self.shared.borrow().document.longest_line(),
+        }
     }
 
     pub fn replace(&mut self, start_idx:&Position, end_idx:&Position, character: String) {
-        self.document.replace(start_idx, end_idx, character);
+        self.splice(*start_idx, *end_idx, character);
     }
 
     pub fn delete(&mut self) {
-        let cursor_pos = self.get_position();
-
         // If the selection is available
         if !self.selection.is_empty() {
             let selection = self.selection.correct_position();
             self.selection.clear(&mut self.cursor);
-            let bytes = self.document.delete(selection.start(), selection.end());
-            if let Some(bytes) = bytes {
-                self.character_deleted(bytes.start, bytes.end, *selection.start(), *selection.end())
-            }
+            self.splice(*selection.start(), *selection.end(), String::new());
         } 
         
         else {
-            let start = self.document.delete(&self.cursor.0, &Position::new(self.cursor.0.line(), self.cursor.0.character() + 1));
-            if let Some(value) = &start {
-                self.character_deleted(value.start, value.end, cursor_pos, cursor_pos)
-            }
+            let end = Position::new(self.cursor.0.line(), self.cursor.0.character() + 1);
+            self.splice(self.cursor.0, end, String::new());
         }
     }
 
-    fn character_deleted(&mut self, start_byte: usize, end_byte: usize, start_pos: Position, end_pos: Position) {
-        self.highlighter.delete(start_byte, end_byte, start_pos, end_pos, &self.document.slice_all())
+    /**
+     * Inserts the content at the cursor, replacing the selection.
+     *
+     * A single character that opens a pair is closed along with it, or surrounds the selection, and a closing
+     * character is stepped over if it's already next to the cursor.
+     */
+    pub fn insert(&mut self, content: String) -> Option<DocumentChange> {
+        let mut characters = content.chars();
+        let (Some(character), None) = (characters.next(), characters.next()) else {
+            return self.insert_text(content)
+        };
+        let next = self.character_at(0);
+        if self.selection.is_empty() && is_closing(character) && next == Some(character) {
+            self.cursor.move_horizontally(1);
+            return None
+        }
+        let Some(closing) = closing_of(character) else {
+            return self.insert_text(content)
+        };
+        if !self.selection.is_empty() {
+            return self.surround_selection(character, closing)
+        }
+
+        // Pairs are only closed before a space or a closing character, quotes also need to start a word
+        let closes = next.is_none_or(|next| next.is_whitespace() || is_closing(next) || ",;".contains(next));
+        let starts_word = character != closing || self.character_at(-1).is_none_or(|previous| previous.is_whitespace() || "([{=,:".contains(previous));
+        if !(closes && starts_word) {
+            return self.insert_text(content)
+        }
+        let change = self.insert_text(format!("{}{}", character, closing));
+        self.cursor.move_horizontally(-1);
+        change
     }
 
+    /**
+     * Surrounds the selection with the pair, and keeps the text inside of it selected.
+     */
+    fn surround_selection(&mut self, opening: char, closing: char) -> Option<DocumentChange> {
+        let backwards = self.selection.is_backwards();
+        let selection = self.selection.correct_position();
+        let (start, mut end) = (*selection.start(), *selection.end());
+        self.selection.clear(&mut self.cursor);
 
-    fn character_inputed(&mut self, start_byte: usize, end_byte:usize, start_pos: Position, end_pos:Position, length: usize) {
-        self.highlighter.insert(start_byte, end_byte, start_pos, end_pos, length, &self.document.slice_all())
+        // The closing character goes first so the start of the selection doesn't move
+        self.cursor.0 = end;
+        self.insert_text(closing.to_string());
+        self.cursor.0 = start;
+        let change = self.insert_text(opening.to_string());
+        if end.line() == start.line() {
+            end.set_character(end.character() + 1);
+        }
+        let inner = Range::new(Position::new(start.line(), start.character() + 1), end);
+        let inner = if backwards { Range::new(inner.end(), inner.start()) } else { inner };
+        self.selection.set_start(inner.start());
+        self.selection.set_end(inner.end());
+        self.cursor.0 = inner.end();
+        change
     }
 
-    pub fn insert(&mut self, content: String) -> Option<DocumentChange> {
-        let cursor_pos = self.get_position();
-        // If the selection is available
-        if !self.selection.is_empty() {
+    /**
+     * Returns the character at the offset from the cursor, `0` being the one right after it.
+     */
+    fn character_at(&self, offset: isize) -> Option<char> {
+        let shared = self.shared.borrow();
+        let rope = shared.document.slice_all();
+        let index = shared.document.get_character_pos(&self.cursor.0).checked_add_signed(offset)?;
+        rope.get_char(index)
+    }
+
+    fn insert_text(&mut self, content: String) -> Option<DocumentChange> {
+        // The selection is replaced, or the content goes at the cursor
+        let (start, end) = if self.selection.is_empty() {
+            (self.cursor.0, self.cursor.0)
+        } else {
             let selection = self.selection.correct_position();
             self.selection.clear(&mut self.cursor);
+            (*selection.start(), *selection.end())
+        };
+        self.cursor.0 = end_of(start, &content);
+        self.splice(start, end, content)
+    }
 
-            let string_len = content.len();
-            let bytes = self.document.replace(selection.start(), selection.end(), content.clone());
-            if let Some(bytes) = bytes {
-                self.character_inputed(bytes.start, bytes.end, *selection.start(), *selection.end(), string_len);
-                self.cursor.move_to_end_of_insert(content.clone());
-                if let Some(filename) = self.document.filename() {
-                    return Some(DocumentChange::new(selection.into(), bytes, content, filename.clone()));
-                }
+    /**
+     * Applies the edits sent by a server, keeping the highlighter and the servers up to date.
+     */
+    pub fn apply_edits(&mut self, edits: Vec<lsp_types::TextEdit>, encoding: PositionEncoding) {
+        let edits = self.shared.borrow().document.resolve_edits(edits, encoding);
+        for (start, end, text) in edits.into_iter().rev() {
+            self.splice(start, end, text);
+        }
+    }
+
+    /**
+     * Replaces the text between the positions, keeping the highlighter and the servers up to date, and records
+     * the edit so it can be undone. The cursor and the selection are left where they are.
+     */
+    fn splice(&mut self, start: Position, end: Position, text: String) -> Option<DocumentChange> {
+        let (edit, change) = self.replace_text(start, end, text)?;
+        self.shared.borrow_mut().history.record(edit);
+        change
+    }
+
+    /**
+     * Replaces the text between the positions without recording it. Returns the edit that was made, along with
+     * the change sent to the servers, or `None` if nothing changed.
+     */
+    fn replace_text(&mut self, start: Position, end: Position, text: String) -> Option<(Edit, Option<DocumentChange>)> {
+        let mut guard = self.shared.borrow_mut();
+        let shared = &mut *guard;
+        let (start_char, end_char) = (shared.document.get_character_pos(&start), shared.document.get_character_pos(&end));
+        if start_char >= end_char && text.is_empty() {
+            return None
+        }
+        let removed = shared.document.str_from_range(start_char, end_char.max(start_char)).to_string();
+        let text_range = shared.document.change_range(&start, &end);
+        let start_byte = shared.document.get_byte_pos(&start);
+        let old_end_byte = start_byte + removed.len();
+        let (start_position, old_end_position) = (point_of_byte(&shared.document, start_byte), point_of_byte(&shared.document, old_end_byte));
+
+        shared.document.delete(&start, &end);
+        if !text.is_empty() {
+            shared.document.insert(&start, text.clone());
+        }
+        // Tree-sitter counts the columns of its points in bytes
+        let new_end_byte = start_byte + text.len();
+        let input = InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position: point_of_byte(&shared.document, new_end_byte),
+        };
+        if let Some(highlighter) = shared.highlighter.as_mut() {
+            highlighter.edit(&input, &shared.document.slice_all());
+        }
+        drop(guard);
+        let edit = Edit { start, removed, inserted: text };
+        self.lines_edited(start.line(), edit.old_end().line());
+
+        let bytes = ByteRange { start: start_byte, end: old_end_byte };
+        let change = self.add_change(Range::new(start, edit.old_end()), text_range, bytes, edit.inserted.clone());
+        Some((edit, change))
+    }
+
+    /**
+     * Ends the command that made the last edits, they're undone together.
+     */
+    pub fn seal_history(&mut self) {
+        self.shared.borrow_mut().history.seal(self.selection, self.cursor);
+    }
+
+    /**
+     * Undoes the last command that edited the buffer. Returns false if there's nothing to undo.
+     */
+    pub fn undo(&mut self) -> bool {
+        self.seal_history();
+        let Some((edits, (selection, cursor))) = self.shared.borrow_mut().history.undo() else {
+            return false
+        };
+        self.replay(edits);
+        self.selection = selection;
+        self.cursor = cursor;
+        true
+    }
+
+    /**
+     * Makes the edits of the last command that was undone again. Returns false if there's nothing to redo.
+     */
+    pub fn redo(&mut self) -> bool {
+        self.seal_history();
+        let Some((edits, (selection, cursor))) = self.shared.borrow_mut().history.redo() else {
+            return false
+        };
+        self.replay(edits);
+        self.selection = selection;
+        self.cursor = cursor;
+        true
+    }
+
+    fn replay(&mut self, edits: Vec<Edit>) {
+        for edit in edits {
+            self.replace_text(edit.start, edit.old_end(), edit.inserted);
+        }
+    }
+
+    /**
+     * Returns the text of the line without its line break.
+     */
+    pub fn line_text(&self, line: usize) -> String {
+        let text = self.shared.borrow().document.get_line(line).map(|text| text.to_string()).unwrap_or_default();
+        text.trim_end_matches(['\n', '\r']).to_owned()
+    }
+
+    /**
+     * Breaks the line at the cursor. The new line keeps the indentation of the line, one level more after an
+     * opening bracket and one less before a closing one.
+     */
+    pub fn new_line(&mut self) {
+        if !self.selection.is_empty() {
+            self.delete();
+        }
+        let cursor = self.cursor.0;
+        let line = self.line_text(cursor.line());
+        let line_start = self.shared.borrow().document.get_line_bytes(cursor.line());
+        let split = (self.shared.borrow().document.get_byte_pos(&cursor) - line_start).min(line.len());
+        let (before, after) = line.split_at(split);
+
+        // The spaces after the cursor would be left in front of the text moved to the new line
+        let spaces = leading_whitespace(after);
+        let after = &after[spaces.len()..];
+        let line_break = match self.shared.borrow().highlighter.as_ref() {
+            Some(config) => LineBreak::new(
+                config.tree(),
+                before.trim_end().char_indices().last().map(|(byte, _)| line_start + byte),
+                (!after.is_empty()).then_some(line_start + split + spaces.len()),
+            ),
+            None => LineBreak::default(),
+        };
+        let (text, indent) = line_break.text(leading_whitespace(before), self.shared.borrow().indent);
+        let end = Position::new(cursor.line(), cursor.character() + spaces.len());
+        self.splice(cursor, end, text);
+        self.cursor.0 = Position::new(cursor.line() + 1, indent.len());
+    }
+
+    /**
+     * Returns the lines the selection covers, or the line of the cursor. A selection that ends at the start of a
+     * line doesn't cover it.
+     */
+    fn selected_lines(&self) -> std::ops::RangeInclusive<usize> {
+        if self.selection.is_empty() {
+            return self.cursor.0.line()..=self.cursor.0.line()
+        }
+        let selection = self.selection.correct_position();
+        let (start, end) = (selection.start(), selection.end());
+        if end.line() > start.line() && end.character() == 0 {
+            start.line()..=end.line() - 1
+        } else {
+            start.line()..=end.line()
+        }
+    }
+
+    /**
+     * Indents every line of a selection over several lines, or inserts indentation up to the next level at the
+     * cursor.
+     */
+    pub fn indent(&mut self) {
+        if self.selection.start().line() == self.selection.end().line() {
+            let unit = match self.shared.borrow().indent {
+                IndentStyle::Tabs => "\t".to_owned(),
+                IndentStyle::Spaces(width) => " ".repeat(width - self.cursor.0.character() % width),
+            };
+            self.insert_text(unit);
+            return
+        }
+        let unit = self.shared.borrow().indent.unit();
+        for line in self.selected_lines() {
+            self.splice(Position::new(line, 0), Position::new(line, 0), unit.clone());
+        }
+        // The ends of the selection move with the text, unless they were at the start of their line
+        let lines = self.selected_lines();
+        self.move_selection(|position| if lines.contains(&position.line()) && position.character() > 0 {
+            position.character() + unit.len()
+        } else {
+            position.character()
+        });
+    }
+
+    /**
+     * Removes a level of indentation from the lines of the selection, or from the line of the cursor.
+     */
+    pub fn outdent(&mut self) {
+        let lines = self.selected_lines();
+        let mut removed = Vec::new();
+        for line in lines.clone() {
+            let text = self.line_text(line);
+            let indent = leading_whitespace(&text);
+            let (indent, level) = (indent.len(), self.shared.borrow().indent.last_level(indent));
+            if level > 0 {
+                self.splice(Position::new(line, indent - level), Position::new(line, indent), String::new());
             }
-            None
-        } 
-        
-        // Input from the cursor positions
-        else {
-            let string_len = content.len();
-            let start = self.document.insert(&self.cursor.0, content.clone());
-            self.character_inputed(start, start, cursor_pos, cursor_pos, string_len);
-            self.cursor.move_to_end_of_insert(content.clone());
-            if let Some(filename) = self.document.filename() {
-                return Some(DocumentChange::new(
-                    Range::new(cursor_pos,cursor_pos), 
-                    ByteRange{start, end: start}, 
-                    content,
-                    filename.clone()
-                ))
+            removed.push((indent, level));
+        }
+        self.move_selection(|position| {
+            let Some(&(indent, level)) = removed.get(position.line().wrapping_sub(*lines.start())) else {
+                return position.character()
+            };
+            if position.character() >= indent {
+                position.character() - level
+            } else {
+                position.character().min(indent - level)
             }
-            None
+        });
+    }
+
+    /**
+     * Moves the cursor and the ends of the selection to a new character of their line.
+     */
+    fn move_selection(&mut self, mut character: impl FnMut(Position) -> usize) {
+        let (start, end) = (*self.selection.start(), *self.selection.end());
+        self.selection.set_start(Position::new(start.line(), character(start)));
+        self.selection.set_end(Position::new(end.line(), character(end)));
+        self.cursor.0 = Position::new(self.cursor.0.line(), character(self.cursor.0));
+    }
+
+    pub fn indent_style(&self) -> IndentStyle {
+        self.shared.borrow().indent
+    }
+
+    /**
+     * Removes the selection, or the indentation level or the character before the cursor.
+     */
+    pub fn backspace(&mut self) {
+        if !self.selection.is_empty() {
+            return self.delete()
         }
+        let cursor = self.cursor.0;
+        if cursor == Position::default() || self.delete_indent() {
+            return
+        }
+        let previous = match cursor.character() {
+            0 => self.line_end(cursor.line() - 1),
+            character => Position::new(cursor.line(), character - 1),
+        };
+        self.cursor.0 = previous;
+        self.splice(previous, cursor, String::new());
+    }
 
-        
+    /**
+     * Removes the last level of indentation when the cursor is within the indentation of its line. Returns false
+     * if it isn't, the character before the cursor is removed as usual.
+     */
+    pub fn delete_indent(&mut self) -> bool {
+        let cursor = self.cursor.0;
+        let line = self.line_text(cursor.line());
+        let indent = leading_whitespace(&line);
+        if !self.selection.is_empty() || cursor.character() == 0 || cursor.character() > indent.len() {
+            return false
+        }
+        let level = self.shared.borrow().indent.last_level(&indent[..cursor.character()]);
+        let start = Position::new(cursor.line(), cursor.character() - level);
+        self.splice(start, cursor, String::new());
+        self.cursor.0 = start;
+        self.selection.set_start(start);
+        self.selection.set_end(start);
+        true
+    }
+
+    /**
+     * Returns the last line that has text, the empty line after the final newline doesn't count.
+     */
+    pub fn last_text_line(&self) -> usize {
+        let last = self.len().saturating_sub(1);
+        if last > 0 && self.line_text(last).is_empty() {
+            last - 1
+        } else {
+            last
+        }
+    }
+
+    /**
+     * Returns the position after the line along with its line break, or the end of the line if it's the last one.
+     */
+    pub fn after_line(&self, line: usize) -> Position {
+        if line + 1 < self.len() {
+            Position::new(line + 1, 0)
+        } else {
+            self.line_end(line)
+        }
+    }
+
+    /**
+     * Returns the position at the end of the line, before its line break.
+     */
+    pub fn line_end(&self, line: usize) -> Position {
+        Position::new(line, self.line_text(line).graphemes(true).count())
+    }
+
+    /**
+     * Moves the cursor and the selection by a number of lines.
+     */
+    fn shift_lines(&mut self, distance: isize) {
+        let shift = |position: Position| Position::new(position.line().saturating_add_signed(distance), position.character());
+        let (start, end) = (*self.selection.start(), *self.selection.end());
+        self.selection.set_start(shift(start));
+        self.selection.set_end(shift(end));
+        self.cursor.0 = shift(self.cursor.0);
+    }
+
+    /**
+     * Replaces the selected lines with the lines the function makes of them.
+     */
+    fn map_lines(&mut self, lines: std::ops::RangeInclusive<usize>, map: impl FnOnce(&mut Vec<String>)) {
+        let mut texts: Vec<String> = lines.clone().map(|line| self.line_text(line)).collect();
+        map(&mut texts);
+        let end = self.line_end(*lines.end());
+        self.splice(Position::new(*lines.start(), 0), end, texts.join("\n"));
+    }
+
+    /**
+     * Moves the selected lines above the line before them, or below the line after them.
+     */
+    pub fn move_lines(&mut self, up: bool) {
+        let lines = self.selected_lines();
+        let (start, end) = (*lines.start(), *lines.end());
+        if (up && start == 0) || (!up && end >= self.last_text_line()) {
+            return
+        }
+        if up {
+            self.map_lines(start - 1..=end, |texts| texts.rotate_left(1));
+            self.shift_lines(-1);
+        } else {
+            self.map_lines(start..=end + 1, |texts| texts.rotate_right(1));
+            self.shift_lines(1);
+        }
+    }
+
+    /**
+     * Copies the selected lines below them, the cursor and the selection move to the copy.
+     */
+    pub fn duplicate_lines(&mut self) {
+        let lines = self.selected_lines();
+        let (start, end) = (*lines.start(), *lines.end());
+        let text = lines.map(|line| self.line_text(line)).collect::<Vec<String>>().join("\n");
+        let after = self.after_line(end);
+        if after.line() > end {
+            self.splice(after, after, format!("{}\n", text));
+        } else {
+            self.splice(after, after, format!("\n{}", text));
+        }
+        self.shift_lines((end - start + 1) as isize);
+    }
+
+    /**
+     * Removes the selected lines along with their line breaks.
+     */
+    pub fn delete_lines(&mut self) {
+        let lines = self.selected_lines();
+        let (start, end) = (*lines.start(), *lines.end());
+        let after = self.after_line(end);
+        // The last line takes the line break before it instead
+        let before = if after.line() == end && start > 0 {
+            self.line_end(start - 1)
+        } else {
+            Position::new(start, 0)
+        };
+        let character = self.cursor.0.character();
+        self.selection.clear(&mut self.cursor);
+        self.splice(before, after, String::new());
+        let line = start.min(self.last_text_line());
+        self.cursor.0 = Position::new(line, character.min(self.line_end(line).character()));
+        self.selection.set_start(self.cursor.0);
+        self.selection.set_end(self.cursor.0);
+    }
+
+    /**
+     * Joins the selected lines, or the line of the cursor and the next one, into one line. The indentation of the
+     * joined lines is replaced by a space.
+     */
+    pub fn join_lines(&mut self) {
+        let lines = self.selected_lines();
+        let (start, end) = (*lines.start(), (*lines.end()).max(*lines.start() + 1));
+        if end > self.last_text_line() {
+            return
+        }
+        let mut joint = self.cursor.0;
+        // From the bottom up, so the lines above keep their positions
+        for line in (start..end).rev() {
+            let text = self.line_text(line);
+            let next = self.line_text(line + 1);
+            let kept = text.trim_end().graphemes(true).count();
+            let indent = leading_whitespace(&next).len();
+            let space = if next.trim().is_empty() || kept == 0 { "" } else { " " };
+            self.splice(Position::new(line, kept), Position::new(line + 1, indent), space.to_owned());
+            joint = Position::new(line, kept);
+        }
+        self.selection.clear(&mut self.cursor);
+        self.cursor.0 = joint;
+        self.selection.set_start(joint);
+        self.selection.set_end(joint);
+    }
+
+    /**
+     * Sorts the selected lines.
+     */
+    pub fn sort_lines(&mut self) {
+        let lines = self.selected_lines();
+        if lines.start() != lines.end() {
+            self.map_lines(lines, |texts| texts.sort());
+        }
+    }
+
+    /**
+     * Reverses the order of the selected lines.
+     */
+    pub fn reverse_lines(&mut self) {
+        let lines = self.selected_lines();
+        if lines.start() != lines.end() {
+            self.map_lines(lines, |texts| texts.reverse());
+        }
+    }
+
+    fn comment_tokens(&self) -> CommentTokens {
+        CommentTokens::for_file(self.shared.borrow().document.filename().map(String::as_str))
+    }
+
+    /**
+     * Comments out the selected lines with the line comment of the language, or uncomments them if they all are.
+     * The comment goes at the indentation of the least indented line, blank lines are left alone.
+     */
+    pub fn toggle_line_comment(&mut self) {
+        let Some(token) = self.comment_tokens().line else {
+            return self.toggle_block_comment()
+        };
+        let lines: Vec<(usize, String)> = self.selected_lines()
+            .map(|line| (line, self.line_text(line)))
+            .filter(|(_, text)| !text.trim().is_empty())
+            .collect();
+        let commented = lines.iter().all(|(_, text)| text.trim_start().starts_with(token));
+        let column = lines.iter().map(|(_, text)| leading_whitespace(text).len()).min().unwrap_or_default();
+        let mut shifts = Vec::new();
+        for (line, text) in lines {
+            if commented {
+                let at = leading_whitespace(&text).len();
+                let length = token.len() + usize::from(text[at + token.len()..].starts_with(' '));
+                self.splice(Position::new(line, at), Position::new(line, at + length), String::new());
+                shifts.push((line, at, -(length as isize)));
+            } else {
+                self.splice(Position::new(line, column), Position::new(line, column), format!("{} ", token));
+                shifts.push((line, column, token.len() as isize + 1));
+            }
+        }
+        self.move_selection(|position| match shifts.iter().find(|(line, ..)| *line == position.line()) {
+            Some(&(_, column, shift)) if position.character() >= column => {
+                position.character().saturating_add_signed(shift).max(column)
+            }
+            _ => position.character(),
+        });
+    }
+
+    /**
+     * Surrounds the selection, or the text of the cursor's line, with the block comment of the language. Removes
+     * the comment if the text is already one.
+     */
+    pub fn toggle_block_comment(&mut self) {
+        let Some((open, close)) = self.comment_tokens().block else {
+            return
+        };
+        let (start, end) = if self.selection.is_empty() {
+            let line = self.cursor.0.line();
+            let text = self.line_text(line);
+            let trimmed = text.trim_end();
+            (Position::new(line, leading_whitespace(trimmed).len()), Position::new(line, trimmed.graphemes(true).count()))
+        } else {
+            let selection = self.selection.correct_position();
+            (*selection.start(), *selection.end())
+        };
+        let text = self.shared.borrow().document
+            .str_from_range(self.shared.borrow().document.get_character_pos(&start), self.shared.borrow().document.get_character_pos(&end))
+            .to_string();
+        let (open_length, close_length) = (open.chars().count(), close.chars().count());
+        let commented = text.len() >= open.len() + close.len() && text.starts_with(open) && text.ends_with(close);
+
+        // The end goes first so the start doesn't move
+        if commented {
+            let close_start = Position::new(end.line(), end.character() - close_length);
+            self.splice(close_start, end, String::new());
+            self.splice(start, Position::new(start.line(), start.character() + open_length), String::new());
+        } else {
+            self.splice(end, end, close.to_owned());
+            self.splice(start, start, open.to_owned());
+        }
+        self.move_selection(|position| {
+            let mut character = position.character();
+            if commented {
+                if position.line() == end.line() && character > end.character() - close_length {
+                    character = character.saturating_sub(close_length).max(end.character() - close_length);
+                }
+                if position.line() == start.line() && character > start.character() {
+                    character = character.saturating_sub(open_length).max(start.character());
+                }
+            } else {
+                if position.line() == end.line() && character > end.character() {
+                    character += close_length;
+                }
+                if position.line() == start.line() && character >= start.character() {
+                    character += open_length;
+                }
+            }
+            character
+        });
+    }
+
+    /**
+     * Returns the text between the positions.
+     */
+    pub fn text_between(&self, start: Position, end: Position) -> String {
+        let (start, end) = (self.shared.borrow().document.get_character_pos(&start), self.shared.borrow().document.get_character_pos(&end));
+        self.shared.borrow().document.str_from_range(start, end.max(start)).to_string()
     }
 
     fn get_selected_text(&self) -> String {
         let selection = self.selection.correct_position();
-        let start_bytes = self.document.get_line_bytes(selection.start().line()) + selection.start().character();
-        let end_bytes = self.document.get_line_bytes(selection.end().line()) + selection.end().character();
+        let shared = self.shared.borrow();
+        let start = shared.document.get_character_pos(selection.start());
+        let end = shared.document.get_character_pos(selection.end());
 
-        let slice = self.document.str_from_range(start_bytes, end_bytes);
+        let slice = shared.document.str_from_range(start, end);
         slice.to_string()
     }
 
     /**
-     * Copies the selected text
+     * Returns the text that is copied: the selection, or the line of the cursor with its line break if nothing is
+     * selected.
      */
-    pub fn copy(&self, commands: &mut Vec<Command<Message>>){
-        let text = self.get_selected_text();
-        commands.push(
-            clipboard::write::<Message>(text)
-        )
+    fn clip(&self) -> Clip {
+        if self.selection.is_empty() {
+            return Clip::new(format!("{}\n", self.line_text(self.cursor.0.line())), true)
+        }
+        Clip::new(self.get_selected_text(), false)
     }
 
     /**
-     * Cuts the selected text
+     * Copies the selected text, or the line of the cursor
      */
-    pub fn cut(&mut self, commands: &mut Vec<Command<Message>>){
-        let text = self.get_selected_text();
-        self.delete();
-        
-        commands.push(clipboard::write::<Message>(text))
+    pub fn copy(&self, commands: &mut Vec<Command<Message>>) -> Clip {
+        let clip = self.clip();
+        commands.push(clipboard::write::<Message>(clip.text.clone()));
+        clip
+    }
+
+    /**
+     * Cuts the selected text, or the line of the cursor
+     */
+    pub fn cut(&mut self, commands: &mut Vec<Command<Message>>) -> Clip {
+        let clip = self.clip();
+        if clip.linewise {
+            let line = self.cursor.0.line();
+            let end = self.after_line(line);
+            self.splice(Position::new(line, 0), end, String::new());
+            self.cursor.0 = Position::new(line, 0);
+        } else {
+            self.delete();
+        }
+        commands.push(clipboard::write::<Message>(clip.text.clone()));
+        clip
+    }
+
+    /**
+     * Pastes lines that were copied without a selection above the line of the cursor, the cursor stays on its
+     * line.
+     */
+    pub fn paste_lines(&mut self, text: String) {
+        let cursor = self.cursor.0;
+        let start = Position::new(cursor.line(), 0);
+        let lines = text.matches('\n').count();
+        self.splice(start, start, text);
+        self.cursor.0 = Position::new(cursor.line() + lines, cursor.character());
     }
 
     pub fn paste(&mut self, commands: &mut Vec<Command<Message>>) {
@@ -237,12 +1188,21 @@ impl Buffer {
         self.cursor.0
     }
 
+    /**
+     * Returns the row and the cell of the grid the cursor is drawn at.
+     */
+    pub fn cursor_on_display(&self) -> (usize, usize) {
+        self.display.to_display(&self.shared.borrow().document, self.cursor.0)
+    }
+
     pub fn correct_position_to_cursor(&mut self, text_width: f32, longest_line: usize) {
-        self.window.correct_position_to_cursor(self.cursor, text_width, longest_line, self.len())
+        // The window counts in rows and cells
+        let (row, cell) = self.cursor_on_display();
+        self.window.correct_position_to_cursor(Cursor(Position::new(row, cell)), text_width, longest_line, self.row_count())
     }
 
     pub fn get_string(&self) -> String {
-        self.document.to_string()
+        self.shared.borrow().document.to_string()
     }
 
     pub fn move_horizontally(&mut self, distance: isize, text_info: &TextInfo) {
@@ -250,26 +1210,157 @@ impl Buffer {
         let longest_line = text_info.longest_line;
         self.cursor.move_horizontally(distance);
         self.correct_position();
-        if self.cursor.0.character() >= self.window.end_character(text_width) - 5 && distance > 0 {
+        let (_, cell) = self.cursor_on_display();
+        if cell >= self.window.end_character(text_width) - 5 && distance > 0 {
             self.window
                 .move_offset_x(MoveDirectionX::Right, longest_line, text_width)
         }
-        if self.cursor.0.character() <= self.window.start_character(text_width) + 5 && distance < 0 {
+        if cell <= self.window.start_character(text_width) + 5 && distance < 0 {
             self.window
                 .move_offset_x(MoveDirectionX::Left, longest_line, text_width)
         }
     }
 
+    /**
+     * Moves the cursor by rows of the screen, keeping it in the same cell when it can.
+     */
     pub fn move_vertically(&mut self, distance: isize) {
-        if self.cursor.0.line() >= self.window.end_line() - 2 && distance > 0 {
+        let (row, cell) = self.cursor_on_display();
+        if row >= self.window.end_line() - 2 && distance > 0 {
             self.window
-                .move_offset_y(MoveDirectionY::Down, self.len())
+                .move_offset_y(MoveDirectionY::Down, self.row_count())
         }
-        if self.cursor.0.line() <= self.window.start_line() + 2 && distance < 0 {
+        if row <= self.window.start_line() + 2 && distance < 0 {
             self.window
-                .move_offset_y(MoveDirectionY::Up, self.len())
+                .move_offset_y(MoveDirectionY::Up, self.row_count())
+        }
+        let row = row.saturating_add_signed(distance).min(self.row_count() - 1);
+        self.cursor.0 = self.display.from_display(&self.shared.borrow().document, row, cell);
+        self.correct_position();
+    }
+
+    fn cursor_row(&self) -> VisualRow {
+        self.display.row(self.display.row_of(self.cursor.0))
+    }
+
+    /**
+     * Moves the cursor to the start of its row. On the first row of a line it goes to the first character that
+     * isn't blank, or to the start of the line once it's there.
+     */
+    pub fn move_to_row_start(&mut self) {
+        let row = self.cursor_row();
+        let indent = leading_whitespace(&self.line_text(self.cursor.0.line())).len();
+        let character = match self.cursor.0.character() {
+            character if row.start > 0 && character > row.start => row.start,
+            character if character != indent && row.start <= indent => indent,
+            _ => 0,
+        };
+        self.cursor.0.set_character(character);
+    }
+
+    pub fn move_to_document_start(&mut self) {
+        self.cursor.0 = Position::default();
+    }
+
+    pub fn move_to_document_end(&mut self) {
+        let line = self.len().saturating_sub(1);
+        self.cursor.0 = self.line_end(line);
+    }
+
+    /**
+     * Returns where the cursor goes moving by a word. The line break between two lines is a stop of its own.
+     */
+    fn word_target(&self, forward: bool, kind: WordKind) -> Position {
+        let (line, character) = (self.cursor.0.line(), self.cursor.0.character());
+        let text = self.line_text(line);
+        if forward {
+            match next_word_end(&text, character, kind) {
+                Some(column) => Position::new(line, column),
+                None if character < self.line_end(line).character() => self.line_end(line),
+                None if line + 1 < self.len() => Position::new(line + 1, 0),
+                None => Position::new(line, character),
+            }
+        } else {
+            match previous_word_start(&text, character, kind) {
+                Some(column) => Position::new(line, column),
+                None if character > 0 => Position::new(line, 0),
+                None if line > 0 => self.line_end(line - 1),
+                None => Position::new(line, character),
+            }
+        }
+    }
+
+    /**
+     * Moves the cursor to the end of the next word, or to the start of the word before it.
+     */
+    pub fn move_word(&mut self, forward: bool, kind: WordKind) {
+        self.cursor.0 = self.word_target(forward, kind);
+    }
+
+    /**
+     * Deletes up to the end of the next word, or back to the start of the word before the cursor. A selection is
+     * deleted instead.
+     */
+    pub fn delete_word(&mut self, forward: bool, kind: WordKind) {
+        if self.selection.is_empty() {
+            let target = self.word_target(forward, kind);
+            let (start, end) = if forward { (self.cursor.0, target) } else { (target, self.cursor.0) };
+            self.selection.set_start(start);
+            self.selection.set_end(end);
+        }
+        self.delete();
+    }
+
+    /**
+     * Moves the cursor past the paragraph, to the next blank line or the one before it, or to the end of the
+     * document if there's none.
+     */
+    pub fn move_paragraph(&mut self, forward: bool) {
+        let is_blank = |line: usize| self.line_text(line).trim().is_empty();
+        let last = self.len().saturating_sub(1);
+        let mut line = self.cursor.0.line();
+        let step = |line: usize| if forward { (line < last).then_some(line + 1) } else { line.checked_sub(1) };
+        // The blank lines the cursor is on are skipped, then the lines of the paragraph
+        while let Some(next) = step(line).filter(|_| is_blank(line)) {
+            line = next;
         }
-        self.cursor.move_vertically(distance);
+        while let Some(next) = step(line) {
+            line = next;
+            if is_blank(line) {
+                break
+            }
+        }
+        self.cursor.0 = if forward && !is_blank(line) { self.line_end(line) } else { Position::new(line, 0) };
+    }
+
+    /**
+     * Selects the word at the position, or the spaces or punctuation there.
+     */
+    pub fn select_word(&mut self, position: Position) {
+        let columns = word_at(&self.line_text(position.line()), position.character());
+        self.selection.set_start(Position::new(position.line(), columns.start));
+        self.selection.set_end(Position::new(position.line(), columns.end));
+        self.cursor.0 = Position::new(position.line(), columns.end);
+    }
+
+    /**
+     * Selects the line along with its line break.
+     */
+    pub fn select_line(&mut self, line: usize) {
+        let end = self.after_line(line);
+        self.selection.set_start(Position::new(line, 0));
+        self.selection.set_end(end);
+        self.cursor.0 = end;
+    }
+
+    /**
+     * Moves the cursor to the end of its row. A wrapped row ends before its last grapheme, the next column is on
+     * the next row.
+     */
+    pub fn move_to_row_end(&mut self) {
+        let row = self.cursor_row();
+        let character = if row.end == usize::MAX { self.line_len(self.cursor.0.line()) } else { row.end - 1 };
+        self.cursor.0.set_character(character);
         self.correct_position();
     }
 
@@ -289,9 +1380,9 @@ impl Buffer {
 
     pub fn move_previous(&mut self, distance: isize, text_info: &TextInfo) {
         if self.cursor.0.character() as isize - distance < 0 {
-            self.move_vertically(-1);
-            self.cursor.0.set_character(self.get_cursor_row_len() - 1);
-
+            // The newline before the cursor ends the line above it, even when that line is folded away
+            let line = self.cursor.0.line().saturating_sub(1);
+            self.cursor.0 = Position::new(line, self.line_len(line).saturating_sub(1));
             return;
         }
         self.move_horizontally(-distance, text_info);
@@ -321,11 +1412,82 @@ impl Buffer {
         }
     }
 
+    /**
+     * Grows the selection to the smallest node of the syntax tree around it. Returns false if the tree can't grow
+     * it, the document isn't parsed or the whole document is selected.
+     */
+    pub fn expand_selection(&mut self) -> bool {
+        let shared = self.shared.borrow();
+        let Some(config) = shared.highlighter.as_ref() else {
+            return false
+        };
+        let current: Range = self.selection.into();
+        let bytes = shared.document.get_byte_pos(&current.start())..shared.document.get_byte_pos(&current.end());
+        let Some(node) = enclosing_node(config.tree(), bytes) else {
+            return false
+        };
+        let grown = Range::new(shared.document.position_of_byte(node.start), shared.document.position_of_byte(node.end));
+        drop(shared);
+        self.grow_selection(current, grown);
+        true
+    }
+
+    /**
+     * Grows the selection to the smallest of the ranges a server sent that holds it. The ranges go from the
+     * innermost to the outermost through their parents.
+     */
+    pub fn expand_to_server_ranges(&mut self, ranges: Vec<lsp_types::SelectionRange>, encoding: PositionEncoding) {
+        let current: Range = self.selection.into();
+        let mut next = ranges.into_iter().next();
+        while let Some(range) = next {
+            let candidate = Range::new(
+                self.shared.borrow().document.from_lsp_position(range.range.start, encoding),
+                self.shared.borrow().document.from_lsp_position(range.range.end, encoding),
+            );
+            if candidate.strictly_contains(&current) {
+                self.grow_selection(current, candidate);
+                return
+            }
+            next = range.parent.map(|parent| *parent);
+        }
+    }
+
+    fn grow_selection(&mut self, from: Range, to: Range) {
+        self.expand_history.push(from, to);
+        self.selection.select(to, &mut self.cursor);
+    }
+
+    /**
+     * Shrinks the selection back to the one it was grown from.
+     */
+    pub fn shrink_selection(&mut self) {
+        if let Some(previous) = self.expand_history.pop(self.selection.into()) {
+            self.selection.select(previous, &mut self.cursor);
+        }
+    }
+
     pub fn is_within_selection(&self, pos: &Position) -> bool {
         self.selection.is_within(pos)
     }
 }
 
+fn point_of_byte(document: &Document, byte: usize) -> Point {
+    let text = document.slice_all();
+    let row = text.byte_to_line(byte.min(text.len_bytes()));
+    Point { row, column: byte - text.line_to_byte(row) }
+}
+
+/**
+ * What the gutter shows next to a row.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GutterRow {
+    /// The line that starts on the row, `None` for a row that continues a wrapped line.
+    pub line: Option<usize>,
+    /// Whether the range that starts on the line is folded, `None` if no range starts there.
+    pub fold: Option<bool>,
+}
+
 pub struct TextInfo {
     pub longest_line: usize,
     pub text_width: f32
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+/**
+ * How many copies the history keeps, the oldest ones are dropped.
+ */
+const HISTORY_SIZE: usize = 30;
+
+/**
+ * How many characters of a copy the history shows.
+ */
+const PREVIEW_LENGTH: usize = 80;
+
+/**
+ * Text that was copied or cut.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clip {
+    pub text: String,
+    /// Copied without a selection, it's pasted as whole lines above the cursor
+    pub linewise: bool,
+}
+
+impl Clip {
+    pub fn new(text: String, linewise: bool) -> Self {
+        Self { text, linewise }
+    }
+
+    /**
+     * Returns the first line of the text that isn't blank, with the number of lines after it.
+     */
+    pub fn preview(&self) -> String {
+        let mut lines = self.text.lines().skip_while(|line| line.trim().is_empty());
+        let first = lines.next().unwrap_or_default().trim();
+        let mut preview: String = first.chars().take(PREVIEW_LENGTH).collect();
+        if first.chars().count() > PREVIEW_LENGTH {
+            preview.push('…');
+        }
+        match lines.count() {
+            0 => preview,
+            1 => format!("{}  (+1 line)", preview),
+            more => format!("{}  (+{} lines)", preview, more),
+        }
+    }
+}
+
+/**
+ * The recent copies, most recent first. Copying a text again moves it to the front.
+ */
+#[derive(Debug, Default)]
+pub struct ClipboardHistory {
+    clips: VecDeque<Clip>,
+}
+
+impl ClipboardHistory {
+    pub fn push(&mut self, clip: Clip) {
+        if clip.text.is_empty() {
+            return
+        }
+        self.clips.retain(|other| other.text != clip.text);
+        self.clips.push_front(clip);
+        self.clips.truncate(HISTORY_SIZE);
+    }
+
+    pub fn clips(&self) -> impl Iterator<Item = &Clip> {
+        self.clips.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Clip> {
+        self.clips.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clips.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clips.is_empty()
+    }
+
+    /**
+     * Returns whether the text read from the clipboard of the system is pasted as lines, when it's the last copy
+     * and it was made without a selection. Text copied in another application is pasted as it is.
+     */
+    pub fn is_linewise(&self, text: &str) -> bool {
+        self.clips.front().is_some_and(|clip| clip.linewise && clip.text == text)
+    }
+}
+
+#[cfg(test)]
+mod clipboard_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, document::Document, position::Position};
+
+    use super::{Clip, ClipboardHistory, HISTORY_SIZE};
+
+    #[test]
+    fn test_clipboard_history() {
+        let mut history = ClipboardHistory::default();
+        history.push(Clip::new("first".to_owned(), false));
+        history.push(Clip::new("let x = 1;\n".to_owned(), true));
+        history.push(Clip::new(String::new(), false));
+        assert!(history.is_linewise("let x = 1;\n"));
+
+        // Copying again moves the copy to the front
+        history.push(Clip::new("first".to_owned(), false));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some(&Clip::new("first".to_owned(), false)));
+        assert!(!history.is_linewise("let x = 1;\n"));
+
+        for index in 0..HISTORY_SIZE {
+            history.push(Clip::new(index.to_string(), false));
+        }
+        assert_eq!(history.len(), HISTORY_SIZE);
+        assert_eq!(history.clips().last().map(|clip| clip.text.as_str()), Some("0"));
+    }
+
+    #[test]
+    fn test_clip_preview() {
+        assert_eq!(Clip::new("\n    fn main() {\n    }\n".to_owned(), false).preview(), "fn main() {  (+1 line)");
+        assert_eq!(Clip::new("a\nb\nc".to_owned(), false).preview(), "a  (+2 lines)");
+        let long = "x".repeat(100);
+        assert_eq!(Clip::new(long, true).preview(), format!("{}…", "x".repeat(80)));
+    }
+
+    #[test]
+    fn test_copy_lines() {
+        let mut buffer = Buffer::new(Document::recovered(None, "one\ntwo\nthree"), None);
+        let mut commands = Vec::new();
+
+        // Without a selection the whole line is cut, and pasted back above the line of the cursor
+        buffer.cursor.0 = Position::new(1, 2);
+        let clip = buffer.cut(&mut commands);
+        assert_eq!(clip, Clip::new("two\n".to_owned(), true));
+        assert_eq!(buffer.get_string(), "one\nthree");
+        buffer.cursor.0 = Position::new(1, 3);
+        buffer.paste_lines(clip.text);
+        assert_eq!(buffer.get_string(), "one\ntwo\nthree");
+        assert_eq!(buffer.cursor.0, Position::new(2, 3));
+
+        assert_eq!(buffer.copy(&mut commands), Clip::new("three\n".to_owned(), true));
+        buffer.selection.set_start(Position::new(0, 1));
+        buffer.selection.set_end(Position::new(0, 3));
+        assert_eq!(buffer.copy(&mut commands), Clip::new("ne".to_owned(), false));
+        assert_eq!(commands.len(), 3);
+    }
+}
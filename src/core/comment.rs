@@ -0,0 +1,67 @@
+use std::path::Path;
+
+/**
+ * The tokens that start a line comment and surround a block comment in the language of a file.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentTokens {
+    pub line: Option<&'static str>,
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+/**
+ * The comments of Rust and of the languages that took them from C, files without an extension get them too.
+ */
+const C_LIKE: CommentTokens = CommentTokens { line: Some("//"), block: Some(("/*", "*/")) };
+
+impl CommentTokens {
+    /**
+     * Returns the comment tokens of the language the file is written in, found from its extension.
+     */
+    pub fn for_file(filename: Option<&str>) -> Self {
+        let extension = filename
+            .and_then(|filename| Path::new(filename).extension())
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        match extension {
+            "py" | "sh" | "bash" | "toml" | "yaml" | "yml" | "rb" | "pl" | "r" => Self { line: Some("#"), block: None },
+            "lua" => Self { line: Some("--"), block: Some(("--[[", "]]")) },
+            "sql" => Self { line: Some("--"), block: Some(("/*", "*/")) },
+            "hs" => Self { line: Some("--"), block: Some(("{-", "-}")) },
+            "html" | "xml" | "svg" | "md" => Self { line: None, block: Some(("<!--", "-->")) },
+            "css" => Self { line: None, block: Some(("/*", "*/")) },
+            _ => C_LIKE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod comment_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, position::Position};
+
+    use super::CommentTokens;
+
+    #[test]
+    fn test_toggle_comments() {
+        let mut buffer = Buffer::from_source("fn main() {\n    a();\n\n        b();\n}\n");
+        buffer.selection.set_start(Position::new(1, 5));
+        buffer.selection.set_end(Position::new(3, 9));
+        buffer.toggle_line_comment();
+        assert_eq!(buffer.get_string(), "fn main() {\n    // a();\n\n    //     b();\n}\n");
+        assert_eq!((*buffer.selection.start(), *buffer.selection.end()), (Position::new(1, 8), Position::new(3, 12)));
+        buffer.toggle_line_comment();
+        assert_eq!(buffer.get_string(), "fn main() {\n    a();\n\n        b();\n}\n");
+
+        assert_eq!(CommentTokens::for_file(Some("scripts/build.py")).line, Some("#"));
+        assert_eq!(CommentTokens::for_file(Some("index.html")).line, None);
+
+        let mut buffer = Buffer::from_source("let x = 1;\n");
+        buffer.selection.set_start(Position::new(0, 8));
+        buffer.selection.set_end(Position::new(0, 9));
+        buffer.toggle_block_comment();
+        assert_eq!(buffer.get_string(), "let x = /*1*/;\n");
+        assert_eq!((*buffer.selection.start(), *buffer.selection.end()), (Position::new(0, 10), Position::new(0, 11)));
+    }
+}
@@ -0,0 +1,409 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{document::{grapheme_width, Document}, position::Position};
+
+/**
+ * The column long lines are wrapped at when they aren't wrapped at the width of the window.
+ */
+pub const WRAP_COLUMN: usize = 80;
+
+/**
+ * Where long lines are wrapped.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WrapMode {
+    #[default]
+    Off,
+    /// At the width of the window
+    Window,
+    /// At a number of cells
+    Column(usize),
+}
+
+impl WrapMode {
+    pub fn label(&self) -> String {
+        match self {
+            WrapMode::Off => "Wrap: Off".to_owned(),
+            WrapMode::Window => "Wrap: Window".to_owned(),
+            WrapMode::Column(column) => format!("Wrap: {}", column),
+        }
+    }
+
+    /**
+     * Returns the mode that comes after this one in the status bar.
+     */
+    pub fn next(&self) -> Self {
+        match self {
+            WrapMode::Off => WrapMode::Window,
+            WrapMode::Window => WrapMode::Column(WRAP_COLUMN),
+            WrapMode::Column(_) => WrapMode::Off,
+        }
+    }
+}
+
+/**
+ * A row of the screen and the part of a line that is drawn on it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisualRow {
+    pub line: usize,
+    /// The first column of the line on the row, counted in graphemes.
+    pub start: usize,
+    /// The column after the last one on the row.
+    pub end: usize,
+    /// The cells left empty before the text. Continuation rows keep the indentation of their line.
+    pub indent: usize,
+}
+
+/**
+ * The rows a line is wrapped in, as the columns each of them starts and ends at.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct WrappedLine {
+    rows: Vec<(usize, usize)>,
+    indent: usize,
+}
+
+/**
+ * Maps the lines of the document to the rows of the screen, without changing the document.
+ *
+ * Without a wrap width every line is one row and nothing is stored, so large files don't pay for it. Lines that are
+ * folded away have no row.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct DisplayMap {
+    wrap_width: Option<usize>,
+    line_count: usize,
+    lines: Vec<WrappedLine>,
+    /// The first row of every line, followed by the number of rows.
+    first_rows: Vec<usize>,
+    /// The lines that are folded away, sorted and apart from each other.
+    hidden: Vec<Range<usize>>,
+}
+
+impl DisplayMap {
+    pub fn new(document: &Document, wrap_width: Option<usize>) -> Self {
+        let mut map = Self { wrap_width, line_count: document.len(), lines: Vec::new(), first_rows: Vec::new(), hidden: Vec::new() };
+        if let Some(width) = wrap_width {
+            map.lines = (0..document.len()).map(|line| wrap_line(document, line, width)).collect();
+            map.count_rows();
+        }
+        map
+    }
+
+    pub fn wrap_width(&self) -> Option<usize> {
+        self.wrap_width
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    fn count_rows(&mut self) {
+        self.first_rows.clear();
+        let mut rows = 0;
+        let mut hidden = self.hidden.iter().peekable();
+        for (index, line) in self.lines.iter().enumerate() {
+            self.first_rows.push(rows);
+            while hidden.next_if(|range| range.end <= index).is_some() {}
+            if !hidden.peek().is_some_and(|range| range.contains(&index)) {
+                rows += line.rows.len();
+            }
+        }
+        self.first_rows.push(rows);
+    }
+
+    /**
+     * Hides the lines of the folded ranges. Returns whether the rows changed.
+     */
+    pub fn set_hidden(&mut self, hidden: Vec<Range<usize>>) -> bool {
+        let hidden: Vec<Range<usize>> = hidden
+            .into_iter()
+            .map(|range| range.start..range.end.min(self.line_count))
+            .filter(|range| !range.is_empty())
+            .collect();
+        if self.hidden == hidden {
+            return false
+        }
+        self.hidden = hidden;
+        if self.wrap_width.is_some() {
+            self.count_rows();
+        }
+        true
+    }
+
+    /**
+     * Returns the range of hidden lines the line is in.
+     */
+    fn hidden_range(&self, line: usize) -> Option<&Range<usize>> {
+        let index = self.hidden.partition_point(|range| range.end <= line);
+        self.hidden.get(index).filter(|range| range.start <= line)
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.hidden_range(line).is_some()
+    }
+
+    /**
+     * Returns the line the line is drawn on, the first line of its fold if it's folded away.
+     */
+    pub fn visible_line(&self, line: usize) -> usize {
+        self.hidden_range(line).map(|range| range.start.saturating_sub(1)).unwrap_or(line)
+    }
+
+    /**
+     * Returns the number of hidden lines before the line.
+     */
+    fn hidden_before(&self, line: usize) -> usize {
+        self.hidden
+            .iter()
+            .take_while(|range| range.start < line)
+            .map(|range| range.end.min(line) - range.start)
+            .sum()
+    }
+
+    /**
+     * Wraps the lines that were edited again. The lines between the first and the last one were replaced, the
+     * number of lines that replaced them comes from the document.
+     */
+    pub fn edit(&mut self, document: &Document, first_line: usize, old_last_line: usize) {
+        let added = document.len() as isize - self.line_count as isize;
+        self.line_count = document.len();
+        let Some(width) = self.wrap_width else {
+            return
+        };
+        let first_line = first_line.min(self.lines.len().saturating_sub(1));
+        // Removing the newline at the end of a line joins it with the next one
+        let old_last_line = old_last_line.max(first_line + (-added).max(0) as usize).min(self.lines.len().saturating_sub(1));
+        let new_last_line = (old_last_line as isize + added).max(first_line as isize) as usize;
+        let wrapped: Vec<WrappedLine> = (first_line..(new_last_line + 1).min(document.len()))
+            .map(|line| wrap_line(document, line, width))
+            .collect();
+        self.lines.splice(first_line..old_last_line + 1, wrapped);
+        self.count_rows();
+    }
+
+    pub fn row_count(&self) -> usize {
+        match self.wrap_width {
+            Some(_) => self.first_rows.last().copied().unwrap_or_default().max(1),
+            None => (self.line_count - self.hidden_before(self.line_count)).max(1),
+        }
+    }
+
+    /**
+     * Returns the rows the line is drawn on, none if it's folded away.
+     */
+    pub fn rows_of(&self, line: usize) -> Range<usize> {
+        match self.wrap_width {
+            Some(_) if line < self.lines.len() => self.first_rows[line]..self.first_rows[line + 1],
+            Some(_) => self.row_count()..self.row_count(),
+            None => {
+                let row = line - self.hidden_before(line);
+                if self.is_hidden(line) { row..row } else { row..row + 1 }
+            }
+        }
+    }
+
+    /**
+     * Returns the row, rows past the last one are the last row.
+     */
+    pub fn row(&self, row: usize) -> VisualRow {
+        let row = row.min(self.row_count() - 1);
+        if self.wrap_width.is_none() || self.lines.is_empty() {
+            // Every hidden range before the line moves it further down
+            let mut line = row;
+            for range in &self.hidden {
+                if range.start > line {
+                    break
+                }
+                line += range.len();
+            }
+            return VisualRow { line, start: 0, end: usize::MAX, indent: 0 }
+        }
+        // The last line that starts at or before the row
+        let line = self.first_rows.partition_point(|first| *first <= row) - 1;
+        let wrapped = &self.lines[line];
+        let index = row - self.first_rows[line];
+        let (start, end) = wrapped.rows[index];
+        let indent = if index == 0 { 0 } else { wrapped.indent };
+        VisualRow { line, start, end, indent }
+    }
+
+    /**
+     * Returns the row the position is drawn on. A position at the end of a row is on the next row, a position
+     * that is folded away is on the last row of its fold.
+     */
+    pub fn row_of(&self, position: Position) -> usize {
+        let line = self.visible_line(position.line());
+        if line != position.line() {
+            return self.rows_of(line).end.saturating_sub(1)
+        }
+        let rows = self.rows_of(position.line());
+        match self.wrap_width.and_then(|_| self.lines.get(position.line())) {
+            Some(wrapped) => {
+                let index = wrapped.rows.iter().rposition(|(start, _)| *start <= position.character()).unwrap_or_default();
+                rows.start + index
+            }
+            None => rows.start,
+        }
+    }
+
+    /**
+     * Returns the row and the cell of the grid the position is drawn at.
+     */
+    pub fn to_display(&self, document: &Document, position: Position) -> (usize, usize) {
+        // A position that is folded away is drawn at the end of its fold
+        let line = self.visible_line(position.line());
+        let position = if line == position.line() { position } else { Position::new(line, document.line_len(line).saturating_sub(1)) };
+        let row = self.row_of(position);
+        let visual = self.row(row);
+        let line = position.line();
+        let cell = document.display_column(line, position.character()) - document.display_column(line, visual.start);
+        (row, visual.indent + cell)
+    }
+
+    /**
+     * Returns the position drawn at the cell of the row. Cells past the text of the row are moved to its last
+     * column, or to the end of the line on its last row.
+     */
+    pub fn from_display(&self, document: &Document, row: usize, cell: usize) -> Position {
+        let row = row.min(self.row_count() - 1);
+        let visual = self.row(row);
+        let line = visual.line.min(document.len().saturating_sub(1));
+        let offset = document.display_column(line, visual.start);
+        let column = document.column_at_display(line, offset + cell.saturating_sub(visual.indent));
+        let is_last_row = self.rows_of(line).end == row + 1 || self.wrap_width.is_none();
+        let column = if is_last_row { column } else { column.min(visual.end.saturating_sub(1)) };
+        Position::new(line, column.max(visual.start))
+    }
+}
+
+/**
+ * Breaks the line in rows of the width, after the last whitespace that fits or in the middle of a word
+ * too long for a row.
+ */
+fn wrap_line(document: &Document, line: usize, width: usize) -> WrappedLine {
+    let text = document.get_line(line).map(|line| line.to_string()).unwrap_or_default();
+    let graphemes: Vec<(usize, bool)> = text
+        .graphemes(true)
+        .filter(|grapheme| *grapheme != "\n")
+        .map(|grapheme| (grapheme_width(grapheme), grapheme.chars().all(char::is_whitespace)))
+        .collect();
+    let indent: usize = graphemes.iter().take_while(|(_, is_space)| *is_space).map(|(width, _)| width).sum();
+    // Deeply indented lines don't keep their indentation, the rows would be too narrow
+    let indent = if indent * 2 > width { 0 } else { indent };
+
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < graphemes.len() {
+        let available = if rows.is_empty() { width } else { width - indent };
+        let mut cells = 0;
+        let mut end = start;
+        let mut last_break = None;
+        while end < graphemes.len() && cells + graphemes[end].0 <= available {
+            cells += graphemes[end].0;
+            if graphemes[end].1 {
+                last_break = Some(end + 1);
+            }
+            end += 1;
+        }
+        if end < graphemes.len() {
+            if let Some(last_break) = last_break.filter(|last_break| *last_break > start) {
+                end = last_break;
+            }
+        }
+        // A grapheme wider than the row still takes a row
+        let end = end.max(start + 1);
+        rows.push((start, end));
+        start = end;
+    }
+    if rows.is_empty() {
+        rows.push((0, 0));
+    }
+    // The last row ends with the line, where the cursor can go after the last grapheme
+    if let Some(last) = rows.last_mut() {
+        last.1 = usize::MAX;
+    }
+    WrappedLine { rows, indent }
+}
+
+#[cfg(test)]
+mod display_map_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{document::Document, position::Position};
+
+    use super::{DisplayMap, VisualRow};
+
+    #[test]
+    fn test_wrap_keeps_indentation() {
+        let document = Document::recovered(None, "fn main() {\n    let value = one + two + three;\n}\n");
+        let map = DisplayMap::new(&document, Some(16));
+        assert_eq!(map.row_count(), 6);
+        assert_eq!(map.rows_of(1), 1..4);
+        // Broken after the last space that fits, continuation rows are indented like their line
+        assert_eq!(map.row(1), VisualRow { line: 1, start: 0, end: 16, indent: 0 });
+        assert_eq!(map.row(2), VisualRow { line: 1, start: 16, end: 28, indent: 4 });
+        assert_eq!(map.row(3), VisualRow { line: 1, start: 28, end: usize::MAX, indent: 4 });
+        assert_eq!(map.row(4).line, 2);
+
+        assert_eq!(map.to_display(&document, Position::new(1, 16)), (2, 4));
+        assert_eq!(map.to_display(&document, Position::new(1, 30)), (3, 6));
+        assert_eq!(map.from_display(&document, 2, 6), Position::new(1, 18));
+        // Past the text of a continuation row, the cursor stays on the row
+        assert_eq!(map.from_display(&document, 2, 40), Position::new(1, 27));
+        assert_eq!(map.from_display(&document, 3, 40), Position::new(1, 34));
+    }
+
+    fn rows(map: &DisplayMap) -> Vec<VisualRow> {
+        (0..map.row_count()).map(|row| map.row(row)).collect()
+    }
+
+    #[test]
+    fn test_edit_wraps_changed_lines() {
+        let mut document = Document::recovered(None, "one two three\nfour\n");
+        let mut map = DisplayMap::new(&document, Some(8));
+        assert_eq!(map.row_count(), 4);
+
+        document.insert(&Position::new(1, 4), " five six seven".to_owned());
+        map.edit(&document, 1, 1);
+        assert_eq!(map.rows_of(1), 2..6);
+        assert_eq!(rows(&map), rows(&DisplayMap::new(&document, Some(8))));
+
+        // Removing the newline joins the first two lines
+        document.delete(&Position::new(0, 13), &Position::new(0, 14));
+        map.edit(&document, 0, 0);
+        assert_eq!(rows(&map), rows(&DisplayMap::new(&document, Some(8))));
+        assert_eq!(map.rows_of(1), map.row_count() - 1..map.row_count());
+    }
+
+    #[test]
+    fn test_without_wrap_lines_are_rows() {
+        let document = Document::recovered(None, "a\nb\nc");
+        let map = DisplayMap::new(&document, None);
+        assert_eq!(map.row_count(), 3);
+        assert_eq!(map.row(1), VisualRow { line: 1, start: 0, end: usize::MAX, indent: 0 });
+        assert_eq!(map.to_display(&document, Position::new(2, 1)), (2, 1));
+    }
+
+    #[test]
+    fn test_hidden_lines_have_no_rows() {
+        let document = Document::recovered(None, "fn main() {\n    one();\n    two();\n}\nfn next() {\n    three();\n}\n");
+        for width in [None, Some(40)] {
+            let mut map = DisplayMap::new(&document, width);
+            assert!(map.set_hidden(vec![1..3, 5..6]));
+            assert!(!map.set_hidden(vec![1..3, 5..6]));
+            assert_eq!(map.row_count(), 5);
+            assert_eq!(rows(&map).iter().map(|row| row.line).collect::<Vec<usize>>(), vec![0, 3, 4, 6, 7]);
+            assert_eq!(map.rows_of(2), 1..1);
+            assert_eq!(map.rows_of(3), 1..2);
+            // Moving down from the first line skips the folded lines
+            assert_eq!(map.from_display(&document, 1, 0), Position::new(3, 0));
+            // A position that is folded away is drawn at the end of the first line of its fold
+            assert_eq!(map.to_display(&document, Position::new(2, 3)), (0, 11));
+        }
+    }
+}
@@ -1,30 +1,67 @@
-use std::io::Error;
+use std::path::Path;
 
 
-use crate::lsp::client::file_path;
+use crate::lsp::{client::file_path, encoding::{ChangeRange, LinePrefix, PositionEncoding}};
+use encoding_rs::Encoding;
 use rfd::FileDialog;
 use ropey::{Rope, RopeSlice};
 use ropey::iter::Lines;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::fs;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+use super::error::{DocumentError, DocumentResult};
+use super::file_format::{read_text, write_atomic, FileFormat, SaveOptions};
 use super::position::{Position};
 
+/**
+ * Files larger than this open in large file mode: they aren't parsed and the language servers don't get them.
+ */
+pub const LARGE_FILE_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
 pub struct ByteRange  {
     pub start: usize,
     pub end: usize
 }
 
+/**
+ * The content of a document being saved. It can be written in the background while the document is edited.
+ */
+#[derive(Debug, Clone)]
+pub struct SaveJob {
+    pub filename: String,
+    rope: Rope,
+    format: FileFormat,
+    /// The version of the document that is saved
+    pub version: i32,
+}
+
+impl SaveJob {
+    pub fn write(&self) -> DocumentResult<()> {
+        let bytes = self.format.encode(&self.rope.to_string())?;
+        write_atomic(Path::new(&self.filename), &bytes)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct FileData {
     name: String,
     uri: String
 }
 
+#[derive(Debug, Clone)]
 pub struct Document {
     rope: Rope,
     file_data: Option<FileData>,
     is_saved: bool,
+    format: FileFormat,
+    /// Increases every time the document is edited. Used as the version of the document for the LSP.
+    version: i32,
+    /// The number of characters of the longest line. It only grows while the document is edited, the lines
+    /// aren't measured again when text is removed.
+    longest_line: usize,
 }
 
 impl ToString for Document {
@@ -43,24 +80,76 @@ impl Document {
         Some(&self.file_data.as_ref()?.name)
     }
 
+    /**
+     * Points the document to another file, used once its file was renamed or moved.
+     */
+    pub fn set_file(&mut self, filename: &str) {
+        self.file_data = Some(FileData { name: filename.to_owned(), uri: file_path(filename) });
+    }
+
     pub fn slice_all(&self) -> RopeSlice {
         self.rope.slice(..)
     }
 
-    pub fn open(filename: &str) -> Result<Self, Error> {
-        let file = File::open(filename)?;
-        let rope = Rope::from_reader(BufReader::new(file))?;
+    /**
+     * Returns a copy of the text that shares the rope's nodes, cheap enough to take after every edit.
+     */
+    pub fn rope(&self) -> Rope {
+        self.rope.clone()
+    }
+
+    pub fn open(filename: &str) -> DocumentResult<Self> {
+        let (text, format) = read_text(Path::new(filename))?;
+        Ok(Self::with_text(filename, &text, format))
+    }
+
+    /**
+     * Opens the file again with another encoding, when its encoding wasn't detected correctly.
+     */
+    pub fn open_as(filename: &str, encoding: &'static Encoding) -> DocumentResult<Self> {
+        let (text, format) = FileFormat::decode_as(&fs::read(filename)?, encoding)?;
+        Ok(Self::with_text(filename, &text, format))
+    }
 
+    fn with_text(filename: &str, text: &str, format: FileFormat) -> Self {
+        let rope = Rope::from_str(text);
+        let longest_line = longest_line(&rope);
         let uri = file_path(filename);
         let file_data = FileData {
             name:filename.to_owned(),
             uri,
         };
-        Ok(Self { 
+        Self { 
             rope, 
             file_data:Some(file_data), 
-            is_saved: true
-        })
+            is_saved: true,
+            format,
+            version: 1,
+            longest_line,
+        }
+    }
+
+    /**
+     * Creates a document with text that wasn't saved yet, like the content of a recovered buffer.
+     * 
+     * The file is only kept if it still exists, the text is saved in the format of the file.
+     */
+    pub fn recovered(filename: Option<&str>, text: &str) -> Self {
+        let filename = filename.filter(|filename| Path::new(filename).exists());
+        let format = filename
+            .and_then(|filename| read_text(Path::new(filename)).ok())
+            .map(|(_, format)| format)
+            .unwrap_or_default();
+        let file_data = filename.map(|filename| FileData { name: filename.to_owned(), uri: file_path(filename) });
+        let rope = Rope::from_str(text);
+        Self {
+            longest_line: longest_line(&rope),
+            rope,
+            file_data,
+            is_saved: false,
+            format,
+            version: 1,
+        }
     }
 
     pub fn new() -> Self {
@@ -68,11 +157,17 @@ impl Document {
         Self {
             rope, 
             file_data: None,
-            is_saved: true
+            is_saved: true,
+            format: FileFormat::default(),
+            version: 1,
+            longest_line: 0,
         }
     }
 
-    pub fn save(&mut self, workspace: Option<String>) -> Result<(), Error> {
+    /**
+     * Returns what saving the document writes. A document that was never saved asks the user where to save it.
+     */
+    pub fn save_job(&self, workspace: Option<String>) -> DocumentResult<SaveJob> {
         let mut dialog = FileDialog::new();
         let filename = match self.filename() {
             Some(value) => value.to_owned(),
@@ -83,32 +178,272 @@ impl Document {
                 }
                 let path = dialog
                     .save_file()
-                    .unwrap();
+                    .ok_or(DocumentError::Cancelled)?;
 
                 path.to_str()
-                    .unwrap()
+                    .ok_or(DocumentError::InvalidPath)?
                     .to_owned()
             }
         };
+        Ok(SaveJob { filename, rope: self.rope.clone(), format: self.format, version: self.version })
+    }
 
-        let file = File::create(filename.clone())?;
-        self.rope.write_to(BufWriter::new(file)).expect("Failed to save file");
-        self.is_saved = true;
-
-        if let None = self.file_data {
-            self.file_data = Some (
-                FileData { name:filename.clone(), uri: file_path(&filename)}
-            )
+    /**
+     * Marks the document as saved to the file, unless it was edited while it was being saved.
+     */
+    pub fn saved(&mut self, filename: &str, version: i32) {
+        if self.file_data.is_none() {
+            self.set_file(filename);
+        }
+        if version == self.version {
+            self.is_saved = true;
         }
+    }
+
+    pub fn save(&mut self, workspace: Option<String>) -> DocumentResult<()> {
+        let job = self.save_job(workspace)?;
+        job.write()?;
+        self.saved(&job.filename, job.version);
         Ok(())
     }
 
+    pub fn format(&self) -> FileFormat {
+        self.format
+    }
+
+    /**
+     * Changes how the document is saved. The document has to be saved again for the change to reach the disk.
+     */
+    pub fn set_format(&mut self, format: FileFormat) {
+        if format != self.format {
+            self.format = format;
+            self.is_saved = false;
+        }
+    }
+
+    pub fn has_final_newline(&self) -> bool {
+        self.rope.len_chars() > 0 && self.rope.char(self.rope.len_chars() - 1) == '\n'
+    }
+
+    /**
+     * Returns the edits that add or remove the newline at the end of the document, in characters.
+     */
+    pub fn final_newline_edits(&self, has_final_newline: bool) -> Vec<lsp_types::TextEdit> {
+        if has_final_newline == self.has_final_newline() {
+            return Vec::new()
+        }
+        let last_line = self.rope.len_lines() - 1;
+        let end = lsp_types::Position::new(last_line as u32, self.rope.line(last_line).len_chars() as u32);
+        if has_final_newline {
+            return vec![lsp_types::TextEdit::new(lsp_types::Range::new(end, end), "\n".to_owned())]
+        }
+        // The last line is empty, the newline ends the line before it
+        let previous = last_line - 1;
+        let start = lsp_types::Position::new(previous as u32, self.rope.line(previous).len_chars() as u32 - 1);
+        vec![lsp_types::TextEdit::new(lsp_types::Range::new(start, end), String::new())]
+    }
+
+    /**
+     * Returns the edits the options make to the document before it's saved, in characters.
+     */
+    pub fn save_edits(&self, options: SaveOptions) -> Vec<lsp_types::TextEdit> {
+        let mut edits = Vec::new();
+        if options.trim_trailing_whitespace {
+            for (index, line) in self.rope.lines().enumerate() {
+                let line = line.to_string();
+                let content = line.trim_end_matches(['\r', '\n']);
+                let trimmed = content.trim_end_matches([' ', '\t']);
+                if trimmed.len() == content.len() {
+                    continue
+                }
+                let start = lsp_types::Position::new(index as u32, trimmed.chars().count() as u32);
+                let end = lsp_types::Position::new(index as u32, content.chars().count() as u32);
+                edits.push(lsp_types::TextEdit::new(lsp_types::Range::new(start, end), String::new()));
+            }
+        }
+        // An empty document stays empty
+        if options.insert_final_newline && self.rope.len_chars() > 0 {
+            edits.extend(self.final_newline_edits(true));
+        }
+        edits
+    }
+
     pub fn lines (&self) -> Lines<'_> {
         self.rope.lines()
     }
 
+    /**
+     * Returns the char index of the position within the document.
+     */
     pub fn get_character_pos(&self, position: &Position) -> usize {
-        self.rope.line_to_char(position.line()) + position.character()
+        let line = position.line().min(self.rope.len_lines().saturating_sub(1));
+        let index = self.rope.line_to_char(line) + self.grapheme_to_char(line, position.character());
+        index.min(self.rope.len_chars())
+    }
+
+    /**
+     * Returns the byte index of the position within the document, the way tree-sitter counts it.
+     */
+    pub fn get_byte_pos(&self, position: &Position) -> usize {
+        self.rope.char_to_byte(self.get_character_pos(position))
+    }
+
+    /**
+     * Returns the position of the byte index. A byte in the middle of a grapheme is moved to the next grapheme.
+     */
+    pub fn position_of_byte(&self, byte: usize) -> Position {
+        let index = self.rope.byte_to_char(byte.min(self.rope.len_bytes()));
+        let line = self.rope.char_to_line(index);
+        Position::new(line, self.char_to_grapheme(line, index - self.rope.line_to_char(line)))
+    }
+
+    /**
+     * Converts the column of a position, counted in graphemes, into the char offset within the line.
+     *
+     * Columns past the end of the line are kept past the end.
+     */
+    pub fn grapheme_to_char(&self, line: usize, column: usize) -> usize {
+        let Some(line) = self.rope.get_line(line) else {
+            return column
+        };
+        let line = line.to_string();
+        let mut graphemes = 0;
+        let mut offset = 0;
+        for grapheme in line.graphemes(true).take(column) {
+            graphemes += 1;
+            offset += grapheme.chars().count();
+        }
+        offset + column - graphemes
+    }
+
+    /**
+     * Converts a char offset within the line into a column counted in graphemes.
+     *
+     * An offset in the middle of a grapheme is moved to the next grapheme.
+     */
+    pub fn char_to_grapheme(&self, line: usize, offset: usize) -> usize {
+        let Some(line) = self.rope.get_line(line) else {
+            return offset
+        };
+        let line = line.to_string();
+        let mut chars = 0;
+        let mut column = 0;
+        for grapheme in line.graphemes(true) {
+            if chars >= offset {
+                break
+            }
+            chars += grapheme.chars().count();
+            column += 1;
+        }
+        column
+    }
+
+    /**
+     * Converts the column of a position, counted in graphemes, into the cell of the grid it's drawn at.
+     *
+     * Wide graphemes like CJK characters take two cells. Columns past the end of the line take one cell each.
+     */
+    pub fn display_column(&self, line: usize, column: usize) -> usize {
+        let Some(line) = self.rope.get_line(line) else {
+            return column
+        };
+        let line = line.to_string();
+        let mut graphemes = 0;
+        let mut cells = 0;
+        for grapheme in line.graphemes(true).take(column) {
+            graphemes += 1;
+            cells += grapheme_width(grapheme);
+        }
+        cells + column - graphemes
+    }
+
+    /**
+     * Returns the column of the grapheme drawn at the cell of the grid. A cell past the end of the line is moved
+     * to the end of the line.
+     */
+    pub fn column_at_display(&self, line: usize, cell: usize) -> usize {
+        let Some(line) = self.rope.get_line(line) else {
+            return 0
+        };
+        let line = line.to_string();
+        let mut cells = 0;
+        let mut column = 0;
+        for grapheme in line.graphemes(true).filter(|grapheme| *grapheme != "\n") {
+            cells += grapheme_width(grapheme);
+            if cells > cell {
+                break
+            }
+            column += 1;
+        }
+        column
+    }
+
+    /**
+     * Returns the point at the char index along with the text of its line that comes before it.
+     */
+    fn line_prefix(&self, index: usize) -> LinePrefix {
+        let index = index.min(self.rope.len_chars());
+        let line = self.rope.char_to_line(index);
+        let prefix = self.rope.slice(self.rope.line_to_char(line)..index).to_string();
+        LinePrefix { line, prefix }
+    }
+
+    /**
+     * Returns the range between the positions in a form that can be sent to a language server
+     * with any encoding.
+     */
+    pub fn change_range(&self, start: &Position, end: &Position) -> ChangeRange {
+        ChangeRange {
+            start: self.line_prefix(self.get_character_pos(start)),
+            end: self.line_prefix(self.get_character_pos(end)),
+        }
+    }
+
+    /**
+     * Converts a position into an LSP position, counting the character in the units of the encoding.
+     */
+    pub fn to_lsp_position(&self, position: &Position, encoding: PositionEncoding) -> lsp_types::Position {
+        self.line_prefix(self.get_character_pos(position)).to_lsp(encoding)
+    }
+
+    /**
+     * Converts an LSP position counted in the units of the encoding into a position.
+     */
+    pub fn from_lsp_position(&self, position: lsp_types::Position, encoding: PositionEncoding) -> Position {
+        let line = position.line as usize;
+        let Some(text) = self.rope.get_line(line) else {
+            return Position::new(line, position.character as usize)
+        };
+        let offset = encoding.char_offset(text, position.character as usize);
+        Position::new(line, self.char_to_grapheme(line, offset))
+    }
+
+    /**
+     * Converts the edits sent by a server into ranges of the document, sorted by their start.
+     *
+     * The edits should be applied starting from the last one, so an edit doesn't move the ones before it.
+     */
+    pub fn resolve_edits(&self, edits: Vec<lsp_types::TextEdit>, encoding: PositionEncoding) -> Vec<(Position, Position, String)> {
+        let mut edits: Vec<(Position, Position, String)> = edits
+            .into_iter()
+            .map(|edit| (
+                self.from_lsp_position(edit.range.start, encoding),
+                self.from_lsp_position(edit.range.end, encoding),
+                edit.new_text,
+            ))
+            .collect();
+        edits.sort_by_key(|edit| edit.0);
+        edits
+    }
+
+    /**
+     * Applies the edits of a server to a document that isn't open in a buffer.
+     */
+    pub fn apply_edits(&mut self, edits: Vec<lsp_types::TextEdit>, encoding: PositionEncoding) {
+        for (start, end, text) in self.resolve_edits(edits, encoding).into_iter().rev() {
+            self.delete(&start, &end);
+            self.insert(&start, text);
+        }
     }
 
     pub fn str_from_range(&self, start: usize, end: usize) -> RopeSlice<'_> {
@@ -126,6 +461,15 @@ impl Document {
         self.is_saved
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    fn edited(&mut self) {
+        self.is_saved = false;
+        self.version += 1;
+    }
+
     /**
      * Get's the length of characters within the line
      */
@@ -146,6 +490,27 @@ impl Document {
         self.rope.len_lines()
     }
 
+    pub fn longest_line(&self) -> usize {
+        self.longest_line
+    }
+
+    /**
+     * Large files are only read and edited, they aren't parsed or sent to the language servers.
+     */
+    pub fn is_large(&self) -> bool {
+        self.rope.len_bytes() > LARGE_FILE_SIZE
+    }
+
+    /**
+     * Measures the lines the text was inserted in, starting at the line.
+     */
+    fn measure_lines(&mut self, line: usize, text: &str) {
+        let end = (line + text.matches('\n').count() + 1).min(self.rope.len_lines());
+        for line in line..end {
+            self.longest_line = self.longest_line.max(self.rope.line(line).len_chars());
+        }
+    }
+
     pub fn get_line(&self, line_idx: usize) -> Option<RopeSlice<'_>> {
         self.rope.get_line(line_idx)
     }
@@ -158,8 +523,10 @@ impl Document {
      * Replaces the strings within the range of the position with the character inputted
      */
     pub fn replace(&mut self, start_idx: &Position, end_idx: &Position, character: String) -> Option<ByteRange> {
-        let result = self.delete(start_idx, end_idx);
-        self.insert(start_idx, character);
+        let result = self.remove(start_idx, end_idx);
+        self.rope.insert(self.get_character_pos(start_idx), &character);
+        self.measure_lines(start_idx.line(), &character);
+        self.edited();
         result
     }
 
@@ -168,23 +535,72 @@ impl Document {
      */
     pub fn insert(&mut self, position: &Position, character: String) -> usize {
         let start_idx = self.get_character_pos(position);
+        let start_byte = self.rope.char_to_byte(start_idx);
         self.rope.insert(start_idx, &character.to_string());
-        self.is_saved = false;
-        start_idx
+        self.measure_lines(position.line(), &character);
+        self.edited();
+        start_byte
     }
 
     /**
      * Returns the byte that was the starting position of the insert
      */
     pub fn delete(&mut self, start_idx: &Position, end_idx: &Position) -> Option<ByteRange> {
+        let result = self.remove(start_idx, end_idx);
+        if result.is_some() {
+            self.edited();
+        }
+        result
+    }
+
+    fn remove(&mut self, start_idx: &Position, end_idx: &Position) -> Option<ByteRange> {
         let start_line = self.rope.get_line(start_idx.line()).unwrap();
         let start_idx = self.get_character_pos(start_idx);
         let end_idx = self.get_character_pos(end_idx);
-        if start_line.len_chars() != 0 && end_idx <= self.rope.len_bytes() {
+        if start_line.len_chars() != 0 && start_idx < end_idx {
+            let bytes = ByteRange{start: self.rope.char_to_byte(start_idx), end: self.rope.char_to_byte(end_idx)};
             self.rope.remove(start_idx..end_idx);
-            self.is_saved = false;
-            return Some(ByteRange{start: start_idx, end: end_idx});
+            return Some(bytes);
         }
         None
     }
 }
+
+/**
+ * Returns the number of cells of the grid the grapheme takes, two for wide characters and one for the rest.
+ *
+ * Control characters and lone combining marks still take a cell so the cursor can be put on them.
+ */
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().clamp(1, 2)
+}
+
+fn longest_line(rope: &Rope) -> usize {
+    rope.lines().map(|line| line.len_chars()).max().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod document_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{grapheme_width, Document};
+
+    #[test]
+    fn test_display_columns() {
+        let document = Document::recovered(None, "a\u{4e16}\u{754c}e\u{301}\u{1F600}!\nab");
+        assert_eq!(grapheme_width("\u{4e16}"), 2);
+        assert_eq!(grapheme_width("e\u{301}"), 1);
+        assert_eq!(grapheme_width("\u{1F600}"), 2);
+
+        // The CJK characters and the emoji take two cells, the e with its accent one
+        let cells: Vec<usize> = (0..7).map(|column| document.display_column(0, column)).collect();
+        assert_eq!(cells, vec![0, 1, 3, 5, 6, 8, 9]);
+
+        assert_eq!(document.column_at_display(0, 2), 1);
+        assert_eq!(document.column_at_display(0, 3), 2);
+        assert_eq!(document.column_at_display(0, 7), 4);
+        // Past the end of the line, before its newline
+        assert_eq!(document.column_at_display(0, 40), 6);
+        assert_eq!(document.column_at_display(1, 40), 2);
+    }
+}
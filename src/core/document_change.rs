@@ -1,19 +1,41 @@
+use lsp_types::TextDocumentContentChangeEvent;
+
+use crate::lsp::encoding::{ChangeRange, PositionEncoding};
+
 use super::{document::ByteRange, selection::Range};
 
+#[derive(Debug, Clone)]
 pub struct DocumentChange {
     pub range: Range,
+    /// The replaced range as it was before the change, used to tell the language servers about it.
+    pub text_range: ChangeRange,
     pub byte_range: ByteRange,
     pub text: String,
-    pub file: String
+    pub file: String,
+    /// The version of the document after the change.
+    pub version: i32
 }
 
 impl DocumentChange {
-    pub fn new(range: Range, byte_range: ByteRange, text: String, file:String) -> Self {
+    pub fn new(range: Range, text_range: ChangeRange, byte_range: ByteRange, text: String, file:String, version: i32) -> Self {
         Self {
             range,
+            text_range,
             byte_range,
             text,
             file,
+            version,
+        }
+    }
+
+    /**
+     * Converts the change into an incremental change for a server that uses the encoding.
+     */
+    pub fn to_lsp(&self, encoding: PositionEncoding) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(self.text_range.to_lsp(encoding)),
+            range_length: None,
+            text: self.text.clone(),
         }
     }
 }
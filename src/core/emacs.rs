@@ -0,0 +1,622 @@
+use std::collections::VecDeque;
+
+use regex::{Regex, RegexBuilder};
+
+use super::{buffer::Buffer, history::end_of, keymap::KeymapRequest, motion::WordKind, position::Position};
+
+/**
+ * How many kills the kill ring keeps, the oldest ones are dropped.
+ */
+const KILL_RING_SIZE: usize = 60;
+
+/**
+ * `M-DEL`, the backspace key along with meta.
+ */
+pub const DEL: char = '\u{7f}';
+
+/**
+ * The keys the bindings of Emacs read. `Meta` is typed with Alt, or with Escape before the key.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmacsKey {
+    Char(char),
+    Ctrl(char),
+    Meta(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Esc,
+}
+
+impl EmacsKey {
+    /**
+     * Returns the name Emacs gives the key.
+     */
+    fn describe(&self) -> String {
+        match self {
+            EmacsKey::Char(character) => character.to_string(),
+            EmacsKey::Ctrl(' ') => "C-SPC".to_owned(),
+            EmacsKey::Ctrl(character) => format!("C-{}", character),
+            EmacsKey::Meta(DEL) => "M-DEL".to_owned(),
+            EmacsKey::Meta(character) => format!("M-{}", character),
+            EmacsKey::Enter => "RET".to_owned(),
+            EmacsKey::Backspace => "DEL".to_owned(),
+            EmacsKey::Delete => "<delete>".to_owned(),
+            EmacsKey::Tab => "TAB".to_owned(),
+            EmacsKey::Esc => "ESC".to_owned(),
+        }
+    }
+}
+
+/**
+ * The text that was killed, most recent first. Kills made in a row are joined into one entry.
+ */
+#[derive(Debug, Default)]
+struct KillRing {
+    entries: VecDeque<String>,
+}
+
+impl KillRing {
+    fn push(&mut self, text: String) {
+        self.entries.push_front(text);
+        self.entries.truncate(KILL_RING_SIZE);
+    }
+
+    /**
+     * Joins the text to the last kill, after it for a kill forward and before it for one backward.
+     */
+    fn append(&mut self, text: &str, forward: bool) {
+        match self.entries.front_mut() {
+            Some(last) if forward => last.push_str(text),
+            Some(last) => last.insert_str(0, text),
+            None => self.push(text.to_owned()),
+        }
+    }
+
+    /**
+     * Returns the entry, counting from the most recent one and going around the ring.
+     */
+    fn get(&self, index: usize) -> Option<&String> {
+        self.entries.get(index % self.entries.len().max(1))
+    }
+}
+
+/**
+ * What the last command was, killing again appends to its kill and `M-y` replaces the text of a yank.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum LastCommand {
+    #[default]
+    Other,
+    Kill,
+    Yank { start: Position, end: Position, index: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prefix {
+    /// `C-x`, followed by the key of the command
+    CtrlX,
+    /// Escape, the next key is read along with meta
+    Meta,
+}
+
+/**
+ * An incremental search, the cursor moves to the match as the query is typed.
+ */
+#[derive(Debug, Clone)]
+struct Search {
+    query: String,
+    forward: bool,
+    /// Where the cursor was when the search started, it goes back there when the search is cancelled.
+    origin: Position,
+    /// The start and the end of the current match.
+    found: Option<(Position, Position)>,
+    failed: bool,
+}
+
+/**
+ * The bindings of Emacs, in front of the buffer. The mark and the cursor make the region, which is shown as the
+ * selection while the mark is active.
+ */
+#[derive(Debug, Default)]
+pub struct Emacs {
+    mark: Option<Position>,
+    /// The region is shown and moving the cursor changes it.
+    active: bool,
+    kill_ring: KillRing,
+    last_command: LastCommand,
+    prefix: Option<Prefix>,
+    search: Option<Search>,
+    /// The query of the last search, `C-s` twice searches for it again.
+    last_query: String,
+}
+
+impl Emacs {
+    /**
+     * Returns what the status bar shows: the search, the prefix being typed or whether the mark is active.
+     */
+    pub fn status(&self) -> String {
+        if let Some(search) = self.search.as_ref() {
+            let failing = if search.failed { "Failing " } else { "" };
+            let direction = if search.forward { "" } else { " backward" };
+            return format!("{}I-search{}: {}", failing, direction, search.query)
+        }
+        match self.prefix {
+            Some(Prefix::CtrlX) => "C-x-".to_owned(),
+            Some(Prefix::Meta) => "ESC-".to_owned(),
+            None if self.active => "Mark set".to_owned(),
+            None => String::new(),
+        }
+    }
+
+    /**
+     * Drops the mark and the command being typed, like when another file is opened. The kill ring is kept.
+     */
+    pub fn reset(&mut self) {
+        self.mark = None;
+        self.active = false;
+        self.prefix = None;
+        self.search = None;
+        self.last_command = LastCommand::Other;
+    }
+
+    /**
+     * Returns whether the bindings take the key. The keys they don't bind are handled as they are without them.
+     */
+    pub fn takes(&self, key: EmacsKey) -> bool {
+        self.search.is_some() || self.prefix.is_some() || match key {
+            EmacsKey::Ctrl(character) => "abdefgknprsvwxy /_?".contains(character),
+            _ => true,
+        }
+    }
+
+    /**
+     * Handles a key, returns what the editor is asked to do.
+     */
+    pub fn handle(&mut self, buffer: &mut Buffer, key: EmacsKey) -> Vec<KeymapRequest> {
+        let mut requests = Vec::new();
+        // A selection made with the mouse is an active region
+        if !self.active && self.search.is_none() && !buffer.selection.is_empty() {
+            self.mark = Some(*buffer.selection.start());
+            self.active = true;
+        }
+        if self.search.is_some() && self.search_key(buffer, key) {
+            self.show_region(buffer);
+            return requests
+        }
+        let key = match (self.prefix.take(), key) {
+            (Some(Prefix::CtrlX), key) => {
+                self.ctrl_x(buffer, key, &mut requests);
+                self.last_command = LastCommand::Other;
+                self.show_region(buffer);
+                return requests
+            }
+            (Some(Prefix::Meta), EmacsKey::Char(character)) => EmacsKey::Meta(character),
+            (Some(Prefix::Meta), EmacsKey::Backspace) => EmacsKey::Meta(DEL),
+            (_, key) => key,
+        };
+        self.last_command = self.command(buffer, key, &mut requests);
+        self.show_region(buffer);
+        requests
+    }
+
+    fn command(&mut self, buffer: &mut Buffer, key: EmacsKey, requests: &mut Vec<KeymapRequest>) -> LastCommand {
+        let cursor = buffer.cursor.0;
+        let line = cursor.line();
+        let page = buffer.window.lines_height() as isize;
+        match key {
+            EmacsKey::Ctrl('f') => buffer.cursor.0 = forward_char(buffer, cursor),
+            EmacsKey::Ctrl('b') => buffer.cursor.0 = backward_char(buffer, cursor),
+            EmacsKey::Ctrl('n') => buffer.move_vertically(1),
+            EmacsKey::Ctrl('p') => buffer.move_vertically(-1),
+            EmacsKey::Ctrl('a') => buffer.cursor.0 = Position::new(line, 0),
+            EmacsKey::Ctrl('e') => buffer.cursor.0 = buffer.line_end(line),
+            EmacsKey::Ctrl('v') => buffer.move_vertically(page),
+            EmacsKey::Meta('v') => buffer.move_vertically(-page),
+            EmacsKey::Meta('f') => buffer.move_word(true, WordKind::Word),
+            EmacsKey::Meta('b') => buffer.move_word(false, WordKind::Word),
+            EmacsKey::Meta('<') => buffer.move_to_document_start(),
+            EmacsKey::Meta('>') => buffer.move_to_document_end(),
+            EmacsKey::Ctrl(' ') => {
+                self.mark = Some(cursor);
+                self.active = true;
+            }
+            EmacsKey::Ctrl('g') => self.active = false,
+            EmacsKey::Ctrl('x') => self.prefix = Some(Prefix::CtrlX),
+            // The command before the prefix is still the last one, `ESC y` pops a yank
+            EmacsKey::Esc => {
+                self.prefix = Some(Prefix::Meta);
+                return self.last_command
+            }
+            EmacsKey::Ctrl('k') => {
+                let end = buffer.line_end(line);
+                // Only the line break is left, or spaces before it
+                let end = if buffer.text_between(cursor, end).trim().is_empty() { buffer.after_line(line) } else { end };
+                if end == cursor {
+                    return LastCommand::Other
+                }
+                return self.kill(buffer, cursor, end, true, requests)
+            }
+            EmacsKey::Ctrl('w') => {
+                let Some((start, end)) = self.region(buffer, requests) else {
+                    return LastCommand::Other
+                };
+                return self.kill(buffer, start, end, cursor == end, requests)
+            }
+            EmacsKey::Meta('w') => {
+                if let Some((start, end)) = self.region(buffer, requests) {
+                    let text = buffer.text_between(start, end);
+                    requests.push(KeymapRequest::Copy(text.clone()));
+                    self.kill_ring.push(text);
+                    self.active = false;
+                }
+            }
+            EmacsKey::Meta('d') | EmacsKey::Meta(DEL) => {
+                let forward = key == EmacsKey::Meta('d');
+                buffer.move_word(forward, WordKind::Word);
+                let target = buffer.cursor.0;
+                let (start, end) = if forward { (cursor, target) } else { (target, cursor) };
+                return self.kill(buffer, start, end, forward, requests)
+            }
+            EmacsKey::Ctrl('y') => return self.yank(buffer, requests),
+            EmacsKey::Meta('y') => {
+                let LastCommand::Yank { start, end, index } = self.last_command else {
+                    requests.push(KeymapRequest::Error("Previous command was not a yank".to_owned()));
+                    return LastCommand::Other
+                };
+                let index = (index + 1) % self.kill_ring.entries.len().max(1);
+                let text = self.kill_ring.get(index).cloned().unwrap_or_default();
+                let new_end = end_of(start, &text);
+                buffer.replace(&start, &end, text);
+                buffer.cursor.0 = new_end;
+                return LastCommand::Yank { start, end: new_end, index }
+            }
+            EmacsKey::Ctrl('/') | EmacsKey::Ctrl('_') => {
+                buffer.undo();
+            }
+            EmacsKey::Ctrl('?') => {
+                buffer.redo();
+            }
+            EmacsKey::Ctrl('s') | EmacsKey::Ctrl('r') => {
+                self.active = false;
+                let forward = key == EmacsKey::Ctrl('s');
+                self.search = Some(Search { query: String::new(), forward, origin: cursor, found: None, failed: false });
+            }
+            EmacsKey::Ctrl('d') | EmacsKey::Delete | EmacsKey::Backspace if self.active => {
+                if let Some((start, end)) = self.region(buffer, requests) {
+                    buffer.replace(&start, &end, String::new());
+                    buffer.cursor.0 = start;
+                }
+                self.active = false;
+            }
+            EmacsKey::Ctrl('d') | EmacsKey::Delete => {
+                if cursor != forward_char(buffer, cursor) {
+                    collapse(buffer, cursor);
+                    buffer.delete();
+                }
+            }
+            EmacsKey::Backspace => {
+                collapse(buffer, cursor);
+                buffer.backspace();
+            }
+            EmacsKey::Enter | EmacsKey::Tab | EmacsKey::Char(_) => {
+                self.active = false;
+                collapse(buffer, cursor);
+                match key {
+                    EmacsKey::Enter => buffer.new_line(),
+                    EmacsKey::Tab => buffer.indent(),
+                    EmacsKey::Char(character) => {
+                        buffer.insert(character.to_string());
+                    }
+                    _ => (),
+                }
+            }
+            key => requests.push(KeymapRequest::Error(format!("{} is undefined", key.describe()))),
+        }
+        LastCommand::Other
+    }
+
+    /**
+     * The commands that follow `C-x`.
+     */
+    fn ctrl_x(&mut self, buffer: &mut Buffer, key: EmacsKey, requests: &mut Vec<KeymapRequest>) {
+        match key {
+            EmacsKey::Ctrl('s') | EmacsKey::Char('s') => requests.push(KeymapRequest::Save),
+            EmacsKey::Ctrl('f') | EmacsKey::Char('b') => requests.push(KeymapRequest::PickFile),
+            EmacsKey::Ctrl('x') => {
+                if let Some(mark) = self.mark.replace(buffer.cursor.0) {
+                    buffer.cursor.0 = mark;
+                    self.active = true;
+                }
+            }
+            EmacsKey::Char('h') => {
+                self.mark = Some(Position::default());
+                buffer.move_to_document_end();
+                self.active = true;
+            }
+            EmacsKey::Char('u') => {
+                buffer.undo();
+            }
+            EmacsKey::Ctrl('g') => (),
+            key => requests.push(KeymapRequest::Error(format!("C-x {} is undefined", key.describe()))),
+        }
+    }
+
+    /**
+     * Returns the region between the mark and the cursor, even if the mark isn't active.
+     */
+    fn region(&self, buffer: &Buffer, requests: &mut Vec<KeymapRequest>) -> Option<(Position, Position)> {
+        let Some(mark) = self.mark else {
+            requests.push(KeymapRequest::Error("The mark is not set now, so there is no region".to_owned()));
+            return None
+        };
+        let cursor = buffer.cursor.0;
+        Some(if mark < cursor { (mark, cursor) } else { (cursor, mark) })
+    }
+
+    /**
+     * Deletes the text into the kill ring, and to the clipboard of the system.
+     */
+    fn kill(&mut self, buffer: &mut Buffer, start: Position, end: Position, forward: bool, requests: &mut Vec<KeymapRequest>) -> LastCommand {
+        let text = buffer.text_between(start, end);
+        if self.last_command == LastCommand::Kill {
+            self.kill_ring.append(&text, forward);
+        } else {
+            self.kill_ring.push(text);
+        }
+        if let Some(killed) = self.kill_ring.get(0) {
+            requests.push(KeymapRequest::Copy(killed.clone()));
+        }
+        buffer.replace(&start, &end, String::new());
+        collapse(buffer, start);
+        self.active = false;
+        LastCommand::Kill
+    }
+
+    /**
+     * Inserts the last kill and sets the mark before it. With nothing killed yet, the clipboard is pasted.
+     */
+    fn yank(&mut self, buffer: &mut Buffer, requests: &mut Vec<KeymapRequest>) -> LastCommand {
+        let start = buffer.cursor.0;
+        self.active = false;
+        collapse(buffer, start);
+        let Some(text) = self.kill_ring.get(0).cloned() else {
+            requests.push(KeymapRequest::Paste);
+            return LastCommand::Other
+        };
+        let end = end_of(start, &text);
+        buffer.replace(&start, &start, text);
+        buffer.cursor.0 = end;
+        self.mark = Some(start);
+        LastCommand::Yank { start, end, index: 0 }
+    }
+
+    /**
+     * Handles a key during a search, returns `false` if it ended the search and is a command of its own.
+     */
+    fn search_key(&mut self, buffer: &mut Buffer, key: EmacsKey) -> bool {
+        let Some(search) = self.search.as_mut() else {
+            return false
+        };
+        match key {
+            EmacsKey::Char(character) => {
+                search.query.push(character);
+                find(buffer, search, false);
+            }
+            EmacsKey::Ctrl('s') | EmacsKey::Ctrl('r') => {
+                search.forward = key == EmacsKey::Ctrl('s');
+                if search.query.is_empty() {
+                    search.query = self.last_query.clone();
+                    find(buffer, search, false);
+                } else {
+                    find(buffer, search, true);
+                }
+            }
+            EmacsKey::Backspace => {
+                search.query.pop();
+                search.found = None;
+                find(buffer, search, false);
+            }
+            EmacsKey::Ctrl('g') => {
+                buffer.cursor.0 = search.origin;
+                self.search = None;
+            }
+            // Enter only ends the search, another key ends it and is handled on its own
+            key => {
+                self.mark = Some(search.origin);
+                if !search.query.is_empty() {
+                    self.last_query = search.query.clone();
+                }
+                self.search = None;
+                return key == EmacsKey::Enter
+            }
+        }
+        true
+    }
+
+    /**
+     * Shows the region as the selection while the mark is active, or the match of the search.
+     */
+    fn show_region(&self, buffer: &mut Buffer) {
+        let cursor = buffer.cursor.0;
+        let (start, end) = match (self.search.as_ref().and_then(|search| search.found), self.mark) {
+            (Some(found), _) => found,
+            (None, Some(mark)) if self.active => (mark, cursor),
+            _ => (cursor, cursor),
+        };
+        buffer.selection.set_start(start);
+        buffer.selection.set_end(end);
+    }
+}
+
+/**
+ * Puts the cursor at the position with nothing selected.
+ */
+fn collapse(buffer: &mut Buffer, position: Position) {
+    buffer.selection.set_start(position);
+    buffer.selection.set_end(position);
+    buffer.cursor.0 = position;
+}
+
+fn forward_char(buffer: &Buffer, position: Position) -> Position {
+    if position.character() < buffer.line_end(position.line()).character() {
+        Position::new(position.line(), position.character() + 1)
+    } else {
+        buffer.after_line(position.line())
+    }
+}
+
+fn backward_char(buffer: &Buffer, position: Position) -> Position {
+    match (position.line(), position.character()) {
+        (0, 0) => position,
+        (line, 0) => buffer.line_end(line - 1),
+        (line, character) => Position::new(line, character - 1),
+    }
+}
+
+/**
+ * Returns the regular expression of the query. Like in Emacs, a query without capitals ignores the case.
+ */
+fn query_regex(query: &str) -> Option<Regex> {
+    RegexBuilder::new(&regex::escape(query))
+        .case_insensitive(!query.chars().any(char::is_uppercase))
+        .build()
+        .ok()
+}
+
+/**
+ * Moves the search to the match of its query. A search that grows stays on the match if it still matches, `next`
+ * goes on to the match after it, or around the document once the search failed.
+ */
+fn find(buffer: &mut Buffer, search: &mut Search, next: bool) {
+    if search.query.is_empty() {
+        search.found = None;
+        search.failed = false;
+        buffer.cursor.0 = search.origin;
+        return
+    }
+    let Some(regex) = query_regex(&search.query) else {
+        return
+    };
+    let document = buffer.buffer();
+    let text = buffer.get_string();
+    let byte = |position: Position| document.get_byte_pos(&position);
+    let wrap = next && search.failed;
+    let found = if search.forward {
+        let from = match search.found {
+            _ if wrap => 0,
+            Some((_, end)) if next => byte(end),
+            Some((start, _)) => byte(start),
+            None => byte(search.origin),
+        };
+        regex.find_at(&text, from)
+    } else {
+        let limit = match search.found {
+            _ if wrap => text.len() + 1,
+            Some((start, _)) if next => byte(start),
+            Some((start, _)) => byte(start) + 1,
+            None => byte(search.origin),
+        };
+        regex.find_iter(&text).take_while(|found| found.start() < limit).last()
+    };
+    let found = found.map(|found| (document.position_of_byte(found.start()), document.position_of_byte(found.end())));
+    drop(document);
+    match found {
+        Some((start, end)) => {
+            search.found = Some((start, end));
+            search.failed = false;
+            buffer.cursor.0 = if search.forward { end } else { start };
+        }
+        None => search.failed = true,
+    }
+}
+
+#[cfg(test)]
+mod emacs_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, keymap::KeymapRequest, position::Position};
+
+    use super::{Emacs, EmacsKey};
+
+    fn press(emacs: &mut Emacs, buffer: &mut Buffer, keys: &[EmacsKey]) -> Vec<KeymapRequest> {
+        let mut requests = Vec::new();
+        for key in keys {
+            requests.extend(emacs.handle(buffer, *key));
+            buffer.seal_history();
+        }
+        requests
+    }
+
+    fn typed(text: &str) -> Vec<EmacsKey> {
+        text.chars().map(EmacsKey::Char).collect()
+    }
+
+    #[test]
+    fn test_kill_ring() {
+        use EmacsKey::{Ctrl, Meta};
+        let mut emacs = Emacs::default();
+        let mut buffer = Buffer::from_source("first line\nsecond line\nthird\n");
+
+        // Kills in a row are yanked back as one
+        let requests = press(&mut emacs, &mut buffer, &[Ctrl('k'), Ctrl('k')]);
+        assert_eq!(buffer.get_string(), "second line\nthird\n");
+        assert_eq!(requests.last(), Some(&KeymapRequest::Copy("first line\n".to_owned())));
+        press(&mut emacs, &mut buffer, &[Meta('f'), Meta('d'), Ctrl('e')]);
+        assert_eq!(buffer.line_text(0), "second");
+        press(&mut emacs, &mut buffer, &[Ctrl('y')]);
+        assert_eq!(buffer.line_text(0), "second line");
+        press(&mut emacs, &mut buffer, &[Meta('y')]);
+        assert_eq!(buffer.get_string(), "secondfirst line\n\nthird\n");
+        assert_eq!(buffer.cursor.0, Position::new(1, 0));
+        press(&mut emacs, &mut buffer, &[EmacsKey::Esc, EmacsKey::Char('y')]);
+        assert_eq!(buffer.line_text(0), "second line");
+
+        // The region from the mark is killed, and the mark can be swapped with the cursor
+        press(&mut emacs, &mut buffer, &[Ctrl('a'), Ctrl(' '), Ctrl('f'), Ctrl('f'), Ctrl('f')]);
+        assert_eq!(emacs.status(), "Mark set");
+        assert_eq!(*buffer.selection.end(), Position::new(0, 3));
+        let requests = press(&mut emacs, &mut buffer, &[Ctrl('x'), Ctrl('x'), Ctrl('w')]);
+        assert_eq!(buffer.line_text(0), "ond line");
+        assert_eq!(requests, vec![KeymapRequest::Copy("sec".to_owned())]);
+        let requests = press(&mut emacs, &mut buffer, &[Meta('q')]);
+        assert_eq!(requests, vec![KeymapRequest::Error("M-q is undefined".to_owned())]);
+    }
+
+    #[test]
+    fn test_incremental_search() {
+        use EmacsKey::Ctrl;
+        let mut emacs = Emacs::default();
+        let mut buffer = Buffer::from_source("let alpha = Alpha::new();\nalpha.run();\n");
+        press(&mut emacs, &mut buffer, &[Ctrl('s')]);
+        press(&mut emacs, &mut buffer, &typed("alp"));
+        assert_eq!(buffer.cursor.0, Position::new(0, 7));
+        assert_eq!(emacs.status(), "I-search: alp");
+        // Without capitals the case is ignored
+        press(&mut emacs, &mut buffer, &[Ctrl('s'), Ctrl('s')]);
+        assert_eq!(buffer.cursor.0, Position::new(1, 3));
+        press(&mut emacs, &mut buffer, &typed("x"));
+        assert_eq!(emacs.status(), "Failing I-search: alpx");
+        press(&mut emacs, &mut buffer, &[EmacsKey::Backspace]);
+        assert_eq!(buffer.cursor.0, Position::new(0, 7));
+        press(&mut emacs, &mut buffer, &[Ctrl('s'), Ctrl('r')]);
+        assert_eq!(buffer.cursor.0, Position::new(0, 4));
+        press(&mut emacs, &mut buffer, &[EmacsKey::Enter]);
+        assert_eq!(emacs.status(), "");
+        assert!(buffer.selection.is_empty());
+
+        // A key that isn't part of the search ends it and is handled
+        press(&mut emacs, &mut buffer, &[Ctrl('r'), Ctrl('r'), Ctrl('e')]);
+        assert_eq!(buffer.cursor.0, Position::new(0, 25));
+        press(&mut emacs, &mut buffer, &[Ctrl('s')]);
+        press(&mut emacs, &mut buffer, &typed("run"));
+        press(&mut emacs, &mut buffer, &[Ctrl('g')]);
+        assert_eq!(buffer.cursor.0, Position::new(0, 25));
+
+        let requests = press(&mut emacs, &mut buffer, &[Ctrl('x'), Ctrl('s'), Ctrl('x'), EmacsKey::Char('b'), Ctrl('x'), EmacsKey::Char('h')]);
+        assert_eq!(requests, vec![KeymapRequest::Save, KeymapRequest::PickFile]);
+        assert_eq!(*buffer.selection.start(), Position::new(0, 0));
+        assert_eq!(*buffer.selection.end(), Position::new(2, 0));
+    }
+}
@@ -0,0 +1,34 @@
+use std::io::{Error, ErrorKind};
+
+/**
+ * Why a document couldn't be opened or saved.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DocumentError {
+    #[error("The file doesn't exist")]
+    NotFound,
+    #[error("Permission denied")]
+    PermissionDenied,
+    #[error("No file was chosen")]
+    Cancelled,
+    #[error("The file isn't valid {0}")]
+    InvalidEncoding(&'static str),
+    #[error("The text has characters that can't be saved as {0}")]
+    Unencodable(&'static str),
+    #[error("The path isn't valid UTF-8")]
+    InvalidPath,
+    #[error("{0}")]
+    Io(String),
+}
+
+impl From<Error> for DocumentError {
+    fn from(error: Error) -> Self {
+        match error.kind() {
+            ErrorKind::NotFound => DocumentError::NotFound,
+            ErrorKind::PermissionDenied => DocumentError::PermissionDenied,
+            _ => DocumentError::Io(error.to_string()),
+        }
+    }
+}
+
+pub type DocumentResult<T> = Result<T, DocumentError>;
@@ -0,0 +1,260 @@
+use std::{
+    fs::{self, File},
+    io::{Error, Write},
+    path::Path,
+};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+
+use super::error::{DocumentError, DocumentResult};
+
+/**
+ * The encodings offered by the status bar. Files can still be opened in any encoding that has a BOM.
+ */
+pub const ENCODINGS: [&Encoding; 14] = [
+    UTF_8,
+    UTF_16LE,
+    UTF_16BE,
+    WINDOWS_1252,
+    encoding_rs::ISO_8859_2,
+    encoding_rs::ISO_8859_15,
+    encoding_rs::WINDOWS_1250,
+    encoding_rs::WINDOWS_1251,
+    encoding_rs::KOI8_R,
+    encoding_rs::SHIFT_JIS,
+    encoding_rs::EUC_JP,
+    encoding_rs::EUC_KR,
+    encoding_rs::GBK,
+    encoding_rs::BIG5,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+}
+
+/**
+ * How the text of a document is stored on disk.
+ *
+ * The text of a document always uses `\n`, the line ending is only applied when it's saved.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFormat {
+    pub encoding: &'static Encoding,
+    pub has_bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        Self { encoding: UTF_8, has_bom: false, line_ending: LineEnding::Lf }
+    }
+}
+
+/**
+ * What is done to the text of a document before it's saved.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveOptions {
+    pub trim_trailing_whitespace: bool,
+    pub insert_final_newline: bool,
+}
+
+impl FileFormat {
+    /**
+     * Detects the format of the bytes and returns their text.
+     *
+     * The encoding comes from the BOM. Without one, the bytes are UTF-8 if they are valid UTF-8 and Windows-1252 otherwise.
+     */
+    pub fn decode(bytes: &[u8]) -> (String, Self) {
+        let (encoding, has_bom) = match Encoding::for_bom(bytes) {
+            Some((encoding, _)) => (encoding, true),
+            None if std::str::from_utf8(bytes).is_ok() => (UTF_8, false),
+            None => (WINDOWS_1252, false),
+        };
+        let (text, format, _) = Self::decode_with(bytes, encoding, has_bom);
+        (text, format)
+    }
+
+    /**
+     * Returns the text of the bytes in the encoding, used when the detected encoding was wrong.
+     *
+     * Fails if the bytes aren't valid in the encoding.
+     */
+    pub fn decode_as(bytes: &[u8], encoding: &'static Encoding) -> DocumentResult<(String, Self)> {
+        let has_bom = Encoding::for_bom(bytes).is_some_and(|(bom_encoding, _)| bom_encoding == encoding);
+        let (text, format, had_errors) = Self::decode_with(bytes, encoding, has_bom);
+        if had_errors {
+            return Err(DocumentError::InvalidEncoding(encoding.name()))
+        }
+        Ok((text, format))
+    }
+
+    fn decode_with(bytes: &[u8], encoding: &'static Encoding, has_bom: bool) -> (String, Self, bool) {
+        let bytes = match Encoding::for_bom(bytes) {
+            Some((_, length)) if has_bom => &bytes[length..],
+            _ => bytes,
+        };
+        let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+        // The first line ending decides, files that mix both are saved with it
+        let line_ending = match text.find('\n') {
+            Some(index) if text[..index].ends_with('\r') => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        };
+        let text = match line_ending {
+            LineEnding::CrLf => text.replace("\r\n", "\n"),
+            LineEnding::Lf => text.into_owned(),
+        };
+        (text, Self { encoding, has_bom, line_ending }, had_errors)
+    }
+
+    /**
+     * Returns the bytes of the text in this format. Fails if a character of the text can't be encoded.
+     */
+    pub fn encode(&self, text: &str) -> DocumentResult<Vec<u8>> {
+        let text = match self.line_ending {
+            LineEnding::CrLf => text.replace('\n', "\r\n"),
+            LineEnding::Lf => text.to_owned(),
+        };
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        // encoding_rs only decodes UTF-16, its encoder writes UTF-8 instead
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            let is_le = self.encoding == UTF_16LE;
+            let unit_bytes = |unit: u16| if is_le { unit.to_le_bytes() } else { unit.to_be_bytes() };
+            if self.has_bom {
+                bytes.extend(unit_bytes(0xFEFF));
+            }
+            for unit in text.encode_utf16() {
+                bytes.extend(unit_bytes(unit));
+            }
+            return Ok(bytes)
+        }
+
+        if self.has_bom && self.encoding == UTF_8 {
+            bytes.extend([0xEF, 0xBB, 0xBF]);
+        }
+        let (encoded, _, had_errors) = self.encoding.encode(&text);
+        if had_errors {
+            return Err(DocumentError::Unencodable(self.encoding.name()))
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+
+    /**
+     * Only Unicode encodings have a BOM.
+     */
+    pub fn can_have_bom(&self) -> bool {
+        self.encoding == UTF_8 || self.encoding == UTF_16LE || self.encoding == UTF_16BE
+    }
+
+    /**
+     * Returns the format with another encoding. UTF-16 files get a BOM, other files keep theirs if they can.
+     */
+    pub fn with_encoding(&self, encoding: &'static Encoding) -> Self {
+        let is_utf16 = encoding == UTF_16LE || encoding == UTF_16BE;
+        let has_bom = is_utf16 || (self.has_bom && encoding == UTF_8);
+        Self { encoding, has_bom, line_ending: self.line_ending }
+    }
+}
+
+/**
+ * Reads the file and returns its text along with its format.
+ */
+pub fn read_text(path: &Path) -> DocumentResult<(String, FileFormat)> {
+    Ok(FileFormat::decode(&fs::read(path)?))
+}
+
+/**
+ * Writes the file through a temporary file in the same folder that replaces it once it's on disk, so a crash in
+ * the middle of a save can't leave half of a file. The permissions of the file are kept.
+ */
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    // A symbolic link stays a link, the file it points to is replaced
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let folder = match path.parent() {
+        Some(folder) if !folder.as_os_str().is_empty() => folder,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut temporary = tempfile::Builder::new()
+        .prefix(&format!(".{}.", name))
+        .suffix(".tmp")
+        .tempfile_in(folder)?;
+    temporary.write_all(bytes)?;
+    if let Ok(metadata) = fs::metadata(&path) {
+        temporary.as_file().set_permissions(metadata.permissions())?;
+    }
+    temporary.as_file().sync_all()?;
+    temporary.persist(&path).map_err(|e| e.error)?;
+    // The rename is only durable once the folder is on disk too
+    #[cfg(unix)]
+    if let Ok(folder) = File::open(folder) {
+        let _ = folder.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod file_format_tests {
+    use std::fs;
+
+    use encoding_rs::{UTF_16LE, UTF_8, WINDOWS_1252};
+    use pretty_assertions::assert_eq;
+
+    use crate::core::error::DocumentError;
+
+    use super::{read_text, write_atomic, FileFormat, LineEnding};
+
+    #[test]
+    fn test_decode_and_encode() {
+        let (text, format) = FileFormat::decode(b"\xEF\xBB\xBFfn main() {\r\n}\r\n");
+        assert_eq!(text, "fn main() {\n}\n");
+        assert_eq!(format, FileFormat { encoding: UTF_8, has_bom: true, line_ending: LineEnding::CrLf });
+        assert_eq!(format.encode(&text).unwrap(), b"\xEF\xBB\xBFfn main() {\r\n}\r\n");
+
+        let (text, format) = FileFormat::decode(b"caf\xE9\n");
+        assert_eq!(text, "caf\u{e9}\n");
+        assert_eq!(format, FileFormat { encoding: WINDOWS_1252, has_bom: false, line_ending: LineEnding::Lf });
+        assert_eq!(format.encode("\u{1F600}"), Err(DocumentError::Unencodable("windows-1252")));
+        assert_eq!(FileFormat::decode_as(b"caf\xE9", UTF_8), Err(DocumentError::InvalidEncoding("UTF-8")));
+
+        let utf16 = FileFormat { encoding: UTF_16LE, has_bom: true, line_ending: LineEnding::Lf };
+        let bytes = utf16.encode("a\n").unwrap();
+        assert_eq!(bytes, vec![0xFF, 0xFE, b'a', 0, b'\n', 0]);
+        assert_eq!(FileFormat::decode(&bytes), ("a\n".to_owned(), utf16));
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.sh");
+        fs::write(&path, "old").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        write_atomic(&path, b"echo hi\r\n").unwrap();
+        assert_eq!(read_text(&path).unwrap().0, "echo hi\n");
+        // Only the file is left in the folder
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o755);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
+
+use super::watcher::{FileChange, FileEvent};
+
+/**
+ * Folders that are never indexed, even if they aren't in a `.gitignore`.
+ */
+pub const DEFAULT_EXCLUDES: [&str; 2] = [".git", "target"];
+
+/**
+ * How many recently opened files are remembered.
+ */
+const RECENT_FILES: usize = 20;
+
+/**
+ * A file of the index that matches the filter of the file selector.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatch {
+    pub path: PathBuf,
+    /// The path relative to the workspace, as it's shown and matched.
+    pub relative: String,
+    /// The indices of the characters of `relative` that matched the filter.
+    pub indices: Vec<usize>,
+}
+
+/**
+ * Every file of the workspace that isn't ignored by a `.gitignore`, an `.ignore` or the excludes.
+ *
+ * The index is built once in the background and then kept up to date with the events of the file watcher.
+ */
+#[derive(Debug, Clone)]
+pub struct FileIndex {
+    root: PathBuf,
+    excludes: Vec<String>,
+    files: BTreeSet<PathBuf>,
+    /// The folders that were walked. Files created outside of them are ignored.
+    folders: HashSet<PathBuf>,
+    recent: VecDeque<PathBuf>,
+}
+
+impl FileIndex {
+    /**
+     * Walks the whole workspace. This can take a while on large workspaces, so it should be run in the background.
+     */
+    pub fn build(root: PathBuf, excludes: &[&str]) -> Self {
+        let mut index = Self {
+            root: root.clone(),
+            excludes: excludes.iter().map(|exclude| exclude.to_string()).collect(),
+            files: BTreeSet::new(),
+            folders: HashSet::new(),
+            recent: VecDeque::new(),
+        };
+        index.folders.insert(root.clone());
+        index.scan(&root, None);
+        index
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    /**
+     * Adds the files within the folder that aren't ignored. With a max depth of 1 only the folder's own entries are read.
+     */
+    fn scan(&mut self, folder: &Path, max_depth: Option<usize>) -> Vec<PathBuf> {
+        let mut overrides = OverrideBuilder::new(&self.root);
+        for exclude in self.excludes.iter() {
+            if let Err(e) = overrides.add(&format!("!{}", exclude)) {
+                eprintln!("Invalid exclude {}: {}", exclude, e);
+            }
+        }
+        let mut walker = WalkBuilder::new(folder);
+        walker
+            .max_depth(max_depth)
+            .hidden(false)
+            .require_git(false);
+        match overrides.build() {
+            Ok(overrides) => {
+                walker.overrides(overrides);
+            }
+            Err(e) => eprintln!("Invalid excludes: {}", e),
+        }
+
+        let mut found = Vec::new();
+        for entry in walker.build() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // Folders that can't be read are skipped
+                Err(_) => continue,
+            };
+            if entry.depth() == 0 {
+                continue
+            }
+            let path = entry.into_path();
+            if path.is_dir() {
+                self.folders.insert(path.clone());
+            } else {
+                self.files.insert(path.clone());
+            }
+            found.push(path);
+        }
+        found
+    }
+
+    /**
+     * Updates the index with the changes of the file watcher.
+     *
+     * Returns true if an ignore file changed, the whole index has to be built again then.
+     */
+    pub fn files_changed(&mut self, events: &[FileEvent]) -> bool {
+        for event in events {
+            let name = event.path.file_name().and_then(|name| name.to_str());
+            if matches!(name, Some(".gitignore" | ".ignore")) {
+                return true
+            }
+            match event.change {
+                FileChange::Created => self.created(&event.path),
+                FileChange::Deleted => self.deleted(&event.path),
+                FileChange::Changed => (),
+            }
+        }
+        false
+    }
+
+    fn created(&mut self, path: &Path) {
+        let Some(parent) = path.parent() else {
+            return
+        };
+        // The parent was ignored or excluded
+        if !self.folders.contains(parent) {
+            return
+        }
+        // Reading the parent applies its ignore files to the new path
+        let found = self.scan(parent, Some(1));
+        if path.is_dir() && found.iter().any(|found| found == path) {
+            self.scan(path, None);
+        }
+    }
+
+    fn deleted(&mut self, path: &Path) {
+        self.files.retain(|file| !file.starts_with(path));
+        self.folders.retain(|folder| !folder.starts_with(path));
+        self.recent.retain(|file| !file.starts_with(path));
+    }
+
+    /**
+     * Remembers the file as the most recently opened one.
+     */
+    pub fn opened(&mut self, path: &Path) {
+        self.recent.retain(|file| file != path);
+        self.recent.push_front(path.to_owned());
+        self.recent.truncate(RECENT_FILES);
+    }
+
+    pub fn recent(&self) -> impl Iterator<Item = &PathBuf> {
+        self.recent.iter()
+    }
+
+    /**
+     * Keeps the recent files of a previous index of the same workspace.
+     */
+    pub fn set_recent(&mut self, recent: impl Iterator<Item = PathBuf>) {
+        self.recent = recent.filter(|file| self.files.contains(file)).collect();
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).to_string_lossy().into_owned()
+    }
+
+    /**
+     * Returns up to `limit` files that match the filter, best match first.
+     *
+     * Without a filter, the recently opened files come first. Recently opened files also win ties.
+     */
+    pub fn search(&self, filter: &str, limit: usize) -> Vec<FileMatch> {
+        let recency = |path: &Path| self.recent.iter().position(|file| file == path).unwrap_or(usize::MAX);
+        if filter.is_empty() {
+            let recent = self.recent.iter().filter(|file| self.files.contains(*file));
+            let others = self.files.iter().filter(|file| !self.recent.contains(file));
+            return recent
+                .chain(others)
+                .take(limit)
+                .map(|path| FileMatch { path: path.clone(), relative: self.relative(path), indices: Vec::new() })
+                .collect()
+        }
+
+        let matcher = SkimMatcherV2::default().smart_case();
+        let mut matches: Vec<(i64, usize, FileMatch)> = self.files
+            .iter()
+            .filter_map(|path| {
+                let relative = self.relative(path);
+                let (score, indices) = matcher.fuzzy_indices(&relative, filter)?;
+                Some((score, recency(path), FileMatch { path: path.clone(), relative, indices }))
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then(a.1.cmp(&b.1))
+                .then(a.2.relative.len().cmp(&b.2.relative.len()))
+        });
+        matches.into_iter().take(limit).map(|(_, _, file)| file).collect()
+    }
+}
+
+#[cfg(test)]
+mod file_index_tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::core::watcher::{FileChange, FileEvent};
+
+    use super::{FileIndex, DEFAULT_EXCLUDES};
+
+    fn relative(index: &FileIndex, filter: &str) -> Vec<String> {
+        index.search(filter, 10).into_iter().map(|file| file.relative).collect()
+    }
+
+    #[test]
+    fn test_build_respects_ignores() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::create_dir_all(dir.path().join("dist")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "dist/\n").unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        fs::write(dir.path().join("target/debug/app"), "").unwrap();
+        fs::write(dir.path().join("dist/bundle.js"), "").unwrap();
+
+        let index = FileIndex::build(dir.path().to_owned(), &DEFAULT_EXCLUDES);
+        assert_eq!(relative(&index, ""), vec![".gitignore".to_owned(), "src/main.rs".to_owned()]);
+    }
+
+    #[test]
+    fn test_events_update_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.path().join("main.rs"), "").unwrap();
+        let mut index = FileIndex::build(dir.path().to_owned(), &DEFAULT_EXCLUDES);
+
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(dir.path().join("debug.log"), "").unwrap();
+        fs::remove_file(dir.path().join("main.rs")).unwrap();
+        let rebuild = index.files_changed(&[
+            FileEvent::new(dir.path().join("src"), FileChange::Created),
+            FileEvent::new(dir.path().join("debug.log"), FileChange::Created),
+            FileEvent::new(dir.path().join("main.rs"), FileChange::Deleted),
+        ]);
+        assert!(!rebuild);
+        assert_eq!(relative(&index, ""), vec![".gitignore".to_owned(), "src/lib.rs".to_owned()]);
+
+        assert!(index.files_changed(&[FileEvent::new(dir.path().join(".gitignore"), FileChange::Changed)]));
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/core")).unwrap();
+        for file in ["src/core/buffer.rs", "src/lib.rs", "src/core/document.rs", "README.md"] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        let mut index = FileIndex::build(dir.path().to_owned(), &DEFAULT_EXCLUDES);
+
+        let matches = index.search("buf", 10);
+        assert_eq!(matches[0].relative, "src/core/buffer.rs");
+        assert_eq!(matches[0].indices, vec![9, 10, 11]);
+        assert!(index.search("zzz", 10).is_empty());
+
+        index.opened(&dir.path().join("src/lib.rs"));
+        assert_eq!(relative(&index, "")[0], "src/lib.rs");
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Error,
+    path::{Component, Path, PathBuf},
+};
+
+use ignore::WalkBuilder;
+
+use super::watcher::FileEvent;
+
+/**
+ * A file or folder shown in the file explorer.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/**
+ * The files of the workspace, as shown by the file explorer.
+ *
+ * Folders are only read the first time they are expanded. The `.gitignore` files of the
+ * workspace are honoured, even if the workspace isn't a git repository.
+ */
+#[derive(Debug)]
+pub struct FileTree {
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    /// The entries of every folder that was read, sorted with the folders first.
+    children: HashMap<PathBuf, Vec<TreeEntry>>,
+}
+
+impl FileTree {
+    pub fn new(root: PathBuf) -> Self {
+        let mut tree = Self {
+            root: root.clone(),
+            expanded: HashSet::new(),
+            children: HashMap::new(),
+        };
+        tree.expanded.insert(root.clone());
+        tree.load(&root);
+        tree
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /**
+     * Returns the path a name typed in the explorer stands for, relative to the folder. Names that lead outside of
+     * the workspace, with `..` or as an absolute path, are refused.
+     */
+    pub fn resolve(&self, folder: &Path, name: &str) -> Option<PathBuf> {
+        let path = normalize(&folder.join(name));
+        (path != normalize(&self.root) && path.starts_with(normalize(&self.root))).then_some(path)
+    }
+
+    pub fn is_expanded(&self, folder: &Path) -> bool {
+        self.expanded.contains(folder)
+    }
+
+    /**
+     * Expands or collapses the folder. The folder is read the first time it's expanded.
+     */
+    pub fn toggle(&mut self, folder: &Path) {
+        if self.expanded.remove(folder) {
+            return
+        }
+        self.expanded.insert(folder.to_owned());
+        if !self.children.contains_key(folder) {
+            self.load(folder);
+        }
+    }
+
+    /**
+     * Returns the expanded folders, without the root.
+     */
+    pub fn expanded_folders(&self) -> Vec<PathBuf> {
+        let mut folders: Vec<PathBuf> = self.expanded.iter().filter(|folder| **folder != self.root).cloned().collect();
+        folders.sort();
+        folders
+    }
+
+    /**
+     * Expands the folder if it's within the workspace and still exists.
+     */
+    pub fn expand(&mut self, folder: &Path) {
+        if folder.starts_with(&self.root) && folder.is_dir() && !self.is_expanded(folder) {
+            self.toggle(folder);
+        }
+    }
+
+    /**
+     * Expands every folder between the root and the path, so the path is visible.
+     */
+    pub fn reveal(&mut self, path: &Path) {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return
+        };
+        let mut folder = self.root.clone();
+        for component in relative.parent().into_iter().flat_map(Path::components) {
+            folder.push(component);
+            if !self.is_expanded(&folder) {
+                self.toggle(&folder.clone());
+            }
+        }
+    }
+
+    /**
+     * Reads the folder again if it was already read.
+     */
+    pub fn refresh(&mut self, folder: &Path) {
+        if self.children.contains_key(folder) {
+            self.load(folder);
+        }
+    }
+
+    /**
+     * Reads again the folders that contain the files that changed.
+     */
+    pub fn files_changed(&mut self, events: &[FileEvent]) {
+        let folders: HashSet<PathBuf> = events
+            .iter()
+            .filter_map(|event| event.path.parent().map(Path::to_owned))
+            .collect();
+        for folder in folders {
+            self.refresh(&folder);
+        }
+    }
+
+    fn load(&mut self, folder: &Path) {
+        let mut entries: Vec<TreeEntry> = WalkBuilder::new(folder)
+            .max_depth(Some(1))
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.depth() == 1)
+            .map(|entry| TreeEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir: entry.file_type().is_some_and(|file_type| file_type.is_dir()),
+                path: entry.into_path(),
+            })
+            .collect();
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+        self.children.insert(folder.to_owned(), entries);
+    }
+
+    /**
+     * Returns the entries of the expanded folders in the order they are shown, along with their depth.
+     */
+    pub fn visible(&self) -> Vec<(usize, &TreeEntry)> {
+        let mut visible = Vec::new();
+        self.push_visible(&self.root, 0, &mut visible);
+        visible
+    }
+
+    fn push_visible<'a>(&'a self, folder: &Path, depth: usize, visible: &mut Vec<(usize, &'a TreeEntry)>) {
+        let Some(entries) = self.children.get(folder) else {
+            return
+        };
+        for entry in entries {
+            visible.push((depth, entry));
+            if entry.is_dir && self.is_expanded(&entry.path) {
+                self.push_visible(&entry.path, depth + 1, visible);
+            }
+        }
+    }
+
+    pub fn create_file(&mut self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+        self.reload_parent(path);
+        Ok(())
+    }
+
+    pub fn create_folder(&mut self, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(path)?;
+        self.reload_parent(path);
+        Ok(())
+    }
+
+    /**
+     * Renames or moves the file or folder. Fails if the destination already exists.
+     */
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        if to.exists() {
+            return Err(Error::new(std::io::ErrorKind::AlreadyExists, format!("{} already exists", to.display())))
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from, to)?;
+        self.forget(from);
+        self.reload_parent(from);
+        self.reload_parent(to);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, path: &Path) -> Result<(), Error> {
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+        self.forget(path);
+        self.reload_parent(path);
+        Ok(())
+    }
+
+    fn reload_parent(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            self.refresh(parent);
+        }
+    }
+
+    /**
+     * Drops what was read from the folder and the folders within it.
+     */
+    fn forget(&mut self, path: &Path) {
+        self.children.retain(|folder, _| !folder.starts_with(path));
+        self.expanded.retain(|folder| !folder.starts_with(path));
+    }
+}
+
+/**
+ * Drops the `.` and `..` of the path without reading the disk, the path may not exist yet.
+ */
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod file_tree_tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::FileTree;
+
+    fn names(tree: &FileTree) -> Vec<(usize, String)> {
+        tree.visible().into_iter().map(|(depth, entry)| (depth, entry.name.clone())).collect()
+    }
+
+    #[test]
+    fn test_lazy_loading_and_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "target\n*.log\n").unwrap();
+        fs::write(dir.path().join("b.rs"), "").unwrap();
+        fs::write(dir.path().join("A.md"), "").unwrap();
+        fs::write(dir.path().join("debug.log"), "").unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+
+        let mut tree = FileTree::new(dir.path().to_owned());
+        assert_eq!(names(&tree), vec![
+            (0, "src".to_owned()),
+            (0, ".gitignore".to_owned()),
+            (0, "A.md".to_owned()),
+            (0, "b.rs".to_owned()),
+        ]);
+
+        tree.toggle(&dir.path().join("src"));
+        assert_eq!(names(&tree)[1..3], [(1, "nested".to_owned()), (1, "main.rs".to_owned())]);
+        tree.toggle(&dir.path().join("src"));
+        assert_eq!(names(&tree).len(), 4);
+    }
+
+    #[test]
+    fn test_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tree = FileTree::new(dir.path().to_owned());
+
+        tree.create_file(&dir.path().join("main.rs")).unwrap();
+        tree.create_folder(&dir.path().join("src")).unwrap();
+        assert!(tree.create_file(&dir.path().join("main.rs")).is_err());
+
+        tree.rename(&dir.path().join("main.rs"), &dir.path().join("src/lib.rs")).unwrap();
+        tree.reveal(&dir.path().join("src/lib.rs"));
+        assert_eq!(names(&tree), vec![(0, "src".to_owned()), (1, "lib.rs".to_owned())]);
+
+        tree.delete(&dir.path().join("src")).unwrap();
+        assert!(tree.visible().is_empty());
+        assert!(!dir.path().join("src").exists());
+    }
+
+    #[test]
+    fn test_resolve_stays_in_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = FileTree::new(dir.path().to_owned());
+        let src = dir.path().join("src");
+
+        assert_eq!(tree.resolve(&src, "lib.rs"), Some(src.join("lib.rs")));
+        assert_eq!(tree.resolve(&src, "./../main.rs"), Some(dir.path().join("main.rs")));
+        assert_eq!(tree.resolve(dir.path(), "../outside.rs"), None);
+        assert_eq!(tree.resolve(&src, "nested/../../.."), None);
+        assert_eq!(tree.resolve(&src, ".."), None);
+        assert_eq!(tree.resolve(&src, "/etc/passwd"), None);
+    }
+}
@@ -0,0 +1,345 @@
+use std::{collections::{BTreeMap, BTreeSet}, ops::Range};
+
+use tree_sitter::{Node, Tree};
+
+/**
+ * The nodes that are folded between their brackets, the line of the closing bracket stays visible.
+ */
+const BRACKETED: [&str; 13] = [
+    "block",
+    "declaration_list",
+    "field_declaration_list",
+    "ordered_field_declaration_list",
+    "enum_variant_list",
+    "field_initializer_list",
+    "match_block",
+    "use_list",
+    "token_tree",
+    "arguments",
+    "parameters",
+    "array_expression",
+    "tuple_expression",
+];
+
+/**
+ * The nodes that are folded up to their last line.
+ */
+const WHOLE: [&str; 2] = ["match_arm", "block_comment"];
+
+/**
+ * The nodes that are folded together when they follow each other, like the comments above an item.
+ */
+const RUNS: [&str; 2] = ["line_comment", "use_declaration"];
+
+/**
+ * Lines that can be folded. The first line stays visible, the lines after it up to the last one are hidden.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldRange {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl FoldRange {
+    pub fn new(start_line: usize, end_line: usize) -> Self {
+        Self { start_line, end_line }
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.start_line <= line && line <= self.end_line
+    }
+
+    /**
+     * Returns the lines hidden once the range is folded.
+     */
+    pub fn hidden(&self) -> Range<usize> {
+        self.start_line + 1..self.end_line + 1
+    }
+}
+
+impl From<lsp_types::FoldingRange> for FoldRange {
+    fn from(value: lsp_types::FoldingRange) -> Self {
+        Self::new(value.start_line as usize, value.end_line as usize)
+    }
+}
+
+/**
+ * The ranges of the document that can be folded and the ones that are.
+ *
+ * A folded range is known by its first line, which is moved along with the edits made above it.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Folds {
+    /// Sorted by their first line, a line starts one range at most.
+    ranges: Vec<FoldRange>,
+    folded: BTreeSet<usize>,
+    /// The ranges were sent by a language server, they aren't taken from the syntax tree anymore.
+    from_server: bool,
+}
+
+impl Folds {
+    /**
+     * Replaces the ranges. The folded ones that don't start a range anymore are unfolded.
+     */
+    pub fn set_ranges(&mut self, ranges: Vec<FoldRange>, from_server: bool) {
+        // The outermost range of a line is the one folded
+        let mut by_start: BTreeMap<usize, usize> = BTreeMap::new();
+        for range in ranges.into_iter().filter(|range| range.end_line > range.start_line) {
+            let end = by_start.entry(range.start_line).or_default();
+            *end = range.end_line.max(*end);
+        }
+        self.ranges = by_start.into_iter().map(|(start, end)| FoldRange::new(start, end)).collect();
+        let ranges = &self.ranges;
+        self.folded.retain(|start| ranges.binary_search_by_key(start, |range| range.start_line).is_ok());
+        self.from_server = from_server;
+    }
+
+    pub fn from_server(&self) -> bool {
+        self.from_server
+    }
+
+    pub fn ranges(&self) -> &[FoldRange] {
+        &self.ranges
+    }
+
+    /**
+     * Moves the ranges after the edited lines by the number of lines that were added. The lines between the first
+     * and the last one were replaced, folds that touch them are unfolded so the edit can be seen.
+     */
+    pub fn edit(&mut self, first_line: usize, old_last_line: usize, added: isize) {
+        let shift = |line: usize| if line > old_last_line { line.saturating_add_signed(added) } else { line };
+        let ranges = std::mem::take(&mut self.ranges);
+        self.folded = ranges
+            .iter()
+            .filter(|range| self.folded.contains(&range.start_line))
+            .filter(|range| range.end_line < first_line || range.start_line > old_last_line)
+            .map(|range| shift(range.start_line))
+            .collect();
+        // The ranges are taken from the syntax tree again, only the ones sent by a server are kept until it sends new ones
+        let ranges = ranges
+            .into_iter()
+            .map(|range| FoldRange::new(shift(range.start_line), shift(range.end_line)))
+            .collect();
+        self.set_ranges(ranges, self.from_server);
+    }
+
+    /**
+     * Returns the range that starts on the line.
+     */
+    pub fn range_at(&self, line: usize) -> Option<FoldRange> {
+        let index = self.ranges.binary_search_by_key(&line, |range| range.start_line).ok()?;
+        Some(self.ranges[index])
+    }
+
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.folded.contains(&line)
+    }
+
+    /**
+     * Folds the range that starts on the line, or unfolds it if it's folded. Returns whether there was one.
+     */
+    pub fn toggle(&mut self, line: usize) -> bool {
+        if self.folded.remove(&line) {
+            return true
+        }
+        self.range_at(line).map(|range| self.folded.insert(range.start_line)).is_some()
+    }
+
+    /**
+     * Folds the innermost range around the line that isn't folded yet.
+     */
+    pub fn fold(&mut self, line: usize) -> bool {
+        let range = self.ranges
+            .iter()
+            .rev()
+            .find(|range| range.contains(line) && !self.folded.contains(&range.start_line))
+            .copied();
+        range.map(|range| self.folded.insert(range.start_line)).is_some()
+    }
+
+    /**
+     * Unfolds the range that starts on the line, or the innermost folded range around it.
+     */
+    pub fn unfold(&mut self, line: usize) -> bool {
+        if self.folded.remove(&line) {
+            return true
+        }
+        let range = self.ranges
+            .iter()
+            .rev()
+            .find(|range| range.contains(line) && self.folded.contains(&range.start_line))
+            .copied();
+        range.is_some_and(|range| self.folded.remove(&range.start_line))
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folded = self.ranges.iter().map(|range| range.start_line).collect();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+    }
+
+    /**
+     * Unfolds the ranges that hide the line. Returns whether any was unfolded.
+     */
+    pub fn reveal(&mut self, line: usize) -> bool {
+        let hiding: Vec<usize> = self.ranges
+            .iter()
+            .filter(|range| self.folded.contains(&range.start_line) && range.hidden().contains(&line))
+            .map(|range| range.start_line)
+            .collect();
+        for start in hiding.iter() {
+            self.folded.remove(start);
+        }
+        !hiding.is_empty()
+    }
+
+    /**
+     * Returns the lines that are hidden, sorted and merged so the ranges don't touch each other.
+     */
+    pub fn hidden(&self) -> Vec<Range<usize>> {
+        let mut hidden: Vec<Range<usize>> = Vec::new();
+        let folded = self.ranges.iter().filter(|range| self.folded.contains(&range.start_line));
+        for range in folded.map(FoldRange::hidden) {
+            match hidden.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => hidden.push(range),
+            }
+        }
+        hidden
+    }
+}
+
+/**
+ * Returns the ranges that can be folded in the syntax tree: blocks and other bracketed lists, match arms,
+ * block comments, and runs of line comments or use declarations.
+ */
+pub fn syntax_folds(tree: &Tree) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    collect_folds(tree.root_node(), &mut ranges);
+    ranges
+}
+
+fn collect_folds(node: Node, ranges: &mut Vec<FoldRange>) {
+    let mut run: Option<(&str, usize, usize)> = None;
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        let (start, end) = (child.start_position().row, last_row(child));
+        let kind = child.kind();
+
+        // A run goes on as long as the same kind of node follows on the next line
+        match run {
+            Some((run_kind, _, run_end)) if run_kind == kind && start == run_end + 1 => {
+                run = run.map(|(kind, run_start, _)| (kind, run_start, end));
+                continue
+            }
+            _ => {
+                if let Some((_, run_start, run_end)) = run.take() {
+                    ranges.push(FoldRange::new(run_start, run_end));
+                }
+                if RUNS.contains(&kind) {
+                    run = Some((kind, start, end));
+                }
+            }
+        }
+
+        // Only nodes over several lines hold a range
+        if end == start {
+            continue
+        }
+        if BRACKETED.contains(&kind) {
+            ranges.push(FoldRange::new(start, end - 1));
+        } else if WHOLE.contains(&kind) {
+            ranges.push(FoldRange::new(start, end));
+        }
+        collect_folds(child, ranges);
+    }
+    if let Some((_, run_start, run_end)) = run {
+        ranges.push(FoldRange::new(run_start, run_end));
+    }
+}
+
+/**
+ * Returns the last line of the node. A node that ends with a newline ends on the line before the next one.
+ */
+fn last_row(node: Node) -> usize {
+    let (start, end) = (node.start_position(), node.end_position());
+    if end.column == 0 && end.row > start.row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+#[cfg(test)]
+mod folding_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{core::document::Document, highlighter::HighlighterConfig};
+
+    use super::{syntax_folds, FoldRange, Folds};
+
+    const SOURCE: &str = "use std::fs;
+use std::io;
+
+// Reads the file
+// and prints it
+fn main() {
+    match fs::read(\"a\") {
+        Ok(bytes) => {
+            println!(\"{:?}\", bytes);
+        }
+        Err(e) => (),
+    }
+}
+";
+
+    fn folds() -> Folds {
+        let document = Document::recovered(None, SOURCE);
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        let mut folds = Folds::default();
+        folds.set_ranges(syntax_folds(config.tree()), false);
+        folds
+    }
+
+    #[test]
+    fn test_syntax_folds() {
+        let ranges = folds().ranges().to_vec();
+        assert_eq!(ranges, vec![
+            // The use declarations and the comments
+            FoldRange::new(0, 1),
+            FoldRange::new(3, 4),
+            // The body of the function and of the match, up to their closing brackets
+            FoldRange::new(5, 11),
+            FoldRange::new(6, 10),
+            // The match arm, along with the closing bracket of its block
+            FoldRange::new(7, 9),
+        ]);
+    }
+
+    #[test]
+    fn test_folds_follow_edits() {
+        let mut folds = folds();
+        assert!(folds.toggle(7));
+        assert!(!folds.toggle(8));
+        assert!(folds.fold(9));
+        assert!(folds.is_folded(6));
+        assert_eq!(folds.hidden(), vec![7..11]);
+
+        // Two lines added above the folds move them down
+        folds.edit(2, 2, 2);
+        assert!(folds.is_folded(8) && folds.is_folded(9));
+        assert_eq!(folds.hidden(), vec![9..13]);
+
+        // Editing a hidden line unfolds the ranges around it
+        folds.edit(10, 10, 0);
+        assert!(!folds.is_folded(8) && !folds.is_folded(9));
+
+        folds.fold_all();
+        assert_eq!(folds.hidden(), vec![1..2, 6..7, 8..14]);
+        // Revealing a line unfolds the ranges that hide it, the ones inside of it stay folded
+        assert!(folds.reveal(9));
+        assert_eq!(folds.hidden(), vec![1..2, 6..7, 10..12]);
+    }
+}
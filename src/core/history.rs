@@ -0,0 +1,196 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{position::{Cursor, Position}, selection::Selection};
+
+/**
+ * Text that replaced the text at a position.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub start: Position,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    /**
+     * Returns the edit that takes the text back to what it was.
+     */
+    pub fn inverse(&self) -> Self {
+        Self { start: self.start, removed: self.inserted.clone(), inserted: self.removed.clone() }
+    }
+
+    /**
+     * Returns the position after the text the edit removed, before it's made.
+     */
+    pub fn old_end(&self) -> Position {
+        end_of(self.start, &self.removed)
+    }
+
+    /**
+     * Returns the position after the text the edit inserted, once it's made.
+     */
+    pub fn new_end(&self) -> Position {
+        end_of(self.start, &self.inserted)
+    }
+
+    /**
+     * Returns true if the edit only typed a character of a word, those are undone along with the ones around them.
+     */
+    fn is_typing(&self) -> bool {
+        self.removed.is_empty() && self.inserted.chars().all(|character| character.is_alphanumeric() || character == '_')
+    }
+}
+
+/**
+ * Returns the position after the text inserted at the position.
+ */
+pub fn end_of(start: Position, text: &str) -> Position {
+    match text.rsplit_once('\n') {
+        Some((before, last)) => Position::new(start.line() + before.matches('\n').count() + 1, last.graphemes(true).count()),
+        None => Position::new(start.line(), start.character() + text.graphemes(true).count()),
+    }
+}
+
+/**
+ * The edits made by one command, undone and redone together, along with where the cursor was around them.
+ */
+#[derive(Debug, Clone)]
+struct Transaction {
+    edits: Vec<Edit>,
+    before: (Selection, Cursor),
+    after: (Selection, Cursor),
+}
+
+/**
+ * The edits of a buffer that can be undone and redone.
+ *
+ * Edits are recorded as they're made and sealed into one transaction once the command that made them is done, so
+ * a command that edits several places is undone at once.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    /// The edits made since the last seal.
+    pending: Vec<Edit>,
+    /// Where the cursor was when the pending edits started.
+    state: (Selection, Cursor),
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit) {
+        self.pending.push(edit);
+        self.redo.clear();
+    }
+
+    /**
+     * Ends the command that made the pending edits, with the cursor where it left it. Words typed one character
+     * after the other are joined into one transaction.
+     */
+    pub fn seal(&mut self, selection: Selection, cursor: Cursor) {
+        let edits = std::mem::take(&mut self.pending);
+        let state = std::mem::replace(&mut self.state, (selection, cursor));
+        if edits.is_empty() {
+            return
+        }
+        if let (Some(last), [edit]) = (self.undo.last_mut(), edits.as_slice()) {
+            let follows = last.edits.last().is_some_and(|previous| previous.is_typing() && previous.new_end() == edit.start);
+            if follows && edit.is_typing() && last.edits.len() < 64 {
+                last.edits.push(edit.clone());
+                last.after = (selection, cursor);
+                return
+            }
+        }
+        self.undo.push(Transaction { edits, before: state, after: (selection, cursor) });
+    }
+
+    /**
+     * Returns the edits that undo the last transaction, in the order they're made, and where the cursor goes.
+     */
+    pub fn undo(&mut self) -> Option<(Vec<Edit>, (Selection, Cursor))> {
+        let transaction = self.undo.pop()?;
+        let edits = transaction.edits.iter().rev().map(Edit::inverse).collect();
+        let state = transaction.before;
+        self.redo.push(transaction);
+        self.state = state;
+        Some((edits, state))
+    }
+
+    /**
+     * Returns the edits of the last transaction that was undone and where the cursor goes.
+     */
+    pub fn redo(&mut self) -> Option<(Vec<Edit>, (Selection, Cursor))> {
+        let transaction = self.redo.pop()?;
+        let edits = transaction.edits.clone();
+        let state = transaction.after;
+        self.undo.push(transaction);
+        self.state = state;
+        Some((edits, state))
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, position::Position};
+
+    #[test]
+    fn test_line_commands_are_undone_at_once() {
+        let mut buffer = Buffer::from_source("fn a() {}\nfn b() {}\nfn c() {}\n");
+        buffer.cursor.0 = Position::new(0, 3);
+        buffer.seal_history();
+
+        buffer.move_lines(false);
+        buffer.seal_history();
+        assert_eq!(buffer.get_string(), "fn b() {}\nfn a() {}\nfn c() {}\n");
+        assert_eq!(buffer.cursor.0, Position::new(1, 3));
+        // The syntax tree moved along with the lines
+        buffer.cursor.0 = Position::new(1, 7);
+        assert_eq!(buffer.matching_brackets(), Some((Position::new(1, 7), Position::new(1, 8))));
+
+        buffer.duplicate_lines();
+        buffer.seal_history();
+        assert_eq!(buffer.get_string(), "fn b() {}\nfn a() {}\nfn a() {}\nfn c() {}\n");
+        assert_eq!(buffer.cursor.0, Position::new(2, 7));
+
+        buffer.selection.set_start(Position::new(0, 0));
+        buffer.selection.set_end(Position::new(2, 3));
+        buffer.join_lines();
+        buffer.seal_history();
+        assert_eq!(buffer.get_string(), "fn b() {} fn a() {} fn a() {}\nfn c() {}\n");
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.get_string(), "fn b() {}\nfn a() {}\nfn a() {}\nfn c() {}\n");
+        assert!(buffer.undo());
+        assert!(buffer.undo());
+        assert_eq!(buffer.get_string(), "fn a() {}\nfn b() {}\nfn c() {}\n");
+        assert_eq!(buffer.cursor.0, Position::new(0, 3));
+        assert!(!buffer.undo());
+
+        assert!(buffer.redo());
+        assert_eq!(buffer.get_string(), "fn b() {}\nfn a() {}\nfn c() {}\n");
+        buffer.cursor.0 = Position::new(2, 0);
+        buffer.delete_lines();
+        buffer.seal_history();
+        assert_eq!(buffer.get_string(), "fn b() {}\nfn a() {}\n");
+        // A new edit drops the edits that were undone
+        assert!(!buffer.redo());
+    }
+
+    #[test]
+    fn test_typed_words_are_undone_at_once() {
+        let mut buffer = Buffer::from_source("\n");
+        for text in ["l", "e", "t", " ", "x"] {
+            buffer.insert(text.to_owned());
+            buffer.seal_history();
+        }
+        assert_eq!(buffer.get_string(), "let x\n");
+        buffer.undo();
+        assert_eq!(buffer.get_string(), "let \n");
+        buffer.undo();
+        buffer.undo();
+        assert_eq!(buffer.get_string(), "\n");
+    }
+}
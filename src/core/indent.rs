@@ -0,0 +1,217 @@
+use tree_sitter::Tree;
+
+use super::{brackets::{bracket_at, is_opening_bracket}, document::Document};
+
+/**
+ * The number of lines read from the start of a document to find how it's indented.
+ */
+const SAMPLE_LINES: usize = 1000;
+
+/**
+ * What one level of indentation is made of.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        Self::Spaces(4)
+    }
+}
+
+impl IndentStyle {
+    /**
+     * Finds the indentation of the document from the lines that are already indented. A document that isn't
+     * indented yet gets the default one.
+     *
+     * The width of the spaces is the most common step between the indentation of a line and the one before it.
+     */
+    pub fn detect(document: &Document) -> Self {
+        let (mut tabs, mut spaces) = (0, 0);
+        let mut steps = [0usize; 9];
+        let mut previous = 0;
+        for line in document.lines().take(SAMPLE_LINES) {
+            let line = line.to_string();
+            if line.trim().is_empty() {
+                continue
+            }
+            let indent = leading_whitespace(&line);
+            if indent.starts_with('\t') {
+                tabs += 1;
+                continue
+            }
+            if !indent.is_empty() {
+                spaces += 1;
+            }
+            let width = indent.len();
+            // Steps of a single space are the stars of block comments
+            let step = width.abs_diff(previous);
+            if (2..steps.len()).contains(&step) {
+                steps[step] += 1;
+            }
+            previous = width;
+        }
+        if tabs > spaces {
+            return Self::Tabs
+        }
+        (2..steps.len())
+            .filter(|width| steps[*width] > 0)
+            .max_by_key(|width| (steps[*width], *width == 4))
+            .map_or_else(Self::default, Self::Spaces)
+    }
+
+    /**
+     * Returns the text of one level of indentation.
+     */
+    pub fn unit(&self) -> String {
+        match self {
+            Self::Tabs => "\t".to_owned(),
+            Self::Spaces(width) => " ".repeat(*width),
+        }
+    }
+
+    /**
+     * Returns the number of whitespace characters at the end of the indentation that make up the last level of it.
+     */
+    pub fn last_level(&self, indent: &str) -> usize {
+        match self {
+            _ if indent.ends_with('\t') => 1,
+            Self::Tabs => indent.len(),
+            Self::Spaces(width) => {
+                let spaces = indent.chars().rev().take_while(|character| *character == ' ').count();
+                match spaces % width {
+                    0 => spaces.min(*width),
+                    partial => partial,
+                }
+            }
+        }
+    }
+
+    /**
+     * Returns the indentation without its last level.
+     */
+    pub fn outdent<'a>(&self, indent: &'a str) -> &'a str {
+        &indent[..indent.len() - self.last_level(indent)]
+    }
+}
+
+/**
+ * Returns the spaces and tabs the line starts with.
+ */
+pub fn leading_whitespace(line: &str) -> &str {
+    let end = line.find(|character: char| character != ' ' && character != '\t').unwrap_or(line.len());
+    &line[..end]
+}
+
+/**
+ * How a line break splits the line it's typed in, found from the brackets on both sides of it.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineBreak {
+    /// An opening bracket ends the text before the break, the new line is indented one more level.
+    pub opens: bool,
+    /// A closing bracket starts the text after the break, it goes one level back.
+    pub closes: bool,
+}
+
+impl LineBreak {
+    /**
+     * Looks at the brackets around the break, `before` and `after` being the bytes of the last character before
+     * it and the first one after it that aren't whitespace. Brackets inside of strings and comments are ignored.
+     */
+    pub fn new(tree: &Tree, before: Option<usize>, after: Option<usize>) -> Self {
+        let bracket = |byte: Option<usize>| byte.and_then(|byte| bracket_at(tree, byte));
+        Self {
+            opens: bracket(before).is_some_and(is_opening_bracket),
+            closes: bracket(after).is_some_and(|bracket| !is_opening_bracket(bracket)),
+        }
+    }
+
+    /**
+     * Returns the text inserted for the break on a line indented with `indent`, along with the indentation the
+     * cursor ends up after.
+     */
+    pub fn text(&self, indent: &str, style: IndentStyle) -> (String, String) {
+        match (self.opens, self.closes) {
+            // The closing bracket goes on its own line, the cursor is on an indented line between the brackets
+            (true, true) => {
+                let inner = format!("{}{}", indent, style.unit());
+                (format!("\n{}\n{}", inner, indent), inner)
+            }
+            (true, false) => {
+                let inner = format!("{}{}", indent, style.unit());
+                (format!("\n{}", inner), inner)
+            }
+            (false, true) => {
+                let outer = style.outdent(indent).to_owned();
+                (format!("\n{}", outer), outer)
+            }
+            (false, false) => (format!("\n{}", indent), indent.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod indent_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, document::Document, position::Position};
+
+    use super::IndentStyle;
+
+    #[test]
+    fn test_detect_indent_style() {
+        let detect = |source: &str| IndentStyle::detect(&Document::recovered(None, source));
+        assert_eq!(detect("fn main() {\n  if x {\n    y();\n  }\n}\n"), IndentStyle::Spaces(2));
+        assert_eq!(detect("fn main() {\n\tlet x = 1;\n\t/**\n\t * Doc\n\t */\n}\n"), IndentStyle::Tabs);
+        // The stars of a block comment aren't a level
+        assert_eq!(detect("/**\n * Doc\n */\nfn main() {}\n"), IndentStyle::Spaces(4));
+        assert_eq!(IndentStyle::Spaces(4).outdent("      "), "    ");
+        assert_eq!(IndentStyle::Spaces(4).outdent("    \t"), "    ");
+    }
+
+    #[test]
+    fn test_new_line_follows_the_brackets() {
+        let mut buffer = Buffer::from_source("fn main() {}\n");
+        buffer.cursor.0 = Position::new(0, 11);
+        buffer.new_line();
+        assert_eq!(buffer.get_string(), "fn main() {\n    \n}\n");
+        assert_eq!(buffer.cursor.0, Position::new(1, 4));
+
+        buffer.insert("x".to_owned());
+        buffer.new_line();
+        assert_eq!(buffer.get_string(), "fn main() {\n    x\n    \n}\n");
+
+        // A bracket inside of a comment doesn't indent
+        buffer.insert("// {".to_owned());
+        buffer.new_line();
+        assert_eq!(buffer.get_string(), "fn main() {\n    x\n    // {\n    \n}\n");
+    }
+
+    #[test]
+    fn test_indent_lines() {
+        let mut buffer = Buffer::from_source("fn main() {\nlet x = 1;\n    y();\n}\n");
+        buffer.selection.set_start(Position::new(1, 2));
+        buffer.selection.set_end(Position::new(3, 0));
+        buffer.indent();
+        assert_eq!(buffer.get_string(), "fn main() {\n    let x = 1;\n        y();\n}\n");
+        assert_eq!((*buffer.selection.start(), *buffer.selection.end()), (Position::new(1, 6), Position::new(3, 0)));
+
+        buffer.outdent();
+        buffer.outdent();
+        assert_eq!(buffer.get_string(), "fn main() {\nlet x = 1;\ny();\n}\n");
+
+        // Backspace in the indentation removes a level
+        buffer.selection.clear(&mut buffer.cursor);
+        buffer.cursor.0 = Position::new(1, 0);
+        buffer.indent();
+        buffer.indent();
+        assert_eq!(buffer.cursor.0, Position::new(1, 8));
+        assert!(buffer.delete_indent());
+        assert_eq!(buffer.get_string(), "fn main() {\n    let x = 1;\ny();\n}\n");
+        assert_eq!(buffer.cursor.0, Position::new(1, 4));
+    }
+}
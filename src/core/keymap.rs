@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/**
+ * The keys the editor is used with, picked from the status bar.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Keymap {
+    #[default]
+    Default,
+    /// The modes and commands of Vim
+    Vim,
+    /// The bindings of Emacs, with its mark and kill ring
+    Emacs,
+}
+
+impl Keymap {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Keymap::Default => "Keys: Default",
+            Keymap::Vim => "Keys: Vim",
+            Keymap::Emacs => "Keys: Emacs",
+        }
+    }
+
+    /**
+     * Returns the keymap that comes after this one in the status bar.
+     */
+    pub fn next(&self) -> Self {
+        match self {
+            Keymap::Default => Keymap::Vim,
+            Keymap::Vim => Keymap::Emacs,
+            Keymap::Emacs => Keymap::Default,
+        }
+    }
+}
+
+/**
+ * What a command of a keymap asks the editor for, the commands that go beyond the buffer.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeymapRequest {
+    Save,
+    /// Opens the file, relative to the workspace
+    Open(String),
+    /// Lets the user pick the file to open
+    PickFile,
+    /// Writes the text to the clipboard of the system
+    Copy(String),
+    /// Pastes the clipboard of the system at the cursor
+    Paste,
+    Error(String),
+}
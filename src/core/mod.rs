@@ -3,4 +3,23 @@ pub mod document;
 pub mod window;
 pub mod buffer;
 pub mod selection;
-pub mod document_change;
\ No newline at end of file
+pub mod document_change;
+pub mod watcher;
+pub mod file_tree;
+pub mod file_index;
+pub mod session;
+pub mod recovery;
+pub mod file_format;
+pub mod error;
+pub mod display_map;
+pub mod folding;
+pub mod brackets;
+pub mod indent;
+pub mod history;
+pub mod comment;
+pub mod motion;
+pub mod keymap;
+pub mod emacs;
+pub mod clipboard;
+pub mod vim;
+
@@ -0,0 +1,250 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/**
+ * The pieces of a line the cursor jumps over by words.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordKind {
+    /// Words and runs of punctuation, split at the word boundaries of Unicode.
+    Word,
+    /// The parts of words, split at underscores and where the case goes up, like `snake` and `Case`.
+    Subword,
+    /// Everything between spaces.
+    Whitespace,
+}
+
+/**
+ * A piece of a line, in columns of graphemes.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    columns: Range<usize>,
+    is_space: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class_of(text: &str) -> Class {
+    match text.chars().next() {
+        Some(character) if character.is_whitespace() => Class::Space,
+        Some(character) if character.is_alphanumeric() || character == '_' => Class::Word,
+        _ => Class::Punctuation,
+    }
+}
+
+/**
+ * Splits the line into the pieces the cursor stops at. Pieces of the same class that follow each other are
+ * joined, so `::` or `->` is one piece.
+ */
+fn segments(line: &str, kind: WordKind) -> Vec<Segment> {
+    // The word boundaries are in bytes, the cursor counts graphemes
+    let mut columns = vec![0; line.len() + 1];
+    let mut count = 0;
+    for (byte, grapheme) in line.grapheme_indices(true) {
+        columns[byte..byte + grapheme.len()].fill(count);
+        count += 1;
+    }
+    columns[line.len()] = count;
+
+    let pieces: Vec<(Range<usize>, Class)> = match kind {
+        WordKind::Whitespace => line
+            .split_word_bound_indices()
+            .map(|(byte, text)| (byte..byte + text.len(), if class_of(text) == Class::Space { Class::Space } else { Class::Word }))
+            .collect(),
+        WordKind::Word => line
+            .split_word_bound_indices()
+            .map(|(byte, text)| (byte..byte + text.len(), class_of(text)))
+            .collect(),
+        WordKind::Subword => line
+            .split_word_bound_indices()
+            .flat_map(|(byte, text)| subwords(text).into_iter().map(move |range| byte + range.start..byte + range.end))
+            .map(|bytes| {
+                // Underscores are skipped like spaces between the parts of a word
+                let text = &line[bytes.clone()];
+                let class = if text.starts_with('_') { Class::Space } else { class_of(text) };
+                (bytes, class)
+            })
+            .collect(),
+    };
+
+    let mut segments: Vec<(Range<usize>, Class)> = Vec::new();
+    for (bytes, class) in pieces {
+        match segments.last_mut() {
+            // Parts of words stay apart, the rest is joined with the pieces of its class
+            Some((last, last_class)) if *last_class == class && !(kind == WordKind::Subword && class == Class::Word) => last.end = bytes.end,
+            _ => segments.push((bytes, class)),
+        }
+    }
+    segments
+        .into_iter()
+        .map(|(bytes, class)| Segment { columns: columns[bytes.start]..columns[bytes.end], is_space: class == Class::Space })
+        .collect()
+}
+
+/**
+ * Splits a word at its underscores and where its case goes up. An uppercase run followed by a lowercase letter
+ * keeps its last letter for the next part, like `HTTP` and `Server`.
+ */
+fn subwords(word: &str) -> Vec<Range<usize>> {
+    let characters: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for index in 1..characters.len() {
+        let (byte, character) = characters[index];
+        let previous = characters[index - 1].1;
+        let next = characters.get(index + 1).map(|(_, next)| *next);
+        let splits = (character == '_') != (previous == '_')
+            || (previous.is_lowercase() || previous.is_numeric()) && character.is_uppercase()
+            || previous.is_uppercase() && character.is_uppercase() && next.is_some_and(char::is_lowercase);
+        if splits {
+            parts.push(start..byte);
+            start = byte;
+        }
+    }
+    parts.push(start..word.len());
+    parts
+}
+
+/**
+ * Returns the column at the end of the next word after the column, or `None` if there's no word left on the line.
+ */
+pub fn next_word_end(line: &str, column: usize, kind: WordKind) -> Option<usize> {
+    segments(line, kind)
+        .into_iter()
+        .find(|segment| !segment.is_space && segment.columns.end > column)
+        .map(|segment| segment.columns.end)
+}
+
+/**
+ * Returns the column at the start of the word before the column, or `None` if there's no word before it.
+ */
+pub fn previous_word_start(line: &str, column: usize, kind: WordKind) -> Option<usize> {
+    segments(line, kind)
+        .into_iter()
+        .rev()
+        .find(|segment| !segment.is_space && segment.columns.start < column)
+        .map(|segment| segment.columns.start)
+}
+
+/**
+ * Returns the column at the start of the next word after the column, or `None` if there's no word left on the line.
+ */
+pub fn next_word_start(line: &str, column: usize, kind: WordKind) -> Option<usize> {
+    segments(line, kind)
+        .into_iter()
+        .find(|segment| !segment.is_space && segment.columns.start > column)
+        .map(|segment| segment.columns.start)
+}
+
+/**
+ * Returns the columns of the word at the column, or of the spaces there. Around a word, the spaces after it are
+ * taken along, or the ones before it if it ends the line. Around spaces, the word after them is.
+ */
+pub fn word_object(line: &str, column: usize, kind: WordKind, around: bool) -> Range<usize> {
+    let segments = segments(line, kind);
+    let Some(index) = segments.iter().position(|segment| segment.columns.contains(&column)).or(segments.len().checked_sub(1)) else {
+        return column..column
+    };
+    let segment = &segments[index];
+    if !around {
+        return segment.columns.clone()
+    }
+    match (segments.get(index + 1), index.checked_sub(1).map(|previous| &segments[previous])) {
+        (Some(next), _) if next.is_space != segment.is_space => segment.columns.start..next.columns.end,
+        (_, Some(previous)) if previous.is_space && !segment.is_space => previous.columns.start..segment.columns.end,
+        _ => segment.columns.clone(),
+    }
+}
+
+/**
+ * Returns the columns of the word at the column, or of the spaces or punctuation there.
+ */
+pub fn word_at(line: &str, column: usize) -> Range<usize> {
+    let segments = segments(line, WordKind::Word);
+    segments
+        .iter()
+        .find(|segment| segment.columns.contains(&column))
+        .or(segments.last())
+        .map(|segment| segment.columns.clone())
+        .unwrap_or(column..column)
+}
+
+#[cfg(test)]
+mod motion_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, document::Document, position::Position};
+
+    use super::{next_word_end, next_word_start, previous_word_start, word_at, word_object, WordKind};
+
+    #[test]
+    fn test_word_motions() {
+        let line = "let café = parseHTTPServer(some_value)::new;";
+        let stops = |kind: WordKind| {
+            let mut stops = Vec::new();
+            let mut column = 0;
+            while let Some(next) = next_word_end(line, column, kind) {
+                stops.push(next);
+                column = next;
+            }
+            stops
+        };
+        assert_eq!(stops(WordKind::Word), vec![3, 8, 10, 26, 27, 37, 40, 43, 44]);
+        assert_eq!(stops(WordKind::Subword), vec![3, 8, 10, 16, 20, 26, 27, 31, 37, 40, 43, 44]);
+        assert_eq!(stops(WordKind::Whitespace), vec![3, 8, 10, 44]);
+
+        assert_eq!(previous_word_start(line, 26, WordKind::Word), Some(11));
+        assert_eq!(previous_word_start(line, 26, WordKind::Subword), Some(20));
+        assert_eq!(previous_word_start(line, 3, WordKind::Word), Some(0));
+        assert_eq!(previous_word_start(line, 0, WordKind::Word), None);
+
+        assert_eq!(word_at(line, 5), 4..8);
+        assert_eq!(word_at(line, 3), 3..4);
+
+        assert_eq!(next_word_start(line, 4, WordKind::Word), Some(9));
+        assert_eq!(next_word_start(line, 4, WordKind::Whitespace), Some(9));
+        assert_eq!(word_object(line, 5, WordKind::Word, false), 4..8);
+        assert_eq!(word_object(line, 5, WordKind::Word, true), 4..9);
+        assert_eq!(word_object(line, 8, WordKind::Word, true), 8..10);
+        assert_eq!(word_object(line, 43, WordKind::Word, true), 43..44);
+    }
+
+    #[test]
+    fn test_buffer_motions() {
+        let mut buffer = Buffer::new(Document::recovered(None, "fn main() {\n    let x = 1;\n\n    y();\n}\n"), None);
+        buffer.cursor.0 = Position::new(1, 14);
+        // The line break is a stop between the lines
+        buffer.move_word(true, WordKind::Word);
+        assert_eq!(buffer.cursor.0, Position::new(2, 0));
+        buffer.move_word(false, WordKind::Word);
+        assert_eq!(buffer.cursor.0, Position::new(1, 14));
+        buffer.move_word(false, WordKind::Word);
+        assert_eq!(buffer.cursor.0, Position::new(1, 13));
+
+        // Home goes to the first character that isn't blank, then to the start of the line
+        buffer.move_to_row_start();
+        assert_eq!(buffer.cursor.0, Position::new(1, 4));
+        buffer.move_to_row_start();
+        assert_eq!(buffer.cursor.0, Position::new(1, 0));
+
+        buffer.move_paragraph(true);
+        assert_eq!(buffer.cursor.0, Position::new(2, 0));
+        buffer.move_paragraph(true);
+        assert_eq!(buffer.cursor.0, Position::new(5, 0));
+        buffer.move_paragraph(false);
+        assert_eq!(buffer.cursor.0, Position::new(2, 0));
+
+        buffer.cursor.0 = Position::new(1, 9);
+        buffer.delete_word(false, WordKind::Word);
+        assert_eq!(buffer.get_string(), "fn main() {\n    let  = 1;\n\n    y();\n}\n");
+        buffer.select_word(Position::new(0, 5));
+        assert_eq!((*buffer.selection.start(), *buffer.selection.end()), (Position::new(0, 3), Position::new(0, 7)));
+    }
+}
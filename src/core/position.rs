@@ -1,9 +1,15 @@
+use serde::{Deserialize, Serialize};
+
 pub trait CursorMessage {
     fn from_cursor_position(pos: Position) -> Self;
     fn from_selection_move(pos: Position) -> Self;
+    /// A double click selects the word at the position.
+    fn from_word_click(pos: Position) -> Self;
+    /// A triple click selects the line at the position.
+    fn from_line_click(pos: Position) -> Self;
 }
 
-#[derive(Debug, Clone, Copy, Default, Eq)]
+#[derive(Debug, Clone, Copy, Default, Eq, Serialize, Deserialize)]
 pub struct Position {
     line: usize,
     character: usize,
@@ -44,18 +50,6 @@ impl From<tree_sitter::Point> for Position {
     }
 }
 
-impl From<lsp_types::Position> for Position {
-    fn from(value: lsp_types::Position) -> Self {
-        Self { line: value.line as usize, character: value.character as usize }
-    }
-}
-
-impl Into<lsp_types::Position> for Position {
-    fn into(self) -> lsp_types::Position {
-        lsp_types::Position{line: self.line as u32, character: self.character as u32}
-    }
-}
-
 impl Position {
 
     pub fn new(line: usize, character: usize) -> Self {
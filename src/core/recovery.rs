@@ -0,0 +1,210 @@
+use std::{
+    cmp::Reverse,
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Error,
+    panic,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use iced::{futures::SinkExt, subscription, Subscription};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use super::file_format::read_text;
+
+/**
+ * The file and content of the dirty buffer.
+ */
+type Latest = Option<(Option<PathBuf>, Rope)>;
+
+/**
+ * The content of a buffer with unsaved changes, written while the editor runs so it survives a crash.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// `None` for a document that was never saved.
+    pub file: Option<PathBuf>,
+    pub text: String,
+    /// When the snapshot was taken, in seconds since the Unix epoch.
+    pub time: u64,
+}
+
+impl Snapshot {
+    pub fn name(&self) -> String {
+        match self.file.as_ref() {
+            Some(file) => file.display().to_string(),
+            None => "Untitled".to_owned(),
+        }
+    }
+
+    /**
+     * Returns the content of the file on disk, or nothing if the file doesn't exist anymore.
+     */
+    pub fn on_disk(&self) -> String {
+        self.file.as_ref().and_then(|file| read_text(file).ok()).map(|(text, _)| text).unwrap_or_default()
+    }
+}
+
+/**
+ * Keeps the snapshots of the dirty buffers in a folder of the user's data.
+ *
+ * A snapshot is removed once its buffer is saved or kept by the session, the ones left behind
+ * are offered for recovery on the next start.
+ */
+#[derive(Debug, Clone)]
+pub struct RecoveryStore {
+    dir: PathBuf,
+    /// The last content of the dirty buffer, written by the panic hook.
+    latest: Arc<Mutex<Latest>>,
+}
+
+impl RecoveryStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, latest: Arc::new(Mutex::new(None)) }
+    }
+
+    fn path(&self, file: Option<&Path>) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        file.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    /**
+     * Writes the content of the buffer, replacing the previous snapshot of the same file.
+     */
+    pub fn snapshot(&self, file: Option<&Path>, text: String) -> Result<PathBuf, Error> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+        let snapshot = Snapshot { file: file.map(Path::to_owned), text, time };
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(file);
+        // Written next to the snapshot first, so a crash doesn't leave half of a snapshot
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string(&snapshot)?)?;
+        fs::rename(temporary, &path)?;
+        Ok(path)
+    }
+
+    pub fn remove(&self, file: Option<&Path>) {
+        let _ = fs::remove_file(self.path(file));
+    }
+
+    pub fn has_snapshot(&self, file: Option<&Path>) -> bool {
+        self.path(file).exists()
+    }
+
+    /**
+     * Returns the snapshots that were left behind, the most recent first. Snapshots that can't be read are skipped.
+     */
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new()
+        };
+        let mut snapshots: Vec<Snapshot> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+        snapshots.sort_by_key(|snapshot| Reverse(snapshot.time));
+        snapshots
+    }
+
+    /**
+     * Keeps the content of the dirty buffer for the panic hook, or forgets it once the buffer is saved.
+     *
+     * Cloning a rope is cheap, its nodes are shared until one of them is edited.
+     */
+    pub fn set_latest(&self, latest: Latest) {
+        if let Ok(mut current) = self.latest.lock() {
+            *current = latest;
+        }
+    }
+
+    /**
+     * Writes the last content of the dirty buffer, the edits made since the last snapshot included.
+     */
+    pub fn flush_latest(&self) -> Result<(), Error> {
+        // The lock is poisoned if the panic happened while it was held, the content is still usable
+        let latest = match self.latest.lock() {
+            Ok(latest) => latest.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        if let Some((file, rope)) = latest {
+            self.snapshot(file.as_deref(), rope.to_string())?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes the dirty buffer before the editor goes down with a panic.
+     */
+    pub fn install_panic_hook(&self) {
+        let store = self.clone();
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Err(e) = store.flush_latest() {
+                eprintln!("Failed to keep the unsaved changes: {}", e);
+            }
+            default_hook(info);
+        }));
+    }
+}
+
+/**
+ * Sends a message at every interval, used to take the snapshots and to autosave.
+ */
+pub fn ticks(interval: Duration) -> Subscription<()> {
+    subscription::channel(interval, 1, move |mut output| async move {
+        loop {
+            smol::Timer::after(interval).await;
+            let _ = output.send(()).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod recovery_tests {
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+    use ropey::Rope;
+
+    use super::RecoveryStore;
+
+    #[test]
+    fn test_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RecoveryStore::new(dir.path().join("snapshots"));
+        assert!(store.snapshots().is_empty());
+
+        store.snapshot(Some(Path::new("/ws/main.rs")), "fn main() {}".to_owned()).unwrap();
+        store.snapshot(Some(Path::new("/ws/main.rs")), "fn main() { }".to_owned()).unwrap();
+        store.snapshot(None, "notes".to_owned()).unwrap();
+        let mut texts: Vec<String> = store.snapshots().into_iter().map(|snapshot| snapshot.text).collect();
+        texts.sort();
+        assert_eq!(texts, vec!["fn main() { }".to_owned(), "notes".to_owned()]);
+
+        store.remove(Some(Path::new("/ws/main.rs")));
+        assert!(!store.has_snapshot(Some(Path::new("/ws/main.rs"))));
+        assert_eq!(store.snapshots()[0].name(), "Untitled");
+    }
+
+    #[test]
+    fn test_flush_latest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RecoveryStore::new(dir.path().to_owned());
+        store.flush_latest().unwrap();
+        assert!(store.snapshots().is_empty());
+
+        store.set_latest(Some((Some(Path::new("/ws/lib.rs").to_owned()), Rope::from_str("pub mod core;"))));
+        store.flush_latest().unwrap();
+        let snapshots = store.snapshots();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].file.as_deref(), Some(Path::new("/ws/lib.rs")));
+        assert_eq!(snapshots[0].text, "pub mod core;");
+    }
+}
@@ -1,4 +1,6 @@
-use std::mem::swap;
+use std::{mem::swap, ops};
+
+use tree_sitter::Tree;
 
 use super::position::{Position, Cursor};
 
@@ -74,9 +76,81 @@ impl Selection {
         self.0.end = position;
         cursor.0 = position;
     }
+
+    /**
+     * Returns true if the selection was made from its end towards its start.
+     */
+    pub fn is_backwards(&self) -> bool {
+        self.0.start > self.0.end
+    }
+
+    /**
+     * Selects the range in the direction of the selection, and puts the cursor at the end the selection moves.
+     */
+    pub fn select(&mut self, range: Range, cursor: &mut Cursor) {
+        self.0 = if self.is_backwards() { Range::new(range.end, range.start) } else { range };
+        cursor.0 = self.0.end;
+    }
+}
+
+/**
+ * The selections that were grown one after the other, so shrinking the selection goes back through them.
+ *
+ * The history is only followed while the selection is the one it last grew to.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ExpandHistory {
+    previous: Vec<Range>,
+    current: Option<Range>,
+}
+
+impl ExpandHistory {
+    /**
+     * Keeps the range the selection grew from.
+     */
+    pub fn push(&mut self, from: Range, to: Range) {
+        if self.current != Some(from) {
+            self.previous.clear();
+        }
+        self.previous.push(from);
+        self.current = Some(to);
+    }
+
+    /**
+     * Returns the range the selection grew from, the selection shrinks back to it.
+     */
+    pub fn pop(&mut self, current: Range) -> Option<Range> {
+        if self.current != Some(current) {
+            self.clear();
+            return None
+        }
+        let previous = self.previous.pop()?;
+        self.current = Some(previous);
+        Some(previous)
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current = None;
+    }
+}
+
+/**
+ * Returns the bytes of the smallest named node of the tree that holds the bytes and more.
+ */
+pub fn enclosing_node(tree: &Tree, bytes: ops::Range<usize>) -> Option<ops::Range<usize>> {
+    let mut node = tree.root_node().named_descendant_for_byte_range(bytes.start, bytes.end)?;
+    loop {
+        let range = node.byte_range();
+        let holds = range.start <= bytes.start && bytes.end <= range.end;
+        if holds && range != bytes {
+            return Some(range)
+        }
+        node = node.parent()?;
+    }
 }
 
-#[derive(Debug, Clone, Default, Copy)]
+#[derive(Debug, Clone, Default, Copy, PartialEq, Eq)]
 pub struct Range {
     start: Position,
     end: Position,
@@ -102,6 +176,17 @@ impl Range {
         self.end
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /**
+     * Returns true if the range holds the other one and more.
+     */
+    pub fn strictly_contains(&self, other: &Range) -> bool {
+        self.start <= other.start && other.end <= self.end && self != other
+    }
+
 }
 
 impl From<Selection> for Range {
@@ -111,17 +196,55 @@ impl From<Selection> for Range {
     }
 }
 
-impl From<lsp_types::Range> for Range {
-    fn from(value: lsp_types::Range) -> Self {
-        Self { start: Position::from(value.start), end: Position::from(value.end) }
-    }
-}
+#[cfg(test)]
+mod selection_tests {
+    use pretty_assertions::assert_eq;
 
-impl Into<lsp_types::Range> for Range {
-    fn into(self) -> lsp_types::Range {
-        lsp_types::Range {
-            start: self.start.into(),
-            end: self.end.into()
+    use crate::{core::{document::Document, position::{Cursor, Position}}, highlighter::HighlighterConfig};
+
+    use super::{enclosing_node, ExpandHistory, Range, Selection};
+
+    #[test]
+    fn test_enclosing_node() {
+        let source = "fn main() {\n    let total = one + two;\n}\n";
+        let document = Document::recovered(None, source);
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        let mut bytes = 28..28;
+        let mut grown = Vec::new();
+        while let Some(range) = enclosing_node(config.tree(), bytes) {
+            grown.push(&source[range.clone()]);
+            bytes = range;
         }
+        assert_eq!(grown, vec![
+            "one",
+            "one + two",
+            "let total = one + two;",
+            "{\n    let total = one + two;\n}",
+            "fn main() {\n    let total = one + two;\n}",
+            source,
+        ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_backwards_selection_and_history() {
+        let mut cursor = Cursor(Position::new(0, 2));
+        let mut selection = Selection::default();
+        selection.set_start(Position::new(0, 4));
+        selection.set_end(Position::new(0, 2));
+        assert!(selection.is_backwards());
+
+        let from: Range = selection.into();
+        let to = Range::new(Position::new(0, 0), Position::new(0, 8));
+        selection.select(to, &mut cursor);
+        // The selection keeps growing towards its start
+        assert_eq!((*selection.start(), *selection.end()), (Position::new(0, 8), Position::new(0, 0)));
+        assert_eq!(cursor.0, Position::new(0, 0));
+
+        let mut history = ExpandHistory::default();
+        history.push(from, to);
+        assert_eq!(history.pop(Range::new(Position::new(0, 1), Position::new(0, 8))), None);
+        history.push(from, to);
+        assert_eq!(history.pop(to), Some(from));
+        assert_eq!(history.pop(from), None);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    fs,
+    hash::{Hash, Hasher},
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{display_map::WrapMode, file_format::SaveOptions, keymap::Keymap, position::Position};
+
+/**
+ * The name of the folder the editor keeps its data in.
+ */
+const APP_NAME: &str = "code-editor-prototype";
+
+/**
+ * How many workspaces are listed on the welcome screen.
+ */
+const RECENT_WORKSPACES: usize = 10;
+
+/**
+ * The state of an open document.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentState {
+    /// `None` for a document that was never saved.
+    pub file: Option<PathBuf>,
+    pub cursor: Position,
+    pub selection: (Position, Position),
+    /// The scroll offsets of the document's window.
+    pub scroll: (f32, f32),
+    /// Where the unsaved content of the document was written, if it had unsaved changes.
+    pub recovery: Option<PathBuf>,
+}
+
+/**
+ * What is restored when a workspace is opened again.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub documents: Vec<DocumentState>,
+    pub show_explorer: bool,
+    pub expanded_folders: Vec<PathBuf>,
+    /// The recently opened files, most recent first.
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            documents: Vec::new(),
+            show_explorer: true,
+            expanded_folders: Vec::new(),
+            recent_files: Vec::new(),
+        }
+    }
+}
+
+/**
+ * The preferences of the user, shared by every workspace.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Saves the dirty buffers to disk after a delay
+    pub autosave: bool,
+    #[serde(default)]
+    pub save_options: SaveOptions,
+    #[serde(default)]
+    pub wrap: WrapMode,
+    /// Colors the brackets by how deep they are nested
+    #[serde(default)]
+    pub rainbow_brackets: bool,
+    #[serde(default)]
+    pub keymap: Keymap,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionData {
+    /// Most recent first.
+    recent_workspaces: Vec<PathBuf>,
+    sessions: HashMap<PathBuf, Session>,
+    #[serde(default)]
+    settings: Settings,
+}
+
+/**
+ * Keeps the sessions of the workspaces and the recent workspaces in a folder of the user's data.
+ */
+#[derive(Debug)]
+pub struct SessionStore {
+    dir: PathBuf,
+    data: SessionData,
+}
+
+/**
+ * The folder where the editor keeps its data, following the conventions of each platform.
+ */
+pub fn data_dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        return env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join(APP_NAME))
+    }
+    if cfg!(target_os = "macos") {
+        return home.map(|home| home.join("Library/Application Support").join(APP_NAME))
+    }
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join(APP_NAME))
+    }
+    home.map(|home| home.join(".local/share").join(APP_NAME))
+}
+
+impl SessionStore {
+    /**
+     * Loads the store kept in the folder. A store that can't be read starts empty.
+     */
+    pub fn open(dir: PathBuf) -> Self {
+        let data = match fs::read_to_string(dir.join("session.json")) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                eprintln!("Failed to read the session: {}", e);
+                SessionData::default()
+            }),
+            Err(_) => SessionData::default(),
+        };
+        Self { dir, data }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /**
+     * Returns the workspaces that still exist, most recent first.
+     */
+    pub fn recent_workspaces(&self) -> Vec<PathBuf> {
+        self.data.recent_workspaces.iter().filter(|workspace| workspace.is_dir()).cloned().collect()
+    }
+
+    pub fn last_workspace(&self) -> Option<PathBuf> {
+        self.recent_workspaces().into_iter().next()
+    }
+
+    pub fn session(&self, workspace: &Path) -> Option<&Session> {
+        self.data.sessions.get(workspace)
+    }
+
+    /**
+     * Stores the session of the workspace and makes it the most recent workspace.
+     *
+     * The recovery files of the previous session that aren't used anymore are removed.
+     */
+    pub fn save_session(&mut self, workspace: PathBuf, session: Session) -> Result<(), Error> {
+        if let Some(previous) = self.data.sessions.get(&workspace) {
+            let stale = previous.documents
+                .iter()
+                .filter_map(|document| document.recovery.as_ref())
+                .filter(|recovery| !session.documents.iter().any(|document| document.recovery.as_ref() == Some(*recovery)));
+            for recovery in stale {
+                let _ = fs::remove_file(recovery);
+            }
+        }
+        self.data.sessions.insert(workspace.clone(), session);
+        self.workspace_opened(workspace)
+    }
+
+    /**
+     * Makes the workspace the most recent one.
+     */
+    pub fn workspace_opened(&mut self, workspace: PathBuf) -> Result<(), Error> {
+        self.data.recent_workspaces.retain(|recent| *recent != workspace);
+        self.data.recent_workspaces.insert(0, workspace);
+        self.data.recent_workspaces.truncate(RECENT_WORKSPACES);
+        let workspaces = &self.data.recent_workspaces;
+        self.data.sessions.retain(|workspace, _| workspaces.contains(workspace));
+        self.write()
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.data.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) -> Result<(), Error> {
+        self.data.settings = settings;
+        self.write()
+    }
+
+    /**
+     * Writes the unsaved content of a document to the recovery area and returns the path of the recovery file.
+     */
+    pub fn write_recovery(&self, workspace: &Path, file: Option<&Path>, text: &str) -> Result<PathBuf, Error> {
+        let mut hasher = DefaultHasher::new();
+        workspace.hash(&mut hasher);
+        file.hash(&mut hasher);
+        let dir = self.dir.join("recovery");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{:016x}.txt", hasher.finish()));
+        fs::write(&path, text)?;
+        Ok(path)
+    }
+
+    fn write(&self) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string_pretty(&self.data)?;
+        // Written next to the session first, so a crash doesn't leave half of a session
+        let temporary = self.dir.join("session.json.tmp");
+        fs::write(&temporary, json)?;
+        fs::rename(temporary, self.dir.join("session.json"))
+    }
+}
+
+#[cfg(test)]
+mod session_tests {
+    use std::{fs, path::PathBuf};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::core::position::Position;
+
+    use super::{DocumentState, Session, SessionStore};
+
+    #[test]
+    fn test_save_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let mut store = SessionStore::open(dir.path().to_owned());
+
+        let recovery = store
+            .write_recovery(workspace.path(), Some(&workspace.path().join("main.rs")), "fn main() {}")
+            .unwrap();
+        let session = Session {
+            documents: vec![DocumentState {
+                file: Some(workspace.path().join("main.rs")),
+                cursor: Position::new(3, 4),
+                selection: (Position::new(3, 0), Position::new(3, 4)),
+                scroll: (0.0, 120.0),
+                recovery: Some(recovery.clone()),
+            }],
+            show_explorer: false,
+            expanded_folders: vec![workspace.path().join("src")],
+            recent_files: vec![workspace.path().join("main.rs")],
+        };
+        store.save_session(workspace.path().to_owned(), session.clone()).unwrap();
+
+        let store = SessionStore::open(dir.path().to_owned());
+        assert_eq!(store.last_workspace(), Some(workspace.path().to_owned()));
+        assert_eq!(store.session(workspace.path()), Some(&session));
+        assert_eq!(fs::read_to_string(recovery).unwrap(), "fn main() {}");
+    }
+
+    #[test]
+    fn test_recent_workspaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let mut store = SessionStore::open(dir.path().to_owned());
+
+        store.workspace_opened(first.path().to_owned()).unwrap();
+        store.workspace_opened(second.path().to_owned()).unwrap();
+        store.workspace_opened(first.path().to_owned()).unwrap();
+        store.workspace_opened(PathBuf::from("/does/not/exist")).unwrap();
+        assert_eq!(store.recent_workspaces(), vec![first.path().to_owned(), second.path().to_owned()]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::ops::RangeInclusive;
+
+/**
+ * A command typed on the command line after `:`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExCommand {
+    /// `:w`
+    Write,
+    /// `:e` followed by a file
+    Edit(String),
+    /// `:s/pattern/replacement/flags` on the lines of the range
+    Substitute { lines: RangeInclusive<usize>, pattern: String, replacement: String, global: bool, ignore_case: bool },
+    /// A line number on its own
+    GoTo(usize),
+}
+
+/**
+ * The lines a command applies to when the range is read.
+ */
+pub struct Lines {
+    pub cursor: usize,
+    pub last: usize,
+    /// The lines of the last visual selection, for `'<,'>`
+    pub visual: Option<(usize, usize)>,
+}
+
+/**
+ * Reads one end of a range, a line number, `.` for the line of the cursor, `$` for the last one or `'<` and `'>`
+ * for the ends of the visual selection.
+ */
+fn address(text: &str, lines: &Lines) -> Result<(Option<usize>, usize), String> {
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        let line: usize = text[..digits].parse().map_err(|_| "Invalid range".to_owned())?;
+        return Ok((Some(line.saturating_sub(1).min(lines.last)), digits))
+    }
+    let visual = |end: bool| lines.visual.map(|(start, last)| if end { last } else { start }).ok_or_else(|| "Mark not set".to_owned());
+    match text.get(..2) {
+        Some("'<") => return Ok((Some(visual(false)?), 2)),
+        Some("'>") => return Ok((Some(visual(true)?), 2)),
+        _ => (),
+    }
+    match text.chars().next() {
+        Some('.') => Ok((Some(lines.cursor), 1)),
+        Some('$') => Ok((Some(lines.last), 1)),
+        _ => Ok((None, 0)),
+    }
+}
+
+/**
+ * Reads the range in front of a command, the line of the cursor if there's none.
+ */
+fn range<'a>(text: &'a str, lines: &Lines) -> Result<(Option<RangeInclusive<usize>>, &'a str), String> {
+    if let Some(rest) = text.strip_prefix('%') {
+        return Ok((Some(0..=lines.last), rest))
+    }
+    let (start, length) = address(text, lines)?;
+    let Some(start) = start else {
+        return Ok((None, text))
+    };
+    let rest = &text[length..];
+    let Some(rest) = rest.strip_prefix(',') else {
+        return Ok((Some(start..=start), rest))
+    };
+    let (end, length) = address(rest, lines)?;
+    let end = end.ok_or_else(|| "Invalid range".to_owned())?;
+    Ok((Some(start.min(end)..=start.max(end)), &rest[length..]))
+}
+
+/**
+ * Splits the text at the delimiters that aren't escaped. An escaped delimiter is kept without its backslash.
+ */
+fn split_delimited(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        let part = parts.last_mut().expect("there's always a part");
+        match character {
+            '\\' => match characters.next() {
+                Some(next) if next == delimiter => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            character if character == delimiter => parts.push(String::new()),
+            character => part.push(character),
+        }
+    }
+    parts
+}
+
+/**
+ * Parses the command line. An empty pattern in `:s` is the last one that was used.
+ */
+pub fn parse(text: &str, lines: &Lines, last_pattern: Option<&str>) -> Result<ExCommand, String> {
+    let (range, command) = range(text.trim(), lines)?;
+    let command = command.trim_start();
+    let (name, argument) = command.split_once(' ').map_or((command, ""), |(name, argument)| (name, argument.trim()));
+    match name {
+        "" => range.map(|range| ExCommand::GoTo(*range.end())).ok_or_else(|| "Missing command".to_owned()),
+        "w" | "w!" | "write" | "write!" if argument.is_empty() => Ok(ExCommand::Write),
+        "w" | "w!" | "write" | "write!" => Err("Writing to another file isn't supported, use Save As".to_owned()),
+        "e" | "e!" | "edit" | "edit!" if argument.is_empty() => Err("No file name".to_owned()),
+        "e" | "e!" | "edit" | "edit!" => Ok(ExCommand::Edit(argument.to_owned())),
+        _ if command.starts_with('s') && command[1..].starts_with(|character: char| !character.is_alphanumeric() && character != ' ') => {
+            let delimiter = command[1..].chars().next().unwrap_or('/');
+            let parts = split_delimited(&command[1 + delimiter.len_utf8()..], delimiter);
+            let pattern = match parts.first().map(String::as_str) {
+                Some("") | None => last_pattern.ok_or_else(|| "No previous regular expression".to_owned())?.to_owned(),
+                Some(pattern) => pattern.to_owned(),
+            };
+            let replacement = parts.get(1).cloned().unwrap_or_default();
+            let flags = parts.get(2).map(String::as_str).unwrap_or_default();
+            Ok(ExCommand::Substitute {
+                lines: range.unwrap_or(lines.cursor..=lines.cursor),
+                pattern,
+                replacement,
+                global: flags.contains('g'),
+                ignore_case: flags.contains('i'),
+            })
+        }
+        _ => Err(format!("Not an editor command: {}", text.trim())),
+    }
+}
+
+/**
+ * Turns a pattern of Vim into a regular expression. Groups, alternatives and repetitions are escaped in Vim and
+ * plain characters in the regular expression, and the other way around.
+ */
+pub fn regex_of(pattern: &str) -> String {
+    let mut regex = String::new();
+    let mut characters = pattern.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some(next @ ('(' | ')' | '|' | '+' | '?' | '{' | '}')) => regex.push(next),
+                Some('<' | '>') => regex.push_str("\\b"),
+                Some(next) if next.is_ascii_alphanumeric() || next == '\\' => {
+                    regex.push('\\');
+                    regex.push(next);
+                }
+                Some(next) => regex.push_str(&regex::escape(&next.to_string())),
+                None => regex.push_str("\\\\"),
+            },
+            '(' | ')' | '|' | '+' | '?' | '{' | '}' => {
+                regex.push('\\');
+                regex.push(character);
+            }
+            character => regex.push(character),
+        }
+    }
+    regex
+}
+
+/**
+ * Turns a replacement of Vim into one of the regular expression: `&` and `\0` are the match, `\1` to `\9` its
+ * groups and `\r` a line break.
+ */
+pub fn replacement_of(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut characters = replacement.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            '\\' => match characters.next() {
+                Some(digit @ '0'..='9') => result.push_str(&format!("${{{}}}", digit)),
+                Some('r' | 'n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(next) => result.push(next),
+                None => result.push('\\'),
+            },
+            character => result.push(character),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod ex_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{parse, regex_of, replacement_of, ExCommand, Lines};
+
+    const LINES: Lines = Lines { cursor: 1, last: 9, visual: None };
+
+    fn substitute(lines: std::ops::RangeInclusive<usize>, pattern: &str, replacement: &str, global: bool, ignore_case: bool) -> ExCommand {
+        ExCommand::Substitute { lines, pattern: pattern.to_owned(), replacement: replacement.to_owned(), global, ignore_case }
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(parse("w", &LINES, None), Ok(ExCommand::Write));
+        assert_eq!(parse("  write! ", &LINES, None), Ok(ExCommand::Write));
+        assert_eq!(parse("w other.rs", &LINES, None), Err("Writing to another file isn't supported, use Save As".to_owned()));
+        assert_eq!(parse("e src/main.rs", &LINES, None), Ok(ExCommand::Edit("src/main.rs".to_owned())));
+        assert_eq!(parse("e", &LINES, None), Err("No file name".to_owned()));
+        // Quitting is left to the window, like the other commands that aren't supported
+        assert_eq!(parse("q", &LINES, None), Err("Not an editor command: q".to_owned()));
+        assert_eq!(parse("wq", &LINES, None), Err("Not an editor command: wq".to_owned()));
+        assert_eq!(parse("set number", &LINES, None), Err("Not an editor command: set number".to_owned()));
+        assert_eq!(parse("", &LINES, None), Err("Missing command".to_owned()));
+
+        // Line numbers count from one and stop at the last line
+        assert_eq!(parse("3", &LINES, None), Ok(ExCommand::GoTo(2)));
+        assert_eq!(parse("0", &LINES, None), Ok(ExCommand::GoTo(0)));
+        assert_eq!(parse("120", &LINES, None), Ok(ExCommand::GoTo(9)));
+        assert_eq!(parse("$", &LINES, None), Ok(ExCommand::GoTo(9)));
+    }
+
+    #[test]
+    fn test_substitute_and_ranges() {
+        assert_eq!(parse("s/a/b/", &LINES, None), Ok(substitute(1..=1, "a", "b", false, false)));
+        assert_eq!(parse("%s/a/b/g", &LINES, None), Ok(substitute(0..=9, "a", "b", true, false)));
+        assert_eq!(parse(".,$s/a/b/gi", &LINES, None), Ok(substitute(1..=9, "a", "b", true, true)));
+        // The ends of a range are swapped if they are backwards
+        assert_eq!(parse("5,3s/a/b", &LINES, None), Ok(substitute(2..=4, "a", "b", false, false)));
+        // Another delimiter can be used, escaped it's part of the pattern
+        assert_eq!(parse("2,4s#a\\#b#c#i", &LINES, None), Ok(substitute(1..=3, "a#b", "c", false, true)));
+        assert_eq!(parse("s/x", &LINES, None), Ok(substitute(1..=1, "x", "", false, false)));
+
+        let visual = Lines { cursor: 1, last: 9, visual: Some((2, 5)) };
+        assert_eq!(parse("'<,'>s/a/b/", &visual, None), Ok(substitute(2..=5, "a", "b", false, false)));
+        assert_eq!(parse("'<,'>s/a/b/", &LINES, None), Err("Mark not set".to_owned()));
+        assert_eq!(parse("1,s/a/b/", &LINES, None), Err("Invalid range".to_owned()));
+
+        // An empty pattern is the last one
+        assert_eq!(parse("s//b/", &LINES, Some("a")), Ok(substitute(1..=1, "a", "b", false, false)));
+        assert_eq!(parse("s//b/", &LINES, None), Err("No previous regular expression".to_owned()));
+        assert_eq!(parse("sort", &LINES, None), Err("Not an editor command: sort".to_owned()));
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(regex_of("foo(\\(\\d\\+\\))"), "foo\\((\\d+)\\)");
+        assert_eq!(regex_of("\\<word\\>|a+"), "\\bword\\b\\|a\\+");
+        assert_eq!(regex_of("a\\.b\\"), "a\\.b\\\\");
+        assert_eq!(replacement_of("[\\1]&$\\r\\t\\/"), "[${1}]${0}$$\n\t/");
+    }
+}
@@ -0,0 +1,939 @@
+use std::collections::HashMap;
+
+use regex::RegexBuilder;
+use unicode_segmentation::UnicodeSegmentation;
+
+use self::ex::{regex_of, replacement_of, ExCommand, Lines};
+use self::parser::{parse, Action, Command, InsertAt, Motion, Operator, Parsed, Target};
+use self::targets::{after_character, first_non_blank, line_length, Span};
+
+use super::{buffer::Buffer, history::end_of, indent::leading_whitespace, keymap::KeymapRequest, position::Position};
+
+pub mod ex;
+pub mod parser;
+pub mod targets;
+
+/**
+ * How many macros and repeats can play each other before the rest of them is dropped.
+ */
+const MAX_REPLAY_DEPTH: usize = 100;
+
+/**
+ * How many times a count plays a macro or repeats a change at most.
+ */
+const MAX_REPLAYS: usize = 10_000;
+
+/**
+ * How long the text put by `p` with a count can get, the count is lowered for a register that would go past it.
+ */
+const MAX_PUT_LENGTH: usize = 1 << 20;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    CommandLine,
+}
+
+impl Mode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
+            Self::VisualLine => "VISUAL LINE",
+            Self::CommandLine => "COMMAND",
+        }
+    }
+
+    fn is_visual(&self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine)
+    }
+}
+
+/**
+ * The keys the modal editing reads. The keys it doesn't take are handled as they are without it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimKey {
+    Char(char),
+    Esc,
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Ctrl(char),
+}
+
+/**
+ * The text a register holds, whole lines are put on lines of their own.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+/**
+ * The modal editing of Vim, in front of the buffer. The keys are read one at a time and turned into commands that
+ * use the cursor and the selection of the buffer.
+ */
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: Mode,
+    /// The keys of the command that's being typed in normal or visual mode.
+    pending: Vec<VimKey>,
+    /// The text typed on the command line, after the colon.
+    command_line: String,
+    /// Where the visual selection started, the cursor is its other end.
+    anchor: Position,
+    /// The lines of the last visual selection, for the `'<,'>` range.
+    last_visual: Option<(usize, usize)>,
+    /// The column `j` and `k` keep, across lines that are shorter than it.
+    column: Option<usize>,
+    registers: HashMap<char, Register>,
+    marks: HashMap<char, Position>,
+    /// The last `f`, `F`, `t` or `T`, for `;` and `,`.
+    last_find: Option<Motion>,
+    last_pattern: Option<String>,
+    /// The keys of the last change, played again by `.`.
+    last_change: Vec<VimKey>,
+    /// The keys of the change being made, until insert mode is left.
+    change: Option<Vec<VimKey>>,
+    /// The register a macro is being recorded to, along with its keys.
+    recording: Option<(char, Vec<VimKey>)>,
+    macros: HashMap<char, Vec<VimKey>>,
+    last_macro: Option<char>,
+    /// How many repeats and macros are playing, the keys they play aren't recorded again.
+    replaying: usize,
+}
+
+impl Vim {
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /**
+     * Returns what the status bar shows: the mode, the keys of the command being typed and the macro being
+     * recorded, or the command line.
+     */
+    pub fn status(&self) -> String {
+        if self.mode == Mode::CommandLine {
+            return format!(":{}", self.command_line)
+        }
+        let mut status = format!("-- {} --", self.mode.label());
+        let pending: String = self.pending.iter().filter_map(|key| match key {
+            VimKey::Char(character) => Some(*character),
+            _ => None,
+        }).collect();
+        if !pending.is_empty() {
+            status.push_str(&format!(" {}", pending));
+        }
+        if let Some((name, _)) = self.recording {
+            status.push_str(&format!(" recording @{}", name));
+        }
+        status
+    }
+
+    /**
+     * Goes back to normal mode and drops the command being typed, like when another file is opened.
+     */
+    pub fn reset(&mut self) {
+        self.mode = Mode::Normal;
+        self.pending.clear();
+        self.command_line.clear();
+        self.change = None;
+        self.column = None;
+    }
+
+    /**
+     * Handles a key, returns what the editor is asked to do.
+     */
+    pub fn handle(&mut self, buffer: &mut Buffer, key: VimKey) -> Vec<KeymapRequest> {
+        let mut requests = Vec::new();
+        self.feed(buffer, key, &mut requests);
+        requests
+    }
+
+    fn feed(&mut self, buffer: &mut Buffer, key: VimKey, requests: &mut Vec<KeymapRequest>) {
+        if self.replaying == 0 {
+            if let Some((_, keys)) = self.recording.as_mut() {
+                keys.push(key);
+            }
+        }
+        match self.mode {
+            Mode::Insert => self.insert_key(buffer, key),
+            Mode::CommandLine => self.command_line_key(buffer, key, requests),
+            Mode::Normal | Mode::Visual | Mode::VisualLine => self.normal_key(buffer, key, requests),
+        }
+    }
+
+    fn insert_key(&mut self, buffer: &mut Buffer, key: VimKey) {
+        if let Some(change) = self.change.as_mut() {
+            change.push(key);
+        }
+        match key {
+            VimKey::Esc => {
+                self.mode = Mode::Normal;
+                let cursor = buffer.cursor.0;
+                collapse(buffer, Position::new(cursor.line(), cursor.character().saturating_sub(1)));
+                self.finish_change();
+            }
+            VimKey::Char(character) => {
+                buffer.insert(character.to_string());
+            }
+            VimKey::Enter => buffer.new_line(),
+            VimKey::Backspace => buffer.backspace(),
+            VimKey::Delete => buffer.delete(),
+            VimKey::Tab => buffer.indent(),
+            VimKey::Ctrl(_) => (),
+        }
+    }
+
+    fn command_line_key(&mut self, buffer: &mut Buffer, key: VimKey, requests: &mut Vec<KeymapRequest>) {
+        match key {
+            VimKey::Esc => self.mode = Mode::Normal,
+            VimKey::Backspace if self.command_line.pop().is_none() => self.mode = Mode::Normal,
+            VimKey::Char(character) => self.command_line.push(character),
+            VimKey::Enter => {
+                self.mode = Mode::Normal;
+                let command_line = std::mem::take(&mut self.command_line);
+                if let Err(message) = self.run_command_line(buffer, &command_line, requests) {
+                    requests.push(KeymapRequest::Error(message));
+                }
+                clamp(buffer);
+            }
+            _ => (),
+        }
+    }
+
+    fn normal_key(&mut self, buffer: &mut Buffer, key: VimKey, requests: &mut Vec<KeymapRequest>) {
+        // A selection made with the mouse is a visual selection
+        if self.mode == Mode::Normal && !buffer.selection.is_empty() {
+            self.anchor = *buffer.selection.start();
+            let end = *buffer.selection.end();
+            buffer.cursor.0 = if end > self.anchor && end.character() > 0 { Position::new(end.line(), end.character() - 1) } else { end };
+            self.mode = Mode::Visual;
+        }
+        if key == VimKey::Esc {
+            self.pending.clear();
+            if self.mode.is_visual() {
+                self.leave_visual(buffer);
+            }
+            return
+        }
+        self.pending.push(key);
+        let command = match parse(&self.pending, self.mode.is_visual(), self.recording.is_some()) {
+            Parsed::Incomplete => return,
+            Parsed::Invalid => {
+                self.pending.clear();
+                return
+            }
+            Parsed::Complete(command) => command,
+        };
+        let keys = std::mem::take(&mut self.pending);
+        if command.action.is_change() && !self.mode.is_visual() {
+            self.change = Some(keys);
+        }
+        if !matches!(command.action, Action::Move(Motion::Up | Motion::Down)) {
+            self.column = None;
+        }
+        self.execute(buffer, command, requests);
+
+        if self.mode != Mode::Insert {
+            self.finish_change();
+        }
+        match self.mode {
+            Mode::Visual | Mode::VisualLine => self.show_selection(buffer),
+            Mode::Normal => {
+                clamp(buffer);
+                collapse(buffer, buffer.cursor.0);
+            }
+            Mode::Insert | Mode::CommandLine => (),
+        }
+    }
+
+    /**
+     * Keeps the keys of the change that was made for `.`, unless it was made by `.` or a macro.
+     */
+    fn finish_change(&mut self) {
+        if let Some(change) = self.change.take() {
+            if self.replaying == 0 {
+                self.last_change = change;
+            }
+        }
+    }
+
+    fn execute(&mut self, buffer: &mut Buffer, command: Command, requests: &mut Vec<KeymapRequest>) {
+        let count = command.count.unwrap_or(1).max(1);
+        match command.action {
+            Action::Move(motion) => {
+                if let Some(target) = self.motion_target(buffer, buffer.cursor.0, motion, command.count) {
+                    buffer.cursor.0 = target;
+                }
+            }
+            Action::Operate(operator, target) => self.operate(buffer, operator, target, command, requests),
+            Action::Put { before } => self.put(buffer, before, command, requests),
+            Action::Replace(character) => {
+                let cursor = buffer.cursor.0;
+                if cursor.character().saturating_add(count) <= line_length(buffer, cursor.line()) {
+                    let end = Position::new(cursor.line(), cursor.character() + count);
+                    buffer.replace(&cursor, &end, character.to_string().repeat(count));
+                    buffer.cursor.0 = Position::new(cursor.line(), end.character() - 1);
+                }
+            }
+            Action::ToggleCase => {
+                let cursor = buffer.cursor.0;
+                let end = Position::new(cursor.line(), cursor.character().saturating_add(count).min(line_length(buffer, cursor.line())));
+                let span = Span { start: cursor, end, linewise: false };
+                self.apply(buffer, Operator::ToggleCase, span, None, requests);
+                buffer.cursor.0 = end;
+            }
+            Action::Join => {
+                let line = buffer.cursor.0.line();
+                for _ in 0..count.saturating_sub(1).max(1) {
+                    if line >= buffer.last_text_line() {
+                        break
+                    }
+                    collapse(buffer, Position::new(line, 0));
+                    buffer.join_lines();
+                }
+            }
+            // The history runs out long before a large count does
+            Action::Undo => {
+                for _ in 0..count {
+                    if !buffer.undo() {
+                        break
+                    }
+                }
+            }
+            Action::Redo => {
+                for _ in 0..count {
+                    if !buffer.redo() {
+                        break
+                    }
+                }
+            }
+            Action::Insert(at) => self.insert(buffer, at),
+            Action::Visual { line } => {
+                let mode = if line { Mode::VisualLine } else { Mode::Visual };
+                match self.mode {
+                    current if current == mode => self.leave_visual(buffer),
+                    Mode::Visual | Mode::VisualLine => self.mode = mode,
+                    _ => {
+                        self.anchor = buffer.cursor.0;
+                        self.mode = mode;
+                    }
+                }
+            }
+            Action::Select(object) => {
+                if let Some(span) = targets::object(buffer, buffer.cursor.0, object) {
+                    if span.linewise {
+                        self.mode = Mode::VisualLine;
+                        self.anchor = span.start;
+                        buffer.cursor.0 = span.end;
+                    } else if span.start < span.end {
+                        self.anchor = span.start;
+                        buffer.cursor.0 = before_position(buffer, span.end);
+                    }
+                }
+            }
+            Action::SwapAnchor => std::mem::swap(&mut self.anchor, &mut buffer.cursor.0),
+            Action::CommandLine => {
+                self.command_line.clear();
+                if self.mode.is_visual() {
+                    self.command_line.push_str("'<,'>");
+                    self.leave_visual(buffer);
+                }
+                self.mode = Mode::CommandLine;
+            }
+            Action::Repeat => {
+                let keys = self.last_change.clone();
+                self.replay_times(buffer, &keys, count, requests);
+            }
+            Action::Record(name) => self.recording = Some((name, Vec::new())),
+            Action::StopRecording => {
+                if let Some((name, mut keys)) = self.recording.take() {
+                    // The `q` that stopped the recording
+                    keys.pop();
+                    self.macros.insert(name.to_ascii_lowercase(), keys);
+                }
+            }
+            Action::Play(name) => {
+                let Some(name) = (if name == '@' { self.last_macro } else { Some(name.to_ascii_lowercase()) }) else {
+                    return
+                };
+                self.last_macro = Some(name);
+                // A register that was yanked into is played as the keys it holds
+                let keys = self.macros.get(&name).cloned().or_else(|| {
+                    self.registers.get(&name).map(|register| register.text.chars().map(VimKey::Char).collect())
+                });
+                self.replay_times(buffer, keys.as_deref().unwrap_or_default(), count, requests);
+            }
+            Action::SetMark(name) => {
+                self.marks.insert(name, buffer.cursor.0);
+            }
+        }
+    }
+
+    /**
+     * Replays the keys the number of times, up to `MAX_REPLAYS`. The replays stop once one leaves the text and the
+     * cursor as they were, the ones after it would too.
+     */
+    fn replay_times(&mut self, buffer: &mut Buffer, keys: &[VimKey], times: usize, requests: &mut Vec<KeymapRequest>) {
+        for _ in 0..times.min(MAX_REPLAYS) {
+            let before = (buffer.buffer().version(), buffer.cursor.0);
+            self.replay(buffer, keys, requests);
+            if (buffer.buffer().version(), buffer.cursor.0) == before {
+                break
+            }
+        }
+    }
+
+    fn replay(&mut self, buffer: &mut Buffer, keys: &[VimKey], requests: &mut Vec<KeymapRequest>) {
+        if self.replaying >= MAX_REPLAY_DEPTH {
+            return
+        }
+        self.replaying += 1;
+        for key in keys {
+            self.feed(buffer, *key, requests);
+        }
+        self.replaying -= 1;
+    }
+
+    /**
+     * Returns where the motion goes from the position, or `None` if it can't move.
+     */
+    fn motion_target(&mut self, buffer: &Buffer, from: Position, motion: Motion, count: Option<usize>) -> Option<Position> {
+        let repeat = count.unwrap_or(1).max(1);
+        let last = buffer.last_text_line();
+        // A motion that stops moving at the end or the start of the buffer isn't repeated any further
+        let repeated = |mut position: Position, step: &dyn Fn(Position) -> Position| {
+            for _ in 0..repeat {
+                let next = step(position);
+                if next == position {
+                    break
+                }
+                position = next;
+            }
+            position
+        };
+        let target = match motion {
+            Motion::Left => Position::new(from.line(), from.character().saturating_sub(repeat)),
+            Motion::Right => Position::new(from.line(), from.character().saturating_add(repeat).min(line_length(buffer, from.line()))),
+            Motion::Up | Motion::Down => {
+                let line = if motion == Motion::Up { from.line().saturating_sub(repeat) } else { from.line().saturating_add(repeat).min(last) };
+                let column = *self.column.get_or_insert(from.character());
+                Position::new(line, column.min(line_length(buffer, line)))
+            }
+            Motion::WordStart(kind) => repeated(from, &|position| targets::word_start(buffer, position, kind)),
+            Motion::WordEnd(kind) => repeated(from, &|position| targets::word_end(buffer, position, kind, false)),
+            Motion::WordBack(kind) => repeated(from, &|position| targets::word_back(buffer, position, kind)),
+            Motion::LineStart => Position::new(from.line(), 0),
+            Motion::FirstNonBlank => first_non_blank(buffer, from.line()),
+            Motion::LineEnd => {
+                let line = from.line().saturating_add(repeat - 1).min(last);
+                self.column = Some(usize::MAX);
+                Position::new(line, line_length(buffer, line).saturating_sub(1))
+            }
+            Motion::FirstLine => first_non_blank(buffer, count.map_or(0, |line| line.saturating_sub(1).min(last))),
+            Motion::LastLine => first_non_blank(buffer, count.map_or(last, |line| line.saturating_sub(1).min(last))),
+            Motion::ParagraphForward => repeated(from, &|position| targets::paragraph(buffer, position, true)),
+            Motion::ParagraphBack => repeated(from, &|position| targets::paragraph(buffer, position, false)),
+            Motion::Find { character, before, backward } => {
+                self.last_find = Some(motion);
+                targets::find(buffer, from, character, before, backward, repeat, false)?
+            }
+            Motion::RepeatFind { reverse } => {
+                let Some(Motion::Find { character, before, backward }) = self.last_find else {
+                    return None
+                };
+                targets::find(buffer, from, character, before, backward != reverse, repeat, true)?
+            }
+            Motion::MatchingBracket => targets::matching_bracket(buffer, from)?,
+            Motion::Mark { name, exact } => {
+                let mark = *self.marks.get(&name)?;
+                let line = mark.line().min(last);
+                if exact { Position::new(line, mark.character().min(line_length(buffer, line))) } else { first_non_blank(buffer, line) }
+            }
+        };
+        Some(target)
+    }
+
+    /**
+     * Returns the text the motion goes over from the cursor, for an operator.
+     */
+    fn motion_span(&mut self, buffer: &Buffer, operator: Operator, motion: Motion, count: Option<usize>) -> Option<Span> {
+        let from = buffer.cursor.0;
+        // `cw` changes up to the end of the word, not the spaces after it
+        let on_word = targets::after_character(buffer, from) != from
+            && !buffer.text_between(from, after_character(buffer, from)).trim().is_empty();
+        if let (Operator::Change, Motion::WordStart(kind), true) = (operator, motion, on_word) {
+            let mut end = targets::word_end(buffer, from, kind, true);
+            for _ in 1..count.unwrap_or(1).max(1) {
+                let next = targets::word_end(buffer, end, kind, false);
+                if next == end {
+                    break
+                }
+                end = next;
+            }
+            return Some(Span { start: from, end: after_character(buffer, end).min(buffer.line_end(end.line())), linewise: false })
+        }
+
+        let target = self.motion_target(buffer, from, motion, count)?;
+        if motion.is_linewise() {
+            return Some(Span::lines(from.line(), target.line()))
+        }
+        let (start, mut end) = if target < from { (target, from) } else { (from, target) };
+        if motion.is_inclusive() {
+            end = Position::new(end.line(), (end.character() + 1).min(line_length(buffer, end.line())));
+        } else if end.line() > start.line() && end <= first_non_blank(buffer, end.line()) {
+            // An exclusive motion that ends at the start of a line stops at the end of the line before it
+            end = buffer.line_end(end.line() - 1).max(start);
+        }
+        Some(Span { start, end, linewise: false })
+    }
+
+    /**
+     * Returns the visual selection as a span, the character at the cursor is part of it.
+     */
+    fn visual_span(&self, buffer: &Buffer, linewise: bool) -> Span {
+        let cursor = buffer.cursor.0;
+        let (start, end) = if cursor < self.anchor { (cursor, self.anchor) } else { (self.anchor, cursor) };
+        if linewise || self.mode == Mode::VisualLine {
+            return Span::lines(start.line(), end.line())
+        }
+        Span { start, end: after_character(buffer, end), linewise: false }
+    }
+
+    fn show_selection(&self, buffer: &mut Buffer) {
+        let span = self.visual_span(buffer, false);
+        let (start, end) = if span.linewise {
+            (span.start, buffer.after_line(span.end.line()))
+        } else {
+            (span.start, span.end)
+        };
+        buffer.selection.set_start(start);
+        buffer.selection.set_end(end);
+    }
+
+    fn leave_visual(&mut self, buffer: &mut Buffer) {
+        let span = self.visual_span(buffer, false);
+        self.last_visual = Some((span.start.line(), span.end.line()));
+        self.mode = Mode::Normal;
+        collapse(buffer, buffer.cursor.0);
+    }
+
+    fn operate(&mut self, buffer: &mut Buffer, operator: Operator, target: Target, command: Command, requests: &mut Vec<KeymapRequest>) {
+        let span = match target {
+            Target::Line => {
+                let line = buffer.cursor.0.line();
+                let end = line.saturating_add(command.count.unwrap_or(1).max(1) - 1).min(buffer.last_text_line());
+                Some(Span::lines(line, end))
+            }
+            Target::Motion(motion) => self.motion_span(buffer, operator, motion, command.count),
+            Target::Object(object) => targets::object(buffer, buffer.cursor.0, object),
+            Target::Selection(linewise) => {
+                let span = self.visual_span(buffer, linewise);
+                self.leave_visual(buffer);
+                Some(span)
+            }
+        };
+        if let Some(span) = span {
+            self.apply(buffer, operator, span, command.register, requests);
+        }
+    }
+
+    /**
+     * Applies the operator to the span.
+     */
+    fn apply(&mut self, buffer: &mut Buffer, operator: Operator, span: Span, register: Option<char>, requests: &mut Vec<KeymapRequest>) {
+        let (first, last) = (span.start.line(), span.end.line());
+        let text = if span.linewise {
+            format!("{}\n", (first..=last).map(|line| buffer.line_text(line)).collect::<Vec<String>>().join("\n"))
+        } else {
+            buffer.text_between(span.start, span.end)
+        };
+        let cursor = buffer.cursor.0;
+        collapse(buffer, cursor);
+        match operator {
+            Operator::Yank => {
+                self.write_register(register, text, span.linewise, true, requests);
+                buffer.cursor.0 = if span.linewise { Position::new(first, cursor.character()).min(cursor) } else { span.start };
+            }
+            Operator::Delete if span.linewise => {
+                self.write_register(register, text, true, false, requests);
+                let after = buffer.after_line(last);
+                // The last line takes the line break before it
+                let before = if after.line() == last && first > 0 { buffer.line_end(first - 1) } else { Position::new(first, 0) };
+                buffer.replace(&before, &after, String::new());
+                buffer.cursor.0 = first_non_blank(buffer, first.min(buffer.last_text_line()));
+            }
+            Operator::Delete => {
+                self.write_register(register, text, false, false, requests);
+                buffer.replace(&span.start, &span.end, String::new());
+                buffer.cursor.0 = span.start;
+            }
+            Operator::Change => {
+                self.write_register(register, text, span.linewise, false, requests);
+                let (start, end) = if span.linewise {
+                    let indent = first_non_blank(buffer, first);
+                    (Position::new(first, leading_whitespace(&buffer.line_text(first)).len().min(indent.character())), buffer.line_end(last))
+                } else {
+                    (span.start, span.end)
+                };
+                buffer.replace(&start, &end, String::new());
+                collapse(buffer, start);
+                self.mode = Mode::Insert;
+            }
+            Operator::Indent | Operator::Outdent => {
+                for line in first..=last {
+                    let text = buffer.line_text(line);
+                    if operator == Operator::Indent && !text.trim().is_empty() {
+                        let start = Position::new(line, 0);
+                        buffer.replace(&start, &start, buffer.indent_style().unit());
+                    } else if operator == Operator::Outdent {
+                        let indent = leading_whitespace(&text);
+                        let level = buffer.indent_style().last_level(indent);
+                        let end = Position::new(line, indent.len());
+                        buffer.replace(&Position::new(line, indent.len() - level), &end, String::new());
+                    }
+                }
+                buffer.cursor.0 = first_non_blank(buffer, first);
+            }
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                let changed: String = match operator {
+                    Operator::Lowercase => text.to_lowercase(),
+                    Operator::Uppercase => text.to_uppercase(),
+                    _ => text.chars().map(|character| if character.is_uppercase() {
+                        character.to_lowercase().collect::<String>()
+                    } else {
+                        character.to_uppercase().collect::<String>()
+                    }).collect(),
+                };
+                let (start, end) = if span.linewise { (Position::new(first, 0), buffer.after_line(last)) } else { (span.start, span.end) };
+                if changed != text {
+                    buffer.replace(&start, &end, changed);
+                }
+                buffer.cursor.0 = start;
+            }
+        }
+    }
+
+    /**
+     * Keeps yanked or deleted text in the register. Without a register, the text goes to the unnamed one, along
+     * with `0` for yanks, `1` to `9` for deleted lines and `-` for smaller deletes.
+     */
+    fn write_register(&mut self, register: Option<char>, text: String, linewise: bool, yank: bool, requests: &mut Vec<KeymapRequest>) {
+        let value = Register { text, linewise };
+        match register {
+            Some('_') => return,
+            Some('+' | '*') => requests.push(KeymapRequest::Copy(value.text.clone())),
+            Some(name) if name.is_ascii_uppercase() => {
+                let register = self.registers.entry(name.to_ascii_lowercase()).or_insert(Register { text: String::new(), linewise });
+                register.text.push_str(&value.text);
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, value.clone());
+            }
+            _ if yank => {
+                self.registers.insert('0', value.clone());
+            }
+            _ if linewise || value.text.contains('\n') => {
+                for index in (1..9).rev() {
+                    let from = char::from_digit(index, 10).unwrap_or('1');
+                    let to = char::from_digit(index + 1, 10).unwrap_or('9');
+                    if let Some(register) = self.registers.remove(&from) {
+                        self.registers.insert(to, register);
+                    }
+                }
+                self.registers.insert('1', value.clone());
+            }
+            _ => {
+                self.registers.insert('-', value.clone());
+            }
+        }
+        let unnamed = match register {
+            Some(name) if name.is_ascii_uppercase() => self.registers.get(&name.to_ascii_lowercase()).cloned().unwrap_or(value),
+            _ => value,
+        };
+        self.registers.insert('"', unnamed);
+    }
+
+    fn put(&mut self, buffer: &mut Buffer, before: bool, command: Command, requests: &mut Vec<KeymapRequest>) {
+        if self.mode.is_visual() {
+            // The selection is replaced, the text it held doesn't replace the one that's put
+            let span = self.visual_span(buffer, false);
+            self.leave_visual(buffer);
+            self.apply(buffer, Operator::Delete, span, Some('_'), requests);
+            return self.put(buffer, true, command, requests)
+        }
+        let cursor = buffer.cursor.0;
+        let after = Position::new(cursor.line(), (cursor.character() + 1).min(line_length(buffer, cursor.line())));
+        let name = command.register.unwrap_or('"').to_ascii_lowercase();
+        if matches!(name, '+' | '*') {
+            if !before {
+                buffer.cursor.0 = after;
+            }
+            return requests.push(KeymapRequest::Paste)
+        }
+        let Some(register) = self.registers.get(&name).cloned() else {
+            return
+        };
+        let count = command.count.unwrap_or(1).min(MAX_PUT_LENGTH / register.text.len().max(1)).max(1);
+        let text = register.text.repeat(count);
+        if register.linewise {
+            let line = cursor.line();
+            let start = if before { Position::new(line, 0) } else { buffer.after_line(line) };
+            if !before && start.line() == line {
+                // Below the last line, which has no line break
+                buffer.replace(&start, &start, format!("\n{}", text.trim_end_matches('\n')));
+                buffer.cursor.0 = first_non_blank(buffer, line + 1);
+            } else {
+                buffer.replace(&start, &start, text);
+                buffer.cursor.0 = first_non_blank(buffer, start.line());
+            }
+        } else {
+            let start = if before { cursor } else { after };
+            let end = end_of(start, &text);
+            buffer.replace(&start, &start, text);
+            buffer.cursor.0 = before_position(buffer, end);
+        }
+    }
+
+    fn insert(&mut self, buffer: &mut Buffer, at: InsertAt) {
+        let cursor = buffer.cursor.0;
+        let line = cursor.line();
+        match at {
+            InsertAt::Cursor => (),
+            InsertAt::After => buffer.cursor.0 = Position::new(line, (cursor.character() + 1).min(line_length(buffer, line))),
+            InsertAt::LineStart => buffer.cursor.0 = first_non_blank(buffer, line),
+            InsertAt::LineEnd => buffer.cursor.0 = buffer.line_end(line),
+            InsertAt::LineBelow => {
+                collapse(buffer, buffer.line_end(line));
+                buffer.new_line();
+            }
+            InsertAt::LineAbove if line == 0 => {
+                let indent = leading_whitespace(&buffer.line_text(0)).to_owned();
+                collapse(buffer, Position::default());
+                buffer.insert(format!("{}\n", indent));
+                buffer.cursor.0 = Position::new(0, indent.graphemes(true).count());
+            }
+            InsertAt::LineAbove => {
+                collapse(buffer, buffer.line_end(line - 1));
+                buffer.new_line();
+            }
+        }
+        collapse(buffer, buffer.cursor.0);
+        self.mode = Mode::Insert;
+    }
+
+    fn run_command_line(&mut self, buffer: &mut Buffer, command_line: &str, requests: &mut Vec<KeymapRequest>) -> Result<(), String> {
+        let lines = Lines { cursor: buffer.cursor.0.line(), last: buffer.last_text_line(), visual: self.last_visual };
+        match ex::parse(command_line, &lines, self.last_pattern.as_deref())? {
+            ExCommand::Write => requests.push(KeymapRequest::Save),
+            ExCommand::Edit(file) => requests.push(KeymapRequest::Open(file)),
+            ExCommand::GoTo(line) => buffer.cursor.0 = first_non_blank(buffer, line),
+            ExCommand::Substitute { lines, pattern, replacement, global, ignore_case } => {
+                let regex = RegexBuilder::new(&regex_of(&pattern))
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .map_err(|e| format!("Invalid pattern: {}", e))?;
+                self.last_pattern = Some(pattern.clone());
+                let replacement = replacement_of(&replacement);
+                let mut last_changed = None;
+                // From the bottom up, so a replacement with line breaks doesn't move the lines left to change
+                for line in lines.rev() {
+                    let text = buffer.line_text(line);
+                    let changed = if global { regex.replace_all(&text, replacement.as_str()) } else { regex.replace(&text, replacement.as_str()) };
+                    if changed != text {
+                        let changed = changed.into_owned();
+                        buffer.replace(&Position::new(line, 0), &buffer.line_end(line), changed);
+                        last_changed = last_changed.or(Some(line));
+                    }
+                }
+                let line = last_changed.ok_or_else(|| format!("Pattern not found: {}", pattern))?;
+                buffer.cursor.0 = first_non_blank(buffer, line);
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Puts the cursor at the position with nothing selected.
+ */
+fn collapse(buffer: &mut Buffer, position: Position) {
+    buffer.selection.set_start(position);
+    buffer.selection.set_end(position);
+    buffer.cursor.0 = position;
+}
+
+/**
+ * Keeps the cursor on a character of its line, it can't be after the last one outside of insert mode.
+ */
+fn clamp(buffer: &mut Buffer) {
+    let cursor = buffer.cursor.0;
+    let line = cursor.line().min(buffer.len().saturating_sub(1));
+    let last = line_length(buffer, line).saturating_sub(1);
+    buffer.cursor.0 = Position::new(line, cursor.character().min(last));
+}
+
+/**
+ * Returns the position of the character before the position, on the same line.
+ */
+fn before_position(buffer: &Buffer, position: Position) -> Position {
+    match position.character() {
+        0 if position.line() > 0 => {
+            let line = position.line() - 1;
+            Position::new(line, line_length(buffer, line).saturating_sub(1))
+        }
+        character => Position::new(position.line(), character.saturating_sub(1)),
+    }
+}
+
+#[cfg(test)]
+mod vim_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, keymap::KeymapRequest, position::Position};
+
+    use super::{Mode, Vim, VimKey, MAX_PUT_LENGTH};
+
+    /**
+     * Types the keys, `<` starts the name of a special key like `<Esc>`.
+     */
+    fn type_keys(vim: &mut Vim, buffer: &mut Buffer, keys: &str) -> Vec<KeymapRequest> {
+        let mut requests = Vec::new();
+        let mut rest = keys;
+        while let Some(character) = rest.chars().next() {
+            let special = [("<Esc>", VimKey::Esc), ("<CR>", VimKey::Enter), ("<BS>", VimKey::Backspace), ("<C-r>", VimKey::Ctrl('r'))]
+                .into_iter()
+                .find(|(name, _)| rest.starts_with(name));
+            let (key, length) = special.map_or((VimKey::Char(character), character.len_utf8()), |(name, key)| (key, name.len()));
+            requests.extend(vim.handle(buffer, key));
+            // Like the editor, an insert is undone at once
+            if vim.mode() != Mode::Insert {
+                buffer.seal_history();
+            }
+            rest = &rest[length..];
+        }
+        requests
+    }
+
+    #[test]
+    fn test_operators_and_motions() {
+        let mut vim = Vim::default();
+        let mut buffer = Buffer::from_source("let alpha = beta(gamma, \"delta\");\nlet x = 1;\n\nfn main() {\n    call();\n}\n");
+        type_keys(&mut vim, &mut buffer, "wdw");
+        assert_eq!(buffer.line_text(0), "let = beta(gamma, \"delta\");");
+        type_keys(&mut vim, &mut buffer, "f(ci(x<Esc>");
+        assert_eq!(buffer.line_text(0), "let = beta(x);");
+        assert_eq!(vim.mode(), Mode::Normal);
+        assert_eq!(buffer.cursor.0, Position::new(0, 11));
+
+        // The change is undone, then the text within the quotes and a word of the next line are changed
+        type_keys(&mut vim, &mut buffer, "u");
+        assert_eq!(buffer.line_text(0), "let = beta(gamma, \"delta\");");
+        type_keys(&mut vim, &mut buffer, "0ci\"epsilon<Esc>");
+        assert_eq!(buffer.line_text(0), "let = beta(gamma, \"epsilon\");");
+        type_keys(&mut vim, &mut buffer, "j0wcwy<Esc>");
+        assert_eq!(buffer.line_text(1), "let y = 1;");
+
+        type_keys(&mut vim, &mut buffer, "5Gdi{");
+        assert_eq!(buffer.get_string(), "let = beta(gamma, \"epsilon\");\nlet y = 1;\n\nfn main() {\n}\n");
+        type_keys(&mut vim, &mut buffer, "ggyapGp");
+        assert_eq!(buffer.get_string(), "let = beta(gamma, \"epsilon\");\nlet y = 1;\n\nfn main() {\n}\nlet = beta(gamma, \"epsilon\");\nlet y = 1;\n\n");
+
+        type_keys(&mut vim, &mut buffer, "gg2dd");
+        assert_eq!(buffer.line_text(0), "");
+        type_keys(&mut vim, &mut buffer, ".");
+        assert_eq!(buffer.line_text(0), "}");
+    }
+
+    #[test]
+    fn test_dot_repeat() {
+        let mut vim = Vim::default();
+        let mut buffer = Buffer::from_source("alpha beta\nalpha gamma\nalpha x delta\n");
+
+        // The dot repeats the change on the next line, with the text typed in insert mode
+        type_keys(&mut vim, &mut buffer, "ciwomega<Esc>j.");
+        assert_eq!(buffer.get_string(), "omega beta\nomega gamma\nalpha x delta\n");
+        assert_eq!(buffer.cursor.0, Position::new(1, 4));
+        type_keys(&mut vim, &mut buffer, "w.");
+        assert_eq!(buffer.line_text(1), "omega omega");
+
+        // A count given to the dot replaces the one of the change
+        type_keys(&mut vim, &mut buffer, "j0dw.");
+        assert_eq!(buffer.line_text(2), "delta");
+        type_keys(&mut vim, &mut buffer, "ggx2.");
+        assert_eq!(buffer.line_text(0), "ga beta");
+    }
+
+    #[test]
+    fn test_huge_counts() {
+        let mut vim = Vim::default();
+        let mut buffer = Buffer::from_source("ab\ncd\nef\n");
+        let huge = "99999999999999999999";
+
+        // Counts stop growing, and the motions stop at the ends of the buffer
+        type_keys(&mut vim, &mut buffer, &format!("{huge}j{huge}$"));
+        assert_eq!(buffer.cursor.0, Position::new(2, 1));
+        type_keys(&mut vim, &mut buffer, &format!("gg{huge}rx{huge}~"));
+        assert_eq!(buffer.get_string(), "AB\ncd\nef\n");
+        type_keys(&mut vim, &mut buffer, &format!("j{huge}dd"));
+        assert_eq!(buffer.get_string(), "AB\n");
+        type_keys(&mut vim, &mut buffer, &format!("{huge}u"));
+        assert_eq!(buffer.get_string(), "ab\ncd\nef\n");
+        type_keys(&mut vim, &mut buffer, &format!("ggx{huge}.{huge}J"));
+        assert_eq!(buffer.get_string(), "cd ef\n");
+
+        // The text put with a count has a limit
+        type_keys(&mut vim, &mut buffer, &format!("y${huge}p"));
+        assert_eq!(buffer.line_text(0).len(), 5 + MAX_PUT_LENGTH / 5 * 5);
+    }
+
+    #[test]
+    fn test_visual_registers_and_macros() {
+        let mut vim = Vim::default();
+        let mut buffer = Buffer::from_source("one\ntwo\nthree\n");
+        type_keys(&mut vim, &mut buffer, "Vj\"ay");
+        assert_eq!(vim.mode(), Mode::Normal);
+        type_keys(&mut vim, &mut buffer, "G\"aP");
+        assert_eq!(buffer.get_string(), "one\ntwo\none\ntwo\nthree\n");
+
+        // A macro that uppercases the first word of a line and moves to the next one
+        type_keys(&mut vim, &mut buffer, "ggqqgUiwjq2@q");
+        assert_eq!(buffer.get_string(), "ONE\nTWO\nONE\ntwo\nthree\n");
+        type_keys(&mut vim, &mut buffer, "@@");
+        assert_eq!(buffer.get_string(), "ONE\nTWO\nONE\nTWO\nthree\n");
+
+        type_keys(&mut vim, &mut buffer, "mxggvlld`xp");
+        assert_eq!(buffer.get_string(), "\nTWO\nONE\nTWO\ntONEhree\n");
+        type_keys(&mut vim, &mut buffer, "uuuu<C-r>");
+        assert_eq!(buffer.get_string(), "ONE\nTWO\nONE\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mut vim = Vim::default();
+        let mut buffer = Buffer::from_source("foo(1);\nfoo(2);\nbar(foo);\n");
+        type_keys(&mut vim, &mut buffer, ":%s/foo(\\(\\d\\))/baz[\\1]/g<CR>");
+        assert_eq!(buffer.get_string(), "baz[1];\nbaz[2];\nbar(foo);\n");
+        assert_eq!(buffer.cursor.0, Position::new(1, 0));
+        type_keys(&mut vim, &mut buffer, ":3<CR>:s/foo/&&/<CR>");
+        assert_eq!(buffer.line_text(2), "bar(foofoo);");
+
+        let requests = type_keys(&mut vim, &mut buffer, ":w<CR>:e src/lib.rs<CR>:s/missing//<CR>:nope<CR>");
+        assert_eq!(requests, vec![
+            KeymapRequest::Save,
+            KeymapRequest::Open("src/lib.rs".to_owned()),
+            KeymapRequest::Error("Pattern not found: missing".to_owned()),
+            KeymapRequest::Error("Not an editor command: nope".to_owned()),
+        ]);
+        assert_eq!(vim.status(), "-- NORMAL --");
+    }
+}
@@ -0,0 +1,500 @@
+use crate::core::motion::WordKind;
+
+use super::VimKey;
+
+/**
+ * The largest count, the digits typed after it are dropped. It's past the lines of any file that can be opened.
+ */
+pub const MAX_COUNT: usize = 9_999_999;
+
+/**
+ * What an operator does to the text a motion or a text object goes over.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// `w` and `W`
+    WordStart(WordKind),
+    /// `e` and `E`
+    WordEnd(WordKind),
+    /// `b` and `B`
+    WordBack(WordKind),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `gg`, or the line of the count
+    FirstLine,
+    /// `G`, or the line of the count
+    LastLine,
+    ParagraphForward,
+    ParagraphBack,
+    /// `f`, `F`, `t` and `T`, `before` stops next to the character
+    Find { character: char, before: bool, backward: bool },
+    /// `;`, or `,` in reverse
+    RepeatFind { reverse: bool },
+    MatchingBracket,
+    /// `` `a`` goes to the mark, `'a` to the first character of its line
+    Mark { name: char, exact: bool },
+}
+
+impl Motion {
+    /**
+     * Returns true if an operator takes the whole lines the motion goes over.
+     */
+    pub fn is_linewise(&self) -> bool {
+        matches!(self, Self::Up | Self::Down | Self::FirstLine | Self::LastLine | Self::Mark { exact: false, .. })
+    }
+
+    /**
+     * Returns true if an operator takes the character the motion stops on.
+     */
+    pub fn is_inclusive(&self) -> bool {
+        matches!(self, Self::WordEnd(_) | Self::LineEnd | Self::Find { .. } | Self::RepeatFind { .. } | Self::MatchingBracket)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+    Word { kind: WordKind, around: bool },
+    Quote { quote: char, around: bool },
+    Bracket { open: char, close: char, around: bool },
+    Paragraph { around: bool },
+}
+
+/**
+ * The text an operator applies to.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Motion(Motion),
+    Object(TextObject),
+    /// The line of the cursor and the ones below it, when the operator is typed twice
+    Line,
+    /// The visual selection, taken as whole lines if `true`
+    Selection(bool),
+}
+
+/**
+ * Where `i`, `a`, `I`, `A`, `o` and `O` start inserting.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertAt {
+    Cursor,
+    After,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// `p` and `P`
+    Put { before: bool },
+    /// `r`
+    Replace(char),
+    /// `~`
+    ToggleCase,
+    Join,
+    Undo,
+    Redo,
+    Insert(InsertAt),
+    /// `v` and `V`
+    Visual { line: bool },
+    /// Selects a text object from visual mode
+    Select(TextObject),
+    /// `o` in visual mode, the cursor goes to the other end of the selection
+    SwapAnchor,
+    CommandLine,
+    /// `.`
+    Repeat,
+    /// `q` followed by a register
+    Record(char),
+    /// `q` while a macro is recorded
+    StopRecording,
+    /// `@` followed by a register, `@@` plays the last macro again
+    Play(char),
+    SetMark(char),
+}
+
+impl Action {
+    /**
+     * Returns true if the action changes the text, it's repeated by `.`.
+     */
+    pub fn is_change(&self) -> bool {
+        match self {
+            Self::Operate(operator, _) => *operator != Operator::Yank,
+            Self::Put { .. } | Self::Replace(_) | Self::ToggleCase | Self::Join | Self::Insert(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command {
+    pub count: Option<usize>,
+    pub register: Option<char>,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parsed {
+    /// More keys are needed
+    Incomplete,
+    /// The keys aren't a command, they're dropped
+    Invalid,
+    Complete(Command),
+}
+
+/**
+ * What was read from the keys, `Err` carries what the parse ends with.
+ */
+type Step<T> = Result<T, Parsed>;
+
+struct Reader<'a> {
+    keys: &'a [VimKey],
+    index: usize,
+}
+
+impl Reader<'_> {
+    fn next(&mut self) -> Step<VimKey> {
+        let key = self.keys.get(self.index).copied().ok_or(Parsed::Incomplete)?;
+        self.index += 1;
+        Ok(key)
+    }
+
+    fn peek(&self) -> Option<VimKey> {
+        self.keys.get(self.index).copied()
+    }
+
+    fn character(&mut self) -> Step<char> {
+        match self.next()? {
+            VimKey::Char(character) => Ok(character),
+            _ => Err(Parsed::Invalid),
+        }
+    }
+
+    /**
+     * Reads a count, a count can't start with `0` as that's a motion. The count stops growing at `MAX_COUNT`.
+     */
+    fn count(&mut self) -> Option<usize> {
+        let mut count: Option<usize> = None;
+        while let Some(VimKey::Char(digit @ '0'..='9')) = self.peek() {
+            if digit == '0' && count.is_none() {
+                break
+            }
+            self.index += 1;
+            let digit = digit.to_digit(10).unwrap_or_default() as usize;
+            count = Some(count.unwrap_or_default().saturating_mul(10).saturating_add(digit).min(MAX_COUNT));
+        }
+        count
+    }
+}
+
+/**
+ * Returns true if the character names a register.
+ */
+fn is_register(name: char) -> bool {
+    name.is_ascii_alphanumeric() || "\"+*_-".contains(name)
+}
+
+/**
+ * Multiplies the count typed before an operator with the one typed after it.
+ */
+fn multiply(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.saturating_mul(second).min(MAX_COUNT)),
+        (first, second) => first.or(second),
+    }
+}
+
+/**
+ * Parses the keys typed in normal or visual mode, along with the count and register in front of them.
+ */
+pub fn parse(keys: &[VimKey], visual: bool, recording: bool) -> Parsed {
+    let mut reader = Reader { keys, index: 0 };
+    match parse_command(&mut reader, visual, recording) {
+        Ok(command) => Parsed::Complete(command),
+        Err(parsed) => parsed,
+    }
+}
+
+fn parse_command(reader: &mut Reader, visual: bool, recording: bool) -> Step<Command> {
+    let mut count = reader.count();
+    let mut register = None;
+    if reader.peek() == Some(VimKey::Char('"')) {
+        reader.next()?;
+        let name = reader.character()?;
+        if !is_register(name) {
+            return Err(Parsed::Invalid)
+        }
+        register = Some(name);
+        count = multiply(count, reader.count());
+    }
+    let command = |count: Option<usize>, action: Action| Ok(Command { count, register, action });
+
+    let key = reader.next()?;
+    let character = match key {
+        VimKey::Char(character) => character,
+        VimKey::Ctrl('r') => return command(count, Action::Redo),
+        VimKey::Enter => return command(count, Action::Move(Motion::Down)),
+        VimKey::Backspace => return command(count, Action::Move(Motion::Left)),
+        VimKey::Delete if visual => return command(count, Action::Operate(Operator::Delete, Target::Selection(false))),
+        VimKey::Delete => return command(count, Action::Operate(Operator::Delete, Target::Motion(Motion::Right))),
+        _ => return Err(Parsed::Invalid),
+    };
+
+    let operator = match character {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        '>' => Some(Operator::Indent),
+        '<' => Some(Operator::Outdent),
+        'g' => match reader.peek() {
+            Some(VimKey::Char('~')) => Some(Operator::ToggleCase),
+            Some(VimKey::Char('u')) => Some(Operator::Lowercase),
+            Some(VimKey::Char('U')) => Some(Operator::Uppercase),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        // The operators that start with `g` are repeated by their second key
+        let repeat = if character == 'g' { reader.character()? } else { character };
+        if visual {
+            return command(count, Action::Operate(operator, Target::Selection(false)))
+        }
+        return parse_operator(reader, operator, repeat, count, register)
+    }
+
+    if visual {
+        let selection = |operator: Operator, line: bool| Action::Operate(operator, Target::Selection(line));
+        let action = match character {
+            'x' => Some(selection(Operator::Delete, false)),
+            's' => Some(selection(Operator::Change, false)),
+            '~' => Some(selection(Operator::ToggleCase, false)),
+            'u' => Some(selection(Operator::Lowercase, false)),
+            'U' => Some(selection(Operator::Uppercase, false)),
+            'D' | 'X' => Some(selection(Operator::Delete, true)),
+            'Y' => Some(selection(Operator::Yank, true)),
+            'C' | 'S' | 'R' => Some(selection(Operator::Change, true)),
+            'o' => Some(Action::SwapAnchor),
+            'i' | 'a' => Some(Action::Select(parse_object(reader, character == 'a')?)),
+            _ => None,
+        };
+        if let Some(action) = action {
+            return command(count, action)
+        }
+    }
+
+    let action = match character {
+        'x' => Action::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+        'X' => Action::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+        's' => Action::Operate(Operator::Change, Target::Motion(Motion::Right)),
+        'S' => Action::Operate(Operator::Change, Target::Line),
+        'D' => Action::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+        'C' => Action::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+        'Y' => Action::Operate(Operator::Yank, Target::Line),
+        'p' => Action::Put { before: false },
+        'P' => Action::Put { before: true },
+        'r' => Action::Replace(reader.character()?),
+        '~' => Action::ToggleCase,
+        'J' => Action::Join,
+        'u' => Action::Undo,
+        'i' => Action::Insert(InsertAt::Cursor),
+        'a' => Action::Insert(InsertAt::After),
+        'I' => Action::Insert(InsertAt::LineStart),
+        'A' => Action::Insert(InsertAt::LineEnd),
+        'o' => Action::Insert(InsertAt::LineBelow),
+        'O' => Action::Insert(InsertAt::LineAbove),
+        'v' => Action::Visual { line: false },
+        'V' => Action::Visual { line: true },
+        ':' => Action::CommandLine,
+        '.' => Action::Repeat,
+        'q' if recording => Action::StopRecording,
+        'q' => match reader.character()? {
+            name if name.is_ascii_alphanumeric() => Action::Record(name),
+            _ => return Err(Parsed::Invalid),
+        },
+        '@' => match reader.character()? {
+            name if name.is_ascii_alphanumeric() || name == '@' => Action::Play(name),
+            _ => return Err(Parsed::Invalid),
+        },
+        'm' => match reader.character()? {
+            name if name.is_ascii_alphabetic() => Action::SetMark(name),
+            _ => return Err(Parsed::Invalid),
+        },
+        _ => Action::Move(parse_motion(reader, character)?),
+    };
+    command(count, action)
+}
+
+/**
+ * Parses what follows an operator: the operator again for whole lines, a text object or a motion.
+ */
+fn parse_operator(reader: &mut Reader, operator: Operator, repeat: char, count: Option<usize>, register: Option<char>) -> Step<Command> {
+    let count = multiply(count, reader.count());
+    let target = match reader.character()? {
+        // `dd`, `cc`, `yy`, `>>`, `<<`, `g~~`, `guu` and `gUU`
+        next if next == repeat => Target::Line,
+        // `g~g~`, `gugu` and `gUgU`
+        'g' if "~uU".contains(repeat) => match reader.character()? {
+            'g' => Target::Motion(Motion::FirstLine),
+            next if next == repeat => Target::Line,
+            _ => return Err(Parsed::Invalid),
+        },
+        next @ ('i' | 'a') => Target::Object(parse_object(reader, next == 'a')?),
+        next => Target::Motion(parse_motion(reader, next)?),
+    };
+    Ok(Command { count, register, action: Action::Operate(operator, target) })
+}
+
+fn parse_motion(reader: &mut Reader, character: char) -> Step<Motion> {
+    let motion = match character {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'j' | '+' => Motion::Down,
+        'k' | '-' => Motion::Up,
+        'w' => Motion::WordStart(WordKind::Word),
+        'W' => Motion::WordStart(WordKind::Whitespace),
+        'e' => Motion::WordEnd(WordKind::Word),
+        'E' => Motion::WordEnd(WordKind::Whitespace),
+        'b' => Motion::WordBack(WordKind::Word),
+        'B' => Motion::WordBack(WordKind::Whitespace),
+        '0' => Motion::LineStart,
+        '^' | '_' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'G' => Motion::LastLine,
+        'g' => match reader.character()? {
+            'g' => Motion::FirstLine,
+            _ => return Err(Parsed::Invalid),
+        },
+        '}' => Motion::ParagraphForward,
+        '{' => Motion::ParagraphBack,
+        'f' => Motion::Find { character: reader.character()?, before: false, backward: false },
+        'F' => Motion::Find { character: reader.character()?, before: false, backward: true },
+        't' => Motion::Find { character: reader.character()?, before: true, backward: false },
+        'T' => Motion::Find { character: reader.character()?, before: true, backward: true },
+        ';' => Motion::RepeatFind { reverse: false },
+        ',' => Motion::RepeatFind { reverse: true },
+        '%' => Motion::MatchingBracket,
+        '`' => Motion::Mark { name: reader.character()?, exact: true },
+        '\'' => Motion::Mark { name: reader.character()?, exact: false },
+        _ => return Err(Parsed::Invalid),
+    };
+    Ok(motion)
+}
+
+/**
+ * Parses the text object after `i` or `a`.
+ */
+fn parse_object(reader: &mut Reader, around: bool) -> Step<TextObject> {
+    let object = match reader.character()? {
+        'w' => TextObject::Word { kind: WordKind::Word, around },
+        'W' => TextObject::Word { kind: WordKind::Whitespace, around },
+        quote @ ('"' | '\'' | '`') => TextObject::Quote { quote, around },
+        '(' | ')' | 'b' => TextObject::Bracket { open: '(', close: ')', around },
+        '{' | '}' | 'B' => TextObject::Bracket { open: '{', close: '}', around },
+        '[' | ']' => TextObject::Bracket { open: '[', close: ']', around },
+        '<' | '>' => TextObject::Bracket { open: '<', close: '>', around },
+        'p' => TextObject::Paragraph { around },
+        _ => return Err(Parsed::Invalid),
+    };
+    Ok(object)
+}
+
+#[cfg(test)]
+mod parser_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{motion::WordKind, vim::VimKey};
+
+    use super::{parse, Action, Command, InsertAt, Motion, Operator, Parsed, Target, TextObject, MAX_COUNT};
+
+    fn keys(text: &str) -> Vec<VimKey> {
+        text.chars().map(VimKey::Char).collect()
+    }
+
+    fn complete(count: Option<usize>, register: Option<char>, action: Action) -> Parsed {
+        Parsed::Complete(Command { count, register, action })
+    }
+
+    #[test]
+    fn test_counts() {
+        let word = Target::Motion(Motion::WordStart(WordKind::Word));
+        assert_eq!(parse(&keys("3dw"), false, false), complete(Some(3), None, Action::Operate(Operator::Delete, word)));
+        // The counts before and after the operator are multiplied
+        assert_eq!(parse(&keys("2d3w"), false, false), complete(Some(6), None, Action::Operate(Operator::Delete, word)));
+        assert_eq!(parse(&keys("10j"), false, false), complete(Some(10), None, Action::Move(Motion::Down)));
+        // A `0` that doesn't follow a digit is a motion
+        assert_eq!(parse(&keys("0"), false, false), complete(None, None, Action::Move(Motion::LineStart)));
+        assert_eq!(parse(&keys("20x"), false, false), complete(Some(20), None, Action::Operate(Operator::Delete, Target::Motion(Motion::Right))));
+        assert_eq!(parse(&keys("99999999999999999999G"), false, false), complete(Some(MAX_COUNT), None, Action::Move(Motion::LastLine)));
+        assert_eq!(parse(&keys("3"), false, false), Parsed::Incomplete);
+    }
+
+    #[test]
+    fn test_operator_pending() {
+        assert_eq!(parse(&keys("d"), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("c2"), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("di"), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("gU"), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("dz"), false, false), Parsed::Invalid);
+        assert_eq!(parse(&keys("diz"), false, false), Parsed::Invalid);
+        assert_eq!(parse(&keys("dgx"), false, false), Parsed::Invalid);
+
+        assert_eq!(parse(&keys("dd"), false, false), complete(None, None, Action::Operate(Operator::Delete, Target::Line)));
+        assert_eq!(parse(&keys("gUU"), false, false), complete(None, None, Action::Operate(Operator::Uppercase, Target::Line)));
+        assert_eq!(parse(&keys("g~g~"), false, false), complete(None, None, Action::Operate(Operator::ToggleCase, Target::Line)));
+        assert_eq!(parse(&keys("gugg"), false, false), complete(None, None, Action::Operate(Operator::Lowercase, Target::Motion(Motion::FirstLine))));
+        let quote = TextObject::Quote { quote: '"', around: false };
+        assert_eq!(parse(&keys("ci\""), false, false), complete(None, None, Action::Operate(Operator::Change, Target::Object(quote))));
+        let block = TextObject::Bracket { open: '{', close: '}', around: true };
+        assert_eq!(parse(&keys("yaB"), false, false), complete(None, None, Action::Operate(Operator::Yank, Target::Object(block))));
+        let find = Motion::Find { character: 'x', before: true, backward: false };
+        assert_eq!(parse(&keys(">tx"), false, false), complete(None, None, Action::Operate(Operator::Indent, Target::Motion(find))));
+
+        // In visual mode the operator applies to the selection at once
+        assert_eq!(parse(&keys("d"), true, false), complete(None, None, Action::Operate(Operator::Delete, Target::Selection(false))));
+        assert_eq!(parse(&keys("Y"), true, false), complete(None, None, Action::Operate(Operator::Yank, Target::Selection(true))));
+        let word = TextObject::Word { kind: WordKind::Whitespace, around: false };
+        assert_eq!(parse(&keys("iW"), true, false), complete(None, None, Action::Select(word)));
+        assert_eq!(parse(&keys("i"), false, false), complete(None, None, Action::Insert(InsertAt::Cursor)));
+    }
+
+    #[test]
+    fn test_registers() {
+        assert_eq!(parse(&keys("\"a2yy"), false, false), complete(Some(2), Some('a'), Action::Operate(Operator::Yank, Target::Line)));
+        assert_eq!(parse(&keys("3\"+2p"), false, false), complete(Some(6), Some('+'), Action::Put { before: false }));
+        assert_eq!(parse(&keys("\"_dd"), false, false), complete(None, Some('_'), Action::Operate(Operator::Delete, Target::Line)));
+        assert_eq!(parse(&keys("\""), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("\"a"), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("\"!p"), false, false), Parsed::Invalid);
+        assert_eq!(parse(&[VimKey::Char('"'), VimKey::Esc], false, false), Parsed::Invalid);
+
+        // Macros and marks take a register of their own
+        assert_eq!(parse(&keys("qa"), false, false), complete(None, None, Action::Record('a')));
+        assert_eq!(parse(&keys("q"), false, true), complete(None, None, Action::StopRecording));
+        assert_eq!(parse(&keys("q"), false, false), Parsed::Incomplete);
+        assert_eq!(parse(&keys("q\""), false, false), Parsed::Invalid);
+        assert_eq!(parse(&keys("2@@"), false, false), complete(Some(2), None, Action::Play('@')));
+        assert_eq!(parse(&keys("m1"), false, false), Parsed::Invalid);
+    }
+}
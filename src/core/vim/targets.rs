@@ -0,0 +1,376 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::core::{buffer::Buffer, indent::leading_whitespace, motion::{next_word_end, next_word_start, previous_word_start, word_object, WordKind}, position::Position};
+
+use super::parser::TextObject;
+
+/**
+ * The text an operator applies to. The end of a span of characters is excluded, a span of lines takes the lines of
+ * both ends.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+    pub linewise: bool,
+}
+
+impl Span {
+    pub fn lines(start: usize, end: usize) -> Self {
+        Self { start: Position::new(start.min(end), 0), end: Position::new(start.max(end), 0), linewise: true }
+    }
+}
+
+/**
+ * Returns the number of graphemes of the line.
+ */
+pub fn line_length(buffer: &Buffer, line: usize) -> usize {
+    buffer.line_end(line).character()
+}
+
+pub fn is_blank(buffer: &Buffer, line: usize) -> bool {
+    buffer.line_text(line).trim().is_empty()
+}
+
+pub fn first_non_blank(buffer: &Buffer, line: usize) -> Position {
+    let text = buffer.line_text(line);
+    Position::new(line, leading_whitespace(&text).graphemes(true).count())
+}
+
+/**
+ * Returns the position after the character at the position, the line break if it's at the end of its line.
+ */
+pub fn after_character(buffer: &Buffer, position: Position) -> Position {
+    if position.character() < line_length(buffer, position.line()) {
+        Position::new(position.line(), position.character() + 1)
+    } else {
+        buffer.after_line(position.line())
+    }
+}
+
+/**
+ * Returns the first word of the line, an empty line being a word of its own.
+ */
+fn first_word(text: &str, kind: WordKind) -> Option<usize> {
+    match text.chars().next() {
+        None => Some(0),
+        Some(character) if !character.is_whitespace() => Some(0),
+        Some(_) => next_word_start(text, 0, kind),
+    }
+}
+
+/**
+ * `w`, the start of the next word, on the lines below if the line has none left.
+ */
+pub fn word_start(buffer: &Buffer, from: Position, kind: WordKind) -> Position {
+    if let Some(column) = next_word_start(&buffer.line_text(from.line()), from.character(), kind) {
+        return Position::new(from.line(), column)
+    }
+    let last = buffer.last_text_line();
+    (from.line() + 1..=last)
+        .find_map(|line| first_word(&buffer.line_text(line), kind).map(|column| Position::new(line, column)))
+        .unwrap_or_else(|| buffer.line_end(last.max(from.line())))
+}
+
+/**
+ * `e`, the last character of the next word. The end of the word at the position counts if `stay` is set, like
+ * `cw` does.
+ */
+pub fn word_end(buffer: &Buffer, from: Position, kind: WordKind, stay: bool) -> Position {
+    let column = from.character() + usize::from(!stay);
+    if let Some(end) = next_word_end(&buffer.line_text(from.line()), column, kind) {
+        return Position::new(from.line(), end - 1)
+    }
+    let last = buffer.last_text_line();
+    (from.line() + 1..=last)
+        .find_map(|line| next_word_end(&buffer.line_text(line), 0, kind).map(|end| Position::new(line, end - 1)))
+        .unwrap_or_else(|| Position::new(last, line_length(buffer, last).saturating_sub(1)))
+}
+
+/**
+ * `b`, the start of the word before the position, on the lines above if there's none before it.
+ */
+pub fn word_back(buffer: &Buffer, from: Position, kind: WordKind) -> Position {
+    if let Some(column) = previous_word_start(&buffer.line_text(from.line()), from.character(), kind) {
+        return Position::new(from.line(), column)
+    }
+    (0..from.line())
+        .rev()
+        .find_map(|line| {
+            let text = buffer.line_text(line);
+            let column = if text.is_empty() { Some(0) } else { previous_word_start(&text, usize::MAX, kind) };
+            column.map(|column| Position::new(line, column))
+        })
+        .unwrap_or_default()
+}
+
+/**
+ * `}` and `{`, the blank line after or before the paragraph.
+ */
+pub fn paragraph(buffer: &Buffer, from: Position, forward: bool) -> Position {
+    let last = buffer.len().saturating_sub(1);
+    let mut line = from.line();
+    if forward {
+        while line < last && is_blank(buffer, line) {
+            line += 1;
+        }
+        while line < last && !is_blank(buffer, line) {
+            line += 1;
+        }
+        if is_blank(buffer, line) { Position::new(line, 0) } else { buffer.line_end(line) }
+    } else {
+        while line > 0 && is_blank(buffer, line) {
+            line -= 1;
+        }
+        while line > 0 && !is_blank(buffer, line) {
+            line -= 1;
+        }
+        Position::new(line, 0)
+    }
+}
+
+/**
+ * `f`, `F`, `t` and `T`, the count-th time the character appears on the line. A repeated `t` doesn't stop before
+ * the character it's already next to.
+ */
+pub fn find(buffer: &Buffer, from: Position, character: char, before: bool, backward: bool, count: usize, repeated: bool) -> Option<Position> {
+    let text = buffer.line_text(from.line());
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let mut encoded = [0; 4];
+    let target = &*character.encode_utf8(&mut encoded);
+    let skip = usize::from(before && repeated);
+    let column = if backward {
+        let end = from.character().checked_sub(skip)?;
+        let index = (0..end).rev().filter(|index| graphemes[*index] == target).nth(count - 1)?;
+        if before { index + 1 } else { index }
+    } else {
+        let start = from.character() + 1 + skip;
+        let index = (start..graphemes.len()).filter(|index| graphemes[*index] == target).nth(count - 1)?;
+        if before { index - 1 } else { index }
+    };
+    Some(Position::new(from.line(), column))
+}
+
+const BRACKETS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+
+/**
+ * Returns the byte of the bracket that matches the one at the byte, counting the pairs in between.
+ */
+fn matching_byte(text: &[u8], byte: usize, open: u8, close: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    if text[byte] == open {
+        for (index, character) in text.iter().enumerate().skip(byte + 1) {
+            match *character {
+                character if character == open => depth += 1,
+                character if character == close && depth == 0 => return Some(index),
+                character if character == close => depth -= 1,
+                _ => (),
+            }
+        }
+    } else {
+        for index in (0..byte).rev() {
+            match text[index] {
+                character if character == close => depth += 1,
+                character if character == open && depth == 0 => return Some(index),
+                character if character == open => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+    None
+}
+
+/**
+ * `%`, the bracket matching the first bracket at or after the cursor on its line.
+ */
+pub fn matching_bracket(buffer: &Buffer, from: Position) -> Option<Position> {
+    let document = buffer.buffer();
+    let text = buffer.get_string();
+    let bytes = text.as_bytes();
+    let line_end = document.get_byte_pos(&buffer.line_end(from.line()));
+    let start = document.get_byte_pos(&from);
+    let byte = (start..line_end).find(|byte| BRACKETS.iter().any(|(open, close)| bytes[*byte] == *open || bytes[*byte] == *close))?;
+    let (open, close) = BRACKETS.into_iter().find(|(open, close)| bytes[byte] == *open || bytes[byte] == *close)?;
+    let pair = matching_byte(bytes, byte, open, close)?;
+    Some(document.position_of_byte(pair))
+}
+
+/**
+ * Returns the span of the text object around the position.
+ */
+pub fn object(buffer: &Buffer, from: Position, object: TextObject) -> Option<Span> {
+    match object {
+        TextObject::Word { kind, around } => {
+            let columns = word_object(&buffer.line_text(from.line()), from.character(), kind, around);
+            let line = from.line();
+            Some(Span { start: Position::new(line, columns.start), end: Position::new(line, columns.end), linewise: false })
+        }
+        TextObject::Quote { quote, around } => quoted(buffer, from, quote, around),
+        TextObject::Bracket { open, close, around } => bracketed(buffer, from, open as u8, close as u8, around),
+        TextObject::Paragraph { around } => Some(paragraph_object(buffer, from.line(), around)),
+    }
+}
+
+/**
+ * The text between two quotes on the line, the quotes around the cursor or the first ones after it.
+ */
+fn quoted(buffer: &Buffer, from: Position, quote: char, around: bool) -> Option<Span> {
+    let text = buffer.line_text(from.line());
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let mut encoded = [0; 4];
+    let quote = &*quote.encode_utf8(&mut encoded);
+    let quotes: Vec<usize> = (0..graphemes.len())
+        .filter(|index| graphemes[*index] == quote && (*index == 0 || graphemes[index - 1] != "\\"))
+        .collect();
+    let column = from.character();
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(open, close)| (*open..=*close).contains(&column) || *open > column)?;
+    let line = from.line();
+    let (start, end) = if around { (open, close + 1) } else { (open + 1, close) };
+    Some(Span { start: Position::new(line, start), end: Position::new(line, end), linewise: false })
+}
+
+/**
+ * The text inside of the brackets around the position. Inside of a block over several lines, the line breaks
+ * after the opening bracket and the indentation before the closing one are left out.
+ */
+fn bracketed(buffer: &Buffer, from: Position, open: u8, close: u8, around: bool) -> Option<Span> {
+    let document = buffer.buffer();
+    let text = buffer.get_string();
+    let bytes = text.as_bytes();
+    let byte = document.get_byte_pos(&from);
+    let (start, end) = match bytes.get(byte) {
+        Some(character) if *character == open => (byte, matching_byte(bytes, byte, open, close)?),
+        Some(character) if *character == close => (matching_byte(bytes, byte, open, close)?, byte),
+        _ => {
+            // Looking back for a bracket that isn't closed before the position
+            let mut depth = 0usize;
+            let start = (0..byte.min(bytes.len())).rev().find(|index| match bytes[*index] {
+                character if character == close => {
+                    depth += 1;
+                    false
+                }
+                character if character == open && depth == 0 => true,
+                character if character == open => {
+                    depth -= 1;
+                    false
+                }
+                _ => false,
+            })?;
+            (start, matching_byte(bytes, start, open, close)?)
+        }
+    };
+    if around {
+        return Some(Span { start: document.position_of_byte(start), end: document.position_of_byte(end + 1), linewise: false })
+    }
+    let mut inner_start = start + 1;
+    let mut inner_end = end;
+    if bytes.get(inner_start) == Some(&b'\n') {
+        inner_start += 1;
+        let line_start = text[..end].rfind('\n').map_or(0, |newline| newline + 1);
+        if line_start > inner_start && text[line_start..end].trim().is_empty() {
+            inner_end = line_start;
+        }
+    }
+    let inner_end = inner_end.max(inner_start);
+    Some(Span { start: document.position_of_byte(inner_start), end: document.position_of_byte(inner_end), linewise: false })
+}
+
+/**
+ * The lines of the paragraph, or of the blank lines, at the line. Around a paragraph, the blank lines after it are
+ * taken along, or the ones before it if it ends the document.
+ */
+fn paragraph_object(buffer: &Buffer, line: usize, around: bool) -> Span {
+    let last = buffer.last_text_line();
+    let blank = is_blank(buffer, line);
+    let (mut start, mut end) = (line, line);
+    while start > 0 && is_blank(buffer, start - 1) == blank {
+        start -= 1;
+    }
+    while end < last && is_blank(buffer, end + 1) == blank {
+        end += 1;
+    }
+    if around {
+        if end < last {
+            end += 1;
+            while end < last && is_blank(buffer, end + 1) != blank {
+                end += 1;
+            }
+        } else {
+            while start > 0 && is_blank(buffer, start - 1) != blank {
+                start -= 1;
+            }
+        }
+    }
+    Span::lines(start, end)
+}
+
+#[cfg(test)]
+mod targets_tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, motion::WordKind, position::Position, vim::parser::TextObject};
+
+    use super::{object, Span};
+
+    fn span(start: (usize, usize), end: (usize, usize)) -> Option<Span> {
+        Some(Span { start: Position::new(start.0, start.1), end: Position::new(end.0, end.1), linewise: false })
+    }
+
+    #[test]
+    fn test_word_objects() {
+        let buffer = Buffer::from_source("foo bar\n");
+        let word = |around: bool| TextObject::Word { kind: WordKind::Word, around };
+        assert_eq!(object(&buffer, Position::new(0, 0), word(false)), span((0, 0), (0, 3)));
+        // Around a word the spaces after it are taken, or the ones before it at the end of the line
+        assert_eq!(object(&buffer, Position::new(0, 0), word(true)), span((0, 0), (0, 4)));
+        assert_eq!(object(&buffer, Position::new(0, 6), word(true)), span((0, 3), (0, 7)));
+        assert_eq!(object(&buffer, Position::new(0, 7), word(false)), span((0, 4), (0, 7)));
+        assert_eq!(object(&buffer, Position::new(1, 0), word(true)), span((1, 0), (1, 0)));
+    }
+
+    #[test]
+    fn test_quote_objects() {
+        let buffer = Buffer::from_source("\"a\" b \"c\"\n'x\n\"a\\\"b\"");
+        let quote = |quote: char, around: bool| TextObject::Quote { quote, around };
+        assert_eq!(object(&buffer, Position::new(0, 0), quote('"', false)), span((0, 1), (0, 2)));
+        assert_eq!(object(&buffer, Position::new(0, 8), quote('"', false)), span((0, 7), (0, 8)));
+        assert_eq!(object(&buffer, Position::new(0, 8), quote('"', true)), span((0, 6), (0, 9)));
+        // Between the quotes, the next ones on the line are taken
+        assert_eq!(object(&buffer, Position::new(0, 4), quote('"', false)), span((0, 7), (0, 8)));
+        assert_eq!(object(&buffer, Position::new(1, 0), quote('\'', false)), None);
+        // An escaped quote doesn't end the string
+        assert_eq!(object(&buffer, Position::new(2, 1), quote('"', false)), span((2, 1), (2, 5)));
+    }
+
+    #[test]
+    fn test_bracket_objects() {
+        let parens = |around: bool| TextObject::Bracket { open: '(', close: ')', around };
+        let braces = |around: bool| TextObject::Bracket { open: '{', close: '}', around };
+        let buffer = Buffer::from_source("(a)");
+        assert_eq!(object(&buffer, Position::new(0, 0), parens(false)), span((0, 1), (0, 2)));
+        assert_eq!(object(&buffer, Position::new(0, 2), parens(true)), span((0, 0), (0, 3)));
+        assert_eq!(object(&Buffer::from_source("x (a)"), Position::new(0, 0), parens(false)), None);
+        assert_eq!(object(&Buffer::from_source("(a"), Position::new(0, 1), parens(false)), None);
+        assert_eq!(object(&Buffer::from_source("{}"), Position::new(0, 0), braces(false)), span((0, 1), (0, 1)));
+
+        // Inside of a block the line breaks and the indentation of the closing bracket are left out
+        let buffer = Buffer::from_source("fn f() {\n    (c);\n}");
+        assert_eq!(object(&buffer, Position::new(1, 4), braces(false)), span((1, 0), (2, 0)));
+        assert_eq!(object(&buffer, Position::new(1, 2), braces(true)), span((0, 7), (2, 1)));
+        assert_eq!(object(&buffer, Position::new(1, 6), parens(false)), span((1, 5), (1, 6)));
+    }
+
+    #[test]
+    fn test_paragraph_objects() {
+        let buffer = Buffer::from_source("a\nb\n\nc");
+        let paragraph = |around: bool| TextObject::Paragraph { around };
+        assert_eq!(object(&buffer, Position::new(0, 0), paragraph(false)), Some(Span::lines(0, 1)));
+        assert_eq!(object(&buffer, Position::new(1, 0), paragraph(true)), Some(Span::lines(0, 2)));
+        // The last paragraph takes the blank lines before it
+        assert_eq!(object(&buffer, Position::new(3, 0), paragraph(true)), Some(Span::lines(2, 3)));
+        assert_eq!(object(&buffer, Position::new(2, 0), paragraph(false)), Some(Span::lines(2, 2)));
+    }
+}
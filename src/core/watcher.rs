@@ -0,0 +1,144 @@
+use std::path::{Component, Path, PathBuf};
+
+use iced::{futures::SinkExt, subscription, Subscription};
+use notify::{event::{ModifyKind, RenameMode}, EventKind, RecursiveMode, Watcher};
+
+/**
+ * Folders that change too often to be worth watching.
+ */
+const IGNORED_FOLDERS: [&str; 2] = [".git", "target"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Created,
+    Changed,
+    Deleted,
+}
+
+/**
+ * A file of the workspace that changed on disk.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEvent {
+    pub path: PathBuf,
+    pub change: FileChange,
+}
+
+impl FileEvent {
+    pub fn new(path: PathBuf, change: FileChange) -> Self {
+        Self { path, change }
+    }
+
+    /**
+     * Returns true if the event is about the file. Both paths are canonicalized when possible.
+     */
+    pub fn is_about(&self, file: &Path) -> bool {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_owned());
+        let path = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        file == path
+    }
+}
+
+/**
+ * Converts an event of the watcher into the changes of each file.
+ */
+pub fn file_events(event: notify::Event) -> Vec<FileEvent> {
+    let change = match event.kind {
+        EventKind::Create(_) => FileChange::Created,
+        EventKind::Remove(_) => FileChange::Deleted,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            // A rename deletes the old path and creates the new one
+            return vec![
+                FileEvent::new(event.paths[0].clone(), FileChange::Deleted),
+                FileEvent::new(event.paths[1].clone(), FileChange::Created),
+            ]
+            .into_iter()
+            .filter(|event| !is_ignored(&event.path))
+            .collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => FileChange::Deleted,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FileChange::Created,
+        EventKind::Modify(ModifyKind::Metadata(_)) => return Vec::new(),
+        EventKind::Modify(_) => FileChange::Changed,
+        EventKind::Any | EventKind::Access(_) | EventKind::Other => return Vec::new(),
+    };
+    event.paths
+        .into_iter()
+        .filter(|path| !is_ignored(path))
+        .map(|path| FileEvent::new(path, change))
+        .collect()
+}
+
+fn is_ignored(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => IGNORED_FOLDERS.iter().any(|folder| name == *folder),
+        _ => false,
+    })
+}
+
+/**
+ * Watches every file of the workspace and sends the changes made on disk.
+ */
+pub fn watch(workspace: PathBuf) -> Subscription<Vec<FileEvent>> {
+    subscription::channel(workspace.clone(), 100, move |mut output| async move {
+        let (sender, receiver) = smol::channel::unbounded();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send_blocking(event);
+        });
+        let watcher = watcher.and_then(|mut watcher| {
+            watcher.watch(&workspace, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        // The watcher stops once it's dropped
+        let _watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("Failed to watch {}: {}", workspace.display(), e);
+                None
+            }
+        };
+
+        while let Ok(event) = receiver.recv().await {
+            let events = match event {
+                Ok(event) => file_events(event),
+                Err(e) => {
+                    eprintln!("The file watcher failed: {}", e);
+                    continue
+                }
+            };
+            if !events.is_empty() {
+                let _ = output.send(events).await;
+            }
+        }
+        smol::future::pending().await
+    })
+}
+
+#[cfg(test)]
+mod watcher_tests {
+    use std::path::PathBuf;
+
+    use notify::{event::{CreateKind, ModifyKind, RenameMode, DataChange}, Event, EventKind};
+    use pretty_assertions::assert_eq;
+
+    use super::{file_events, FileChange, FileEvent};
+
+    #[test]
+    fn test_file_events() {
+        let event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(PathBuf::from("/ws/src/main.rs"))
+            .add_path(PathBuf::from("/ws/target/debug/main"));
+        assert_eq!(file_events(event), vec![FileEvent::new(PathBuf::from("/ws/src/main.rs"), FileChange::Changed)]);
+
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("/ws/a.rs"))
+            .add_path(PathBuf::from("/ws/b.rs"));
+        assert_eq!(file_events(event), vec![
+            FileEvent::new(PathBuf::from("/ws/a.rs"), FileChange::Deleted),
+            FileEvent::new(PathBuf::from("/ws/b.rs"), FileChange::Created),
+        ]);
+
+        let event = Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/ws/.git/index"));
+        assert!(file_events(event).is_empty());
+    }
+}
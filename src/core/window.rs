@@ -38,6 +38,10 @@ impl VirtualWindow {
         self.offset_y = y;
     }
 
+    pub fn offset(&self) -> (f32, f32) {
+        (self.offset_x, self.offset_y)
+    }
+
     pub fn size_is_different(&self, width: f32, height: f32)  -> bool{
         self.width == width && height == self.height
     }
@@ -51,6 +55,10 @@ impl VirtualWindow {
         self.height
     }
 
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
     pub fn correct_position_to_cursor(
         &mut self,
         cursor: Cursor,
@@ -30,6 +30,9 @@ impl ColorSelector {
             "comment" => Color::from_rgb8(153, 153, 153),
             "string" => Color::from_rgb8(110, 255, 89),
             "punctuation.bracket" | "punctuation.delimiter" => Color::from_rgb8(190, 190, 190),
+            "bracket.depth0" => Color::from_rgb8(255, 215, 0),
+            "bracket.depth1" => Color::from_rgb8(218, 112, 214),
+            "bracket.depth2" => Color::from_rgb8(23, 159, 255),
             "variable.builtin" | "variable.parameter" => Color::from_rgb8(224, 108, 117),
             "keyword" | "operator" => Color::from_rgb8(198, 120, 221),
             _ => Color::from_rgb8(171, 178, 191),
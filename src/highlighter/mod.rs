@@ -1,10 +1,15 @@
 use std::ops;
 use ropey::{RopeSlice};
-use tree_sitter::{InputEdit, Parser, Query, QueryCursor, Range, Tree};
+use tree_sitter::{InputEdit, Node, Parser, Point, Query, QueryCursor, Range, Tree};
 
-use crate::core::{document::Document, position::Position};
+use crate::core::{brackets::bracket_depth, document::Document, position::Position};
 pub mod color_selector;
 
+/**
+ * The captures of the brackets once they are colored by their depth, the colors go around once they run out.
+ */
+pub const BRACKET_DEPTHS: [&str; 3] = ["bracket.depth0", "bracket.depth1", "bracket.depth2"];
+
 pub struct HighlighterConfig {
     tree: Tree,
     query: Query,
@@ -20,12 +25,12 @@ impl HighlighterConfig {
         }
     }
 
-    pub fn rust_config(source_code: &str) -> Self {
+    pub fn rust_config(source_code: RopeSlice) -> Self {
         let mut parser = Parser::new();
         parser
             .set_language(tree_sitter_rust::language())
             .expect("Error loading Rust grammar");
-        let tree = parser.parse(source_code, None).unwrap();
+        let tree = parse(&mut parser, source_code, None);
 
         let query = Query::new(
             tree_sitter_rust::language(),
@@ -36,10 +41,19 @@ impl HighlighterConfig {
         Self::new(tree, query, parser)
     }
 
-    fn edit(&mut self, input: &InputEdit, content: &RopeSlice) {
+    /**
+     * Returns the syntax tree of the document, kept up to date with its edits.
+     */
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /**
+     * Moves the syntax tree along with the edit and parses the content again.
+     */
+    pub fn edit(&mut self, input: &InputEdit, content: &RopeSlice) {
         self.tree.edit(input);
-        let tree = self.parser.parse(content.to_string(), Some(&self.tree));
-        self.tree = tree.unwrap()
+        self.tree = parse(&mut self.parser, *content, Some(&self.tree));
     }
 
     pub fn delete(&mut self, start_byte: usize, end_byte: usize, start_pos: Position, end_pos: Position, content:&RopeSlice) {
@@ -86,6 +100,20 @@ impl HighlighterConfig {
 
 }
 
+/**
+ * Parses the text by handing the chunks of the rope to tree-sitter, so the text is never copied into one string.
+ */
+fn parse(parser: &mut Parser, content: RopeSlice, old_tree: Option<&Tree>) -> Tree {
+    let mut read = |byte: usize, _: Point| -> &[u8] {
+        if byte >= content.len_bytes() {
+            return &[]
+        }
+        let (chunk, chunk_start, _, _) = content.chunk_at_byte(byte);
+        &chunk.as_bytes()[byte - chunk_start..]
+    };
+    parser.parse_with(&mut read, old_tree).expect("The parser has a language and no timeout")
+}
+
 #[derive(Debug, Clone)]
 pub struct HighlightItem {
     pub capture_name: String,
@@ -107,8 +135,10 @@ impl Highlighter {
         let mut cursor = QueryCursor::new();
         cursor.set_byte_range(range);
 
-        let content = buffer.to_string();
-        let captures = cursor.captures(&config.query, root_node, content.as_bytes());
+        // The text of a node is read from the chunks of the rope it spans
+        let content = buffer.slice_all();
+        let text = |node: Node| content.byte_slice(node.byte_range()).chunks().map(str::as_bytes);
+        let captures = cursor.captures(&config.query, root_node, text);
 
         let mut highlight_items: Vec<HighlightItem> = Vec::new();
         for (q_match, _i) in captures {
@@ -126,4 +156,50 @@ impl Highlighter {
             captures: highlight_items,
         })
     }
+
+    /**
+     * Colors the brackets by how deep they are nested, so the brackets of a pair share a color.
+     */
+    pub fn color_brackets(&mut self, config: &HighlighterConfig) {
+        let root_node = config.tree.root_node();
+        let brackets = self.captures
+            .iter_mut()
+            .filter(|item| item.capture_name == "punctuation.bracket" && matches!(item.kind.as_str(), "(" | ")" | "[" | "]" | "{" | "}"));
+        for item in brackets {
+            if let Some(node) = root_node.descendant_for_byte_range(item.range.start_byte, item.range.end_byte) {
+                item.capture_name = BRACKET_DEPTHS[bracket_depth(node) % BRACKET_DEPTHS.len()].to_owned();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod highlighter_tests {
+    use pretty_assertions::assert_eq;
+    use tree_sitter::Parser;
+
+    use crate::core::document::Document;
+
+    use super::{Highlighter, HighlighterConfig};
+
+    #[test]
+    fn test_parse_from_rope_chunks() {
+        // Long enough to be split in many chunks of the rope
+        let text = "fn main() {\n    let value = \"text\";\n}\n".repeat(500);
+        let document = Document::recovered(None, &text);
+        assert!(document.slice_all().chunks().count() > 1);
+
+        let config = HighlighterConfig::rust_config(document.slice_all());
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_rust::language()).unwrap();
+        let expected = parser.parse(&text, None).unwrap();
+        assert_eq!(config.tree.root_node().to_sexp(), expected.root_node().to_sexp());
+
+        // The last function of the document is read from the chunks it spans
+        let start = document.get_line_bytes(1497);
+        let highlighter = Highlighter::new(&config, start..text.len(), &document).unwrap();
+        let names: Vec<&str> = highlighter.captures.iter().map(|item| item.capture_name.as_str()).collect();
+        assert!(names.contains(&"function"));
+        assert!(names.contains(&"string"));
+    }
 }
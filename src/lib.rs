@@ -1,31 +1,67 @@
 use core::buffer::Buffer;
+use core::file_index::{FileIndex, DEFAULT_EXCLUDES};
+use core::file_tree::FileTree;
+use core::recovery::{self, RecoveryStore, Snapshot};
+use core::file_format::{read_text, LineEnding};
+use core::session::{data_dir, DocumentState, Session, SessionStore, Settings};
+use core::watcher::{self, FileChange, FileEvent};
 use core::window::VirtualWindow;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::vec;
 
-use core::document::Document;
+use core::document::{Document, SaveJob};
+use core::folding::FoldRange;
+use core::motion::WordKind;
+use core::clipboard::{Clip, ClipboardHistory};
+use core::emacs::{Emacs, EmacsKey, DEL};
+use core::keymap::{Keymap, KeymapRequest};
+use core::vim::{Mode as VimMode, Vim, VimKey};
+use core::error::{DocumentError, DocumentResult};
+use encoding_rs::Encoding;
 use highlighter::HighlighterConfig;
 use iced::keyboard::KeyCode;
 
-use iced::widget::scrollable::Properties;
+use iced::widget::scrollable::{Properties, RelativeOffset};
 use iced::{
     executor, keyboard, window, Application, Color, Command, Element, Font, Length, Padding, Renderer, Subscription
 };
 
-use iced::widget::{container, row, scrollable, text, column};
+use iced::widget::{button, container, scrollable, text, column, row, Row};
+use iced::widget::pane_grid::{self, PaneGrid};
 use iced_style::theme::{Custom, Palette};
-use lsp::client::{LspClient, LspConnection};
+use jsonrpc_lite::Error as RpcError;
+use lsp_types::notification::{DidChangeWatchedFiles, Notification as _};
+use lsp_types::request::{Request, WorkDoneProgressCreate, WorkspaceConfiguration};
+use lsp_types::{ConfigurationParams, FileDelete, InitializeResult, TextDocumentSyncKind, Url, WorkspaceEdit};
+use serde_json::Value;
+use lsp::client::LspClient;
 use lsp::connect;
+use lsp::file_operations::{is_interested, text_edits, FileOperation, PendingRename};
+use lsp::inspector::TrafficRecorder;
+use lsp::manager::{default_servers, language_id, LspManager};
 use lsp::response::LspResponse;
+use lsp::transport::{InitializedSender, MessageSender};
 use rfd::FileDialog;
-use widgets::modal::file_selector::Modal;
+use styles::button::MenuButton;
+use styles::container::PaneContainer;
+use widgets::file_explorer::{file_explorer, ExplorerAction};
+use widgets::banner::large_file_banner;
+use widgets::panes::{EditorPane, Panes};
+use widgets::notification::{notifications, Notification, NotificationLevel};
+use widgets::status_bar::{status_bar, FormatAction};
+use widgets::modal::file_operation::FileAction;
+use widgets::modal::clipboard_history::clip_list_id;
+use widgets::modal::file_selector::{file_list_id, Modal};
 use core::position::{Position, CursorMessage};
 use iced::event::Event;
 use iced::subscription;
 use iced_style::Theme;
 use widgets::textbox_container::TextboxContainer;
 use widgets::{line_number, layout};
-use widgets::textbox::Textbox;
+use widgets::textbox::{LineCommand, Textbox};
 use widgets::view_port::{ViewPort, ViewPortMessage};
 
 
@@ -36,6 +72,35 @@ pub mod widgets;
 pub mod styles;
 pub mod lsp;
 
+/**
+ * How long a rename waits for the servers to answer `willRenameFiles`.
+ */
+const RENAME_TIMEOUT: Duration = Duration::from_secs(3);
+
+/**
+ * How many files the file selector shows at most.
+ */
+const SELECTOR_RESULTS: usize = 200;
+
+/**
+ * How long an informative notification is shown. Errors stay until they are dismissed.
+ */
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(6);
+
+/**
+ * How often the dirty buffer is checked for a snapshot or an autosave.
+ */
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/**
+ * The least time between two snapshots of the dirty buffer.
+ */
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+
+/**
+ * How long after the last edit the buffer is saved, if autosave is on.
+ */
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Clone, Copy)]
 pub enum Key {
@@ -52,6 +117,9 @@ pub enum Key {
     KeyEnd,
     KeyPgUp,
     KeyPgDown,
+    KeyLBracket,
+    KeyRBracket,
+    KeySlash,
     Key(char),
     None,
     KeyA,
@@ -81,6 +149,24 @@ pub enum Key {
     KeyY,
     KeyZ,
 }
+
+impl Key {
+    /**
+     * Returns the letter of the key, for the bindings that are typed along with Ctrl.
+     */
+    fn letter(&self) -> Option<char> {
+        let letter = match self {
+            Key::KeyA => 'a', Key::KeyB => 'b', Key::KeyC => 'c', Key::KeyD => 'd', Key::KeyE => 'e', Key::KeyF => 'f',
+            Key::KeyG => 'g', Key::KeyH => 'h', Key::KeyI => 'i', Key::KeyJ => 'j', Key::KeyK => 'k', Key::KeyL => 'l',
+            Key::KeyM => 'm', Key::KeyN => 'n', Key::KeyO => 'o', Key::KeyP => 'p', Key::KeyQ => 'q', Key::KeyR => 'r',
+            Key::KeyS => 's', Key::KeyT => 't', Key::KeyU => 'u', Key::KeyV => 'v', Key::KeyW => 'w', Key::KeyX => 'x',
+            Key::KeyY => 'y', Key::KeyZ => 'z',
+            _ => return None,
+        };
+        Some(letter)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 
 pub enum KeyEvent {
@@ -95,16 +181,20 @@ pub enum Message {
     Offset(f32, f32),
     View(f32, f32),
     SelectionMove(Position),
+    SelectWord(Position),
+    SelectLine(Position),
     Paste(String),
+    /// Pastes a copy from the clipboard history
+    PasteClip(usize),
     Open(String),
-    LspMessage(LspResponse),
+    LspMessage(usize, LspResponse),
     DismissModal,
-    FileFilter(String),
+    ModalFilter(String),
     SetTextBoxFocus(bool),
-    CloseClient,
+    CloseClient(usize),
 
     // Client messages
-    ClientStart(()),
+    ClientStart(usize, Box<InitializeResult>),
     OpenDocument(()),
     CloseDocument(()),
     DocChanged(()),
@@ -112,6 +202,59 @@ pub enum Message {
     DidSave(()),
     Done(()),
 
+    // File watcher messages
+    FilesChanged(Vec<FileEvent>),
+    KeepMine,
+    TakeTheirs,
+    ToggleConflictDiff,
+
+    /// The index of the workspace's files was built in the background
+    IndexBuilt(Box<FileIndex>),
+
+    // File explorer messages
+    ExplorerSelect(PathBuf),
+    ExplorerAction(ExplorerAction),
+    ConfirmFileAction,
+    /// Renames the file even if some servers haven't answered `willRenameFiles`
+    FinishRename,
+
+    // LSP inspector messages
+    InspectTraffic(u64),
+    ToggleTrafficRecording,
+    ClearTraffic,
+    ExportTraffic,
+
+
+    /// Opens a workspace from the list of recent workspaces
+    OpenWorkspace(PathBuf),
+    /// The window is about to close, the session is saved first
+    Quit,
+
+    // Recovery messages
+    Tick,
+    ToggleAutosave,
+    /// Changes how the open file is saved, from the status bar
+    Format(FormatAction),
+
+    // Document messages
+    /// The file was read in the background, the view is restored once it's open
+    DocumentOpened(String, Box<DocumentResult<Document>>, Option<DocumentState>),
+    /// The document was written in the background
+    Saved(SaveJob, DocumentResult<()>),
+    DismissNotification(u64),
+    RecoverySelect(usize),
+    Recover,
+    DiscardSnapshot,
+
+    // Gutter messages
+    /// Folds the range that starts on the line, or unfolds it
+    ToggleFold(usize),
+
+    // Pane messages
+    /// A message of a pane, one that clicks in a pane that isn't focused focuses it first
+    Pane(pane_grid::Pane, Box<Message>),
+    /// The split between two panes was dragged
+    ResizePane(pane_grid::ResizeEvent),
 
     // Menu Messages
     SelectFile,
@@ -140,6 +283,21 @@ impl Message {
             KeyCode::PageUp => Some(Self::KeyEvent(KeyEvent::Special(Key::KeyPgUp, modifier))),
             KeyCode::PageDown => Some(Self::KeyEvent(KeyEvent::Special(Key::KeyPgDown, modifier))),
             KeyCode::Space => Some(Self::KeyEvent(KeyEvent::Special(Key::Key(' '), modifier))),
+            KeyCode::LBracket => Some(Self::KeyEvent(KeyEvent::Special(Key::KeyLBracket, modifier))),
+            KeyCode::RBracket => Some(Self::KeyEvent(KeyEvent::Special(Key::KeyRBracket, modifier))),
+            KeyCode::Slash => Some(Self::KeyEvent(KeyEvent::Special(Key::KeySlash, modifier))),
+            KeyCode::Backslash => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('\\'), modifier))),
+
+            // Digits, for the bindings that are typed along with Ctrl
+            KeyCode::Key1 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('1'), modifier))),
+            KeyCode::Key2 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('2'), modifier))),
+            KeyCode::Key3 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('3'), modifier))),
+            KeyCode::Key4 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('4'), modifier))),
+            KeyCode::Key5 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('5'), modifier))),
+            KeyCode::Key6 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('6'), modifier))),
+            KeyCode::Key7 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('7'), modifier))),
+            KeyCode::Key8 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('8'), modifier))),
+            KeyCode::Key9 => Some(Self::KeyEvent(KeyEvent::Special(Key::Key('9'), modifier))),
             
             // Alphabet
             KeyCode::A => Some(Self::KeyEvent(KeyEvent::Special(Key::KeyA, modifier))),
@@ -181,6 +339,12 @@ impl CursorMessage for Message {
     fn from_selection_move(pos: Position) -> Self {
         Self::SelectionMove(pos)
     }
+    fn from_word_click(pos: Position) -> Self {
+        Self::SelectWord(pos)
+    }
+    fn from_line_click(pos: Position) -> Self {
+        Self::SelectLine(pos)
+    }
 }
 
 impl ViewPortMessage for Message {
@@ -200,7 +364,7 @@ impl ViewPortMessage for Message {
 pub struct Modifiers {
     ctrl: bool,
     shift: bool,
-    _alt: bool
+    alt: bool
 }
 
 impl From <iced::keyboard::Modifiers> for Modifiers {
@@ -208,20 +372,69 @@ impl From <iced::keyboard::Modifiers> for Modifiers {
         Self {
             ctrl: value.control(),
             shift: value.shift(),
-            _alt: value.alt(),
+            alt: value.alt(),
         }
     }
 }
 
+/**
+ * What tells the edits of the open buffer apart.
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct EditState {
+    file: Option<String>,
+    version: i32,
+    is_saved: bool,
+}
+
 pub struct Editor {
     last_event: Key,
+    /// The textbox of the focused pane.
     text_box: Option<Textbox>,
+    /// The panes the editor area is split into, they keep their textboxes while they aren't focused.
+    panes: Panes,
     modifiers: Modifiers,
     modal: Option<Modal>,
     workspace: Option<PathBuf>,
-    lsp: Option<LspConnection>,
-    file_filter: String,
-    client_id: usize
+    file_tree: Option<FileTree>,
+    /// The files of the workspace for the file selector, `None` while it's being built.
+    file_index: Option<FileIndex>,
+    show_explorer: bool,
+    /// The entry of the file explorer the toolbar actions apply to.
+    explorer_selected: Option<PathBuf>,
+    pending_rename: Option<PendingRename>,
+    /// Where the sessions are saved, `None` if there is no folder for the user's data.
+    session_store: Option<SessionStore>,
+    /// Where the dirty buffers are snapshotted, `None` if there is no folder for the user's data.
+    recovery: Option<RecoveryStore>,
+    settings: Settings,
+    /// The modes and commands of Vim, used with its keymap.
+    vim: Vim,
+    /// The mark and the kill ring of Emacs, used with its keymap.
+    emacs: Emacs,
+    /// The recent copies, they can be pasted again from the clipboard history.
+    clipboard: ClipboardHistory,
+    /// The state of the open buffer after its last edit, and when that was.
+    last_edit: Option<(EditState, Instant)>,
+    /// The file and version of the open buffer when it was last snapshotted, and when that was.
+    last_snapshot: Option<(Option<String>, i32, Instant)>,
+    /// The file and version of the open buffer the folding ranges were last asked for.
+    folding_request: Option<(String, i32)>,
+    /// The file and version of the open buffer a server was asked to grow the selection of.
+    selection_request: Option<(String, i32)>,
+    /// The file being read in the background. Only the last file that was asked for is opened.
+    opening: Option<String>,
+    /// A save is being written in the background
+    saving: bool,
+    /// The buffer is saved again once the current save is done
+    save_queued: bool,
+    /// The state of the open buffer autosave last tried to save, it isn't tried again until the buffer changes.
+    autosaved: Option<EditState>,
+    notifications: Vec<Notification>,
+    next_notification: u64,
+    lsp: LspManager,
+    lsp_traffic: TrafficRecorder,
+    modal_filter: String,
 }
 
 impl Application for Editor {
@@ -232,19 +445,54 @@ impl Application for Editor {
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
 
-        (
-            Editor {
+        let lsp_traffic = TrafficRecorder::default();
+        let data_dir = data_dir();
+        let session_store = data_dir.clone().map(SessionStore::open);
+        let recovery = data_dir.map(|dir| RecoveryStore::new(dir.join("snapshots")));
+        if let Some(recovery) = recovery.as_ref() {
+            recovery.install_panic_hook();
+        }
+        let mut editor = Editor {
                 last_event: Key::None,
                 text_box: None,
+                panes: Panes::default(),
                 modifiers: Modifiers::default(),
                 modal: None,
-                lsp: None,
+                lsp: LspManager::new(None, default_servers(), Some(lsp_traffic.clone())),
+                lsp_traffic,
                 workspace: None,
-                file_filter: String::default(),
-                client_id: 1,
-            },    
-            Command::none(),
-        )
+                file_tree: None,
+                file_index: None,
+                show_explorer: true,
+                explorer_selected: None,
+                pending_rename: None,
+                settings: session_store.as_ref().map(SessionStore::settings).unwrap_or_default(),
+                vim: Vim::default(),
+                emacs: Emacs::default(),
+                clipboard: ClipboardHistory::default(),
+                session_store,
+                recovery,
+                last_edit: None,
+                last_snapshot: None,
+                folding_request: None,
+                selection_request: None,
+                opening: None,
+                saving: false,
+                save_queued: false,
+                autosaved: None,
+                notifications: Vec::new(),
+                next_notification: 0,
+                modal_filter: String::default(),
+            };
+
+        // The last workspace is opened again, as it was left
+        let mut commands = Vec::new();
+        let last_workspace = editor.session_store.as_ref().and_then(|store| store.last_workspace());
+        if let Some(workspace) = last_workspace {
+            editor.open_workspace(Some(workspace), &mut commands);
+        }
+        editor.offer_recovery();
+        (editor, Command::batch(commands))
     }
 
     fn title(&self) -> String {
@@ -252,10 +500,10 @@ impl Application for Editor {
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Message> {
-        match self.process_event(message) {
-            Some(commands) => Command::batch(commands),
-            None => Command::none()
-        }
+        let mut commands = self.process_event(message).unwrap_or_default();
+        self.send_changes(&mut commands);
+        self.track_edits();
+        Command::batch(commands)
     }
 
     fn view(&self) -> Element<Self::Message> {
@@ -292,6 +540,7 @@ impl Application for Editor {
                         None
                     }
                 }
+                Event::Window(window::Event::CloseRequested) => Some(Message::Quit),
                 Event::Window(window::Event::Resized {
                     width: _,
                     height: _,
@@ -299,17 +548,18 @@ impl Application for Editor {
                 _ => None,
             }
         });
-        let mut events: Vec<Subscription<Message>> = vec![app_events];
-        if let Some(lsp) = self.lsp.as_ref() {
-            let lsp_events = connect::connect(self.client_id, lsp.new_receiver()).map(|event| {
+        let mut events: Vec<Subscription<Message>> = vec![app_events, recovery::ticks(TICK_INTERVAL).map(|_| Message::Tick)];
+        if let Some(workspace) = self.workspace.clone() {
+            events.push(watcher::watch(workspace).map(Message::FilesChanged))
+        }
+        for server in self.lsp.servers() {
+            let lsp_events = connect::connect(server.id, server.connection.new_receiver())
+            .with(server.id)
+            .map(|(id, event)| {
                 match event {
-                    connect::Event::Response(value) => {
-                        if let LspResponse::Initialized = value {
-                            return Message::ClientStart(())
-                        }
-                        Message::LspMessage(value)
-                    },
-                    connect::Event::Disconnected => Message::CloseClient,
+                    connect::Event::Response(LspResponse::Initialized(result)) => Message::ClientStart(id, result),
+                    connect::Event::Response(value) => Message::LspMessage(id, value),
+                    connect::Event::Disconnected => Message::CloseClient(id),
                 }
             });
             events.push(lsp_events)
@@ -331,46 +581,557 @@ impl Editor {
         self.set_file(document, None, commands);
     }
 
-    fn open(&mut self, file: &str, old_file: Option<String>, commands: &mut Vec<Command<Message>>) {
-        let document = Document::open(file).expect("Couldn't open file");
+    /**
+     * Reads the file in the background, with the encoding if it's given. The view is restored once the document is open.
+     */
+    fn open(&mut self, file: &str, encoding: Option<&'static Encoding>, view: Option<DocumentState>, commands: &mut Vec<Command<Message>>) {
+        self.opening = Some(file.to_owned());
+        let path = file.to_owned();
+        let read = smol::unblock(move || match encoding {
+            Some(encoding) => Document::open_as(&path, encoding),
+            None => Document::open(&path),
+        });
+        let file = file.to_owned();
+        commands.push(Command::perform(read, move |result| Message::DocumentOpened(file, Box::new(result), view)));
+    }
+
+    fn document_opened(&mut self, file: String, result: DocumentResult<Document>, view: Option<DocumentState>, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        // Another file was asked for in the meantime
+        if self.opening.as_ref() != Some(&file) {
+            return None
+        }
+        self.opening = None;
+        let document = match result {
+            Ok(document) => document,
+            Err(e) => {
+                self.notify(NotificationLevel::Error, format!("Failed to open {}: {}", file, e));
+                return None
+            }
+        };
+        let old_file = self.text_box.as_ref().and_then(|textbox| textbox.file());
         self.set_file(document, old_file, commands);
+
+        let has_modal = self.modal.is_some();
+        let textbox = self.text_box.as_mut()?;
+        if let Some(view) = view {
+            textbox
+                .set_selection(view.selection.0)
+                .set_selection_end(view.selection.1)
+                .set_curor(view.cursor);
+            textbox.set_offset(view.scroll.0, view.scroll.1);
+        }
+        textbox.set_focus(!has_modal);
+        Some(())
+    }
+
+    /**
+     * Shows a message to the user. A message that is already shown isn't shown twice, it's shown for longer.
+     */
+    fn notify(&mut self, level: NotificationLevel, message: String) {
+        if let Some(shown) = self.notifications.iter_mut().find(|shown| shown.level == level && shown.message == message) {
+            shown.created = Instant::now();
+            return
+        }
+        self.notifications.push(Notification { id: self.next_notification, level, message, created: Instant::now() });
+        self.next_notification += 1;
+    }
+
+    /**
+     * Returns where the open buffer is, so the view can be restored once the file is opened again.
+     */
+    fn current_view(&self) -> Option<DocumentState> {
+        let buffer = self.text_box.as_ref()?.buffer();
+        Some(DocumentState {
+            file: buffer.filename().map(PathBuf::from),
+            cursor: buffer.cursor.0,
+            selection: (buffer.cursor.0, buffer.cursor.0),
+            scroll: buffer.window.offset(),
+            recovery: None,
+        })
     }
 
     fn set_file(&mut self, document: Document, old_file: Option<String>, commands: &mut Vec<Command<Message>>) {
-        let document_string = document.to_string();
-        let config = HighlighterConfig::rust_config(&document_string);
+        let new_file = document.filename().cloned();
+        // The unsaved changes of another file are offered for recovery on the next start, reloading a file drops them
+        if old_file.is_none() || old_file != new_file {
+            self.take_snapshot();
+        }
+        // Large files aren't parsed, the tree of a multi-megabyte file takes too long to build and to keep up to date
+        let config = (!document.is_large()).then(|| HighlighterConfig::rust_config(document.slice_all()));
         let document = Buffer::new(document, config);
+        // The new buffer has the ranges of its syntax tree, the servers are asked for theirs again
+        self.folding_request = None;
+        self.vim.reset();
+        self.emacs.reset();
 
-        if let Some(lsp_client) = self.lsp.as_ref() {
+        self.text_box = Some(Textbox::new(document)
+        .font(Font::MONOSPACE)
+        .font_size(14.0));
+        self.switch_document(old_file, new_file, commands);
+    }
 
-            if let Some(filename) = document.filename() {
-                if let Some(old_file) = old_file {
-                    if let Some(sender) =  lsp_client.as_initialized() {
+    /**
+     * Returns the text and version of the open buffer if it's the file.
+     */
+    fn open_text(&self, file: &str) -> Option<(String, i32)> {
+        let textbox = self.text_box.as_ref().filter(|textbox| textbox.file().is_some_and(|open| open == file))?;
+        Some((textbox.buffer().get_string(), textbox.buffer().buffer().version()))
+    }
 
-                        let filename = filename.to_string();
-                        let fut = async move {
-                            sender.clone().closed_document(old_file.to_string()).await;
-                            sender.open_document(filename).await;
-                        };
-                        let command = Command::perform(fut, Message::DocChanged);
-                        commands.push(command);
-                    }; 
-                } else {
-                    if let Some(sender) = lsp_client.as_initialized() {
-                        let command = Command::perform(
-                            sender.open_document(filename.to_string())
-                            , Message::OpenDocument
-                        );
-                        commands.push(command)
+    /**
+     * Tells the servers that the old file was closed and the new one opened.
+     * 
+     * The servers of the new file's language are started if they aren't running yet, they get the
+     * document once they are initialized.
+     */
+    fn switch_document(&mut self, old_file: Option<String>, new_file: Option<String>, commands: &mut Vec<Command<Message>>) {
+        // The servers don't get large files, they would be sent the whole text on every change
+        let is_large = self.text_box.as_ref().is_some_and(|textbox| textbox.buffer().is_large());
+        let new_language = new_file
+            .as_deref()
+            .filter(|_| !is_large)
+            .and_then(|file| language_id(Path::new(file)));
+        if let Some(language) = new_language {
+            for id in self.lsp.start_servers(language) {
+                let Some(lsp) = self.lsp.get(id) else {
+                    continue
+                };
+                if let LspClient::Uninitialized(sender) = lsp.new_sender() {
+                    commands.push(Command::perform(sender.initialize(lsp.init_params()), Message::Done));
+                }
+            }
+        }
+
+        for server in self.lsp.servers() {
+            let Some(sender) = server.connection.as_initialized() else {
+                continue
+            };
+            if !server.connection.sends_open_close() {
+                continue
+            }
+            let close = old_file
+                .clone()
+                .filter(|file| language_id(Path::new(file)).is_some_and(|language| server.config.handles(language)));
+            let open = new_file
+                .clone()
+                .zip(new_language)
+                .filter(|(_, language)| server.config.handles(language))
+                .and_then(|(file, language)| Some((self.open_text(&file)?, file, language)));
+            if close.is_none() && open.is_none() {
+                continue
+            }
+            let fut = async move {
+                if let Some(file) = close {
+                    sender.clone().closed_document(file).await;
+                }
+                if let Some(((text, version), file, language)) = open {
+                    sender.open_document(file, language.to_owned(), text, version).await;
+                }
+            };
+            commands.push(Command::perform(fut, Message::DocChanged));
+        }
+    }
+
+    /**
+     * Lets every server of the file's language know that it was saved.
+     */
+    fn did_save(&self, file_path: String, commands: &mut Vec<Command<Message>>) {
+        let Some(language) = language_id(Path::new(&file_path)) else {
+            return
+        };
+        for server in self.lsp.servers_for(language) {
+            if !server.connection.sends_save() {
+                continue
+            }
+            if let Some(sender) = server.connection.as_initialized() {
+                commands.push(Command::perform(sender.did_save(file_path.clone()), Message::DidSave));
+            }
+        }
+    }
+
+    /**
+     * Sends the edits made to the document to the servers of its language, the way each server
+     * asked for them.
+     */
+    fn send_changes(&mut self, commands: &mut Vec<Command<Message>>) {
+        let Some(textbox) = self.text_box.as_mut() else {
+            return
+        };
+        let changes = textbox.take_changes();
+        let Some(last) = changes.last().filter(|_| !textbox.buffer().is_large()) else {
+            return
+        };
+        let Some(language) = language_id(Path::new(&last.file)) else {
+            return
+        };
+        let (file, version) = (last.file.clone(), last.version);
+        // Only the servers that want the full text get it, the rest of them get the edits
+        let mut text: Option<String> = None;
+
+        for server in self.lsp.servers_for(language) {
+            let Some(sender) = server.connection.as_initialized() else {
+                continue
+            };
+            let sync_kind = server.connection.sync_kind();
+            if sync_kind == TextDocumentSyncKind::INCREMENTAL {
+                let encoding = server.connection.position_encoding();
+                let changes = changes.clone();
+                let fut = async move {
+                    for change in changes {
+                        sender.clone().doc_changed(change, encoding).await;
+                    }
+                };
+                commands.push(Command::perform(fut, Message::DocChanged));
+            } else if sync_kind == TextDocumentSyncKind::FULL {
+                let text = text.get_or_insert_with(|| textbox.buffer().get_string()).clone();
+                let fut = sender.doc_replaced(file.clone(), version, text);
+                commands.push(Command::perform(fut, Message::DocChanged));
+            }
+        }
+    }
+
+    /**
+     * Answers a request sent by a server.
+     */
+    fn server_request(&mut self, id: usize, request: LspResponse, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let lsp = self.lsp.get_mut(id)?;
+        let (request_id, result) = match request {
+            LspResponse::RegisterCapability(request_id, registrations) => {
+                for registration in registrations {
+                    if registration.method != DidChangeWatchedFiles::METHOD {
+                        continue
+                    }
+                    let options = registration.register_options.and_then(|options| serde_json::from_value(options).ok());
+                    if let Some(options) = options {
+                        lsp.watched_files_mut().register(registration.id, options);
                     }
                 }
+                (request_id, Ok(Value::Null))
+            }
+            LspResponse::UnregisterCapability(request_id, unregistrations) => {
+                for unregistration in unregistrations {
+                    lsp.watched_files_mut().unregister(&unregistration.id);
+                }
+                (request_id, Ok(Value::Null))
+            }
+            LspResponse::Request(request_id, method, params) => {
+                let result = match method.as_str() {
+                    WorkDoneProgressCreate::METHOD => Ok(Value::Null),
+                    // There are no settings yet, an empty value for each item lets the server use its defaults
+                    WorkspaceConfiguration::METHOD => {
+                        let items = serde_json::from_value::<ConfigurationParams>(params).map(|params| params.items.len()).unwrap_or(0);
+                        Ok(Value::Array(vec![Value::Null; items]))
+                    }
+                    _ => Err(RpcError::method_not_found()),
+                };
+                (request_id, result)
+            }
+            _ => return None
+        };
+        let sender = lsp.as_initialized()?;
+        commands.push(Command::perform(sender.respond(request_id, result), Message::Done));
+        Some(())
+    }
+
+    /**
+     * Tells the servers about the files that changed on disk, and reloads the open file if it's one of them.
+     * 
+     * If the open file has unsaved changes, the user chooses which version to keep.
+     */
+    fn files_changed(&mut self, events: Vec<FileEvent>, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        if let Some(workspace) = self.workspace.as_ref() {
+            for server in self.lsp.servers() {
+                let changes = server.connection.watched_files().filter(&events, workspace);
+                if changes.is_empty() {
+                    continue
+                }
+                if let Some(sender) = server.connection.as_initialized() {
+                    commands.push(Command::perform(sender.did_change_watched_files(changes), Message::Done));
+                }
             }
-            
         }
 
-        self.text_box = Some(Textbox::new(document)
-        .font(Font::MONOSPACE)
-        .font_size(14.0));
+        let textbox = self.text_box.as_ref()?;
+        let file = textbox.file()?;
+        let changed = events
+            .iter()
+            .any(|event| event.change != FileChange::Deleted && event.is_about(Path::new(&file)));
+        if !changed {
+            return None
+        }
+        let (theirs, _) = read_text(Path::new(&file)).ok()?;
+        let mine = textbox.buffer().get_string();
+        // Our own saves also trigger the watcher
+        if theirs == mine {
+            return None
+        }
+
+        if textbox.is_saved() {
+            self.reload(file, commands);
+        } else {
+            self.modal = Some(Modal::FileConflict { file, mine, theirs, show_diff: false });
+            self.text_box.as_mut()?.set_focus(false);
+        }
+        Some(())
+    }
+
+    /**
+     * Opens the file again from disk, keeping the cursor where it was.
+     */
+    fn reload(&mut self, file: String, commands: &mut Vec<Command<Message>>) {
+        let view = self.current_view();
+        self.open(&file, None, view, commands);
+    }
+
+    /**
+     * Returns the senders of the servers that asked to be told about the operation on the path.
+     */
+    fn file_operation_senders(&self, operation: FileOperation, path: &Path, is_dir: bool) -> Vec<(usize, MessageSender<InitializedSender>)> {
+        self.lsp.servers()
+            .filter(|server| {
+                server.connection
+                    .capabilities()
+                    .is_some_and(|capabilities| is_interested(capabilities, operation, path, is_dir))
+            })
+            .filter_map(|server| Some((server.id, server.connection.as_initialized()?)))
+            .collect()
+    }
+
+    /**
+     * Expands or collapses a folder of the file explorer, or opens the file.
+     */
+    fn explorer_select(&mut self, path: PathBuf, commands: &mut Vec<Command<Message>>) {
+        if path.is_dir() {
+            if let Some(tree) = self.file_tree.as_mut() {
+                tree.toggle(&path);
+            }
+        } else if let Some(file) = path.to_str() {
+            self.change_file(file.to_owned(), commands);
+        }
+        self.explorer_selected = Some(path);
+    }
+
+    /**
+     * Opens the modal of an action of the file explorer's toolbar.
+     * 
+     * Renames and deletes apply to the selected entry, or to the open file if nothing is selected.
+     */
+    fn explorer_action(&mut self, action: ExplorerAction) -> Option<()> {
+        let root = self.file_tree.as_ref()?.root().to_owned();
+        let selected = self.explorer_selected
+            .clone()
+            .or_else(|| self.text_box.as_ref()?.file().map(PathBuf::from));
+        // New entries go in the selected folder, or next to the selected file
+        let folder = match selected.as_ref() {
+            Some(path) if path.is_dir() => path.clone(),
+            Some(path) => path.parent().map(Path::to_owned).unwrap_or_else(|| root.clone()),
+            None => root.clone(),
+        };
+        self.modal_filter.clear();
+        let action = match action {
+            ExplorerAction::NewFile => FileAction::NewFile(folder),
+            ExplorerAction::NewFolder => FileAction::NewFolder(folder),
+            ExplorerAction::Rename => {
+                let path = selected?;
+                self.modal_filter = path.strip_prefix(&root).unwrap_or(&path).to_string_lossy().into_owned();
+                FileAction::Rename(path)
+            }
+            ExplorerAction::Delete => FileAction::Delete(selected?),
+        };
+        self.modal = Some(Modal::FileOperation(action));
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(false);
+        }
+        Some(())
+    }
+
+    /**
+     * Applies the file operation confirmed in the modal. The name comes from the modal's input.
+     */
+    fn confirm_file_action(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let Some(Modal::FileOperation(action)) = self.modal.as_ref() else {
+            return None
+        };
+        let action = action.clone();
+        let name = self.modal_filter.trim().to_owned();
+        if name.is_empty() && !matches!(action, FileAction::Delete(_)) {
+            return None
+        }
+        self.modal = None;
+        self.modal_filter.clear();
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(true);
+        }
+
+        let tree = self.file_tree.as_mut()?;
+        match action {
+            FileAction::NewFile(folder) => {
+                let Some(path) = tree.resolve(&folder, &name) else {
+                    self.notify(NotificationLevel::Error, format!("{} is outside of the workspace", name));
+                    return None
+                };
+                if let Err(e) = tree.create_file(&path) {
+                    self.notify(NotificationLevel::Error, format!("Failed to create {}: {}", path.display(), e));
+                    return None
+                }
+                tree.reveal(&path);
+                self.explorer_selected = Some(path.clone());
+                self.change_file(path.to_str()?.to_owned(), commands);
+            }
+            FileAction::NewFolder(folder) => {
+                let Some(path) = tree.resolve(&folder, &name) else {
+                    self.notify(NotificationLevel::Error, format!("{} is outside of the workspace", name));
+                    return None
+                };
+                if let Err(e) = tree.create_folder(&path) {
+                    self.notify(NotificationLevel::Error, format!("Failed to create {}: {}", path.display(), e));
+                    return None
+                }
+                tree.reveal(&path);
+                self.explorer_selected = Some(path);
+            }
+            FileAction::Rename(from) => {
+                let Some(to) = tree.resolve(tree.root(), &name) else {
+                    self.notify(NotificationLevel::Error, format!("{} is outside of the workspace", name));
+                    return None
+                };
+                if to != from {
+                    self.rename(from, to, commands);
+                }
+            }
+            FileAction::Delete(path) => {
+                self.delete(path, commands);
+            }
+        }
+        Some(())
+    }
+
+    /**
+     * Renames or moves a file or folder of the workspace.
+     * 
+     * The servers that asked for `willRenameFiles` can update the imports first, the file is renamed
+     * once they all answered.
+     */
+    fn rename(&mut self, from: PathBuf, to: PathBuf, commands: &mut Vec<Command<Message>>) {
+        if self.pending_rename.is_some() {
+            self.notify(NotificationLevel::Error, "Another rename is still waiting for the language servers".to_owned());
+            return
+        }
+        let is_dir = from.is_dir();
+        let senders = self.file_operation_senders(FileOperation::WillRename, &from, is_dir);
+        let pending = PendingRename {
+            from,
+            to,
+            is_dir,
+            waiting: senders.iter().map(|(id, _)| *id).collect::<HashSet<usize>>(),
+        };
+        for (_, sender) in senders {
+            commands.push(Command::perform(sender.will_rename_files(pending.files()), Message::Done));
+        }
+        let is_waiting = !pending.waiting.is_empty();
+        self.pending_rename = Some(pending);
+        if is_waiting {
+            commands.push(Command::perform(smol::Timer::after(RENAME_TIMEOUT), |_| Message::FinishRename));
+        } else {
+            self.finish_rename(commands);
+        }
+    }
+
+    /**
+     * Applies the edits a server wants made before the rename. The file is renamed once the last server answered.
+     */
+    fn will_rename_answered(&mut self, id: usize, edit: Option<WorkspaceEdit>, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        // Answers that come after the timeout are too late, the file was already renamed
+        if !self.pending_rename.as_mut()?.waiting.remove(&id) {
+            return None
+        }
+        if let Some(edit) = edit {
+            self.apply_workspace_edit(id, edit);
+        }
+        if self.pending_rename.as_ref()?.waiting.is_empty() {
+            self.finish_rename(commands);
+        }
+        Some(())
+    }
+
+    /**
+     * Applies the text edits of a server. The open file is edited in its buffer, the other files are edited on disk.
+     */
+    fn apply_workspace_edit(&mut self, id: usize, edit: WorkspaceEdit) -> Option<()> {
+        let encoding = self.lsp.get(id)?.position_encoding();
+        let open_file = self.text_box.as_ref().and_then(|textbox| textbox.file()).map(PathBuf::from);
+        for (path, edits) in text_edits(edit) {
+            if open_file.as_ref() == Some(&path) {
+                self.text_box.as_mut()?.apply_edits(edits, encoding);
+                continue
+            }
+            let Some(file) = path.to_str() else {
+                continue
+            };
+            let result = Document::open(file).and_then(|mut document| {
+                document.apply_edits(edits, encoding);
+                document.save(None)
+            });
+            if let Err(e) = result {
+                self.notify(NotificationLevel::Error, format!("Failed to edit {}: {}", file, e));
+            }
+        }
+        Some(())
+    }
+
+    /**
+     * Renames the file on disk, tells the servers about it and follows the rename with the open file.
+     */
+    fn finish_rename(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let pending = self.pending_rename.take()?;
+        // The edits made to the open file are sent while the servers still know it under its old name
+        self.send_changes(commands);
+        if let Err(e) = self.file_tree.as_mut()?.rename(&pending.from, &pending.to) {
+            self.notify(NotificationLevel::Error, format!("Failed to rename {}: {}", pending.from.display(), e));
+            return None
+        }
+        for (_, sender) in self.file_operation_senders(FileOperation::DidRename, &pending.from, pending.is_dir) {
+            commands.push(Command::perform(sender.did_rename_files(pending.files()), Message::Done));
+        }
+        self.file_tree.as_mut()?.reveal(&pending.to);
+        self.explorer_selected = Some(pending.to.clone());
+
+        // The open file is renamed too if it was the file or within the folder
+        let old_file = self.text_box.as_ref()?.file()?;
+        let relative = Path::new(&old_file).strip_prefix(&pending.from).ok()?;
+        let new_file = if relative.as_os_str().is_empty() {
+            pending.to.clone()
+        } else {
+            pending.to.join(relative)
+        };
+        let new_file = new_file.to_str()?.to_owned();
+        self.text_box.as_mut()?.set_file(&new_file);
+        self.switch_document(Some(old_file), Some(new_file), commands);
+        Some(())
+    }
+
+    /**
+     * Deletes a file or folder of the workspace. The open file is closed if it was deleted.
+     */
+    fn delete(&mut self, path: PathBuf, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let senders = self.file_operation_senders(FileOperation::DidDelete, &path, path.is_dir());
+        if let Err(e) = self.file_tree.as_mut()?.delete(&path) {
+            self.notify(NotificationLevel::Error, format!("Failed to delete {}: {}", path.display(), e));
+            return None
+        }
+        let uri = Url::from_file_path(&path).ok()?.to_string();
+        for (_, sender) in senders {
+            commands.push(Command::perform(sender.did_delete_files(vec![FileDelete { uri: uri.clone() }]), Message::Done));
+        }
+        if self.explorer_selected.as_ref().is_some_and(|selected| selected.starts_with(&path)) {
+            self.explorer_selected = None;
+        }
+
+        let open_file = self.text_box.as_ref()?.file()?;
+        if Path::new(&open_file).starts_with(&path) {
+            self.set_file(Document::new(), Some(open_file), commands);
+        }
+        Some(())
     }
 
     fn process_event(&mut self, message: Message) -> Option<Vec<Command<Message>>> {
@@ -405,14 +1166,35 @@ impl Editor {
                 .set_curor(pos)
                 .clear();
             }
+            Message::SelectWord(pos) => self.can_edit_textbox()?.select_word(pos),
+            Message::SelectLine(pos) => self.can_edit_textbox()?.select_line(pos),
             Message::Paste(value) => {
-                self.can_edit_textbox()?.insert(value);
+                let linewise = self.clipboard.is_linewise(&value);
+                self.can_edit_textbox()?.paste_text(value, linewise);
             },
+            Message::PasteClip(index) => {
+                self.paste_clip(index, &mut commands);
+            }
             Message::Open(file) => {
                 self.change_file(file, &mut commands);
             }
-            Message::LspMessage(message) => {
-                self.can_edit_textbox()?.process_lsp_response(message);
+            Message::LspMessage(id, LspResponse::WillRenameFiles(edit)) => {
+                self.will_rename_answered(id, edit, &mut commands);
+            },
+            Message::LspMessage(_, LspResponse::FoldingRanges(ranges)) => {
+                self.folding_ranges_answered(ranges);
+            },
+            Message::LspMessage(id, LspResponse::SelectionRanges(ranges)) => {
+                self.selection_ranges_answered(id, ranges);
+            },
+            Message::LspMessage(id, message @ (
+                LspResponse::RegisterCapability(..) | LspResponse::UnregisterCapability(..) | LspResponse::Request(..)
+            )) => {
+                self.server_request(id, message, &mut commands);
+            },
+            Message::LspMessage(id, message) => {
+                let encoding = self.lsp.get(id)?.position_encoding();
+                self.can_edit_textbox()?.process_lsp_response(id, encoding, message);
                 self.can_edit_textbox()?
                 .clear();
             },
@@ -428,51 +1210,183 @@ impl Editor {
                 }
 
             },
-            Message::SelectFolder => self.set_workspace(self.open_folder(), &mut commands),
-            Message::FileFilter(filter) => {
-                self.file_filter = filter
+            Message::SelectFolder => self.open_workspace(self.open_folder(), &mut commands),
+            Message::OpenWorkspace(workspace) => self.open_workspace(Some(workspace), &mut commands),
+            Message::Quit => {
+                self.save_session();
+                self.keep_unsaved_changes();
+                commands.push(window::close());
+            },
+            Message::Tick => self.tick(&mut commands),
+            Message::ToggleAutosave => {
+                self.settings.autosave = !self.settings.autosave;
+                self.save_settings();
+            },
+            Message::Format(action) => {
+                self.format_action(action, &mut commands);
+            },
+            Message::DocumentOpened(file, result, view) => {
+                self.document_opened(file, *result, view, &mut commands);
+            },
+            Message::Saved(job, result) => {
+                self.saved(job, result, &mut commands);
+            },
+            Message::ToggleFold(line) => self.text_box.as_mut()?.toggle_fold(line),
+            Message::Pane(pane, message) => {
+                if pane != self.panes.focused() && !self.unfocused_pane_event(pane, &message, &mut commands) {
+                    return Some(commands)
+                }
+                commands.extend(self.process_event(*message).unwrap_or_default());
+                return Some(commands)
+            },
+            Message::ResizePane(event) => self.panes.resize(event),
+            Message::DismissNotification(id) => self.notifications.retain(|notification| notification.id != id),
+            Message::RecoverySelect(index) => self.select_snapshot(index),
+            Message::Recover => {
+                self.recover(&mut commands);
+            },
+            Message::DiscardSnapshot => {
+                self.discard_snapshot();
+            },
+            Message::ModalFilter(filter) => {
+                self.modal_filter = filter;
+                if let Some(Modal::FileSelector { selected, .. }) = self.modal.as_mut() {
+                    *selected = 0;
+                }
+                self.update_file_selector();
+            },
+            Message::IndexBuilt(mut index) => {
+                // The workspace changed while the index was being built
+                if self.workspace.as_deref() != Some(index.root()) {
+                    return Some(commands)
+                }
+                if let Some(old) = self.file_index.take() {
+                    index.set_recent(old.recent().cloned());
+                } else if let Some(session) = self.session_store.as_ref().and_then(|store| store.session(index.root())) {
+                    index.set_recent(session.recent_files.iter().cloned());
+                }
+                self.file_index = Some(*index);
+                self.update_file_selector();
             },
             Message::NewFile => self.new_file(&mut commands),
             Message::Save => {
-                let workspace = self.workspace().to_owned().clone();
-                if let Some(textbox) = self.text_box.as_mut() {
-                    textbox.save(workspace);
-                    let file_path = textbox.buffer().filename().unwrap().clone();
-                    // Save command
-                    let command = Command::perform(
-                        self.lsp.as_ref()?
-                        .as_initialized()?
-                        .did_save(file_path), Message::DidSave
-                    );
-                    commands.push(command);
-                }
-
+                self.save(&mut commands);
             },
             Message::SetTextBoxFocus(focus) => if let Some(textbox) = self.text_box.as_mut() {
                 textbox.set_focus(focus)
             },
-            Message::CloseClient => {
-            },
-            Message::ClientStart(_) => {
-                if let Some(lsp) = self.lsp.as_mut() {
-                    lsp.has_initialize_client();
+            Message::CloseClient(id) => {
+                self.lsp.remove_server(id);
+                // A rename doesn't wait for a server that stopped
+                let was_last = self.pending_rename
+                    .as_mut()
+                    .is_some_and(|pending| pending.waiting.remove(&id) && pending.waiting.is_empty());
+                if was_last {
+                    self.finish_rename(&mut commands);
                 }
-                let command = Command::perform(self.lsp.as_ref()?.as_initialized()?.has_initialized(), Message::Done);
-                commands.push(command);
-                // sender.se
+                if let Some(textbox) = self.text_box.as_mut() {
+                    textbox.remove_diagnostics(id);
+                }
+            },
+            Message::ClientStart(id, result) => {
+                let lsp = self.lsp.get_mut(id)?;
+                lsp.set_capabilities(result.capabilities);
+                lsp.has_initialize_client();
+                let sender = lsp.as_initialized()?;
+
+                // Send the open document if the server handles its language
+                let file = self.text_box.as_ref().and_then(|textbox| textbox.file());
+                let open = file.and_then(|file| {
+                    let language = language_id(Path::new(&file))?;
+                    self.lsp.servers_for(language)
+                        .any(|server| server.id == id && server.connection.sends_open_close())
+                        .then_some((file, language))
+                });
+                let open = open.and_then(|(file, language)| Some((self.open_text(&file)?, file, language)));
+                let fut = async move {
+                    sender.clone().has_initialized().await;
+                    if let Some(((text, version), file, language)) = open {
+                        sender.open_document(file, language.to_owned(), text, version).await;
+                    }
+                };
+                commands.push(Command::perform(fut, Message::Done));
             },
             Message::OpenDocument(_) => (),
             Message::CloseDocument(_) => (),
             Message::DocChanged(_) => (),
             Message::HasInitialized(_) => (),
             Message::DidSave(_) => (),
-            Message::Done(_) =>  ()
+            Message::Done(_) =>  (),
+            Message::FilesChanged(events) => {
+                if let Some(tree) = self.file_tree.as_mut() {
+                    tree.files_changed(&events);
+                }
+                if self.file_index.as_mut().is_some_and(|index| index.files_changed(&events)) {
+                    self.build_file_index(&mut commands);
+                }
+                self.update_file_selector();
+                self.files_changed(events, &mut commands);
+            },
+            Message::KeepMine => {
+                // The next save overwrites the file on disk
+                self.modal = None;
+                self.text_box.as_mut()?.set_focus(true);
+            },
+            Message::TakeTheirs => {
+                self.modal = None;
+                let file = self.text_box.as_ref()?.file()?;
+                self.reload(file.clone(), &mut commands);
+                // The unsaved changes were thrown away on purpose
+                if let Some(recovery) = self.recovery.as_ref() {
+                    recovery.remove(Some(Path::new(&file)));
+                }
+            },
+            Message::ExplorerSelect(path) => self.explorer_select(path, &mut commands),
+            Message::ExplorerAction(action) => {
+                self.explorer_action(action);
+            },
+            Message::ConfirmFileAction => {
+                self.confirm_file_action(&mut commands);
+            },
+            Message::FinishRename => {
+                self.finish_rename(&mut commands);
+            },
+            Message::ToggleConflictDiff => {
+                if let Some(Modal::FileConflict { show_diff, .. }) = self.modal.as_mut() {
+                    *show_diff = !*show_diff;
+                }
+            },
+            Message::InspectTraffic(seq) => {
+                if let Some(Modal::LspInspector { selected, .. }) = self.modal.as_mut() {
+                    *selected = if *selected == Some(seq) { None } else { Some(seq) };
+                }
+            },
+            Message::ToggleTrafficRecording => {
+                self.lsp_traffic.set_enabled(!self.lsp_traffic.is_enabled())
+            },
+            Message::ClearTraffic => self.lsp_traffic.clear(),
+            Message::ExportTraffic => self.export_lsp_traffic(),
         }
         if let Some(textbox) = self.text_box.as_mut() {
+            // The window or the text may have changed size, the rows are wrapped again if they did
+            textbox.set_wrap(self.settings.wrap);
+            textbox.set_rainbow_brackets(self.settings.rainbow_brackets);
+            // The edits made by the message are undone together, and the ones made in an insert of Vim
+            if self.settings.keymap != Keymap::Vim || self.vim.mode() != VimMode::Insert {
+                textbox.seal_history();
+            }
             textbox.correct_position();
+            // A cursor moved to a line that is folded away unfolds it
+            textbox.reveal_cursor();
             let window = textbox.buffer().window;
-            self.correct_scroll(&mut commands, window);
+            self.correct_scroll(self.panes.focused_pane(), &mut commands, window);
         }
+        // The other panes show the edits made in the focused one
+        for textbox in self.panes.unfocused() {
+            textbox.set_wrap(self.settings.wrap);
+            textbox.set_rainbow_brackets(self.settings.rainbow_brackets);
+        }
+        self.panes.sync(&mut self.text_box);
 
         Some(commands)
     }
@@ -487,7 +1401,8 @@ impl Editor {
                     ).padding(padding),
                     container(
                         text("Use the keyboard command ctrl/command+o to open a new folder") 
-                    )
+                    ),
+                    self.recent_workspaces_view()
                 ).align_items(iced::alignment::Alignment::Center)
                 
             )
@@ -497,50 +1412,113 @@ impl Editor {
             .height(Length::Fill)
             .into(),
             self.modal_view(),
-            self.is_saved()
+            notifications(&self.notifications),
+            self.is_saved(),
+            self.settings.autosave
         )
     }
 
+    /**
+     * The workspaces that were opened before, shown on the welcome screen.
+     */
+    fn recent_workspaces_view<'a>(&self) -> Element<'a, Message, Renderer> {
+        let workspaces = self.session_store.as_ref().map(SessionStore::recent_workspaces).unwrap_or_default();
+        if workspaces.is_empty() {
+            return column!().into()
+        }
+        let mut list = column!(text("Recent workspaces").size(14.0)).spacing(5).padding(Padding::from([30, 0, 0, 0]));
+        for workspace in workspaces {
+            let button_theme = Box::new(MenuButton);
+            list = list.push(
+                button(text(workspace.display().to_string()).size(14.0))
+                .style(iced::theme::Button::Custom(button_theme))
+                .on_press(Message::OpenWorkspace(workspace))
+            );
+        }
+        list.into()
+    }
+
     fn process_keyboard_event(&mut self, event: KeyEvent,  commands: &mut Vec<Command<Message>> ) -> Option<()> {
         match event {
+            _ if self.keymap_event(event, commands) => (),
              KeyEvent::Special(key, modifiers) => {
                 self.last_event = key;
                 self.modifiers = modifiers;
+                if self.file_selector_key(key, commands) || self.clipboard_history_key(key, commands) {
+                    return Some(())
+                }
                 if self.modifiers.ctrl {
                     dbg!("Ctrl!");
                     match key {
+                        Key::KeyS if modifiers.alt => self.can_edit_textbox()?.edit_lines(LineCommand::Sort),
+                        Key::KeyR if modifiers.alt => self.can_edit_textbox()?.edit_lines(LineCommand::Reverse),
                         Key::KeyS => {
                             dbg!("S!");
-                            let workspace = self.workspace().to_owned().clone();
-                            self.can_edit_textbox()?.save(workspace);
-                            let file_path = self.text_box.as_ref()?.buffer().filename().unwrap().clone();
-
-                            // Save command
-                            let command = Command::perform(
-                                self.lsp.as_ref()?
-                                .as_initialized()?
-                                .did_save(file_path), Message::DidSave
-                            );
-                            commands.push(command);
+                            self.can_edit_textbox()?;
+                            self.save(commands);
                         }
-                        Key::KeyC => self.can_edit_textbox()?.copy(commands),
+                        Key::KeyC => {
+                            let clip = self.can_edit_textbox()?.copy(commands);
+                            self.clipboard.push(clip);
+                        }
+                        Key::KeyV if modifiers.shift => self.open_clipboard_history(),
                         Key::KeyV => self.can_edit_textbox()?.paste(commands),
-                        Key::KeyX => self.can_edit_textbox()?.cut(commands),
-                        Key::KeyP => {
-                            if let Some(path) = &self.workspace {
-                                if let Some(file) = path.to_str() {
-                                    self.set_modal(file.to_owned());
-                                }
-                            }
-                        },
+                        Key::KeyX => {
+                            let clip = self.can_edit_textbox()?.cut(commands);
+                            self.clipboard.push(clip);
+                        }
+                        Key::KeyP if self.workspace.is_some() => self.open_file_selector(),
                         Key::KeyN => self.new_file(commands),
-                        Key::KeyO => self.set_workspace(self.open_folder(), commands),
+                        Key::KeyO => self.open_workspace(self.open_folder(), commands),
                         Key::KeyL => self.can_edit_textbox()?.set_floating_message(),
+                        Key::KeyI if modifiers.shift => self.set_inspector_modal(),
+                        Key::KeyA if modifiers.shift => self.can_edit_textbox()?.edit_lines(LineCommand::ToggleBlockComment),
                         Key::KeyA => self.can_edit_textbox()?.select_all(commands),
+                        Key::KeySlash => self.can_edit_textbox()?.edit_lines(LineCommand::ToggleLineComment),
+                        Key::KeyD if modifiers.shift => self.can_edit_textbox()?.edit_lines(LineCommand::Duplicate),
+                        Key::KeyK if modifiers.shift => self.can_edit_textbox()?.edit_lines(LineCommand::Delete),
+                        Key::KeyJ => self.can_edit_textbox()?.edit_lines(LineCommand::Join),
+                        Key::KeyZ if modifiers.shift => self.can_edit_textbox()?.redo(),
+                        Key::KeyZ => self.can_edit_textbox()?.undo(),
+                        Key::KeyY => self.can_edit_textbox()?.redo(),
+                        Key::KeyB => self.show_explorer = !self.show_explorer,
+                        Key::KeyLBracket if modifiers.alt => self.can_edit_textbox()?.fold_all(),
+                        Key::KeyRBracket if modifiers.alt => self.can_edit_textbox()?.unfold_all(),
+                        Key::KeyLBracket if modifiers.shift => self.can_edit_textbox()?.fold(),
+                        Key::KeyRBracket if modifiers.shift => self.can_edit_textbox()?.unfold(),
+                        Key::KeyM => self.can_edit_textbox()?.jump_to_matching_bracket(),
+                        Key::Key('\\') if modifiers.shift => self.split_pane(pane_grid::Axis::Horizontal),
+                        Key::Key('\\') => self.split_pane(pane_grid::Axis::Vertical),
+                        Key::Key(digit @ '1'..='9') => {
+                            let pane = self.panes.nth(digit as usize - '1' as usize)?;
+                            self.focus_pane(pane, commands);
+                        }
+                        Key::KeyW => self.close_pane(commands),
                         _ => ()
                     }
                 }
                 match key {
+                    Key::KeyRight if modifiers.alt && modifiers.shift => {
+                        self.expand_selection(commands);
+                    },
+                    Key::KeyLeft if modifiers.alt && modifiers.shift => self.can_edit_textbox()?.shrink_selection(),
+                    Key::KeyUp if modifiers.alt => self.can_edit_textbox()?.edit_lines(LineCommand::MoveUp),
+                    Key::KeyDown if modifiers.alt => self.can_edit_textbox()?.edit_lines(LineCommand::MoveDown),
+                    Key::KeyUp if modifiers.ctrl => self.can_edit_textbox()?.move_paragraph(false, modifiers),
+                    Key::KeyDown if modifiers.ctrl => self.can_edit_textbox()?.move_paragraph(true, modifiers),
+                    // Ctrl moves by words, along with Alt by the parts of words, and Alt alone between spaces
+                    Key::KeyRight | Key::KeyLeft if modifiers.ctrl || modifiers.alt => {
+                        let kind = match (modifiers.ctrl, modifiers.alt) {
+                            (true, true) => WordKind::Subword,
+                            (true, false) => WordKind::Word,
+                            _ => WordKind::Whitespace,
+                        };
+                        self.can_edit_textbox()?.move_word(matches!(key, Key::KeyRight), kind, modifiers);
+                    }
+                    Key::KeyDelete | Key::KeyBackSpace if modifiers.ctrl => {
+                        let kind = if modifiers.alt { WordKind::Subword } else { WordKind::Word };
+                        self.can_edit_textbox()?.delete_word(matches!(key, Key::KeyDelete), kind);
+                    }
                     Key::KeyUp => self.can_edit_textbox()?.move_up(modifiers),
                     Key::KeyDown => self.can_edit_textbox()?.move_down(modifiers),
                     Key::KeyRight => self.can_edit_textbox()?.move_right(modifiers),
@@ -548,16 +1526,11 @@ impl Editor {
                     Key::KeyDelete => self.can_edit_textbox()?.delete(),
                     Key::KeyBackSpace => self.can_edit_textbox()?.backspace(),
                     Key::KeyEnter => self.can_edit_textbox()?.new_line(),
-                    Key::KeyTab => {
-                        self.text_box.as_mut()?.insert(" ".repeat(4).to_owned());
-                        // if let Some(change) = document_change {
-                        //     // self.doc_changed(change);
-                        // }
-                    },
+                    Key::KeyTab => self.can_edit_textbox()?.indent(modifiers),
                     Key::KeyEsc => self.modal = None,
                     Key::None => (),
-                    Key::KeyHome => self.can_edit_textbox()?.move_start(),
-                    Key::KeyEnd => self.can_edit_textbox()?.move_end(),
+                    Key::KeyHome => self.can_edit_textbox()?.move_start(modifiers),
+                    Key::KeyEnd => self.can_edit_textbox()?.move_end(modifiers),
                     Key::KeyPgUp => self.can_edit_textbox()?.page_up(),
                     Key::KeyPgDown => self.can_edit_textbox()?.page_down(),
                     _ => ()
@@ -589,6 +1562,234 @@ impl Editor {
     /*
         For methods that requiere editing the state of the textbox.
      */
+    /**
+     * Returns the key Vim reads for the event, `None` for the keys that are handled as they are without it.
+     */
+    fn vim_key(&self, event: KeyEvent) -> Option<VimKey> {
+        let moves = !matches!(self.vim.mode(), VimMode::Insert | VimMode::CommandLine);
+        match event {
+            KeyEvent::CharacterReceived(character) if !self.modifiers.ctrl => Some(VimKey::Char(character)),
+            KeyEvent::CharacterReceived(_) => None,
+            KeyEvent::Special(key, modifiers) => match key {
+                Key::KeyEsc => Some(VimKey::Esc),
+                _ if modifiers.ctrl || modifiers.alt => match key {
+                    Key::KeyR if modifiers.ctrl && moves => Some(VimKey::Ctrl('r')),
+                    _ => None,
+                },
+                Key::KeyEnter => Some(VimKey::Enter),
+                Key::KeyBackSpace => Some(VimKey::Backspace),
+                Key::KeyDelete => Some(VimKey::Delete),
+                Key::KeyTab => Some(VimKey::Tab),
+                // The arrows move like the keys of Vim, so they work with counts and operators
+                Key::KeyLeft if moves => Some(VimKey::Char('h')),
+                Key::KeyDown if moves => Some(VimKey::Char('j')),
+                Key::KeyUp if moves => Some(VimKey::Char('k')),
+                Key::KeyRight if moves => Some(VimKey::Char('l')),
+                _ => None,
+            },
+        }
+    }
+
+    /**
+     * Returns the key Emacs reads for the event, `None` for the keys that are handled as they are without it.
+     */
+    fn emacs_key(&self, event: KeyEvent) -> Option<EmacsKey> {
+        let key = match event {
+            KeyEvent::CharacterReceived(_) if self.modifiers.ctrl => return None,
+            KeyEvent::CharacterReceived(character) if self.modifiers.alt => EmacsKey::Meta(character),
+            KeyEvent::CharacterReceived(character) => EmacsKey::Char(character),
+            KeyEvent::Special(key, modifiers) => match key {
+                Key::KeyEsc => EmacsKey::Esc,
+                Key::KeyBackSpace if modifiers.alt && !modifiers.ctrl => EmacsKey::Meta(DEL),
+                Key::Key(' ') if modifiers.ctrl => EmacsKey::Ctrl(' '),
+                Key::KeySlash if modifiers.ctrl => EmacsKey::Ctrl(if modifiers.shift { '?' } else { '/' }),
+                _ if modifiers.ctrl && !modifiers.alt => EmacsKey::Ctrl(key.letter()?),
+                _ if modifiers.ctrl || modifiers.alt => return None,
+                Key::KeyEnter => EmacsKey::Enter,
+                Key::KeyBackSpace => EmacsKey::Backspace,
+                Key::KeyDelete => EmacsKey::Delete,
+                Key::KeyTab => EmacsKey::Tab,
+                // The arrows move the cursor like their keys, so they grow an active region
+                Key::KeyLeft => EmacsKey::Ctrl('b'),
+                Key::KeyRight => EmacsKey::Ctrl('f'),
+                Key::KeyUp => EmacsKey::Ctrl('p'),
+                Key::KeyDown => EmacsKey::Ctrl('n'),
+                _ => return None,
+            },
+        };
+        self.emacs.takes(key).then_some(key)
+    }
+
+    /**
+     * Lets the keymap handle the event when it isn't the default one and the open buffer has the focus, returns
+     * whether it did.
+     */
+    fn keymap_event(&mut self, event: KeyEvent, commands: &mut Vec<Command<Message>>) -> bool {
+        if let KeyEvent::Special(key, modifiers) = event {
+            self.last_event = key;
+            self.modifiers = modifiers;
+        }
+        if self.modal.is_some() || !self.text_box.as_ref().is_some_and(Textbox::is_focused) {
+            return false
+        }
+        let requests = match self.settings.keymap {
+            Keymap::Default => return false,
+            Keymap::Vim => {
+                let Some(key) = self.vim_key(event) else {
+                    return false
+                };
+                self.text_box.as_mut().map(|textbox| textbox.vim(&mut self.vim, key))
+            }
+            Keymap::Emacs => {
+                let Some(key) = self.emacs_key(event) else {
+                    return false
+                };
+                self.text_box.as_mut().map(|textbox| textbox.emacs(&mut self.emacs, key))
+            }
+        };
+        for request in requests.unwrap_or_default() {
+            self.keymap_request(request, commands);
+        }
+        true
+    }
+
+    /**
+     * Does what a command of the keymap asks the editor for.
+     */
+    fn keymap_request(&mut self, request: KeymapRequest, commands: &mut Vec<Command<Message>>) {
+        match request {
+            KeymapRequest::Save => {
+                self.save(commands);
+            }
+            KeymapRequest::Open(file) => {
+                let path = match self.workspace.as_ref() {
+                    Some(workspace) => workspace.join(&file),
+                    None => PathBuf::from(&file),
+                };
+                if path.is_file() {
+                    self.change_file(path.to_string_lossy().into_owned(), commands);
+                } else {
+                    self.notify(NotificationLevel::Error, format!("File not found: {}", file));
+                }
+            }
+            KeymapRequest::PickFile if self.workspace.is_some() => self.open_file_selector(),
+            KeymapRequest::PickFile => {
+                let file = self.open_file(None);
+                if let Some(file) = file.as_ref().and_then(|file| file.to_str()) {
+                    self.change_file(file.to_owned(), commands);
+                }
+            }
+            KeymapRequest::Copy(text) => {
+                self.clipboard.push(Clip::new(text.clone(), false));
+                commands.push(iced::clipboard::write::<Message>(text));
+            }
+            KeymapRequest::Paste => {
+                if let Some(textbox) = self.text_box.as_mut() {
+                    textbox.paste(commands);
+                }
+            }
+            KeymapRequest::Error(message) => self.notify(NotificationLevel::Error, message),
+        }
+    }
+
+    /**
+     * Returns what the status bar shows for the keymap, like the mode of Vim.
+     */
+    fn keymap_status(&self) -> String {
+        match self.settings.keymap {
+            Keymap::Default => String::new(),
+            Keymap::Vim => self.vim.status(),
+            Keymap::Emacs => self.emacs.status(),
+        }
+    }
+
+    /**
+     * Handles a message of a pane that isn't focused. Its window scrolls and resizes on its own, a click in it focuses
+     * it first.
+     *
+     * Returns whether the message is then processed by the focused textbox.
+     */
+    fn unfocused_pane_event(&mut self, pane: pane_grid::Pane, message: &Message, commands: &mut Vec<Command<Message>>) -> bool {
+        match *message {
+            Message::View(height, width) => {
+                if let Some(textbox) = self.panes.unfocused_mut(pane) {
+                    if !textbox.buffer().window.size_is_different(width, height) {
+                        textbox.set_size(width, height);
+                        textbox.clear();
+                    }
+                }
+                false
+            }
+            Message::Offset(offset_x, offset_y) => {
+                let Some(textbox) = self.panes.unfocused_mut(pane) else {
+                    return false
+                };
+                textbox.set_offset(offset_x, offset_y);
+                textbox.clear();
+                // The gutter scrolls with the text
+                let window = textbox.buffer().window;
+                if let Some(editor_pane) = self.panes.get(pane) {
+                    let scroll = iced::widget::scrollable::scroll_to(editor_pane.gutter_scroll_id(), window.into());
+                    commands.push(scroll);
+                }
+                false
+            }
+            // The focused pane loses the keyboard to the click, not this one
+            Message::SetTextBoxFocus(false) => false,
+            Message::CursorEvent(_) | Message::SelectWord(_) | Message::SelectLine(_) | Message::SelectionMove(_)
+            | Message::SetTextBoxFocus(true) | Message::ToggleFold(_) => {
+                self.focus_pane(pane, commands);
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /**
+     * Splits the focused pane, the new pane shows the same document and takes the focus.
+     */
+    fn split_pane(&mut self, axis: pane_grid::Axis) {
+        self.panes.split(axis, &mut self.text_box);
+    }
+
+    /**
+     * Focuses the pane. The servers are told when it shows another file than the pane focused before.
+     */
+    fn focus_pane(&mut self, pane: pane_grid::Pane, commands: &mut Vec<Command<Message>>) {
+        let old_file = self.text_box.as_ref().and_then(Textbox::file);
+        self.take_snapshot();
+        if !self.panes.focus(pane, &mut self.text_box) {
+            return
+        }
+        self.panes.sync(&mut self.text_box);
+        self.document_switched(old_file, commands);
+    }
+
+    /**
+     * Closes the focused pane, the last one stays open.
+     */
+    fn close_pane(&mut self, commands: &mut Vec<Command<Message>>) {
+        let old_file = self.text_box.as_ref().and_then(Textbox::file);
+        self.take_snapshot();
+        if !self.panes.close(&mut self.text_box) {
+            return
+        }
+        self.panes.sync(&mut self.text_box);
+        self.document_switched(old_file, commands);
+    }
+
+    fn document_switched(&mut self, old_file: Option<String>, commands: &mut Vec<Command<Message>>) {
+        let new_file = self.text_box.as_ref().and_then(Textbox::file);
+        if old_file == new_file {
+            return
+        }
+        // The folding ranges of the servers were asked for the other document
+        self.folding_request = None;
+        self.vim.reset();
+        self.emacs.reset();
+        self.switch_document(old_file, new_file, commands);
+    }
+
     fn can_edit_textbox(&mut self) -> Option<&mut Textbox> {
         if let Some (textbox) = self.text_box.as_ref() {
             if textbox.is_focused() {
@@ -598,8 +1799,393 @@ impl Editor {
         None
     }
 
+    /**
+     * Writes the open buffer in the background, the servers are told once it's written. A save asked for while
+     * another one is written waits for it, and a save the user cancelled isn't an error.
+     */
+    fn save(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        if self.saving {
+            self.save_queued = true;
+            return Some(())
+        }
+        let workspace = self.workspace();
+        let textbox = self.text_box.as_mut()?;
+        let job = match textbox.save_job(workspace, self.settings.save_options) {
+            Ok(job) => job,
+            Err(DocumentError::Cancelled) => {
+                self.notify(NotificationLevel::Info, "The file wasn't saved, no file was chosen".to_owned());
+                return None
+            }
+            Err(e) => {
+                self.notify(NotificationLevel::Error, format!("Failed to save the file: {}", e));
+                return None
+            }
+        };
+        // The edits made by the save options reach the servers before the save
+        self.send_changes(commands);
+        self.saving = true;
+        let write = job.clone();
+        commands.push(Command::perform(smol::unblock(move || write.write()), move |result| Message::Saved(job, result)));
+        Some(())
+    }
+
+    /**
+     * Marks the buffer as saved once its save was written. A failed save leaves the buffer dirty.
+     */
+    fn saved(&mut self, job: SaveJob, result: DocumentResult<()>, commands: &mut Vec<Command<Message>>) {
+        self.saving = false;
+        match result {
+            Ok(()) => {
+                self.finish_save(job, commands);
+            }
+            Err(e) => self.notify(NotificationLevel::Error, format!("Failed to save {}: {}", job.filename, e)),
+        }
+        if std::mem::take(&mut self.save_queued) {
+            self.save(commands);
+        }
+    }
+
+    fn finish_save(&mut self, job: SaveJob, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        // The buffer is either the saved file or the new file that was saved
+        let textbox = self.text_box
+            .as_mut()
+            .filter(|textbox| textbox.file().is_none_or(|file| *file == job.filename))?;
+        let old_file = textbox.file();
+        textbox.saved(&job.filename, job.version);
+        if let Some(recovery) = self.recovery.as_ref() {
+            recovery.remove(old_file.as_deref().map(Path::new));
+        }
+        // The servers are told about a new file once it has a name
+        if old_file.is_none() {
+            self.switch_document(None, Some(job.filename.clone()), commands);
+        }
+        self.did_save(job.filename, commands);
+        Some(())
+    }
+
+    fn save_settings(&mut self) {
+        if let Some(store) = self.session_store.as_mut() {
+            if let Err(e) = store.set_settings(self.settings) {
+                eprintln!("Failed to save the settings: {}", e);
+            }
+        }
+    }
+
+    /**
+     * Changes the format of the open file or the save options, from the status bar.
+     */
+    fn format_action(&mut self, action: FormatAction, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        match action {
+            FormatAction::ToggleTrimWhitespace => {
+                let options = &mut self.settings.save_options;
+                options.trim_trailing_whitespace = !options.trim_trailing_whitespace;
+                self.save_settings();
+                return Some(())
+            }
+            FormatAction::ToggleInsertFinalNewline => {
+                let options = &mut self.settings.save_options;
+                options.insert_final_newline = !options.insert_final_newline;
+                self.save_settings();
+                return Some(())
+            }
+            FormatAction::CycleWrap => {
+                self.settings.wrap = self.settings.wrap.next();
+                self.save_settings();
+                return Some(())
+            }
+            FormatAction::ToggleRainbowBrackets => {
+                self.settings.rainbow_brackets = !self.settings.rainbow_brackets;
+                self.save_settings();
+                return Some(())
+            }
+            FormatAction::CycleKeymap => {
+                self.settings.keymap = self.settings.keymap.next();
+                self.vim.reset();
+                self.emacs.reset();
+                self.save_settings();
+                return Some(())
+            }
+            _ => (),
+        }
+
+        let textbox = self.text_box.as_mut()?;
+        let mut format = textbox.buffer().format();
+        match action {
+            FormatAction::PickEncoding => {
+                self.modal = Some(Modal::EncodingPicker(format.encoding));
+                textbox.set_focus(false);
+            }
+            FormatAction::ReopenWith(encoding) => {
+                let file = textbox.file()?;
+                self.modal = None;
+                let view = self.current_view();
+                self.open(&file, Some(encoding), view, commands);
+            }
+            FormatAction::SaveWith(encoding) => {
+                textbox.set_format(format.with_encoding(encoding));
+                textbox.set_focus(true);
+                self.modal = None;
+            }
+            FormatAction::ToggleBom => {
+                format.has_bom = !format.has_bom;
+                textbox.set_format(format);
+            }
+            FormatAction::ToggleLineEnding => {
+                format.line_ending = match format.line_ending {
+                    LineEnding::Lf => LineEnding::CrLf,
+                    LineEnding::CrLf => LineEnding::Lf,
+                };
+                textbox.set_format(format);
+            }
+            FormatAction::ToggleFinalNewline => {
+                let has_final_newline = textbox.buffer().has_final_newline();
+                textbox.set_final_newline(!has_final_newline);
+            }
+            FormatAction::ToggleTrimWhitespace | FormatAction::ToggleInsertFinalNewline | FormatAction::CycleWrap | FormatAction::ToggleRainbowBrackets
+            | FormatAction::CycleKeymap => (),
+        }
+        Some(())
+    }
+
+    /**
+     * Remembers when the open buffer was last edited, and keeps its content for the panic hook while it's dirty.
+     */
+    fn track_edits(&mut self) {
+        let Some(textbox) = self.text_box.as_ref() else {
+            return
+        };
+        let state = EditState {
+            file: textbox.file(),
+            version: textbox.buffer().buffer().version(),
+            is_saved: textbox.is_saved(),
+        };
+        if self.last_edit.as_ref().is_some_and(|(last, _)| *last == state) {
+            return
+        }
+        if let Some(recovery) = self.recovery.as_ref() {
+            let latest = (!state.is_saved).then(|| (state.file.clone().map(PathBuf::from), textbox.buffer().buffer().rope()));
+            recovery.set_latest(latest);
+        }
+        self.last_edit = Some((state, Instant::now()));
+    }
+
+    /**
+     * Snapshots the dirty buffer every so often, and saves it once it hasn't been edited for a while if autosave is on.
+     */
+    fn tick(&mut self, commands: &mut Vec<Command<Message>>) {
+        self.notifications.retain(|notification| {
+            notification.level == NotificationLevel::Error || notification.created.elapsed() < NOTIFICATION_TIMEOUT
+        });
+        self.request_folding_ranges(commands);
+        let Some((state, edited)) = self.last_edit.clone() else {
+            return
+        };
+        if state.is_saved {
+            return
+        }
+        // The buffer is snapshotted until it's saved, an autosave may still fail
+        let is_recent = self.last_snapshot
+            .as_ref()
+            .is_some_and(|(snapshot_file, snapshot_version, time)| {
+                (*snapshot_file == state.file && *snapshot_version == state.version) || time.elapsed() < SNAPSHOT_INTERVAL
+            });
+        if !is_recent {
+            self.take_snapshot();
+        }
+        // A new file would open the save dialog, so only files that exist are autosaved. A save that failed isn't
+        // tried again on every tick, only once the buffer was edited again
+        let is_tried = self.autosaved.as_ref() == Some(&state);
+        if self.settings.autosave && state.file.is_some() && edited.elapsed() >= AUTOSAVE_DELAY && !is_tried {
+            self.save(commands);
+            self.autosaved = Some(state);
+        }
+    }
+
+    /**
+     * Asks a server of the open file's language for its folding ranges, once the file changed since they were
+     * last asked for.
+     */
+    fn request_folding_ranges(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let textbox = self.text_box.as_ref().filter(|textbox| !textbox.buffer().is_large())?;
+        let file = textbox.file()?;
+        let request = (file.clone(), textbox.buffer().buffer().version());
+        if self.folding_request.as_ref() == Some(&request) {
+            return None
+        }
+        let language = language_id(Path::new(&file))?;
+        let sender = self.lsp
+            .servers_for(language)
+            .filter(|server| server.connection.provides_folding_ranges())
+            .find_map(|server| server.connection.as_initialized())?;
+        commands.push(Command::perform(sender.folding_range(file), Message::Done));
+        self.folding_request = Some(request);
+        Some(())
+    }
+
+    /**
+     * Grows the selection to the next node of the syntax tree. A server of the file's language is asked for the
+     * ranges around the selection when the tree can't grow it.
+     */
+    fn expand_selection(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let textbox = self.can_edit_textbox()?;
+        if textbox.expand_selection() {
+            return Some(())
+        }
+        let textbox = self.text_box.as_ref()?;
+        let file = textbox.file()?;
+        let buffer = textbox.buffer();
+        let start = *buffer.selection.correct_position().start();
+        let server = self.lsp
+            .servers_for(language_id(Path::new(&file))?)
+            .find(|server| server.connection.provides_selection_ranges() && server.connection.as_initialized().is_some())?;
+        let position = buffer.buffer().to_lsp_position(&start, server.connection.position_encoding());
+        let sender = server.connection.as_initialized()?;
+        self.selection_request = Some((file.clone(), buffer.buffer().version()));
+        commands.push(Command::perform(sender.selection_range(file, position), Message::Done));
+        Some(())
+    }
+
+    /**
+     * Grows the selection to the ranges sent by the server, unless the buffer was edited or another file was
+     * opened since they were asked for.
+     */
+    fn selection_ranges_answered(&mut self, id: usize, ranges: Vec<lsp_types::SelectionRange>) -> Option<()> {
+        let request = self.selection_request.take()?;
+        let encoding = self.lsp.get(id)?.position_encoding();
+        let textbox = self.text_box.as_mut()?;
+        if (textbox.file()?, textbox.buffer().buffer().version()) != request {
+            return None
+        }
+        textbox.expand_to_server_ranges(ranges, encoding);
+        Some(())
+    }
+
+    /**
+     * Folds the ranges sent by the server, unless the buffer was edited or another file was opened since they
+     * were asked for.
+     */
+    fn folding_ranges_answered(&mut self, ranges: Vec<lsp_types::FoldingRange>) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let current = (textbox.file()?, textbox.buffer().buffer().version());
+        if self.folding_request.as_ref() != Some(&current) {
+            return None
+        }
+        textbox.set_server_folds(ranges.into_iter().map(FoldRange::from).collect());
+        Some(())
+    }
+
+    /**
+     * Writes the content of the open buffer to the recovery folder, if it has unsaved changes.
+     */
+    fn take_snapshot(&mut self) {
+        let (Some(recovery), Some(textbox)) = (self.recovery.as_ref(), self.text_box.as_ref()) else {
+            return
+        };
+        if textbox.is_saved() {
+            return
+        }
+        let file = textbox.file();
+        if let Err(e) = recovery.snapshot(file.as_deref().map(Path::new), textbox.buffer().get_string()) {
+            eprintln!("Failed to snapshot the unsaved changes: {}", e);
+        }
+        self.last_snapshot = Some((file, textbox.buffer().buffer().version(), Instant::now()));
+    }
+
+    /**
+     * Keeps the unsaved changes of the open buffer when the editor quits. The session keeps them if there is a
+     * workspace, otherwise the snapshot is kept for the next start.
+     */
+    fn keep_unsaved_changes(&mut self) {
+        if self.workspace.is_none() || self.session_store.is_none() {
+            self.take_snapshot();
+            return
+        }
+        if let (Some(recovery), Some(textbox)) = (self.recovery.as_ref(), self.text_box.as_ref()) {
+            recovery.remove(textbox.file().as_deref().map(Path::new));
+        }
+    }
+
+    /**
+     * Offers the snapshots that were left behind by a crash. Snapshots that match the file on disk are dropped.
+     */
+    fn offer_recovery(&mut self) {
+        let Some(recovery) = self.recovery.as_ref() else {
+            return
+        };
+        let mut snapshots = recovery.snapshots();
+        snapshots.retain(|snapshot| {
+            let is_stale = snapshot.file.is_some() && snapshot.text == snapshot.on_disk();
+            if is_stale {
+                recovery.remove(snapshot.file.as_deref());
+            }
+            !is_stale
+        });
+        if snapshots.is_empty() {
+            return
+        }
+        self.modal = Some(Modal::Recovery { snapshots, selected: 0, on_disk: String::new() });
+        self.select_snapshot(0);
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(false);
+        }
+    }
+
+    fn select_snapshot(&mut self, index: usize) {
+        if let Some(Modal::Recovery { snapshots, selected, on_disk }) = self.modal.as_mut() {
+            if let Some(snapshot) = snapshots.get(index) {
+                *selected = index;
+                *on_disk = snapshot.on_disk();
+            }
+        }
+    }
+
+    /**
+     * Takes the selected snapshot out of the recovery modal, closing it once there are no snapshots left.
+     */
+    fn take_selected_snapshot(&mut self) -> Option<Snapshot> {
+        let Some(Modal::Recovery { snapshots, selected, .. }) = self.modal.as_mut() else {
+            return None
+        };
+        if *selected >= snapshots.len() {
+            return None
+        }
+        let snapshot = snapshots.remove(*selected);
+        if let Some(recovery) = self.recovery.as_ref() {
+            recovery.remove(snapshot.file.as_deref());
+        }
+        if snapshots.is_empty() {
+            self.modal = None;
+        } else {
+            let index = (*selected).min(snapshots.len() - 1);
+            self.select_snapshot(index);
+        }
+        Some(snapshot)
+    }
+
+    /**
+     * Opens the selected snapshot as an unsaved buffer of its file.
+     */
+    fn recover(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let snapshot = self.take_selected_snapshot()?;
+        let document = Document::recovered(snapshot.file.as_deref().and_then(Path::to_str), &snapshot.text);
+        let old_file = self.text_box.as_ref().and_then(|textbox| textbox.file());
+        self.set_file(document, old_file, commands);
+        // The modal may still hold other snapshots
+        let has_modal = self.modal.is_some();
+        self.text_box.as_mut()?.set_focus(!has_modal);
+        Some(())
+    }
+
+    fn discard_snapshot(&mut self) -> Option<()> {
+        self.take_selected_snapshot()?;
+        if self.modal.is_none() {
+            self.text_box.as_mut()?.set_focus(true);
+        }
+        Some(())
+    }
+
     fn modal_view<'a> (&self) -> Option<Element<'a, Message, Renderer>>{
-        self.modal.as_ref().map(|value| value.show(&self.file_filter))
+        self.modal.as_ref().map(|value| value.show(&self.modal_filter))
     }
 
     fn is_saved(&self) -> bool {
@@ -609,13 +2195,136 @@ impl Editor {
         true
     }
 
-    fn set_modal(&mut self, file: String) {
-        self.modal = Some(Modal::FileSelector(file.to_owned()));
+    fn open_file_selector(&mut self) {
+        self.modal_filter.clear();
+        self.modal = Some(Modal::FileSelector { matches: Vec::new(), selected: 0, is_indexing: true });
+        self.update_file_selector();
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(false);
+        }
+    }
+
+    /**
+     * Searches the index again for the file selector, once the filter or the index changed.
+     */
+    fn update_file_selector(&mut self) {
+        let Some(Modal::FileSelector { matches, selected, is_indexing }) = self.modal.as_mut() else {
+            return
+        };
+        *matches = self.file_index
+            .as_ref()
+            .map(|index| index.search(&self.modal_filter, SELECTOR_RESULTS))
+            .unwrap_or_default();
+        *selected = (*selected).min(matches.len().saturating_sub(1));
+        *is_indexing = self.file_index.is_none();
+    }
+
+    /**
+     * Moves through the file selector with the arrow keys and opens the selected file with Enter.
+     * 
+     * Returns true if the key was used by the file selector.
+     */
+    fn file_selector_key(&mut self, key: Key, commands: &mut Vec<Command<Message>>) -> bool {
+        let Some(Modal::FileSelector { matches, selected, .. }) = self.modal.as_mut() else {
+            return false
+        };
+        match key {
+            Key::KeyUp => *selected = selected.saturating_sub(1),
+            Key::KeyDown => *selected = (*selected + 1).min(matches.len().saturating_sub(1)),
+            Key::KeyEnter => {
+                if let Some(file) = matches.get(*selected) {
+                    let file = file.path.to_string_lossy().into_owned();
+                    self.change_file(file, commands);
+                }
+                return true
+            }
+            _ => return false
+        }
+        // Keeps the selected file in view
+        let offset = if matches.len() > 1 { *selected as f32 / (matches.len() - 1) as f32 } else { 0.0 };
+        commands.push(scrollable::snap_to(file_list_id(), RelativeOffset { x: 0.0, y: offset }));
+        true
+    }
+
+    fn open_clipboard_history(&mut self) {
+        let clips = self.clipboard.clips().cloned().collect();
+        self.modal = Some(Modal::ClipboardHistory { clips, selected: 0 });
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(false);
+        }
+    }
+
+    fn clipboard_history_key(&mut self, key: Key, commands: &mut Vec<Command<Message>>) -> bool {
+        let Some(Modal::ClipboardHistory { clips, selected }) = self.modal.as_mut() else {
+            return false
+        };
+        match key {
+            Key::KeyUp => *selected = selected.saturating_sub(1),
+            Key::KeyDown => *selected = (*selected + 1).min(clips.len().saturating_sub(1)),
+            Key::KeyEnter => {
+                let index = *selected;
+                self.paste_clip(index, commands);
+                return true
+            }
+            _ => return false
+        }
+        // Keeps the selected copy in view
+        let offset = if clips.len() > 1 { *selected as f32 / (clips.len() - 1) as f32 } else { 0.0 };
+        commands.push(scrollable::snap_to(clip_list_id(), RelativeOffset { x: 0.0, y: offset }));
+        true
+    }
+
+    /**
+     * Pastes the copy from the clipboard history. It becomes the most recent copy, on the clipboard of the system
+     * too.
+     */
+    fn paste_clip(&mut self, index: usize, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let Some(Modal::ClipboardHistory { clips, .. }) = self.modal.take() else {
+            return None
+        };
+        let clip = clips.get(index)?.clone();
+        commands.push(iced::clipboard::write::<Message>(clip.text.clone()));
+        self.clipboard.push(clip.clone());
+        let textbox = self.text_box.as_mut()?;
+        textbox.set_focus(true);
+        textbox.paste_text(clip.text, clip.linewise);
+        Some(())
+    }
+
+    /**
+     * Walks the workspace in the background. The file selector uses the index once it's built.
+     */
+    fn build_file_index(&self, commands: &mut Vec<Command<Message>>) {
+        let Some(root) = self.workspace.clone() else {
+            return
+        };
+        let build = smol::unblock(move || FileIndex::build(root, &DEFAULT_EXCLUDES));
+        commands.push(Command::perform(build, |index| Message::IndexBuilt(Box::new(index))));
+    }
+
+    /**
+     * Opens the inspector with the messages sent to and received from the language server.
+     */
+    fn set_inspector_modal(&mut self) {
+        self.modal_filter.clear();
+        self.modal = Some(Modal::LspInspector { recorder: self.lsp_traffic.clone(), selected: None });
         if let Some(textbox) = self.text_box.as_mut() {
             textbox.set_focus(false);
         }
     }
 
+    fn export_lsp_traffic(&self) {
+        let mut dialog = FileDialog::new().set_file_name("lsp-traffic.json");
+        if let Some(workspace) = self.workspace() {
+            dialog = dialog.set_directory(workspace);
+        }
+        if let Some(path) = dialog.save_file() {
+            if let Err(e) = self.lsp_traffic.export(&path) {
+                eprintln!("Failed to export the LSP traffic: {}", e);
+            }
+        }
+    }
+
     fn open_folder(&self) -> Option<PathBuf> {
         let file = FileDialog::new()
             // .set_directory("/")
@@ -639,11 +2348,10 @@ impl Editor {
     }
 
     fn change_file(&mut self, file: String, commands: &mut Vec<Command<Message>>) {
-        let old_file = match self.text_box.as_ref() {
-            Some(text_box) => text_box.file().map(|file| file.to_owned()),
-            None => None
-        };
-        self.open(&file, old_file, commands);
+        self.open(&file, None, None, commands);
+        if let Some(index) = self.file_index.as_mut() {
+            index.opened(Path::new(&file));
+        }
         self.modal = None;
     }
 
@@ -654,31 +2362,123 @@ impl Editor {
        None
     }
     
+    /**
+     * Saves the session of the current workspace, then opens the workspace as it was left.
+     */
+    fn open_workspace(&mut self, workspace: Option<PathBuf>, commands: &mut Vec<Command<Message>>) {
+        self.save_session();
+        self.set_workspace(workspace, commands);
+        self.restore_session(commands);
+    }
+
+    /**
+     * Returns the state of the workspace, with the unsaved content of the open document written to the recovery area.
+     */
+    fn current_session(&self, workspace: &Path, store: &SessionStore) -> Session {
+        let mut session = Session {
+            documents: Vec::new(),
+            show_explorer: self.show_explorer,
+            expanded_folders: self.file_tree.as_ref().map(FileTree::expanded_folders).unwrap_or_default(),
+            recent_files: self.file_index.as_ref().map(|index| index.recent().cloned().collect()).unwrap_or_default(),
+        };
+        let Some(textbox) = self.text_box.as_ref() else {
+            return session
+        };
+        let file = textbox.file().map(PathBuf::from);
+        // An empty new file isn't worth restoring
+        if file.is_none() && textbox.is_saved() {
+            return session
+        }
+        let recovery = if textbox.is_saved() {
+            None
+        } else {
+            store
+                .write_recovery(workspace, file.as_deref(), &textbox.buffer().get_string())
+                .map_err(|e| eprintln!("Failed to keep the unsaved changes: {}", e))
+                .ok()
+        };
+        let buffer = textbox.buffer();
+        session.documents.push(DocumentState {
+            file,
+            cursor: buffer.cursor.0,
+            selection: (*buffer.selection.start(), *buffer.selection.end()),
+            scroll: buffer.window.offset(),
+            recovery,
+        });
+        session
+    }
+
+    /**
+     * Saves the session of the current workspace, if there is one.
+     */
+    fn save_session(&mut self) {
+        let (Some(workspace), Some(store)) = (self.workspace.as_ref(), self.session_store.as_ref()) else {
+            return
+        };
+        let session = self.current_session(workspace, store);
+        let workspace = workspace.clone();
+        if let Some(store) = self.session_store.as_mut() {
+            if let Err(e) = store.save_session(workspace, session) {
+                eprintln!("Failed to save the session: {}", e);
+            }
+        }
+    }
+
+    /**
+     * Opens the documents of the workspace's last session, along with their cursor, selection and scroll position.
+     */
+    fn restore_session(&mut self, commands: &mut Vec<Command<Message>>) -> Option<()> {
+        let workspace = self.workspace.clone()?;
+        let store = self.session_store.as_mut()?;
+        if let Err(e) = store.workspace_opened(workspace.clone()) {
+            eprintln!("Failed to save the recent workspaces: {}", e);
+        }
+        let session = store.session(&workspace)?.clone();
+
+        self.show_explorer = session.show_explorer;
+        if let Some(tree) = self.file_tree.as_mut() {
+            for folder in session.expanded_folders.iter() {
+                tree.expand(folder);
+            }
+        }
+
+        let state = session.documents.first()?;
+        let file = state.file.as_ref().and_then(|file| file.to_str());
+        let recovered = state.recovery.as_ref().and_then(|recovery| fs::read_to_string(recovery).ok());
+        self.modal = None;
+        let document = match (file, recovered) {
+            (file, Some(text)) => Document::recovered(file, &text),
+            (Some(file), None) => {
+                self.open(file, None, Some(state.clone()), commands);
+                return Some(())
+            }
+            (None, None) => return None,
+        };
+        let old_file = self.text_box.as_ref().and_then(|textbox| textbox.file());
+        self.set_file(document, old_file, commands);
+
+        let textbox = self.text_box.as_mut()?;
+        textbox
+            .set_selection(state.selection.0)
+            .set_selection_end(state.selection.1)
+            .set_curor(state.cursor);
+        textbox.set_offset(state.scroll.0, state.scroll.1);
+        textbox.set_focus(true);
+        Some(())
+    }
+
     fn set_workspace(&mut self, file: Option<PathBuf>, commands: &mut Vec<Command<Message>>) {
         self.workspace = file;
-        if let Some(file) = &self.workspace {
-
-            let lsp = LspConnection::new(file);
-            let client = match lsp {
-                Ok(lsp) => {
-                    let init_params = lsp.init_params();
-                    if let LspClient::Uninitialized(sender) = lsp.new_sender() {
-                        let fut = sender.initialize(init_params);
-                        commands.push(Command::perform(fut, Message::Done));
-                    }
-                    Some(lsp)
-                },
-                Err(_e) => {
-                    eprintln!("Failed to initialize the client");
-                    None
-                }
-            };
-            self.client_id += 1;
-            self.lsp = client;
-            if self.text_box.is_none() {
-                self.set_modal(file.to_str().unwrap().to_owned());
-            }
-            
+        self.file_tree = self.workspace.clone().map(FileTree::new);
+        self.explorer_selected = None;
+        self.file_index = None;
+        self.build_file_index(commands);
+        self.lsp.set_workspace(self.workspace.clone());
+        // The servers are started again for the open document
+        let open_file = self.text_box.as_ref().and_then(|textbox| textbox.file());
+        self.switch_document(None, open_file, commands);
+        if self.workspace.is_some() && self.text_box.is_none() {
+            self.open_file_selector();
         }
         if self.text_box.is_none() {
             self.new_file(commands);
@@ -687,46 +2487,42 @@ impl Editor {
     }
 
     fn text_box_view<'a>(&'a self, text_box: &'a Textbox) -> Element<'a, Message, Renderer>  {
-        let id = iced::widget::scrollable::Id::new("1");
-        let scroll_properties = Properties::default();
-
-        let second_scroll_id = iced::widget::scrollable::Id::new("2");
+        let mut content = Row::new().spacing(5).height(Length::Fill);
+        if let Some(tree) = self.file_tree.as_ref().filter(|_| self.show_explorer) {
+            let active = text_box.file();
+            content = content.push(file_explorer(tree, active.as_deref().map(Path::new), self.explorer_selected.as_deref()));
+        }
+        let focused = self.panes.focused();
+        let panes = PaneGrid::new(self.panes.state(), |pane, editor_pane, _| {
+            let pane_text_box = if pane == focused { Some(text_box) } else { editor_pane.text_box() };
+            let element = match pane_text_box {
+                Some(pane_text_box) => self.pane_view(editor_pane, pane_text_box),
+                None => column!().into(),
+            };
+            // A lone pane isn't outlined
+            let theme = Box::new(PaneContainer { focused: pane == focused && self.panes.len() > 1 });
+            pane_grid::Content::new(element.map(move |message| Message::Pane(pane, Box::new(message))))
+            .style(iced::theme::Container::Custom(theme))
+        })
+        .spacing(5.0)
+        .on_resize(10.0, Message::ResizePane);
         container(
             layout::layout(
-                row![
-                    line_number(
-                        text_box.buffer().len(),
-                        text_box.get_font_size(),
-                        text_box.height() + text_box.window_height(),
-                        second_scroll_id
-                    ),
-                    ViewPort::new(
-                        scrollable(TextboxContainer::new(
-                            text_box.view(),
-                            text_box,
-                            text_box.longest_line(),
-                            text_box.floating_element(),
-                            text_box.get_font_size(),
-                            text_box.get_font()
-                        ))
-                        .id(id)
-                        .width(iced::Length::Fill)
-                        .on_scroll(|viewport| {
-                            Message::Offset(viewport.absolute_offset().x, viewport.absolute_offset().y)
-                        })
-                        .direction(scrollable::Direction::Both {
-                            vertical: scroll_properties,
-                            horizontal: scroll_properties
-                        })
-                        .into(),
-                        self.modal_view(),
-                        &text_box.buffer().window,
-                    )
-                ]
-                .spacing(5)
+                column!(
+                if text_box.buffer().is_large() { large_file_banner() } else { column!().into() },
+                content.push(panes),
+                status_bar(
+                    text_box.buffer().cursor.0,
+                    text_box.buffer().format(),
+                    text_box.buffer().has_final_newline(),
+                    self.settings,
+                    self.keymap_status(),
+                ))
                 .into(), 
                 self.modal_view(),
-                self.is_saved()
+                notifications(&self.notifications),
+                self.is_saved(),
+                self.settings.autosave
             )
 
         )
@@ -735,6 +2531,47 @@ impl Editor {
         .into()
     }
 
+    /**
+     * The gutter and the text of a pane, each pane scrolls on its own.
+     */
+    fn pane_view<'a>(&'a self, editor_pane: &EditorPane, text_box: &'a Textbox) -> Element<'a, Message, Renderer> {
+        let scroll_properties = Properties::default();
+        row!(
+            line_number(
+                text_box.buffer().visible_rows().start,
+                text_box.buffer().gutter_rows(),
+                text_box.get_font_size(),
+                text_box.height() + text_box.window_height(),
+                editor_pane.gutter_scroll_id()
+            ),
+            ViewPort::new(
+                scrollable(TextboxContainer::new(
+                    text_box.view(),
+                    text_box,
+                    text_box.longest_line(),
+                    text_box.floating_element(),
+                    text_box.get_font_size(),
+                    text_box.get_font()
+                ))
+                .id(editor_pane.text_scroll_id())
+                .width(iced::Length::Fill)
+                .on_scroll(|viewport| {
+                    Message::Offset(viewport.absolute_offset().x, viewport.absolute_offset().y)
+                })
+                .direction(scrollable::Direction::Both {
+                    vertical: scroll_properties,
+                    horizontal: scroll_properties
+                })
+                .into(),
+                self.modal_view(),
+                &text_box.buffer().window,
+            )
+        )
+        .spacing(5)
+        .height(Length::Fill)
+        .into()
+    }
+
     fn no_file_view<'a>(&self) -> Element<'a, Message, Renderer> {
         
         layout::layout(
@@ -749,19 +2586,21 @@ impl Editor {
             .height(Length::Fill)
             .into(),
             self.modal_view(),
-            self.is_saved()
+            notifications(&self.notifications),
+            self.is_saved(),
+            self.settings.autosave
         )
     }
 
-    fn correct_scroll(&self, commands: &mut Vec<Command<Message>>, window: VirtualWindow) {
-        let id = iced::widget::scrollable::Id::new("1");
+    fn correct_scroll(&self, pane: &EditorPane, commands: &mut Vec<Command<Message>>, window: VirtualWindow) {
+        let id = pane.text_scroll_id();
         let scroll_command: Command<Message> = iced::widget::scrollable::scroll_to(
             id,
             window.into()
         );
         commands.push(scroll_command);
 
-        let id = iced::widget::scrollable::Id::new("2");
+        let id = pane.gutter_scroll_id();
         let scroll_command: Command<Message> = iced::widget::scrollable::scroll_to(
             id,
             window.into()
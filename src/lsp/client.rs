@@ -8,6 +8,7 @@ use lsp::WorkspaceFolder;
 use lsp_types as lsp;
 
 
+use futures::{AsyncRead, AsyncWrite};
 use serde_json::json;
 use smol::{
     io::{BufReader, BufWriter},
@@ -16,7 +17,7 @@ use smol::{
 };
 
 use super::{
-    error::{LspClientError, LspClientResult}, transport::{start_transport, InitializedSender, MessageReciever, MessageSender, UninitializedSender}
+    encoding::PositionEncoding, error::{LspClientError, LspClientResult}, inspector::TrafficRecorder, manager::ServerConfig, watched_files::WatchedFiles, transport::{start_transport, InitializedSender, MessageReciever, MessageSender, UninitializedSender}
 };
 
 #[derive(Clone, Default)]
@@ -61,18 +62,29 @@ struct Tasks {
 }
 
 pub struct LspConnection {
-    _process: Child,
+    /// The language server process, if the server isn't running in process.
+    _process: Option<Child>,
     receiver: MessageReciever,
     sender: LspClient,
     _tasks: Tasks,
     file_name:  String,
-    file_path: String
+    file_path: String,
+    /// What the server supports, known once it answered the initialize request.
+    capabilities: Option<lsp::ServerCapabilities>,
+    position_encoding: PositionEncoding,
+    /// The files the server registered to be notified about.
+    watched_files: WatchedFiles,
 }
 
 impl LspConnection {
-    pub fn new(file: &Path) -> LspClientResult<Self> {
-
-        let process: Result<smol::process::Child, Error> = Command::new("rust-analyzer")
+    /**
+     * Starts the server described by the config for the workspace. The recorder, if there is one, keeps the messages sent through the connection.
+     */
+    pub fn new(file: &Path, config: &ServerConfig, recorder: Option<TrafficRecorder>) -> LspClientResult<Self> {
+
+        let process: Result<smol::process::Child, Error> = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(file)
             // .env("RA_LOG", "info")
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -89,10 +101,24 @@ impl LspConnection {
             }
         };
 
-        let reader = BufReader::new(process.stdout.take().expect("Failed to open stdout"));
-        let writer = BufWriter::new(process.stdin.take().expect("Failed to open stdin"));
-        let stderr = BufReader::new(process.stderr.take().expect("Failed to open stderr"));
+        let reader = process.stdout.take().expect("Failed to open stdout");
+        let writer = process.stdin.take().expect("Failed to open stdin");
+        let stderr = process.stderr.take().expect("Failed to open stderr");
+
+        let mut connection = Self::from_streams(file, reader, writer, stderr, recorder)?;
+        connection._process = Some(process);
+        Ok(connection)
+    }
 
+    /**
+     * Creates a connection over any stream. Used to talk with servers that are not a child process.
+     */
+    pub fn from_streams<R, W, E>(file: &Path, reader: R, writer: W, stderr: E, recorder: Option<TrafficRecorder>) -> LspClientResult<Self>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+        E: AsyncRead + Unpin + Send + 'static,
+    {
         let file_path = match file.to_str() {
             Some(value) => value,
             None => return Err(LspClientError::FailedInitiation("Invalid path".to_owned()))
@@ -102,13 +128,16 @@ impl LspConnection {
         // 1, change the initialize method of the transport to NOT be static.
         // 2, Create a seprate client that is only for the start state, we won't pass the transport
         // other information there.
-        let transport = start_transport(reader, writer, stderr);
+        let transport = start_transport(BufReader::new(reader), BufWriter::new(writer), BufReader::new(stderr), recorder);
         Ok(Self {
-            _process: process,
+            _process: None,
             file_name: filename,
             file_path: file_path.to_owned(),
-            sender: LspClient::Uninitialized(MessageSender::from(transport.sender)),
-            receiver: MessageReciever::from(transport.receiver),
+            sender: LspClient::Uninitialized(MessageSender::new(transport.sender, transport.pending.clone())),
+            receiver: MessageReciever::new(transport.receiver, transport.pending),
+            capabilities: None,
+            position_encoding: PositionEncoding::default(),
+            watched_files: WatchedFiles::default(),
             _tasks: Tasks {
                 _reciever_task: transport.reading_task,
                 _writer_task: transport.writing_task
@@ -141,6 +170,92 @@ impl LspConnection {
         self.sender.as_uninitialized()
     }
 
+    pub fn capabilities(&self) -> Option<&lsp::ServerCapabilities> {
+        self.capabilities.as_ref()
+    }
+
+    /**
+     * Stores the capabilities sent in the `InitializeResult`, along with the position encoding the server picked.
+     */
+    pub fn set_capabilities(&mut self, capabilities: lsp::ServerCapabilities) {
+        self.position_encoding = PositionEncoding::negotiated(&capabilities);
+        self.capabilities = Some(capabilities)
+    }
+
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.position_encoding
+    }
+
+    /**
+     * How the server wants to be told about edits. Servers that haven't been initialized don't get any.
+     */
+    pub fn sync_kind(&self) -> lsp::TextDocumentSyncKind {
+        match self.text_document_sync() {
+            Some(lsp::TextDocumentSyncCapability::Kind(kind)) => *kind,
+            Some(lsp::TextDocumentSyncCapability::Options(options)) => options.change.unwrap_or(lsp::TextDocumentSyncKind::NONE),
+            None => lsp::TextDocumentSyncKind::NONE,
+        }
+    }
+
+    /**
+     * Returns true if the server wants the `didOpen` and `didClose` notifications.
+     */
+    pub fn sends_open_close(&self) -> bool {
+        match self.text_document_sync() {
+            Some(lsp::TextDocumentSyncCapability::Kind(kind)) => *kind != lsp::TextDocumentSyncKind::NONE,
+            Some(lsp::TextDocumentSyncCapability::Options(options)) => options.open_close.unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /**
+     * Returns true if the server wants the `didSave` notification.
+     */
+    pub fn sends_save(&self) -> bool {
+        match self.text_document_sync() {
+            Some(lsp::TextDocumentSyncCapability::Kind(kind)) => *kind != lsp::TextDocumentSyncKind::NONE,
+            Some(lsp::TextDocumentSyncCapability::Options(options)) => !matches!(
+                options.save,
+                None | Some(lsp::TextDocumentSyncSaveOptions::Supported(false))
+            ),
+            None => false,
+        }
+    }
+
+    /**
+     * Returns true if the server answers `textDocument/foldingRange`.
+     */
+    pub fn provides_folding_ranges(&self) -> bool {
+        match self.capabilities.as_ref().and_then(|capabilities| capabilities.folding_range_provider.as_ref()) {
+            Some(lsp::FoldingRangeProviderCapability::Simple(provides)) => *provides,
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /**
+     * Returns true if the server answers `textDocument/selectionRange`.
+     */
+    pub fn provides_selection_ranges(&self) -> bool {
+        match self.capabilities.as_ref().and_then(|capabilities| capabilities.selection_range_provider.as_ref()) {
+            Some(lsp::SelectionRangeProviderCapability::Simple(provides)) => *provides,
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn watched_files(&self) -> &WatchedFiles {
+        &self.watched_files
+    }
+
+    pub fn watched_files_mut(&mut self) -> &mut WatchedFiles {
+        &mut self.watched_files
+    }
+
+    fn text_document_sync(&self) -> Option<&lsp::TextDocumentSyncCapability> {
+        self.capabilities.as_ref()?.text_document_sync.as_ref()
+    }
+
 }
 
 pub fn file_path(relative_path: &str) -> String {
@@ -212,6 +327,12 @@ fn init_params(file: String, name: String) -> lsp::InitializeParams {
                     dynamic_registration: Some(true),
                     relative_pattern_support: Some(false),
                 }),
+                file_operations: Some(lsp::WorkspaceFileOperationsClientCapabilities {
+                    will_rename: Some(true),
+                    did_rename: Some(true),
+                    did_delete: Some(true),
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             text_document: Some(lsp::TextDocumentClientCapabilities {
@@ -295,6 +416,15 @@ fn init_params(file: String, name: String) -> lsp::InitializeParams {
                     dynamic_registration: Some(false),
                     resolve_support: None,
                 }),
+                selection_range: Some(lsp::SelectionRangeClientCapabilities {
+                    dynamic_registration: Some(false),
+                }),
+                // The gutter folds whole lines, the columns of the ranges aren't used
+                folding_range: Some(lsp::FoldingRangeClientCapabilities {
+                    dynamic_registration: Some(false),
+                    line_folding_only: Some(true),
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             window: Some(lsp::WindowClientCapabilities {
@@ -0,0 +1,157 @@
+use lsp_types::{PositionEncodingKind, ServerCapabilities};
+use ropey::RopeSlice;
+
+/**
+ * The unit used to count the `character` of an LSP position.
+ *
+ * The client offers every encoding when initializing, the server picks one in its capabilities.
+ * Servers that don't pick one use UTF-16, as required by the specification.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    /// Bytes
+    Utf8,
+    /// Code units, characters outside the BMP count as two
+    #[default]
+    Utf16,
+    /// Characters
+    Utf32,
+}
+
+impl PositionEncoding {
+    pub fn negotiated(capabilities: &ServerCapabilities) -> Self {
+        match &capabilities.position_encoding {
+            Some(kind) if *kind == PositionEncodingKind::UTF8 => Self::Utf8,
+            Some(kind) if *kind == PositionEncodingKind::UTF32 => Self::Utf32,
+            _ => Self::Utf16,
+        }
+    }
+
+    pub fn kind(&self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    fn char_len(&self, character: char) -> usize {
+        match self {
+            Self::Utf8 => character.len_utf8(),
+            Self::Utf16 => character.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+
+    /**
+     * The length of the text in the units of the encoding.
+     */
+    pub fn len(&self, text: RopeSlice) -> usize {
+        match self {
+            Self::Utf8 => text.len_bytes(),
+            Self::Utf16 => text.len_utf16_cu(),
+            Self::Utf32 => text.len_chars(),
+        }
+    }
+
+    /**
+     * Converts a column in the units of the encoding into the char offset within the line.
+     *
+     * A column in the middle of a character is moved to the start of the character, and a column
+     * past the end of the line is moved to the end of the line.
+     */
+    pub fn char_offset(&self, line: RopeSlice, column: usize) -> usize {
+        let mut units = 0;
+        for (offset, character) in line.chars().enumerate() {
+            if character == '\n' || character == '\r' {
+                return offset
+            }
+            units += self.char_len(character);
+            if units > column {
+                return offset
+            }
+        }
+        line.len_chars()
+    }
+}
+
+/**
+ * A point of the text and the text of its line before it.
+ *
+ * The prefix is enough to express the point in any encoding, so it can be taken before an edit and
+ * sent after it to servers that use different encodings.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinePrefix {
+    pub line: usize,
+    pub prefix: String,
+}
+
+impl LinePrefix {
+    pub fn to_lsp(&self, encoding: PositionEncoding) -> lsp_types::Position {
+        lsp_types::Position {
+            line: self.line as u32,
+            character: encoding.len(RopeSlice::from(self.prefix.as_str())) as u32,
+        }
+    }
+}
+
+/**
+ * The range of text replaced by an edit, taken before the edit was applied.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeRange {
+    pub start: LinePrefix,
+    pub end: LinePrefix,
+}
+
+impl ChangeRange {
+    pub fn to_lsp(&self, encoding: PositionEncoding) -> lsp_types::Range {
+        lsp_types::Range {
+            start: self.start.to_lsp(encoding),
+            end: self.end.to_lsp(encoding),
+        }
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use lsp_types::{PositionEncodingKind, ServerCapabilities};
+    use pretty_assertions::assert_eq;
+    use ropey::RopeSlice;
+
+    use super::{LinePrefix, PositionEncoding};
+
+    #[test]
+    fn test_negotiated_defaults_to_utf16() {
+        let mut capabilities = ServerCapabilities::default();
+        assert_eq!(PositionEncoding::negotiated(&capabilities), PositionEncoding::Utf16);
+        capabilities.position_encoding = Some(PositionEncodingKind::UTF8);
+        assert_eq!(PositionEncoding::negotiated(&capabilities), PositionEncoding::Utf8);
+        capabilities.position_encoding = Some(PositionEncodingKind::new("utf-7"));
+        assert_eq!(PositionEncoding::negotiated(&capabilities), PositionEncoding::Utf16);
+    }
+
+    #[test]
+    fn test_lengths() {
+        // `é` is two bytes, `𝄞` is four bytes and two UTF-16 code units
+        let prefix = LinePrefix { line: 3, prefix: "é𝄞a".to_owned() };
+        assert_eq!(prefix.to_lsp(PositionEncoding::Utf8).character, 7);
+        assert_eq!(prefix.to_lsp(PositionEncoding::Utf16).character, 4);
+        assert_eq!(prefix.to_lsp(PositionEncoding::Utf32).character, 3);
+        assert_eq!(prefix.to_lsp(PositionEncoding::Utf32).line, 3);
+    }
+
+    #[test]
+    fn test_char_offset() {
+        let line = RopeSlice::from("é𝄞a\n");
+        assert_eq!(PositionEncoding::Utf8.char_offset(line, 2), 1);
+        // In the middle of `𝄞`
+        assert_eq!(PositionEncoding::Utf8.char_offset(line, 4), 1);
+        assert_eq!(PositionEncoding::Utf16.char_offset(line, 3), 2);
+        assert_eq!(PositionEncoding::Utf16.char_offset(line, 2), 1);
+        assert_eq!(PositionEncoding::Utf32.char_offset(line, 2), 2);
+        // Past the end of the line, before the line break
+        assert_eq!(PositionEncoding::Utf32.char_offset(line, 10), 3);
+    }
+}
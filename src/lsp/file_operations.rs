@@ -0,0 +1,162 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use globset::GlobBuilder;
+use lsp_types::{DocumentChangeOperation, DocumentChanges, FileOperationPatternKind, FileRename, Url, FileOperationRegistrationOptions, OneOf, ServerCapabilities, TextEdit, WorkspaceEdit};
+
+/**
+ * The file operations the editor tells the servers about.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOperation {
+    WillRename,
+    DidRename,
+    DidDelete,
+}
+
+/**
+ * A rename waiting for the servers to answer `workspace/willRenameFiles`. The file is renamed once
+ * every server answered, or after a timeout.
+ */
+#[derive(Debug)]
+pub struct PendingRename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub is_dir: bool,
+    /// The ids of the servers that haven't answered yet.
+    pub waiting: HashSet<usize>,
+}
+
+impl PendingRename {
+    pub fn files(&self) -> Vec<FileRename> {
+        let (Ok(old_uri), Ok(new_uri)) = (Url::from_file_path(&self.from), Url::from_file_path(&self.to)) else {
+            return Vec::new()
+        };
+        vec![FileRename { old_uri: old_uri.to_string(), new_uri: new_uri.to_string() }]
+    }
+}
+
+/**
+ * Returns true if the server asked to be told about the operation on the path.
+ */
+pub fn is_interested(capabilities: &ServerCapabilities, operation: FileOperation, path: &Path, is_dir: bool) -> bool {
+    let Some(operations) = capabilities.workspace.as_ref().and_then(|workspace| workspace.file_operations.as_ref()) else {
+        return false
+    };
+    let options = match operation {
+        FileOperation::WillRename => operations.will_rename.as_ref(),
+        FileOperation::DidRename => operations.did_rename.as_ref(),
+        FileOperation::DidDelete => operations.did_delete.as_ref(),
+    };
+    options.is_some_and(|options| matches(options, path, is_dir))
+}
+
+fn matches(options: &FileOperationRegistrationOptions, path: &Path, is_dir: bool) -> bool {
+    options.filters.iter().any(|filter| {
+        if filter.scheme.as_deref().is_some_and(|scheme| scheme != "file") {
+            return false
+        }
+        let kind_matches = match filter.pattern.matches {
+            Some(FileOperationPatternKind::File) => !is_dir,
+            Some(FileOperationPatternKind::Folder) => is_dir,
+            None => true,
+        };
+        let ignore_case = filter.pattern.options.as_ref().and_then(|options| options.ignore_case).unwrap_or(false);
+        let glob = GlobBuilder::new(&filter.pattern.glob).case_insensitive(ignore_case).build();
+        match glob {
+            Ok(glob) => kind_matches && glob.compile_matcher().is_match(path),
+            Err(e) => {
+                eprintln!("Invalid glob pattern {}: {}", filter.pattern.glob, e);
+                false
+            }
+        }
+    })
+}
+
+/**
+ * Returns the text edits of the workspace edit for each file.
+ *
+ * Creating, renaming and deleting files through a workspace edit isn't supported yet, those operations are skipped.
+ */
+pub fn text_edits(edit: WorkspaceEdit) -> Vec<(PathBuf, Vec<TextEdit>)> {
+    let mut files = Vec::new();
+    let document_edits = match edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits,
+        Some(DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    for document_edit in document_edits {
+        let edits = document_edit.edits
+            .into_iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit,
+                OneOf::Right(annotated) => annotated.text_edit,
+            })
+            .collect();
+        if let Ok(path) = document_edit.text_document.uri.to_file_path() {
+            files.push((path, edits));
+        }
+    }
+    // `changes` is only used by servers that can't send `documentChanges`
+    for (uri, edits) in edit.changes.unwrap_or_default() {
+        if let Ok(path) = uri.to_file_path() {
+            files.push((path, edits));
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod file_operations_tests {
+    use std::{collections::HashMap, path::{Path, PathBuf}};
+
+    use lsp_types::{FileOperationFilter, FileOperationPattern, FileOperationPatternKind, FileOperationRegistrationOptions, Position, Range, ServerCapabilities, TextEdit, Url, WorkspaceEdit, WorkspaceFileOperationsServerCapabilities, WorkspaceServerCapabilities};
+    use pretty_assertions::assert_eq;
+
+    use super::{is_interested, text_edits, FileOperation};
+
+    #[test]
+    fn test_is_interested() {
+        let options = FileOperationRegistrationOptions {
+            filters: vec![FileOperationFilter {
+                scheme: Some("file".to_owned()),
+                pattern: FileOperationPattern {
+                    glob: "**/*.rs".to_owned(),
+                    matches: Some(FileOperationPatternKind::File),
+                    options: None,
+                },
+            }],
+        };
+        let capabilities = ServerCapabilities {
+            workspace: Some(WorkspaceServerCapabilities {
+                workspace_folders: None,
+                file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                    will_rename: Some(options),
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        };
+
+        assert!(is_interested(&capabilities, FileOperation::WillRename, Path::new("/ws/src/main.rs"), false));
+        assert!(!is_interested(&capabilities, FileOperation::WillRename, Path::new("/ws/src/main.rs"), true));
+        assert!(!is_interested(&capabilities, FileOperation::WillRename, Path::new("/ws/README.md"), false));
+        assert!(!is_interested(&capabilities, FileOperation::DidRename, Path::new("/ws/src/main.rs"), false));
+        assert!(!is_interested(&ServerCapabilities::default(), FileOperation::DidDelete, Path::new("/ws/a.rs"), false));
+    }
+
+    #[test]
+    fn test_text_edits() {
+        let edit = TextEdit::new(Range::new(Position::new(0, 4), Position::new(0, 7)), "bar".to_owned());
+        let mut changes = HashMap::new();
+        changes.insert(Url::parse("file:///ws/src/lib.rs").unwrap(), vec![edit.clone()]);
+        let workspace_edit = WorkspaceEdit { changes: Some(changes), ..Default::default() };
+
+        assert_eq!(text_edits(workspace_edit), vec![(PathBuf::from("/ws/src/lib.rs"), vec![edit])]);
+    }
+}
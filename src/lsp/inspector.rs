@@ -0,0 +1,331 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Error, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+/**
+ * The default amount of messages the recorder keeps before dropping the oldest ones.
+ */
+pub const TRAFFIC_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent from the editor to the language server
+    Outgoing,
+    /// Sent from the language server to the editor
+    Incoming,
+}
+
+impl Direction {
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            Direction::Outgoing => "-->",
+            Direction::Incoming => "<--",
+        }
+    }
+
+    fn opposite(&self) -> Self {
+        match self {
+            Direction::Outgoing => Direction::Incoming,
+            Direction::Incoming => Direction::Outgoing,
+        }
+    }
+}
+
+/**
+ * A single JSON-RPC message that went through the transport.
+ */
+#[derive(Debug, Clone)]
+pub struct TrafficEntry {
+    pub seq: u64,
+    pub direction: Direction,
+    pub timestamp: SystemTime,
+    pub method: Option<String>,
+    pub id: Option<String>,
+    /// Only set on responses, it's the time between the request and its response.
+    pub latency: Option<Duration>,
+    pub body: Value,
+}
+
+impl TrafficEntry {
+    pub fn pretty_body(&self) -> String {
+        serde_json::to_string_pretty(&self.body).unwrap_or_else(|_| self.body.to_string())
+    }
+
+    /**
+     * Formats the timestamp as `HH:MM:SS.mmm` in UTC.
+     */
+    pub fn time(&self) -> String {
+        let since_epoch = self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seconds = since_epoch.as_secs() % 86_400;
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            seconds / 3600,
+            (seconds % 3600) / 60,
+            seconds % 60,
+            since_epoch.subsec_millis()
+        )
+    }
+
+    /**
+     * A one line summary of the message, used as the title of the entry in the inspector.
+     */
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} {} {}", self.time(), self.direction.arrow(), self.method.as_deref().unwrap_or("response"));
+        if let Some(id) = &self.id {
+            summary.push_str(&format!(" #{}", id));
+        }
+        if let Some(latency) = self.latency {
+            summary.push_str(&format!(" ({} ms)", latency.as_millis()));
+        }
+        summary
+    }
+
+    /**
+     * Returns true if the method, the id or the body contains the query. The search is case insensitive.
+     */
+    pub fn matches(&self, query: &str) -> bool {
+        if query.is_empty() {
+            return true
+        }
+        let query = query.to_lowercase();
+        let in_method = self.method.as_ref().is_some_and(|method| method.to_lowercase().contains(&query));
+        let in_id = self.id.as_ref().is_some_and(|id| id.to_lowercase().contains(&query));
+        in_method || in_id || self.body.to_string().to_lowercase().contains(&query)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "seq": self.seq,
+            "direction": format!("{:?}", self.direction),
+            "timestamp_ms": self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            "method": self.method,
+            "id": self.id,
+            "latency_ms": self.latency.map(|latency| latency.as_secs_f64() * 1000.0),
+            "body": self.body,
+        })
+    }
+}
+
+struct PendingRequest {
+    method: String,
+    sent_at: Instant,
+}
+
+struct Recording {
+    enabled: bool,
+    capacity: usize,
+    next_seq: u64,
+    entries: VecDeque<TrafficEntry>,
+    /// Requests waiting for a response, keyed by the direction they were sent in and their id.
+    pending: HashMap<(Direction, String), PendingRequest>,
+}
+
+/**
+ * Keeps the most recent JSON-RPC messages sent and received by a language server.
+ *
+ * The recorder is cheap to clone, every clone writes to the same recording. This allows the
+ * transport tasks to record messages while the editor reads them.
+ */
+#[derive(Clone)]
+pub struct TrafficRecorder(Arc<Mutex<Recording>>);
+
+impl std::fmt::Debug for TrafficRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrafficRecorder").field("entries", &self.len()).finish()
+    }
+}
+
+impl Default for TrafficRecorder {
+    fn default() -> Self {
+        Self::new(TRAFFIC_CAPACITY)
+    }
+}
+
+impl TrafficRecorder {
+    pub fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(Recording {
+            enabled: true,
+            capacity,
+            next_seq: 0,
+            entries: VecDeque::new(),
+            pending: HashMap::new(),
+        })))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.lock().map(|recording| recording.enabled).unwrap_or(false)
+    }
+
+    /**
+     * Pauses or resumes the recording. Messages that pass through while paused are not kept.
+     */
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut recording) = self.0.lock() {
+            recording.enabled = enabled;
+            if !enabled {
+                recording.pending.clear();
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().map(|recording| recording.entries.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut recording) = self.0.lock() {
+            recording.entries.clear();
+            recording.pending.clear();
+        }
+    }
+
+    /**
+     * Records a raw message. If the message isn't valid JSON it's kept as a string.
+     */
+    pub fn record(&self, direction: Direction, message: &str) {
+        let body = serde_json::from_str(message).unwrap_or_else(|_| Value::String(message.to_owned()));
+        self.record_value(direction, body)
+    }
+
+    pub fn record_value(&self, direction: Direction, body: Value) {
+        let Ok(mut recording) = self.0.lock() else {
+            return
+        };
+        if !recording.enabled {
+            return
+        }
+
+        let now = Instant::now();
+        let mut method = body.get("method").and_then(Value::as_str).map(str::to_owned);
+        let id = body.get("id").and_then(|id| match id {
+            Value::String(value) => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        });
+
+        let mut latency = None;
+        if let Some(id) = &id {
+            if let Some(method) = &method {
+                // A request, wait for the response to come back the other way
+                let request = PendingRequest { method: method.clone(), sent_at: now };
+                recording.pending.insert((direction, id.clone()), request);
+            } else if let Some(request) = recording.pending.remove(&(direction.opposite(), id.clone())) {
+                latency = Some(now.duration_since(request.sent_at));
+                method = Some(request.method);
+            }
+        }
+
+        let entry = TrafficEntry {
+            seq: recording.next_seq,
+            direction,
+            timestamp: SystemTime::now(),
+            method,
+            id,
+            latency,
+            body,
+        };
+        recording.next_seq += 1;
+        recording.entries.push_back(entry);
+        while recording.entries.len() > recording.capacity {
+            recording.entries.pop_front();
+        }
+    }
+
+    pub fn entries(&self) -> Vec<TrafficEntry> {
+        self.search("")
+    }
+
+    /**
+     * Returns the entries that match the query, oldest first.
+     */
+    pub fn search(&self, query: &str) -> Vec<TrafficEntry> {
+        match self.0.lock() {
+            Ok(recording) => recording
+                .entries
+                .iter()
+                .filter(|entry| entry.matches(query))
+                .cloned()
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /**
+     * Writes the recording to a file as a JSON array, so it can be attached to bug reports.
+     */
+    pub fn export(&self, path: &Path) -> Result<(), Error> {
+        let entries: Vec<Value> = self.entries().iter().map(TrafficEntry::to_json).collect();
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &entries)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod inspector_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{Direction, TrafficRecorder};
+
+    #[test]
+    fn test_response_gets_latency_and_method() {
+        let recorder = TrafficRecorder::new(10);
+        recorder.record(Direction::Outgoing, r#"{"jsonrpc":"2.0","id":"1","method":"initialize","params":{}}"#);
+        recorder.record(Direction::Incoming, r#"{"jsonrpc":"2.0","id":"1","result":{}}"#);
+
+        let entries = recorder.entries();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].latency.is_none());
+        assert!(entries[1].latency.is_some());
+        assert_eq!(entries[1].method.as_deref(), Some("initialize"));
+        assert_eq!(entries[1].id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_server_requests_are_matched_the_other_way() {
+        let recorder = TrafficRecorder::new(10);
+        recorder.record(Direction::Incoming, r#"{"jsonrpc":"2.0","id":5,"method":"client/registerCapability"}"#);
+        // A response with the same id coming from the server is not the answer to this request
+        recorder.record(Direction::Incoming, r#"{"jsonrpc":"2.0","id":5,"result":null}"#);
+        recorder.record(Direction::Outgoing, r#"{"jsonrpc":"2.0","id":5,"result":null}"#);
+
+        let entries = recorder.entries();
+        assert!(entries[1].latency.is_none());
+        assert_eq!(entries[2].method.as_deref(), Some("client/registerCapability"));
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let recorder = TrafficRecorder::new(2);
+        for i in 0..3 {
+            recorder.record(Direction::Incoming, &format!(r#"{{"method":"m{}"}}"#, i));
+        }
+        let methods: Vec<String> = recorder.entries().into_iter().filter_map(|entry| entry.method).collect();
+        assert_eq!(methods, vec!["m1".to_owned(), "m2".to_owned()]);
+    }
+
+    #[test]
+    fn test_search_and_pause() {
+        let recorder = TrafficRecorder::new(10);
+        recorder.record(Direction::Incoming, r#"{"method":"textDocument/publishDiagnostics","params":{"uri":"file:///main.rs"}}"#);
+        recorder.record(Direction::Incoming, r#"{"method":"$/progress"}"#);
+        recorder.set_enabled(false);
+        recorder.record(Direction::Incoming, r#"{"method":"$/progress"}"#);
+
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.search("MAIN.RS").len(), 1);
+        assert_eq!(recorder.search("progress").len(), 1);
+        assert_eq!(recorder.search("not there").len(), 0);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use lsp_types::{HoverProviderCapability, OneOf, ServerCapabilities};
+
+use super::{client::LspConnection, inspector::TrafficRecorder};
+
+/**
+ * How to start a language server, and which languages it should be used for.
+ */
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub languages: Vec<String>,
+}
+
+impl ServerConfig {
+    pub fn new(name: &str, command: &str, args: &[&str], languages: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            command: command.to_owned(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            languages: languages.iter().map(|language| language.to_string()).collect(),
+        }
+    }
+
+    pub fn handles(&self, language: &str) -> bool {
+        self.languages.iter().any(|value| value == language)
+    }
+}
+
+/**
+ * The servers the editor knows how to start. A language can have more than one server.
+ */
+pub fn default_servers() -> Vec<ServerConfig> {
+    vec![
+        ServerConfig::new("rust-analyzer", "rust-analyzer", &[], &["rust"]),
+        ServerConfig::new("taplo", "taplo", &["lsp", "stdio"], &["toml"]),
+        ServerConfig::new("marksman", "marksman", &["server"], &["markdown"]),
+        ServerConfig::new(
+            "typescript-language-server",
+            "typescript-language-server",
+            &["--stdio"],
+            &["typescript", "typescriptreact", "javascript", "javascriptreact"],
+        ),
+    ]
+}
+
+/**
+ * Returns the LSP language id of the file based on its extension.
+ */
+pub fn language_id(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    let language = match extension {
+        "rs" => "rust",
+        "toml" => "toml",
+        "md" | "markdown" => "markdown",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "json" => "json",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/**
+ * The features a request can need from a server.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Hover,
+    Completion,
+    Definition,
+    References,
+    Rename,
+    Formatting,
+}
+
+impl Capability {
+    /**
+     * Returns true if the server advertised the capability in its `InitializeResult`.
+     */
+    pub fn is_supported(&self, capabilities: &ServerCapabilities) -> bool {
+        fn one_of<T>(value: &Option<OneOf<bool, T>>) -> bool {
+            !matches!(value, None | Some(OneOf::Left(false)))
+        }
+        match self {
+            Capability::Hover => !matches!(capabilities.hover_provider, None | Some(HoverProviderCapability::Simple(false))),
+            Capability::Completion => capabilities.completion_provider.is_some(),
+            Capability::Definition => one_of(&capabilities.definition_provider),
+            Capability::References => one_of(&capabilities.references_provider),
+            Capability::Rename => one_of(&capabilities.rename_provider),
+            Capability::Formatting => one_of(&capabilities.document_formatting_provider),
+        }
+    }
+}
+
+/**
+ * A running language server.
+ */
+pub struct LanguageServer {
+    /// Unique for the lifetime of the editor, used to tell the messages of each server apart.
+    pub id: usize,
+    pub config: ServerConfig,
+    pub connection: LspConnection,
+}
+
+/**
+ * Starts and keeps track of the language servers of a workspace.
+ *
+ * Servers are started lazily, the first time a document of one of their languages is opened.
+ */
+pub struct LspManager {
+    workspace: Option<PathBuf>,
+    configs: Vec<ServerConfig>,
+    servers: Vec<LanguageServer>,
+    /// Servers that failed to start or disconnected. They are not started again.
+    failed: HashSet<String>,
+    recorder: Option<TrafficRecorder>,
+    next_id: usize,
+}
+
+impl Default for LspManager {
+    fn default() -> Self {
+        Self::new(None, default_servers(), None)
+    }
+}
+
+impl LspManager {
+    pub fn new(workspace: Option<PathBuf>, configs: Vec<ServerConfig>, recorder: Option<TrafficRecorder>) -> Self {
+        Self {
+            workspace,
+            configs,
+            servers: Vec::new(),
+            failed: HashSet::new(),
+            recorder,
+            next_id: 1,
+        }
+    }
+
+    /**
+     * Stops every server and uses the new workspace. The ids keep increasing so the subscriptions of the old servers are dropped.
+     */
+    pub fn set_workspace(&mut self, workspace: Option<PathBuf>) {
+        self.workspace = workspace;
+        self.servers.clear();
+        self.failed.clear();
+    }
+
+    /**
+     * Starts the servers registered for the language that are not running yet.
+     *
+     * Returns the ids of the servers that were started, they still need to be initialized.
+     */
+    pub fn start_servers(&mut self, language: &str) -> Vec<usize> {
+        let Some(workspace) = self.workspace.clone() else {
+            return Vec::new()
+        };
+        let configs: Vec<ServerConfig> = self.configs
+            .iter()
+            .filter(|config| config.handles(language))
+            .filter(|config| !self.failed.contains(&config.name))
+            .filter(|config| !self.servers.iter().any(|server| server.config.name == config.name))
+            .cloned()
+            .collect();
+
+        let mut started = Vec::new();
+        for config in configs {
+            match LspConnection::new(&workspace, &config, self.recorder.clone()) {
+                Ok(connection) => started.push(self.add_server(config, connection)),
+                Err(e) => {
+                    eprintln!("Failed to start {}: {}", config.name, e);
+                    self.failed.insert(config.name);
+                }
+            }
+        }
+        started
+    }
+
+    /**
+     * Adds a server that is already connected. Returns the id of the server.
+     */
+    pub fn add_server(&mut self, config: ServerConfig, connection: LspConnection) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.servers.push(LanguageServer { id, config, connection });
+        id
+    }
+
+    /**
+     * Removes a server that disconnected. It won't be started again for this workspace.
+     */
+    pub fn remove_server(&mut self, id: usize) {
+        if let Some(index) = self.servers.iter().position(|server| server.id == id) {
+            let server = self.servers.remove(index);
+            self.failed.insert(server.config.name);
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&LspConnection> {
+        self.servers.iter().find(|server| server.id == id).map(|server| &server.connection)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut LspConnection> {
+        self.servers.iter_mut().find(|server| server.id == id).map(|server| &mut server.connection)
+    }
+
+    pub fn servers(&self) -> impl Iterator<Item = &LanguageServer> {
+        self.servers.iter()
+    }
+
+    /**
+     * The servers registered for the language, in the order of their configuration.
+     */
+    pub fn servers_for<'a>(&'a self, language: &'a str) -> impl Iterator<Item = &'a LanguageServer> {
+        self.servers.iter().filter(move |server| server.config.handles(language))
+    }
+
+    /**
+     * Returns the first initialized server of the language that advertised the capability.
+     */
+    pub fn server_with_capability<'a>(&'a self, language: &'a str, capability: Capability) -> Option<&'a LanguageServer> {
+        self.servers_for(language).find(|server| {
+            server.connection.as_initialized().is_some()
+                && server.connection.capabilities().is_some_and(|capabilities| capability.is_supported(capabilities))
+        })
+    }
+}
+
+#[cfg(test)]
+mod manager_tests {
+    use std::path::Path;
+
+    use lsp_types::{HoverProviderCapability, OneOf, ServerCapabilities};
+    use pretty_assertions::assert_eq;
+
+    use crate::lsp::mock_server::MockServer;
+
+    use super::{language_id, Capability, LspManager, ServerConfig};
+
+    #[test]
+    fn test_language_id() {
+        assert_eq!(language_id(Path::new("src/main.rs")), Some("rust"));
+        assert_eq!(language_id(Path::new("Cargo.toml")), Some("toml"));
+        assert_eq!(language_id(Path::new("web/app.tsx")), Some("typescriptreact"));
+        assert_eq!(language_id(Path::new("LICENSE")), None);
+    }
+
+    #[test]
+    fn test_capability() {
+        let mut capabilities = ServerCapabilities::default();
+        assert!(!Capability::Hover.is_supported(&capabilities));
+        capabilities.hover_provider = Some(HoverProviderCapability::Simple(false));
+        assert!(!Capability::Hover.is_supported(&capabilities));
+        capabilities.hover_provider = Some(HoverProviderCapability::Simple(true));
+        assert!(Capability::Hover.is_supported(&capabilities));
+
+        capabilities.rename_provider = Some(OneOf::Left(false));
+        assert!(!Capability::Rename.is_supported(&capabilities));
+    }
+
+    #[test]
+    fn test_request_goes_to_first_capable_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = LspManager::new(Some(dir.path().to_owned()), Vec::new(), None);
+
+        let mut ids = Vec::new();
+        for (name, hover) in [("first", false), ("second", true), ("third", true)] {
+            let (mut connection, _server) = MockServer::new().start(dir.path());
+            connection.has_initialize_client();
+            let capabilities = ServerCapabilities {
+                hover_provider: Some(HoverProviderCapability::Simple(hover)),
+                ..Default::default()
+            };
+            connection.set_capabilities(capabilities);
+            let config = ServerConfig::new(name, name, &[], &["rust"]);
+            ids.push(manager.add_server(config, connection));
+        }
+
+        let server = manager.server_with_capability("rust", Capability::Hover).unwrap();
+        assert_eq!(server.config.name, "second");
+        assert!(manager.server_with_capability("toml", Capability::Hover).is_none());
+        assert!(manager.server_with_capability("rust", Capability::Rename).is_none());
+
+        manager.remove_server(ids[1]);
+        let server = manager.server_with_capability("rust", Capability::Hover).unwrap();
+        assert_eq!(server.config.name, "third");
+        assert_eq!(manager.servers_for("rust").count(), 2);
+    }
+}
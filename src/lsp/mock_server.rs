@@ -0,0 +1,415 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+use smol::{io::{BufReader, BufWriter}, Task};
+
+use super::{client::LspConnection, transport::{read_message, send}};
+
+/**
+ * The size of the in memory pipes between the client and the mock server.
+ */
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/**
+ * A single step of the script played back by the mock server.
+ */
+pub enum Step {
+    /// Waits for a request from the client and answers it with the result
+    Request { method: &'static str, result: Value },
+    /// Waits for a notification from the client
+    Notification { method: &'static str },
+    /// Sends a notification to the client
+    Notify { method: &'static str, params: Value },
+    /// Sends a request to the client and waits for its answer
+    Ask { method: &'static str, params: Value },
+}
+
+/**
+ * An in process language server used to test the client.
+ *
+ * The server plays back a script of expected messages and canned responses. If the client sends
+ * something that isn't the next expected message, the server stops and returns an error.
+ */
+#[derive(Default)]
+pub struct MockServer {
+    steps: Vec<Step>,
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect_request(mut self, method: &'static str, result: Value) -> Self {
+        self.steps.push(Step::Request { method, result });
+        self
+    }
+
+    pub fn expect_notification(mut self, method: &'static str) -> Self {
+        self.steps.push(Step::Notification { method });
+        self
+    }
+
+    pub fn notify(mut self, method: &'static str, params: Value) -> Self {
+        self.steps.push(Step::Notify { method, params });
+        self
+    }
+
+    pub fn ask(mut self, method: &'static str, params: Value) -> Self {
+        self.steps.push(Step::Ask { method, params });
+        self
+    }
+
+    /**
+     * Starts the server and connects a client to it.
+     *
+     * The task resolves once the script is done, with every message the server received.
+     */
+    pub fn start(self, workspace: &Path) -> (LspConnection, Task<Result<Vec<Value>, String>>) {
+        let (client_reader, server_writer) = piper::pipe(PIPE_CAPACITY);
+        let (server_reader, client_writer) = piper::pipe(PIPE_CAPACITY);
+        let (err_reader, err_writer) = piper::pipe(PIPE_CAPACITY);
+
+        let connection = LspConnection::from_streams(workspace, client_reader, client_writer, err_reader, None)
+            .expect("Failed to connect to the mock server");
+
+        let steps = self.steps;
+        let task = smol::spawn(async move {
+            // Keep stderr open for as long as the server is running
+            let _err_writer = err_writer;
+            let mut reader = BufReader::new(server_reader);
+            let mut writer = BufWriter::new(server_writer);
+            let mut received = Vec::new();
+
+            for (index, step) in steps.into_iter().enumerate() {
+                match step {
+                    Step::Request { method, result } => {
+                        let message = next_message(&mut reader, method).await?;
+                        let id = message.get("id").cloned().ok_or(format!("Expected `{}` to be a request", method))?;
+                        send(&mut writer, json!({ "jsonrpc": "2.0", "id": id, "result": result })).await;
+                        received.push(message);
+                    }
+                    Step::Notification { method } => {
+                        let message = next_message(&mut reader, method).await?;
+                        if message.get("id").is_some() {
+                            return Err(format!("Expected `{}` to be a notification", method))
+                        }
+                        received.push(message);
+                    }
+                    Step::Notify { method, params } => {
+                        send(&mut writer, json!({ "jsonrpc": "2.0", "method": method, "params": params })).await;
+                    }
+                    Step::Ask { method, params } => {
+                        let id = format!("server-{}", index);
+                        send(&mut writer, json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await;
+                        let message = read_message(&mut reader).await.ok_or("The client closed the connection")?;
+                        let message: Value = serde_json::from_str(&message).map_err(|e| e.to_string())?;
+                        if message["id"] != json!(id) || message.get("method").is_some() {
+                            return Err(format!("Expected an answer to `{}`, received {}", method, message))
+                        }
+                        received.push(message);
+                    }
+                }
+            }
+            Ok(received)
+        });
+        (connection, task)
+    }
+}
+
+async fn next_message(reader: &mut BufReader<piper::Reader>, method: &str) -> Result<Value, String> {
+    let message = read_message(reader).await.ok_or("The client closed the connection")?;
+    let message: Value = serde_json::from_str(&message).map_err(|e| format!("Invalid message `{}`: {}", message, e))?;
+    match message.get("method").and_then(Value::as_str) {
+        Some(value) if value == method => Ok(message),
+        _ => Err(format!("Expected `{}`, received {}", method, message)),
+    }
+}
+
+#[cfg(test)]
+mod lsp_client_tests {
+    use std::{fs, path::PathBuf};
+
+    use lsp_types::{FileRename, TextDocumentSyncKind, Url};
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    use crate::{core::{buffer::Buffer, document::Document, position::Position, watcher::{FileChange, FileEvent}}, highlighter::HighlighterConfig, lsp::{client::{file_path, LspConnection}, encoding::PositionEncoding, file_operations::text_edits, response::LspResponse, transport::{InitializedSender, MessageSender}}};
+
+    use super::MockServer;
+
+    fn workspace() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        fs::write(&file, "fn main() {}\n").unwrap();
+        (dir, file)
+    }
+
+    /**
+     * Receives messages until one that isn't a `NoMessage` arrives.
+     */
+    async fn next_response(connection: &LspConnection) -> LspResponse {
+        loop {
+            match connection.new_receiver().wait_for_message().await.unwrap() {
+                LspResponse::NoMessage => continue,
+                response => return response,
+            }
+        }
+    }
+
+    /**
+     * Goes through the initialization handshake and returns the initialized sender.
+     */
+    async fn initialize(connection: &mut LspConnection) -> MessageSender<InitializedSender> {
+        connection.as_uninitialized().unwrap().initialize(connection.init_params()).await;
+        assert!(matches!(next_response(connection).await, LspResponse::Initialized(_)));
+        connection.has_initialize_client();
+        let sender = connection.as_initialized().unwrap();
+        sender.clone().has_initialized().await;
+        sender
+    }
+
+    fn server() -> MockServer {
+        MockServer::new()
+            .expect_request("initialize", json!({ "capabilities": {} }))
+            .expect_notification("initialized")
+    }
+
+    #[test]
+    fn test_initialize() {
+        let (dir, _) = workspace();
+        let (mut connection, server) = server().start(dir.path());
+        smol::block_on(async {
+            initialize(&mut connection).await;
+            let received = server.await.unwrap();
+
+            assert_eq!(received[0]["params"]["rootUri"], json!(file_path(dir.path().to_str().unwrap())));
+            assert!(connection.as_uninitialized().is_none());
+        });
+    }
+
+    #[test]
+    fn test_did_open_and_diagnostics() {
+        let (dir, file) = workspace();
+        let filename = file.to_str().unwrap().to_owned();
+        let diagnostics = json!({
+            "uri": file_path(&filename),
+            "diagnostics": [{
+                "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } },
+                "severity": 2,
+                "message": "function `main` is never used"
+            }]
+        });
+        let (mut connection, server) = server()
+            .expect_notification("textDocument/didOpen")
+            .notify("textDocument/publishDiagnostics", diagnostics)
+            .start(dir.path());
+
+        smol::block_on(async {
+            let sender = initialize(&mut connection).await;
+            sender.open_document(filename.clone(), "rust".to_owned(), "fn main() {}\n".to_owned(), 1).await;
+
+            let LspResponse::Diagnostics(diagnostics) = next_response(&connection).await else {
+                panic!("Expected diagnostics")
+            };
+            let document = Document::open(&filename).unwrap();
+            let config = Some(HighlighterConfig::rust_config(document.slice_all()));
+            let mut buffer = Buffer::new(document, config);
+            buffer.add_diagnostics(1, diagnostics, PositionEncoding::Utf16);
+
+            let issue = buffer.find_diagnostic(Some(Position::new(0, 4))).unwrap();
+            assert_eq!(issue.message, "function `main` is never used");
+            assert!(buffer.find_diagnostic(Some(Position::new(0, 8))).is_none());
+
+            let received = server.await.unwrap();
+            let params = &received[2]["params"]["textDocument"];
+            assert_eq!(params["version"], json!(1));
+            assert_eq!(params["text"], json!("fn main() {}\n"));
+        });
+    }
+
+    #[test]
+    fn test_did_change_versions() {
+        let (dir, file) = workspace();
+        let filename = file.to_str().unwrap().to_owned();
+        let (mut connection, server) = server()
+            .expect_notification("textDocument/didChange")
+            .expect_notification("textDocument/didChange")
+            .start(dir.path());
+
+        smol::block_on(async {
+            let sender = initialize(&mut connection).await;
+
+            let document = Document::open(&filename).unwrap();
+            let config = Some(HighlighterConfig::rust_config(document.slice_all()));
+            let mut buffer = Buffer::new(document, config);
+            for text in ["a", "b"] {
+                let change = buffer.insert(text.to_owned()).unwrap();
+                sender.clone().doc_changed(change, PositionEncoding::Utf16).await;
+            }
+
+            let received = server.await.unwrap();
+            let changes: Vec<&Value> = received[2..].iter().map(|message| &message["params"]).collect();
+            assert_eq!(changes[0]["textDocument"]["version"], json!(2));
+            assert_eq!(changes[1]["textDocument"]["version"], json!(3));
+            assert_eq!(changes[1]["contentChanges"][0]["text"], json!("b"));
+            assert_eq!(changes[1]["contentChanges"][0]["range"]["start"], json!({ "line": 0, "character": 1 }));
+        });
+    }
+
+    #[test]
+    fn test_positions_follow_the_encoding() {
+        let (_dir, file) = workspace();
+        fs::write(&file, "let s = \"𝄞é\"; x\n").unwrap();
+        let filename = file.to_str().unwrap().to_owned();
+        let document = Document::open(&filename).unwrap();
+        let config = Some(HighlighterConfig::rust_config(document.slice_all()));
+        let mut buffer = Buffer::new(document, config);
+
+        // `x` is the 14th grapheme, 15 UTF-16 code units and 18 bytes into the line
+        let diagnostics = json!({
+            "uri": file_path(&filename),
+            "diagnostics": [{
+                "range": { "start": { "line": 0, "character": 15 }, "end": { "line": 0, "character": 16 } },
+                "message": "cannot find value `x`"
+            }]
+        });
+        let LspResponse::Diagnostics(diagnostics) = LspResponse::from_response("textDocument/publishDiagnostics", &json!({ "params": diagnostics })) else {
+            panic!("Expected diagnostics")
+        };
+        buffer.add_diagnostics(1, diagnostics, PositionEncoding::Utf16);
+        assert!(buffer.find_diagnostic(Some(Position::new(0, 14))).is_some());
+        assert!(buffer.find_diagnostic(Some(Position::new(0, 15))).is_none());
+
+        buffer.cursor.0 = Position::new(0, 14);
+        let change = buffer.insert("y".to_owned()).unwrap();
+        assert_eq!(change.to_lsp(PositionEncoding::Utf8).range.unwrap().start.character, 18);
+        assert_eq!(change.to_lsp(PositionEncoding::Utf16).range.unwrap().start.character, 15);
+        assert_eq!(change.to_lsp(PositionEncoding::Utf32).range.unwrap().start.character, 14);
+        assert_eq!(buffer.get_string(), "let s = \"𝄞é\"; yx\n");
+    }
+
+    #[test]
+    fn test_sync_follows_the_capabilities() {
+        let (dir, _) = workspace();
+        let (mut connection, _server) = MockServer::new().start(dir.path());
+        assert_eq!(connection.sync_kind(), TextDocumentSyncKind::NONE);
+        assert!(!connection.sends_open_close());
+
+        connection.set_capabilities(serde_json::from_value(json!({
+            "positionEncoding": "utf-8",
+            "textDocumentSync": { "openClose": true, "change": 2 }
+        })).unwrap());
+        assert_eq!(connection.position_encoding(), PositionEncoding::Utf8);
+        assert_eq!(connection.sync_kind(), TextDocumentSyncKind::INCREMENTAL);
+        assert!(connection.sends_open_close());
+        assert!(!connection.sends_save());
+    }
+
+    #[test]
+    fn test_register_watched_files() {
+        let (dir, _) = workspace();
+        let registration = json!({
+            "registrations": [{
+                "id": "watch",
+                "method": "workspace/didChangeWatchedFiles",
+                "registerOptions": { "watchers": [{ "globPattern": "**/*.rs" }] }
+            }]
+        });
+        let (mut connection, server) = server()
+            .ask("client/registerCapability", registration)
+            .expect_notification("workspace/didChangeWatchedFiles")
+            .start(dir.path());
+
+        smol::block_on(async {
+            let sender = initialize(&mut connection).await;
+            let LspResponse::RegisterCapability(id, registrations) = next_response(&connection).await else {
+                panic!("Expected a registration")
+            };
+            assert_eq!(registrations[0].method, "workspace/didChangeWatchedFiles");
+            let options = serde_json::from_value(registrations[0].register_options.clone().unwrap()).unwrap();
+            connection.watched_files_mut().register(registrations[0].id.clone(), options);
+            sender.clone().respond(id, Ok(Value::Null)).await;
+
+            let events = vec![
+                FileEvent::new(dir.path().join("main.rs"), FileChange::Changed),
+                FileEvent::new(dir.path().join("notes.txt"), FileChange::Changed),
+            ];
+            let changes = connection.watched_files().filter(&events, dir.path());
+            assert_eq!(changes.len(), 1);
+            sender.did_change_watched_files(changes).await;
+
+            let received = server.await.unwrap();
+            assert_eq!(received[2]["result"], Value::Null);
+            assert_eq!(received[3]["params"]["changes"][0]["type"], json!(2));
+        });
+    }
+
+    #[test]
+    fn test_will_rename_files() {
+        let (dir, _) = workspace();
+        let old_uri = Url::from_file_path(dir.path().join("main.rs")).unwrap();
+        let mut changes = serde_json::Map::new();
+        changes.insert(old_uri.to_string(), json!([{
+            "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } },
+            "newText": "start"
+        }]));
+        let edit = json!({ "changes": changes });
+        let (mut connection, server) = server()
+            .expect_request("workspace/willRenameFiles", edit)
+            .expect_notification("workspace/didRenameFiles")
+            .start(dir.path());
+
+        smol::block_on(async {
+            let sender = initialize(&mut connection).await;
+            let files = vec![FileRename {
+                old_uri: old_uri.to_string(),
+                new_uri: Url::from_file_path(dir.path().join("lib.rs")).unwrap().to_string(),
+            }];
+            sender.clone().will_rename_files(files.clone()).await;
+            let LspResponse::WillRenameFiles(Some(edit)) = next_response(&connection).await else {
+                panic!("Expected the edits of the rename")
+            };
+            let edits = text_edits(edit);
+            assert_eq!(edits[0].0, dir.path().join("main.rs"));
+            assert_eq!(edits[0].1[0].new_text, "start");
+            sender.did_rename_files(files).await;
+
+            let received = server.await.unwrap();
+            assert_eq!(received[3]["params"]["files"][0]["newUri"], json!(Url::from_file_path(dir.path().join("lib.rs")).unwrap()));
+        });
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let (dir, _) = workspace();
+        let (mut connection, server) = server()
+            .expect_request("shutdown", Value::Null)
+            .expect_notification("exit")
+            .start(dir.path());
+
+        smol::block_on(async {
+            let sender = initialize(&mut connection).await;
+            sender.clone().shutdown().await;
+            assert!(matches!(next_response(&connection).await, LspResponse::Shutdown));
+            sender.exit().await;
+
+            let received = server.await.unwrap();
+            assert_eq!(received.len(), 4);
+        });
+    }
+
+    #[test]
+    fn test_unexpected_message_fails_the_script() {
+        let (dir, _) = workspace();
+        let (connection, server) = MockServer::new()
+            .expect_notification("initialized")
+            .start(dir.path());
+
+        smol::block_on(async {
+            connection.as_uninitialized().unwrap().initialize(connection.init_params()).await;
+            assert!(server.await.is_err());
+        });
+    }
+}
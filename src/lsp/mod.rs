@@ -2,4 +2,11 @@ pub mod client;
 pub mod error;
 pub mod transport;
 pub mod response;
-pub mod connect;
\ No newline at end of file
+pub mod connect;
+pub mod inspector;
+pub mod manager;
+pub mod encoding;
+pub mod watched_files;
+pub mod file_operations;
+#[cfg(test)]
+pub mod mock_server;
@@ -1,8 +1,11 @@
 use iced::Color;
-use lsp_types::DiagnosticSeverity;
+use jsonrpc_lite::Id;
+use lsp_types::{request::{RegisterCapability, Request, UnregisterCapability}, DiagnosticSeverity, FoldingRange, InitializeResult, Registration, SelectionRange, Unregistration, WorkspaceEdit};
 use serde_json::Value;
 
-use crate::core::{position::Position, selection::Range};
+use crate::core::{document::Document, position::Position, selection::Range};
+
+use super::encoding::PositionEncoding;
 
 #[derive(Debug, Clone)]
 pub enum LspResponse {
@@ -11,7 +14,20 @@ pub enum LspResponse {
     NoMessage,
     ErrorMessage(String),
     UnknownMessage,
-    Initialized,
+    /// The server answered the initialize request with its capabilities
+    Initialized(Box<InitializeResult>),
+    Shutdown,
+    /// The edits to make before the files are renamed, if the server has any
+    WillRenameFiles(Option<WorkspaceEdit>),
+    /// The ranges of the open document that can be folded
+    FoldingRanges(Vec<FoldingRange>),
+    /// The ranges around the position the selection can grow to, each one inside of its parent
+    SelectionRanges(Vec<SelectionRange>),
+    /// The server wants to use a capability that has to be registered, like watching files
+    RegisterCapability(Id, Vec<Registration>),
+    UnregisterCapability(Id, Vec<Unregistration>),
+    /// A request from the server that the editor doesn't handle, it still needs an answer
+    Request(Id, String, Value),
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +41,18 @@ impl ClientDiagnostics {
         
         self.issues.clone().into_iter().find(|value| value.range.pos_in_range(position))
     }
+
+    /**
+     * Converts the ranges sent by the server into positions of the document.
+     */
+    pub fn resolve(&mut self, document: &Document, encoding: PositionEncoding) {
+        for issue in self.issues.iter_mut() {
+            issue.range = Range::new(
+                document.from_lsp_position(issue.lsp_range.start, encoding),
+                document.from_lsp_position(issue.lsp_range.end, encoding),
+            );
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -81,6 +109,25 @@ impl LspResponse {
         }
     }
 
+    pub fn from_request(id: Id, method: &str, json: &Value) -> Self {
+        let params = json.get("params").cloned().unwrap_or_default();
+        match method {
+            RegisterCapability::METHOD => {
+                match serde_json::from_value::<lsp_types::RegistrationParams>(params.clone()) {
+                    Ok(params) => LspResponse::RegisterCapability(id, params.registrations),
+                    Err(_) => LspResponse::Request(id, method.to_owned(), Value::Null),
+                }
+            }
+            UnregisterCapability::METHOD => {
+                match serde_json::from_value::<lsp_types::UnregistrationParams>(params.clone()) {
+                    Ok(params) => LspResponse::UnregisterCapability(id, params.unregisterations),
+                    Err(_) => LspResponse::Request(id, method.to_owned(), Value::Null),
+                }
+            }
+            _ => LspResponse::Request(id, method.to_owned(), params),
+        }
+    }
+
 }
 
 /**
@@ -88,7 +135,10 @@ impl LspResponse {
  */
 #[derive(Debug, Clone)]
 pub struct Issue {
+    /// The range within the document, set once the diagnostics are resolved against it.
     pub range: Range,
+    /// The range as it was sent by the server.
+    pub lsp_range: lsp_types::Range,
     pub code_description: Option<lsp_types::CodeDescription>,
     pub message: String,
     pub severity: Severity
@@ -101,7 +151,8 @@ impl From<lsp_types::Diagnostic> for Issue {
         let severity = Severity::from(serverity);
 
         Self {
-            range: Range::from(value.range),
+            range: Range::default(),
+            lsp_range: value.range,
             code_description: value.code_description,
             message: value.message,
             severity
@@ -1,9 +1,9 @@
-use std::{fs, path::Path};
+use std::{collections::HashMap, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}};
 
-use futures::AsyncRead;
-use jsonrpc_lite::{JsonRpc, Params};
+use futures::{AsyncRead, AsyncWrite};
+use jsonrpc_lite::{Error as RpcError, Id, JsonRpc, Params};
 use lsp::{
-    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument, Initialized, Notification}, request::Request, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams, InitializeResult, InitializedParams, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, Url, VersionedTextDocumentIdentifier
+    notification::{DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument, DidDeleteFiles, DidOpenTextDocument, DidRenameFiles, DidSaveTextDocument, Exit, Initialized, Notification}, request::{FoldingRangeRequest, Request, SelectionRangeRequest, Shutdown, WillRenameFiles}, DeleteFilesParams, FoldingRange, FoldingRangeParams, SelectionRange, SelectionRangeParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidCloseTextDocumentParams, FileDelete, FileRename, RenameFilesParams, WorkspaceEdit, DidOpenTextDocumentParams, DidSaveTextDocumentParams, InitializeResult, InitializedParams, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, Url, VersionedTextDocumentIdentifier
 };
 use lsp_types as lsp;
 use lsp_types::request::Initialize;
@@ -20,29 +20,67 @@ use smol::{
 
 use crate::core::document_change::DocumentChange;
 
-use super::{client::file_path, error::{LspClientError, LspClientResult}, response::LspResponse};
+use super::{client::file_path, encoding::PositionEncoding, error::{LspClientError, LspClientResult}, inspector::{Direction, TrafficRecorder}, response::LspResponse};
 #[derive(Debug)]
 pub enum Input {
     Error(String),
     Message(String),
 }
 
-async fn process_reader<T>(reader: &mut BufReader<T>, sender: &Sender<Input>, message: impl Fn(String) -> Input) 
+/**
+ * Reads a message and passes it to the channel. Returns false once the stream is closed.
+ */
+async fn process_reader<T>(reader: &mut BufReader<T>, sender: &Sender<Input>, recorder: Option<&TrafficRecorder>, message: impl Fn(String) -> Input) -> bool
     where T: AsyncRead + Unpin + Send {
-    let result = read_message(reader).await;
+    let Some(result) = read_message(reader).await else {
+        return false
+    };
     if !result.is_empty() {
+        if let Some(recorder) = recorder {
+            recorder.record(Direction::Incoming, &result);
+        }
         if let Err(_e) = sender.send(message(result)).await {
             eprintln!("Failed to send a message. Channel must have closed.");
         }
     }
-
+    true
 }
 pub struct TransortResult {
     pub receiver: Receiver<Input>,
     pub sender: Sender<JsonRpc>,
+    pub pending: PendingRequests,
     pub writing_task: Task<()>,
     pub reading_task: Task<()>
 }
+
+/**
+ * Keeps the method of every request that is waiting for a response.
+ * 
+ * Responses only contain the id of the request, so the receiver uses this to know what the response is for.
+ * The ids start at 1, so the `initialize` request is always the first one.
+ */
+#[derive(Clone, Default)]
+pub struct PendingRequests {
+    last_id: Arc<AtomicUsize>,
+    methods: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl PendingRequests {
+    fn register(&self, method: &str) -> usize {
+        let id = self.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Ok(mut methods) = self.methods.lock() {
+            methods.insert(id.to_string(), method.to_owned());
+        }
+        id
+    }
+
+    /**
+     * Removes the request from the pending requests and returns its method.
+     */
+    pub fn take(&self, id: &str) -> Option<String> {
+        self.methods.lock().ok()?.remove(id)
+    }
+}
 pub trait SenderState {}
 #[derive(Clone)]
 pub struct InitializedSender;
@@ -57,13 +95,15 @@ impl SenderState for UninitializedSender {}
 pub struct MessageSender<S>
 where S: SenderState{
     pub sender: Sender<JsonRpc>,
+    pub pending: PendingRequests,
     pub state: S
 }
 
-impl From<Sender<JsonRpc>> for MessageSender<UninitializedSender> {
-    fn from(value: Sender<JsonRpc>) -> Self {
+impl MessageSender<UninitializedSender> {
+    pub fn new(sender: Sender<JsonRpc>, pending: PendingRequests) -> Self {
         Self {
-            sender: value,
+            sender,
+            pending,
             state: UninitializedSender
         }
     }
@@ -94,12 +134,14 @@ where S: SenderState {
      * Sends an LSP request
         * 
         * This method should not be used alone. It should only be used with the `Client` method with the same name.
+        * The id of the request is registered so the response can be matched with its method.
         */
-    async fn send_request<T>(self, id: usize, method: &str, params: T)
+    async fn send_request<T>(self, method: &str, params: T)
     where
         T: Serialize,
         S: SenderState
     {
+        let id = self.pending.register(method);
         let msg = JsonRpc::request_with_params(
             id.to_string(),
             method,
@@ -111,11 +153,11 @@ where S: SenderState {
 
 impl MessageSender<UninitializedSender> {
     pub fn init(self) -> MessageSender<InitializedSender>{
-        MessageSender { sender: self.sender, state: InitializedSender }
+        MessageSender { sender: self.sender, pending: self.pending, state: InitializedSender }
     }
 
     pub async fn initialize(self, init_params: lsp::InitializeParams) {
-        self.send_request(1, Initialize::METHOD, init_params)
+        self.send_request(Initialize::METHOD, init_params)
         .await    
     }
 }
@@ -126,18 +168,17 @@ impl MessageSender<InitializedSender> {
         .await;
     }
 
-    pub async fn open_document(self, path: String){
-        // Open a document
-        let rust_path = Path::new(&path);
-        let file = fs::read_to_string(rust_path).unwrap();
-
+    /**
+     * Sends the text of the open buffer, which can differ from the file on disk.
+     */
+    pub async fn open_document(self, path: String, language_id: String, text: String, version: i32) {
         let url = Url::parse(&file_path(&path)).unwrap();
         let params = DidOpenTextDocumentParams {
             text_document: TextDocumentItem {
                 uri: url,
-                language_id: "rust".to_string(),
-                version: 1,
-                text: file,
+                language_id,
+                version,
+                text,
             },
         };
 
@@ -158,18 +199,33 @@ impl MessageSender<InitializedSender> {
     }
 
 
-    pub async fn doc_changed(self, changes: DocumentChange) {
-        let _event = TextDocumentContentChangeEvent {
-            range: Some(changes.range.clone().into()),
-            range_length: Some(1),
-            text: changes.text.clone(),
+    /**
+     * Sends the edit as an incremental change, with its range in the encoding of the server.
+     */
+    pub async fn doc_changed(self, changes: DocumentChange, encoding: PositionEncoding) {
+        let event = changes.to_lsp(encoding);
+        self.send_changes(changes.file, changes.version, vec![event]).await
+    }
+
+    /**
+     * Sends the whole text of the document, for servers that don't accept incremental changes.
+     */
+    pub async fn doc_replaced(self, path: String, version: i32, text: String) {
+        let event = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text,
         };
+        self.send_changes(path, version, vec![event]).await
+    }
+
+    async fn send_changes(self, path: String, version: i32, content_changes: Vec<TextDocumentContentChangeEvent>) {
         let params = DidChangeTextDocumentParams {
             text_document: VersionedTextDocumentIdentifier {
-                uri: Url::parse(&file_path(&changes.file)).unwrap(),
-                version: 2,
+                uri: Url::parse(&file_path(&path)).unwrap(),
+                version,
             },
-            content_changes: vec![],
+            content_changes,
         };
         self.send_notification(DidChangeTextDocument::METHOD, params)
         .await
@@ -186,18 +242,95 @@ impl MessageSender<InitializedSender> {
         .await
     }
 
-}
+    /**
+     * Asks the server to shut down. The `exit` notification should be sent once the server answers.
+     */
+    pub async fn shutdown(self) {
+        self.send_request(Shutdown::METHOD, ())
+        .await
+    }
 
-#[derive(Clone)]
-pub struct MessageReciever(Receiver<Input>);
+    pub async fn exit(self) {
+        self.send_notification(Exit::METHOD, ())
+        .await
+    }
 
-impl From<Receiver<Input>> for MessageReciever {
-    fn from(value:  Receiver<Input>) -> Self {
-        Self(value)
+    pub async fn did_change_watched_files(self, changes: Vec<lsp::FileEvent>) {
+        let params = DidChangeWatchedFilesParams { changes };
+        self.send_notification(DidChangeWatchedFiles::METHOD, params)
+        .await
     }
+
+    /**
+     * Asks the server for the edits to make before the files are renamed, like updating the imports.
+     */
+    pub async fn will_rename_files(self, files: Vec<FileRename>) {
+        self.send_request(WillRenameFiles::METHOD, RenameFilesParams { files })
+        .await
+    }
+
+    /**
+     * Asks the server for the ranges of the document that can be folded.
+     */
+    pub async fn folding_range(self, path: String) {
+        let params = FoldingRangeParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::parse(&file_path(&path)).unwrap(),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        self.send_request(FoldingRangeRequest::METHOD, params)
+        .await
+    }
+
+    /**
+     * Asks the server for the ranges around the position, from the innermost to the whole document.
+     */
+    pub async fn selection_range(self, path: String, position: lsp::Position) {
+        let params = SelectionRangeParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::parse(&file_path(&path)).unwrap(),
+            },
+            positions: vec![position],
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        self.send_request(SelectionRangeRequest::METHOD, params)
+        .await
+    }
+
+    pub async fn did_rename_files(self, files: Vec<FileRename>) {
+        self.send_notification(DidRenameFiles::METHOD, RenameFilesParams { files })
+        .await
+    }
+
+    pub async fn did_delete_files(self, files: Vec<FileDelete>) {
+        self.send_notification(DidDeleteFiles::METHOD, DeleteFilesParams { files })
+        .await
+    }
+
+    /**
+     * Answers a request sent by the server.
+     */
+    pub async fn respond(self, id: Id, result: Result<Value, RpcError>) {
+        let msg = match result {
+            Ok(value) => JsonRpc::success(id, &value),
+            Err(error) => JsonRpc::error(id, error),
+        };
+        self.send(msg).await
+    }
+
 }
 
+#[derive(Clone)]
+pub struct MessageReciever(Receiver<Input>, PendingRequests);
+
 impl MessageReciever {
+    pub fn new(receiver: Receiver<Input>, pending: PendingRequests) -> Self {
+        Self(receiver, pending)
+    }
+
     /**
      * Receives a message from the LSP. 
      * 
@@ -244,9 +377,8 @@ impl MessageReciever {
     }
 
     fn get_response_from_message(&self, json: &Value) -> LspResponse {
-        if let Some(value) = json.get("id") {
-            // The message is a request
-            //
+        if let (Some(value), None) = (json.get("id"), json.get("method")) {
+            // The message is a response to one of our requests
             let id = if let Some(value) = value.as_str() {
                 value.to_string()
             } else if let Some(value) = value.as_u64() {
@@ -254,10 +386,42 @@ impl MessageReciever {
             } else {
                 panic!("Id is something unexpected.")
             };
-            if id.as_str() == "1" {
-                return LspResponse::Initialized
+            return match self.1.take(&id).as_deref() {
+                Some(Initialize::METHOD) => {
+                    let result = json.get("result").cloned().unwrap_or_default();
+                    match serde_json::from_value::<InitializeResult>(result) {
+                        Ok(result) => LspResponse::Initialized(Box::new(result)),
+                        Err(e) => LspResponse::ErrorMessage(format!("Invalid initialize result: {}", e))
+                    }
+                },
+                Some(Shutdown::METHOD) => LspResponse::Shutdown,
+                Some(WillRenameFiles::METHOD) => {
+                    // A server that failed to compute the edits answers with an error, the files are renamed anyway
+                    let result = json.get("result").cloned().unwrap_or_default();
+                    LspResponse::WillRenameFiles(serde_json::from_value::<Option<WorkspaceEdit>>(result).ok().flatten())
+                },
+                Some(FoldingRangeRequest::METHOD) => {
+                    // Without an answer the ranges of the syntax tree are kept
+                    let result = json.get("result").cloned().unwrap_or_default();
+                    match serde_json::from_value::<Option<Vec<FoldingRange>>>(result) {
+                        Ok(Some(ranges)) => LspResponse::FoldingRanges(ranges),
+                        _ => LspResponse::NoMessage,
+                    }
+                },
+                Some(SelectionRangeRequest::METHOD) => {
+                    let result = json.get("result").cloned().unwrap_or_default();
+                    match serde_json::from_value::<Option<Vec<SelectionRange>>>(result) {
+                        Ok(Some(ranges)) => LspResponse::SelectionRanges(ranges),
+                        _ => LspResponse::NoMessage,
+                    }
+                },
+                _ => LspResponse::NoMessage
             }
-            return LspResponse::NoMessage
+        } else if let (Some(id), Some(method)) = (json.get("id"), json.get("method")) {
+            // The message is a request from the server, it has to be answered
+            let id: Id = serde_json::from_value(id.clone()).unwrap_or(Id::None(()));
+            let method = method.as_str().unwrap_or_default();
+            return LspResponse::from_request(id, method, json)
         } else if let Some(value) = json.get("method") {
 
             // The message is a notification.
@@ -270,47 +434,69 @@ impl MessageReciever {
 
 }
 
-async fn recieve_messages(sender: Sender<Input>, reader: BufReader<ChildStdout>, err_reader: BufReader<ChildStderr>) {
+async fn recieve_messages<R, E>(sender: Sender<Input>, reader: BufReader<R>, err_reader: BufReader<E>, recorder: Option<TrafficRecorder>) 
+where 
+    R: AsyncRead + Unpin + Send,
+    E: AsyncRead + Unpin + Send {
     let mut reader = reader;
     let mut err_reader = err_reader;
-    loop {
-        smol::future::race(
-            process_reader(&mut reader, &sender, |result| {Input::Message(result)} ), 
-            process_reader(&mut err_reader, &sender, |result| {Input::Error(result)} )
-        ).await;
-    }
+    let messages = async {
+        while process_reader(&mut reader, &sender, recorder.as_ref(), |result| {Input::Message(result)} ).await {}
+    };
+    let errors = async {
+        // stderr is only logs, it's not part of the JSON-RPC traffic
+        while process_reader(&mut err_reader, &sender, None, |result| {Input::Error(result)} ).await {}
+        // The server can close stderr and keep running, only stop when stdout is closed.
+        smol::future::pending::<()>().await
+    };
+    // Once the server closes stdout the channel is dropped, which lets the receiver know it disconnected.
+    smol::future::or(messages, errors).await;
 }
 
-async fn write_messages(writer: BufWriter<ChildStdin>, rx: Receiver<JsonRpc>) {
+async fn write_messages<W>(writer: BufWriter<W>, rx: Receiver<JsonRpc>, recorder: Option<TrafficRecorder>) 
+where 
+    W: AsyncWrite + Unpin {
     let mut writer = writer;
-    loop {
-        let result = rx.recv().await;
-        if let Ok(value) = result {
-            send(&mut writer, value).await;
+    // Stops once every sender is dropped
+    while let Ok(value) = rx.recv().await {
+        if let Some(recorder) = recorder.as_ref() {
+            if let Ok(json) = serde_json::to_value(&value) {
+                recorder.record_value(Direction::Outgoing, json);
+            }
         }
+        send(&mut writer, value).await;
     }
 }
 
 /**
  * Starts the transport by spawning to async tasks which will listen to errors and inputs from the lsp.
+ * 
+ * The streams are usually the stdio of the language server process, but any stream can be used.
+ * If a recorder is passed, every message that goes through the transport is recorded.
     */
-pub fn start_transport(
-    reader: BufReader<ChildStdout>,
-    writer: BufWriter<ChildStdin>,
-    err_reader: BufReader<ChildStderr>,
-) -> TransortResult {
+pub fn start_transport<R, W, E>(
+    reader: BufReader<R>,
+    writer: BufWriter<W>,
+    err_reader: BufReader<E>,
+    recorder: Option<TrafficRecorder>,
+) -> TransortResult 
+where 
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+    E: AsyncRead + Unpin + Send + 'static {
     // Receiving messages from the LSP channel
     let (s, receiver) = smol::channel::unbounded::<Input>();
-    let reading_task = smol::spawn(recieve_messages(s, reader, err_reader));
+    let reading_task = smol::spawn(recieve_messages(s, reader, err_reader, recorder.clone()));
 
     let (sender, rx) = smol::channel::unbounded::<JsonRpc>();
-    let writing_task = smol::spawn(write_messages(writer, rx));
+    let writing_task = smol::spawn(write_messages(writer, rx, recorder));
 
     TransortResult {
         reading_task,
         writing_task,
         receiver,
-        sender
+        sender,
+        pending: PendingRequests::default()
     }
 }
 
@@ -344,8 +530,9 @@ pub async fn initialize(
 /**
  * A generic send method for both notifications and requests
     */
-async fn send<T>(writer: &mut BufWriter<ChildStdin>, value: T)
+pub(crate) async fn send<W, T>(writer: &mut BufWriter<W>, value: T)
 where
+    W: AsyncWrite + Unpin,
     T: Serialize,
 {
     let value = serde_json::to_string(&value).unwrap();
@@ -360,7 +547,7 @@ async fn recieve_input<T>(reader: &mut BufReader<T>) -> Input
 where 
 T: smol::io::AsyncRead + Unpin {
     let response = read_message(reader).await;
-    Input::Message(response)
+    Input::Message(response.unwrap_or_default())
 }
 
 async fn recieve_err<T>(reader: &mut BufReader<T>) -> Input
@@ -368,29 +555,32 @@ where
     T: smol::io::AsyncRead + Unpin
 {
     let response = read_message(reader).await;
-    Input::Error(response)
+    Input::Error(response.unwrap_or_default())
 }
 
-async fn read_message<T>(reader: &mut BufReader<T>) -> String
+/**
+ * Reads a message from the stream. Returns `None` when the stream is closed.
+ */
+pub(crate) async fn read_message<T>(reader: &mut BufReader<T>) -> Option<String>
 where
     T: smol::io::AsyncRead + Unpin
 {
 
-    let content_length = get_content_length(reader).await;
+    let content_length = get_content_length(reader).await?;
     match content_length {
-        0 => String::new(),
+        0 => Some(String::new()),
         content_length => {
             let mut content = vec![0; content_length];
-            reader.read_exact(&mut content).await.unwrap();
+            reader.read_exact(&mut content).await.ok()?;
             let result = std::str::from_utf8(&content).unwrap();
-            result.to_owned()
+            Some(result.to_owned())
         },
 
     }
 
 }
 
-async fn get_content_length<T>(reader: &mut BufReader<T>) -> usize 
+async fn get_content_length<T>(reader: &mut BufReader<T>) -> Option<usize> 
 where 
     T: smol::io::AsyncRead + Unpin { 
 let mut buffer = String::new();
@@ -399,8 +589,8 @@ let mut buffer = String::new();
         if buffer == "\r\n" {break}
         
         buffer.clear();
-        if reader.read_line(&mut buffer).await.unwrap() == 0 {
-            return 0
+        if reader.read_line(&mut buffer).await.ok()? == 0 {
+            return None
         };
         let buffer = buffer.trim();
         let parts = buffer.split_once(": ");
@@ -409,6 +599,6 @@ let mut buffer = String::new();
            content_length = value.parse().unwrap()
         }
     }
-    content_length
+    Some(content_length)
 }
 
@@ -0,0 +1,156 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use globset::{Glob, GlobMatcher};
+use lsp_types::{DidChangeWatchedFilesRegistrationOptions, FileChangeType, GlobPattern, OneOf, WatchKind};
+
+use crate::core::watcher::{FileChange, FileEvent};
+
+struct FileWatcher {
+    glob: GlobMatcher,
+    /// Patterns of relative globs are matched against the path relative to this folder.
+    base: Option<PathBuf>,
+    kind: WatchKind,
+}
+
+impl FileWatcher {
+    fn matches(&self, event: &FileEvent, workspace: &Path) -> bool {
+        let kind = match event.change {
+            FileChange::Created => WatchKind::Create,
+            FileChange::Changed => WatchKind::Change,
+            FileChange::Deleted => WatchKind::Delete,
+        };
+        if !self.kind.contains(kind) {
+            return false
+        }
+        if let Some(base) = &self.base {
+            return event.path.strip_prefix(base).is_ok_and(|path| self.glob.is_match(path))
+        }
+        // Servers send both absolute globs and globs relative to the workspace
+        self.glob.is_match(&event.path)
+            || event.path.strip_prefix(workspace).is_ok_and(|path| self.glob.is_match(path))
+    }
+}
+
+/**
+ * The files a language server asked to be notified about through `client/registerCapability`.
+ */
+#[derive(Default)]
+pub struct WatchedFiles {
+    registrations: HashMap<String, Vec<FileWatcher>>,
+}
+
+impl WatchedFiles {
+    /**
+     * Adds the watchers of a registration. Globs that can't be parsed are skipped.
+     */
+    pub fn register(&mut self, id: String, options: DidChangeWatchedFilesRegistrationOptions) {
+        let watchers = options.watchers
+            .into_iter()
+            .filter_map(|watcher| {
+                let (pattern, base) = match watcher.glob_pattern {
+                    GlobPattern::String(pattern) => (pattern, None),
+                    GlobPattern::Relative(relative) => {
+                        let base = match relative.base_uri {
+                            OneOf::Left(folder) => folder.uri,
+                            OneOf::Right(uri) => uri,
+                        };
+                        (relative.pattern, base.to_file_path().ok())
+                    }
+                };
+                let glob = match Glob::new(&pattern) {
+                    Ok(glob) => glob.compile_matcher(),
+                    Err(e) => {
+                        eprintln!("Invalid glob pattern {}: {}", pattern, e);
+                        return None
+                    }
+                };
+                let kind = watcher.kind.unwrap_or(WatchKind::all());
+                Some(FileWatcher { glob, base, kind })
+            })
+            .collect();
+        self.registrations.insert(id, watchers);
+    }
+
+    pub fn unregister(&mut self, id: &str) {
+        self.registrations.remove(id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /**
+     * Returns the events the server wants to know about, converted into LSP file events.
+     */
+    pub fn filter(&self, events: &[FileEvent], workspace: &Path) -> Vec<lsp_types::FileEvent> {
+        events
+            .iter()
+            .filter(|event| {
+                self.registrations
+                    .values()
+                    .flatten()
+                    .any(|watcher| watcher.matches(event, workspace))
+            })
+            .filter_map(|event| {
+                let typ = match event.change {
+                    FileChange::Created => FileChangeType::CREATED,
+                    FileChange::Changed => FileChangeType::CHANGED,
+                    FileChange::Deleted => FileChangeType::DELETED,
+                };
+                let uri = lsp_types::Url::from_file_path(&event.path).ok()?;
+                Some(lsp_types::FileEvent { uri, typ })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod watched_files_tests {
+    use std::path::{Path, PathBuf};
+
+    use lsp_types::{DidChangeWatchedFilesRegistrationOptions, FileChangeType, FileSystemWatcher, GlobPattern, WatchKind};
+    use pretty_assertions::assert_eq;
+
+    use crate::core::watcher::{FileChange, FileEvent};
+
+    use super::WatchedFiles;
+
+    fn options(watchers: Vec<(&str, Option<WatchKind>)>) -> DidChangeWatchedFilesRegistrationOptions {
+        DidChangeWatchedFilesRegistrationOptions {
+            watchers: watchers
+                .into_iter()
+                .map(|(pattern, kind)| FileSystemWatcher { glob_pattern: GlobPattern::String(pattern.to_owned()), kind })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let workspace = Path::new("/ws");
+        let mut watched = WatchedFiles::default();
+        watched.register("1".to_owned(), options(vec![
+            ("**/*.rs", None),
+            ("/ws/Cargo.toml", Some(WatchKind::Change)),
+        ]));
+
+        let events = vec![
+            FileEvent::new(PathBuf::from("/ws/src/main.rs"), FileChange::Deleted),
+            FileEvent::new(PathBuf::from("/ws/Cargo.toml"), FileChange::Changed),
+            FileEvent::new(PathBuf::from("/ws/Cargo.toml"), FileChange::Deleted),
+            FileEvent::new(PathBuf::from("/ws/README.md"), FileChange::Changed),
+        ];
+        let filtered: Vec<(String, FileChangeType)> = watched
+            .filter(&events, workspace)
+            .into_iter()
+            .map(|event| (event.uri.to_string(), event.typ))
+            .collect();
+        assert_eq!(filtered, vec![
+            ("file:///ws/src/main.rs".to_owned(), FileChangeType::DELETED),
+            ("file:///ws/Cargo.toml".to_owned(), FileChangeType::CHANGED),
+        ]);
+
+        watched.unregister("1");
+        assert!(watched.is_empty());
+        assert!(watched.filter(&events, workspace).is_empty());
+    }
+}
@@ -18,4 +18,46 @@ impl button::StyleSheet for MenuButton {
         button::Appearance { shadow_offset: Vector::default(), background: Some(Background::from(Color::from_rgba8(70, 77, 89, 1.0))), border_radius: BorderRadius::from(0.0), border_width: 0.0, border_color: Color::TRANSPARENT, text_color: Color::WHITE }
     }
 
-}
\ No newline at end of file
+}
+
+/**
+ * A menu button that stands out, used for the open file in the file explorer.
+ */
+#[derive(Debug, Clone, Copy)]
+
+pub struct ActiveButton;
+
+impl button::StyleSheet for ActiveButton {
+    type Style = Theme;
+
+
+    fn active(&self, _style: &Self::Style) -> button::Appearance {
+        button::Appearance { shadow_offset: Vector::default(), background: Some(Background::from(Color::from_rgba8(55, 62, 74, 1.0))), border_radius: BorderRadius::from(0.0), border_width: 0.0, border_color: Color::TRANSPARENT, text_color: Color::WHITE }
+    }
+
+    fn hovered(&self, _style: &Self::Style) -> button::Appearance {
+        button::Appearance { shadow_offset: Vector::default(), background: Some(Background::from(Color::from_rgba8(70, 77, 89, 1.0))), border_radius: BorderRadius::from(0.0), border_width: 0.0, border_color: Color::TRANSPARENT, text_color: Color::WHITE }
+    }
+
+}
+
+/**
+ * A button without a background, used for the fold toggles of the gutter.
+ */
+#[derive(Debug, Clone, Copy)]
+
+pub struct GutterButton;
+
+impl button::StyleSheet for GutterButton {
+    type Style = Theme;
+
+
+    fn active(&self, _style: &Self::Style) -> button::Appearance {
+        button::Appearance { shadow_offset: Vector::default(), background: None, border_radius: BorderRadius::from(0.0), border_width: 0.0, border_color: Color::TRANSPARENT, text_color: Color::from_rgb8(153, 153, 153) }
+    }
+
+    fn hovered(&self, _style: &Self::Style) -> button::Appearance {
+        button::Appearance { shadow_offset: Vector::default(), background: None, border_radius: BorderRadius::from(0.0), border_width: 0.0, border_color: Color::TRANSPARENT, text_color: Color::WHITE }
+    }
+
+}
@@ -63,4 +63,42 @@ impl container::StyleSheet for FloatingContainer {
             text_color: Some(Color::WHITE),
         }
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, Clone)]
+
+pub struct NotificationContainer;
+
+impl container::StyleSheet for NotificationContainer {
+    type Style = Theme;
+
+    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+        container::Appearance {
+            background: Some(Background::from(Color::from_rgb8(33, 37, 43))),
+            border_radius: BorderRadius::from(4.0),
+            border_width: 1.0,
+            border_color: Color::from_rgb8(62, 68, 81),
+            text_color: Some(Color::WHITE),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+
+pub struct PaneContainer {
+    /// The focused pane is outlined once the editor is split
+    pub focused: bool,
+}
+
+impl container::StyleSheet for PaneContainer {
+    type Style = Theme;
+
+    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+        container::Appearance {
+            background: None,
+            border_radius: BorderRadius::from(0.0),
+            border_width: 1.0,
+            border_color: if self.focused { Color::from_rgb8(62, 68, 81) } else { Color::TRANSPARENT },
+            text_color: None,
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use iced::{widget::{text, container}, Color, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{core::document::LARGE_FILE_SIZE, styles::container::NotificationContainer, Message};
+
+/**
+ * Tells the user that the open file is in large file mode, so the missing colors don't look like a bug.
+ */
+pub fn large_file_banner() -> Element<'static, Message> {
+    let message = format!(
+        "This file is larger than {} MB. It isn't highlighted and the language servers don't get it.",
+        LARGE_FILE_SIZE / (1024 * 1024)
+    );
+    let theme = Box::new(NotificationContainer);
+    container(text(message).size(Pixels::from(13.0)).style(Color::from_rgb8(229, 192, 123)))
+    .style(theme::Container::Custom(theme))
+    .padding(Padding::from([4, 10]))
+    .width(Length::Fill)
+    .into()
+}
@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use iced::{widget::{text, container, button, row, scrollable, scrollable::Properties, column, Column}, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{core::file_tree::FileTree, styles::{button::{ActiveButton, MenuButton}, container::MenuContainer}, Message};
+
+/**
+ * The actions of the file explorer's toolbar. They apply to the selected entry, or to the workspace if there is none.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplorerAction {
+    NewFile,
+    NewFolder,
+    Rename,
+    Delete,
+}
+
+/**
+ * Shows the files of the workspace as a tree. The open file is highlighted.
+ */
+pub fn file_explorer(tree: &FileTree, active: Option<&Path>, selected: Option<&Path>) -> Element<'static, Message> {
+    let button_theme = Box::new(MenuButton);
+    let toolbar = row!(
+        button(text("+File").size(Pixels::from(12.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([5, 6]))
+        .on_press(Message::ExplorerAction(ExplorerAction::NewFile)),
+
+        button(text("+Folder").size(Pixels::from(12.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([5, 6]))
+        .on_press(Message::ExplorerAction(ExplorerAction::NewFolder)),
+
+        button(text("Rename").size(Pixels::from(12.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([5, 6]))
+        .on_press(Message::ExplorerAction(ExplorerAction::Rename)),
+
+        button(text("Delete").size(Pixels::from(12.0))).style(theme::Button::Custom(button_theme))
+        .padding(Padding::from([5, 6]))
+        .on_press(Message::ExplorerAction(ExplorerAction::Delete)),
+    );
+
+    let mut entries = Column::new();
+    for (depth, entry) in tree.visible() {
+        let marker = match (entry.is_dir, tree.is_expanded(&entry.path)) {
+            (true, true) => "v ",
+            (true, false) => "> ",
+            (false, _) => "  ",
+        };
+        let is_active = active == Some(entry.path.as_path()) || selected == Some(entry.path.as_path());
+        let style = if is_active {
+            theme::Button::Custom(Box::new(ActiveButton))
+        } else {
+            theme::Button::Custom(Box::new(MenuButton))
+        };
+        entries = entries.push(
+            button(text(format!("{}{}", marker, entry.name)).size(Pixels::from(13.0)))
+            .width(Length::Fill)
+            .padding(Padding::from([3, 6, 3, 8 + 12 * depth as u16]))
+            .style(style)
+            .on_press(Message::ExplorerSelect(entry.path.clone()))
+        );
+    }
+
+    let theme = Box::new(MenuContainer);
+    container(
+        column!(
+            toolbar,
+            scrollable(entries)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .direction(scrollable::Direction::Vertical(
+                Properties::default().scroller_width(5.0).width(5.0),
+            ))
+        )
+    )
+    .style(theme::Container::Custom(theme))
+    .width(Length::Fixed(230.0))
+    .height(Length::Fill)
+    .into()
+}
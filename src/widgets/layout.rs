@@ -7,18 +7,21 @@ use crate::{styles::{button::MenuButton, container::MenuContainer, text::TextSav
 
 
 
-pub fn layout<'a> (child: Element<'a, Message>, modal: Option<Element<'a, Message>>, is_saved: bool)->  Element<'a, Message> 
+pub fn layout<'a> (child: Element<'a, Message>, modal: Option<Element<'a, Message>>, notifications: Option<Element<'a, Message>>, is_saved: bool, autosave: bool)->  Element<'a, Message> 
 {
     let theme = Box::new(MenuContainer);
     
-    column!(
-        container(navbar(is_saved)).width(Length::Fill)
+    let mut content = column!(
+        container(navbar(is_saved, autosave)).width(Length::Fill)
         .style(theme::Container::Custom(theme.clone())),
-        main_view(child, modal),
-    ).into()
+    );
+    if let Some(notifications) = notifications {
+        content = content.push(notifications);
+    }
+    content.push(main_view(child, modal)).into()
 }
 
-fn navbar(is_saved: bool) -> Element<'static, Message>{
+fn navbar(is_saved: bool, autosave: bool) -> Element<'static, Message>{
     let theme = Box::new(MenuButton);
     let text_container = Box::new(TextSaved);
     let mut row = row!(
@@ -39,6 +42,11 @@ fn navbar(is_saved: bool) -> Element<'static, Message>{
         .padding(Padding::from([7, 12, 10, 12]))
         .on_press(Message::Save),
 
+        button(text(if autosave { "Autosave: On" } else { "Autosave: Off" }).size(Pixels::from(14.0)))
+        .style(theme::Button::Custom(theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::ToggleAutosave),
+
     )
     .padding(Padding::from([0, 0, 0, 15]))
     .align_items(iced::Alignment::Start);
@@ -1,7 +1,9 @@
 use iced::widget::scrollable::{Id, Properties};
-use iced::widget::{column, container, scrollable , text};
+use iced::widget::{button, column, container, row, scrollable , text, vertical_space};
 use iced::{Element};
 use iced::{alignment, theme, Background, BorderRadius, Color, Length, Padding, Pixels, Theme};
+use crate::core::buffer::GutterRow;
+use crate::styles::button::GutterButton;
 use crate::Message;
 
 pub mod modal;
@@ -11,6 +13,11 @@ pub mod view_port;
 pub mod main_view;
 pub mod floating_text;
 pub mod layout;
+pub mod file_explorer;
+pub mod status_bar;
+pub mod notification;
+pub mod banner;
+pub mod panes;
 
 struct CodeLineTheme;
 
@@ -31,10 +38,15 @@ impl container::StyleSheet for CodeLineTheme {
 
 
 
-pub fn line_number(number_of_lines: usize, font_size: f32, height: f32, id: Id) -> Element<'static, Message> {
+/**
+ * Shows the numbers of the visible rows, the space above them stands in for the rows that are scrolled past.
+ * Rows that continue a wrapped line are left blank, lines that start a fold get a toggle next to their number.
+ */
+pub fn line_number(first_row: usize, rows: Vec<GutterRow>, font_size: f32, height: f32, id: Id) -> Element<'static, Message> {
     let mut lines: Vec<Element<'static, Message>> = Vec::new();
     let box_height = text::LineHeight::default().to_absolute(Pixels(font_size)).0;
-    for i in 1..number_of_lines.saturating_add(1) {
+    lines.push(vertical_space(Length::Fixed(box_height * first_row as f32)).into());
+    for gutter_row in rows {
         let padding = Padding {
             top: 0.0,
             bottom: 0.0,
@@ -42,13 +54,23 @@ pub fn line_number(number_of_lines: usize, font_size: f32, height: f32, id: Id)
             right: 5.0,
         };
 
-        let container = container(text(i).size(font_size))
+        let label = gutter_row.line.map(|line| (line + 1).to_string()).unwrap_or_default();
+        let number = container(text(label).size(font_size))
             .center_x()
             .align_y(alignment::Vertical::Top)
-            .width(Length::Fixed(80.0))
+            .width(Length::Fixed(64.0))
             .padding(padding)
             .height(box_height);
-        lines.push(container.into())
+        let toggle: Element<'static, Message> = match gutter_row.line.zip(gutter_row.fold) {
+            Some((line, is_folded)) => button(text(if is_folded { "▸" } else { "▾" }).size(font_size))
+                .padding(0)
+                .style(theme::Button::Custom(Box::new(GutterButton)))
+                .on_press(Message::ToggleFold(line))
+                .into(),
+            None => text("").into(),
+        };
+        let toggle = container(toggle).width(Length::Fixed(16.0)).height(box_height);
+        lines.push(row!(number, toggle).into())
     }
     let theme = Box::new(CodeLineTheme);
     scrollable(
@@ -0,0 +1,54 @@
+use iced::{widget::{text, container, button, scrollable, scrollable::Properties, column, Column}, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{core::clipboard::Clip, styles::{button::{ActiveButton, MenuButton}, container::NormalContainer}, Message};
+
+/**
+ * The id of the list of copies, used to keep the selected copy in view.
+ */
+pub fn clip_list_id() -> scrollable::Id {
+    scrollable::Id::new("clipboard_history")
+}
+
+/**
+ * Lists the recent copies, most recent first. The selected copy is pasted with Enter.
+ */
+pub fn clipboard_history(clips: &[Clip], selected: usize) -> Element<'static, Message> {
+    let mut list = Column::new().spacing(2);
+    for (index, clip) in clips.iter().enumerate() {
+        let style = if index == selected {
+            theme::Button::Custom(Box::new(ActiveButton))
+        } else {
+            theme::Button::Custom(Box::new(MenuButton))
+        };
+        list = list.push(
+            button(text(clip.preview()).size(Pixels::from(13.0)))
+            .width(Length::Fill)
+            .padding(Padding::from([3, 12]))
+            .style(style)
+            .on_press(Message::PasteClip(index))
+        );
+    }
+    if clips.is_empty() {
+        list = list.push(container(text("Nothing was copied yet").size(Pixels::from(13.0))).padding(Padding::from([3, 12])));
+    }
+
+    let content = column!(
+        container(text("Paste a recent copy").size(Pixels::from(14.0)))
+        .padding(Padding::from([10, 12])),
+        scrollable(list)
+        .id(clip_list_id())
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .direction(scrollable::Direction::Vertical(
+            Properties::default().scroller_width(7.0).width(7.0),
+        ))
+    );
+
+    let theme = Box::new(NormalContainer);
+    container(content.spacing(5).padding(Padding::from([10, 0])))
+    .style(theme::Container::Custom(theme))
+    .height(Length::Fixed(450.0))
+    .width(Length::Fixed(600.0))
+    .into()
+}
@@ -0,0 +1,53 @@
+use encoding_rs::Encoding;
+use iced::{widget::{text, container, button, row, scrollable, scrollable::Properties, column, Column}, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{core::file_format::ENCODINGS, styles::{button::{ActiveButton, MenuButton}, container::NormalContainer}, widgets::status_bar::FormatAction, Message};
+
+/**
+ * Lets the user read the open file again with another encoding, or save it with another encoding.
+ */
+pub fn encoding_picker(current: &'static Encoding) -> Element<'static, Message> {
+    let mut list = Column::new().spacing(2);
+    for encoding in ENCODINGS {
+        let name_style = if encoding == current {
+            theme::Button::Custom(Box::new(ActiveButton))
+        } else {
+            theme::Button::Custom(Box::new(MenuButton))
+        };
+        list = list.push(row!(
+            button(text(encoding.name()).size(Pixels::from(13.0)))
+            .width(Length::Fill)
+            .padding(Padding::from([3, 12]))
+            .style(name_style),
+
+            button(text("Reopen").size(Pixels::from(13.0)))
+            .padding(Padding::from([3, 12]))
+            .style(theme::Button::Custom(Box::new(MenuButton)))
+            .on_press(Message::Format(FormatAction::ReopenWith(encoding))),
+
+            button(text("Save with").size(Pixels::from(13.0)))
+            .padding(Padding::from([3, 12]))
+            .style(theme::Button::Custom(Box::new(MenuButton)))
+            .on_press(Message::Format(FormatAction::SaveWith(encoding))),
+        ));
+    }
+
+    let content = column!(
+        container(text("Reopen the file with an encoding, or save it with one").size(Pixels::from(14.0)))
+        .padding(Padding::from([10, 12])),
+        scrollable(list)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .direction(scrollable::Direction::Vertical(
+            Properties::default().scroller_width(7.0).width(7.0),
+        ))
+    );
+
+    let theme = Box::new(NormalContainer);
+    container(content.spacing(5).padding(Padding::from([10, 0])))
+    .style(theme::Container::Custom(theme))
+    .height(Length::Fixed(450.0))
+    .width(Length::Fixed(500.0))
+    .into()
+}
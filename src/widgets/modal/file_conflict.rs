@@ -0,0 +1,70 @@
+use iced::{widget::{text, container, button, row, scrollable, scrollable::Properties, column, Column}, Color, Element, Font, Length, Padding, Pixels};
+use iced_style::theme;
+use similar::{ChangeTag, TextDiff};
+
+use crate::{Message, styles::{button::MenuButton, container::NormalContainer}};
+
+/**
+ * Asks what to do with a file that changed on disk while it had unsaved edits.
+ */
+pub fn file_conflict(file: &str, mine: &str, theirs: &str, show_diff: bool) -> Element<'static, Message> {
+    let button_theme = Box::new(MenuButton);
+    let diff_label = if show_diff { "Hide diff" } else { "Show diff" };
+    let actions = row!(
+        button(text("Keep mine").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::KeepMine),
+
+        button(text("Take theirs").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::TakeTheirs),
+
+        button(text(diff_label).size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::ToggleConflictDiff),
+    );
+
+    let mut content = column!(
+        container(text(format!("{} changed on disk and has unsaved changes.", file)).size(Pixels::from(14.0)))
+        .padding(Padding::from([10, 12])),
+        actions
+    );
+
+    if show_diff {
+        content = content.push(
+            scrollable(diff(mine, theirs))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .direction(scrollable::Direction::Vertical(
+                Properties::default().scroller_width(7.0).width(7.0),
+            ))
+        );
+    }
+
+    let theme = Box::new(NormalContainer);
+    let height = if show_diff { 700.0 } else { 120.0 };
+    container(content.spacing(0).padding(Padding::from([10, 0])))
+    .style(theme::Container::Custom(theme))
+    .height(Length::Fixed(height))
+    .width(Length::Fixed(900.0))
+    .into()
+}
+
+/**
+ * Shows the lines removed from the buffer in red and the lines added on disk in green.
+ */
+pub fn diff(mine: &str, theirs: &str) -> Column<'static, Message> {
+    let mut lines = Column::new().padding(Padding::from([0, 12]));
+    for change in TextDiff::from_lines(mine, theirs).iter_all_changes() {
+        let (sign, color) = match change.tag() {
+            ChangeTag::Delete => ("-", Color::from_rgb8(239, 48, 84)),
+            ChangeTag::Insert => ("+", Color::from_rgb8(152, 195, 121)),
+            ChangeTag::Equal => (" ", Color::from_rgb8(171, 178, 191)),
+        };
+        let line = change.value().trim_end_matches(['\r', '\n']);
+        lines = lines.push(
+            text(format!("{} {}", sign, line)).font(Font::MONOSPACE).size(Pixels::from(13.0)).style(color)
+        );
+    }
+    lines
+}
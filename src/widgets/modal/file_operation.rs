@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use iced::{widget::{text, text_input, container, button, row, column}, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{Message, styles::{button::MenuButton, container::NormalContainer}};
+
+/**
+ * A change to the files of the workspace, waiting to be confirmed.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileAction {
+    /// Creates a file in the folder
+    NewFile(PathBuf),
+    /// Creates a folder in the folder
+    NewFolder(PathBuf),
+    /// Renames or moves the file or folder
+    Rename(PathBuf),
+    Delete(PathBuf),
+}
+
+/**
+ * Asks for the name of the new path, or for a confirmation before deleting.
+ */
+pub fn file_operation(action: &FileAction, filter: &str) -> Element<'static, Message> {
+    let (title, placeholder) = match action {
+        FileAction::NewFile(folder) => (format!("New file in {}", folder.display()), Some("File name")),
+        FileAction::NewFolder(folder) => (format!("New folder in {}", folder.display()), Some("Folder name")),
+        FileAction::Rename(path) => (format!("Rename or move {}", path.display()), Some("New path, relative to the workspace")),
+        FileAction::Delete(path) => (format!("Delete {}? This can't be undone.", path.display()), None),
+    };
+    let confirm_label = match action {
+        FileAction::Delete(_) => "Delete",
+        FileAction::Rename(_) => "Rename",
+        _ => "Create",
+    };
+
+    let button_theme = Box::new(MenuButton);
+    let actions = row!(
+        button(text(confirm_label).size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::ConfirmFileAction),
+
+        button(text("Cancel").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::DismissModal),
+    );
+
+    let mut content = column!(
+        container(text(title).size(Pixels::from(14.0)))
+        .padding(Padding::from([10, 12])),
+    );
+    if let Some(placeholder) = placeholder {
+        content = content.push(
+            text_input(placeholder, filter)
+            .on_input(Message::ModalFilter)
+            .on_submit(Message::ConfirmFileAction)
+        );
+    }
+    content = content.push(actions);
+
+    let theme = Box::new(NormalContainer);
+    container(content.spacing(5).padding(Padding::from([10, 0])))
+    .style(theme::Container::Custom(theme))
+    .height(Length::Fixed(150.0))
+    .width(Length::Fixed(600.0))
+    .into()
+}
@@ -1,9 +1,10 @@
-use fuzzy_matcher::{skim::SkimMatcher, FuzzyMatcher};
-use iced::{widget::{text,text_input, container, button,scrollable, scrollable::Properties, column, Column}, Element, Length, Padding};
+use encoding_rs::Encoding;
+use iced::{widget::{text,text_input, container, button,scrollable, scrollable::Properties, column, Column, Row}, Color, Element, Length, Padding, Pixels};
 use iced_style::theme;
-use std::{fs, collections::VecDeque, path::Path};
 
-use crate::{Message, styles::{button::MenuButton, container::NormalContainer}};
+use crate::{Message, core::{clipboard::Clip, file_index::FileMatch, recovery::Snapshot}, lsp::inspector::TrafficRecorder, styles::{button::{ActiveButton, MenuButton}, container::NormalContainer}};
+
+use super::{clipboard_history::clipboard_history, file_conflict::file_conflict, file_operation::{file_operation, FileAction}, encoding_picker::encoding_picker, lsp_inspector::lsp_inspector, recovery::recovery};
 
 /**
  * A modal that can be of different types
@@ -11,60 +12,109 @@ use crate::{Message, styles::{button::MenuButton, container::NormalContainer}};
 
 #[derive(Debug)]
 pub enum Modal {
-    FileSelector(String)
+    /// The files of the workspace that match the filter, computed when the filter changes
+    FileSelector {
+        matches: Vec<FileMatch>,
+        selected: usize,
+        is_indexing: bool
+    },
+    LspInspector {
+        recorder: TrafficRecorder,
+        selected: Option<u64>
+    },
+    /// The open file changed on disk while it had unsaved changes
+    FileConflict {
+        file: String,
+        mine: String,
+        theirs: String,
+        show_diff: bool
+    },
+    /// A file operation started from the file explorer
+    FileOperation(FileAction),
+    /// The unsaved changes left behind by a crash
+    Recovery {
+        snapshots: Vec<Snapshot>,
+        selected: usize,
+        /// The content of the selected snapshot's file on disk
+        on_disk: String
+    },
+    /// Changes the encoding of the open file
+    EncodingPicker(&'static Encoding),
+    /// The recent copies, one of them is pasted
+    ClipboardHistory {
+        clips: Vec<Clip>,
+        selected: usize
+    }
 }
 
 impl Modal {
     pub fn show(&self, filter: &str) -> Element<'static, Message> {
         match self {
-            Modal::FileSelector(file) => {
-                file_selector(file, filter)
+            Modal::FileSelector { matches, selected, is_indexing } => {
+                file_selector(matches, *selected, filter, *is_indexing)
+            }
+            Modal::LspInspector { recorder, selected } => {
+                lsp_inspector(recorder, *selected, filter)
+            }
+            Modal::FileConflict { file, mine, theirs, show_diff } => {
+                file_conflict(file, mine, theirs, *show_diff)
+            }
+            Modal::FileOperation(action) => {
+                file_operation(action, filter)
+            }
+            Modal::Recovery { snapshots, selected, on_disk } => {
+                recovery(snapshots, *selected, on_disk)
+            }
+            Modal::EncodingPicker(current) => {
+                encoding_picker(current)
+            }
+            Modal::ClipboardHistory { clips, selected } => {
+                clipboard_history(clips, *selected)
             }
         }
     }
 }
 
-fn macthes(choice: &str, pattern: &str) -> bool {
-    let matcher = SkimMatcher::default();
-    if pattern.is_empty() {
-        return true
-    }
-    if let Some(value) = matcher.fuzzy_match(choice, pattern) {
-        return value > 0
-    }
-    false
+/**
+ * The id of the list of files, used to keep the selected file in view.
+ */
+pub fn file_list_id() -> scrollable::Id {
+    scrollable::Id::new("file_selector")
 }
 
-pub fn file_selector(path: &str, filter: &str) -> Element<'static, Message> {
-    let excluded_list = ["target", ".git"];
-    let files = get_files(path, &excluded_list);
-
+/**
+ * Lists the files that match the filter. The selected file can be opened with Enter.
+ */
+pub fn file_selector(matches: &[FileMatch], selected: usize, filter: &str, is_indexing: bool) -> Element<'static, Message> {
     let mut buttons = Column::new();
-    
-    for file in files {
-        let filename = file.clone();
-        let relative_path = filename.replace(path, "");
-        
-        let button_theme = Box::new(MenuButton);
-        if macthes(&file, filter) {
-            buttons = buttons.push(
-                button(text(&relative_path))
-                .width(Length::Fill)
-                .style(theme::Button::Custom(button_theme))
-                
-                .on_press(Message::Open(file.to_owned()))
-            )
-        }
+
+    for (index, file) in matches.iter().enumerate() {
+        let style = if index == selected {
+            theme::Button::Custom(Box::new(ActiveButton))
+        } else {
+            theme::Button::Custom(Box::new(MenuButton))
+        };
+        buttons = buttons.push(
+            button(highlighted(&file.relative, &file.indices))
+            .width(Length::Fill)
+            .style(style)
+            .on_press(Message::Open(file.path.to_string_lossy().into_owned()))
+        )
     }
-    
-    
+
+    if matches.is_empty() {
+        let message = if is_indexing { "Indexing the workspace..." } else { "No matching files" };
+        buttons = buttons.push(container(text(message).size(Pixels::from(14.0))).padding(Padding::from([5, 10])));
+    }
+
     let theme = Box::new(NormalContainer);
     container(
         column!(
-            text_input("Filter files", filter).on_input(Message::FileFilter),
+            text_input("Filter files", filter).on_input(Message::ModalFilter),
             scrollable(
                 buttons
             )
+            .id(file_list_id())
             .width(Length::Fill)
             .direction(scrollable::Direction::Vertical(
                 Properties::default().scroller_width(0.0).width(0.0),
@@ -79,47 +129,26 @@ pub fn file_selector(path: &str, filter: &str) -> Element<'static, Message> {
     .into()
 }
 
-
 /**
- * Uses breadth first search to find all the folders in a given directory. Does not search for the files within the excluded list.
+ * Shows the path with the characters that matched the filter in another color.
  */
-pub fn get_files(path: &str, excluded_list: &[&str]) -> Vec<String>  {
-    let mut files: Vec<String> = Vec::new();
-    let mut folders: VecDeque<String> = VecDeque::new();
-    
-    folders.push_front(path.to_owned());
-    
-    while !folders.is_empty() {
-        let folder_string = folders.pop_front().unwrap();
-        let folder = Path::new(&folder_string);
-        let paths = fs::read_dir(folder).unwrap();
-
-        for path in paths {
-            let entry = path.unwrap().path();
-            if entry.is_dir() {
-                let is_in_list = excluded_list.iter().find(|filename| &&entry.file_name().unwrap().to_str().unwrap() == filename);
-                if is_in_list.is_some() {
-                    continue
-                }
-                folders.push_back(entry.to_str().unwrap().to_owned())
-            }
-            else if entry.is_file() {
-                files.push(entry.to_str().unwrap().to_owned())
-            }
+fn highlighted(path: &str, indices: &[usize]) -> Row<'static, Message> {
+    let matched_color = Color::from_rgb8(97, 175, 239);
+    let mut row = Row::new();
+    let mut part = String::new();
+    let mut is_matched = false;
+    for (index, character) in path.chars().enumerate() {
+        let matched = indices.contains(&index);
+        if matched != is_matched && !part.is_empty() {
+            let segment = text(std::mem::take(&mut part));
+            row = row.push(if is_matched { segment.style(matched_color) } else { segment });
         }
+        is_matched = matched;
+        part.push(character);
     }
-    files
-}
-
-#[cfg(test)]
-mod file_selector_tests {
-    use pretty_assertions::assert_eq;
-    use crate::widgets::modal::file_selector::get_files;
-
-    #[test]
-    fn test_breadth_first_search() {
-        let excluded_list = ["target", ".git"];
-        let files = get_files("./", &excluded_list);
-        assert_eq!(files.get(0), Some(&"./Cargo.lock".to_owned()));
+    if !part.is_empty() {
+        let segment = text(part);
+        row = row.push(if is_matched { segment.style(matched_color) } else { segment });
     }
-}
\ No newline at end of file
+    row
+}
@@ -0,0 +1,74 @@
+use iced::{widget::{text, text_input, container, button, row, scrollable, scrollable::Properties, column, Column}, Color, Element, Font, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{Message, lsp::inspector::{Direction, TrafficRecorder}, styles::{button::MenuButton, container::{FloatingContainer, NormalContainer}}};
+
+/**
+ * Shows the messages kept by the recorder. Clicking on a message shows its pretty printed JSON.
+ */
+pub fn lsp_inspector(recorder: &TrafficRecorder, selected: Option<u64>, filter: &str) -> Element<'static, Message> {
+    let mut entries = Column::new();
+    for entry in recorder.search(filter) {
+        let color = match entry.direction {
+            Direction::Outgoing => Color::from_rgb8(97, 175, 239),
+            Direction::Incoming => Color::from_rgb8(229, 192, 123),
+        };
+        let button_theme = Box::new(MenuButton);
+        entries = entries.push(
+            button(text(entry.summary()).font(Font::MONOSPACE).size(Pixels::from(13.0)).style(color))
+            .width(Length::Fill)
+            .style(theme::Button::Custom(button_theme))
+            .on_press(Message::InspectTraffic(entry.seq))
+        );
+
+        if selected == Some(entry.seq) {
+            let theme = Box::new(FloatingContainer);
+            entries = entries.push(
+                container(text(entry.pretty_body()).font(Font::MONOSPACE).size(Pixels::from(12.0)))
+                .width(Length::Fill)
+                .padding(Padding::from([10, 20]))
+                .style(theme::Container::Custom(theme))
+            );
+        }
+    }
+
+    let recording_label = if recorder.is_enabled() { "Pause" } else { "Resume" };
+    let button_theme = Box::new(MenuButton);
+    let actions = row!(
+        button(text(recording_label).size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::ToggleTrafficRecording),
+
+        button(text("Clear").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::ClearTraffic),
+
+        button(text("Export").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::ExportTraffic),
+
+        container(text(format!("{} messages", recorder.len())).size(Pixels::from(12.0)))
+        .padding(Padding::from([10, 12]))
+    );
+
+    let theme = Box::new(NormalContainer);
+    container(
+        column!(
+            text_input("Search messages", filter).on_input(Message::ModalFilter),
+            actions,
+            scrollable(
+                entries
+            )
+            .width(Length::Fill)
+            .direction(scrollable::Direction::Vertical(
+                Properties::default().scroller_width(7.0).width(7.0),
+            ))
+        )
+        .spacing(0)
+        .padding(Padding::from([10, 0]))
+    )
+    .style(theme::Container::Custom(theme))
+    .height(Length::Fixed(700.0))
+    .width(Length::Fixed(900.0))
+    .into()
+}
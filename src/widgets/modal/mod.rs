@@ -1,2 +1,8 @@
 pub mod modal_overlay;
-pub mod file_selector;
\ No newline at end of file
+pub mod file_selector;
+pub mod lsp_inspector;
+pub mod file_conflict;
+pub mod file_operation;
+pub mod recovery;
+pub mod encoding_picker;
+pub mod clipboard_history;
@@ -0,0 +1,66 @@
+use iced::{widget::{text, container, button, row, scrollable, scrollable::Properties, column, Column}, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{core::recovery::Snapshot, styles::{button::{ActiveButton, MenuButton}, container::NormalContainer}, Message};
+
+use super::file_conflict::diff;
+
+/**
+ * Offers the unsaved changes that were left behind by a crash, with a diff of the selected one against the file on disk.
+ */
+pub fn recovery(snapshots: &[Snapshot], selected: usize, on_disk: &str) -> Element<'static, Message> {
+    let button_theme = Box::new(MenuButton);
+    let actions = row!(
+        button(text("Recover").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::Recover),
+
+        button(text("Discard").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme.clone()))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::DiscardSnapshot),
+
+        button(text("Later").size(Pixels::from(14.0))).style(theme::Button::Custom(button_theme))
+        .padding(Padding::from([7, 12, 10, 12]))
+        .on_press(Message::DismissModal),
+    );
+
+    let mut list = Column::new();
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        let style = if index == selected {
+            theme::Button::Custom(Box::new(ActiveButton))
+        } else {
+            theme::Button::Custom(Box::new(MenuButton))
+        };
+        list = list.push(
+            button(text(snapshot.name()).size(Pixels::from(13.0)))
+            .width(Length::Fill)
+            .padding(Padding::from([3, 12]))
+            .style(style)
+            .on_press(Message::RecoverySelect(index))
+        );
+    }
+
+    let mut content = column!(
+        container(text("These files had unsaved changes when the editor stopped.").size(Pixels::from(14.0)))
+        .padding(Padding::from([10, 12])),
+        list,
+        actions
+    );
+    if let Some(snapshot) = snapshots.get(selected) {
+        content = content.push(
+            scrollable(diff(on_disk, &snapshot.text))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .direction(scrollable::Direction::Vertical(
+                Properties::default().scroller_width(7.0).width(7.0),
+            ))
+        );
+    }
+
+    let theme = Box::new(NormalContainer);
+    container(content.spacing(5).padding(Padding::from([10, 0])))
+    .style(theme::Container::Custom(theme))
+    .height(Length::Fixed(700.0))
+    .width(Length::Fixed(900.0))
+    .into()
+}
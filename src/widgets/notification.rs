@@ -0,0 +1,57 @@
+use std::time::Instant;
+
+use iced::{widget::{text, container, button, row, Column}, Color, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{styles::{button::MenuButton, container::NotificationContainer}, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationLevel {
+    /// Goes away on its own after a while
+    Info,
+    /// Stays until it's dismissed
+    Error,
+}
+
+/**
+ * A message for the user, like a file that couldn't be saved.
+ */
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub id: u64,
+    pub level: NotificationLevel,
+    pub message: String,
+    pub created: Instant,
+}
+
+/**
+ * Shows the notifications below the menu, the most recent last.
+ */
+pub fn notifications(notifications: &[Notification]) -> Option<Element<'static, Message>> {
+    if notifications.is_empty() {
+        return None
+    }
+    let mut list = Column::new().spacing(4).padding(Padding::from([6, 15]));
+    for notification in notifications {
+        let color = match notification.level {
+            NotificationLevel::Info => Color::from_rgb8(171, 178, 191),
+            NotificationLevel::Error => Color::from_rgb8(239, 48, 84),
+        };
+        let theme = Box::new(NotificationContainer);
+        list = list.push(
+            container(
+                row!(
+                    text(&notification.message).size(Pixels::from(13.0)).style(color).width(Length::Fill),
+                    button(text("x").size(Pixels::from(12.0)))
+                    .style(theme::Button::Custom(Box::new(MenuButton)))
+                    .padding(Padding::from([2, 8]))
+                    .on_press(Message::DismissNotification(notification.id)),
+                )
+            )
+            .style(theme::Container::Custom(theme))
+            .padding(Padding::from([4, 10]))
+            .width(Length::Fill)
+        );
+    }
+    Some(list.into())
+}
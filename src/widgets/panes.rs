@@ -0,0 +1,220 @@
+use iced::widget::{pane_grid, scrollable};
+use iced::Size;
+
+use super::textbox::Textbox;
+
+/**
+ * A pane of the editor area.
+ */
+pub struct EditorPane {
+    /// Tells the scrollables of the pane apart from the ones of the other panes.
+    id: usize,
+    /// `None` while the pane is focused, its textbox is the editor's then.
+    text_box: Option<Textbox>,
+}
+
+impl EditorPane {
+    pub fn text_box(&self) -> Option<&Textbox> {
+        self.text_box.as_ref()
+    }
+
+    pub fn text_scroll_id(&self) -> scrollable::Id {
+        scrollable::Id::new(format!("text-{}", self.id))
+    }
+
+    pub fn gutter_scroll_id(&self) -> scrollable::Id {
+        scrollable::Id::new(format!("gutter-{}", self.id))
+    }
+}
+
+/**
+ * The panes the editor area is split into. Each one has a textbox of its own, with its own cursor, selection and
+ * window. The textboxes of the panes that show the same document share its text, its highlighter and its history.
+ *
+ * The textbox of the focused pane is the one the editor edits, it's handed over when another pane is focused.
+ */
+pub struct Panes {
+    state: pane_grid::State<EditorPane>,
+    focused: pane_grid::Pane,
+    next_id: usize,
+}
+
+impl Default for Panes {
+    fn default() -> Self {
+        let (state, focused) = pane_grid::State::new(EditorPane { id: 0, text_box: None });
+        Self { state, focused, next_id: 1 }
+    }
+}
+
+impl Panes {
+    pub fn state(&self) -> &pane_grid::State<EditorPane> {
+        &self.state
+    }
+
+    pub fn focused(&self) -> pane_grid::Pane {
+        self.focused
+    }
+
+    pub fn focused_pane(&self) -> &EditorPane {
+        &self.state.panes[&self.focused]
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /**
+     * Splits the focused pane in two along the axis. The new pane shows the same document from the same place and
+     * takes the focus.
+     */
+    pub fn split(&mut self, axis: pane_grid::Axis, active: &mut Option<Textbox>) -> bool {
+        let Some(text_box) = active.as_ref().map(Textbox::split) else {
+            return false
+        };
+        let pane = EditorPane { id: self.next_id, text_box: Some(text_box) };
+        let Some((pane, _)) = self.state.split(axis, &self.focused, pane) else {
+            return false
+        };
+        self.next_id += 1;
+        self.focus(pane, active)
+    }
+
+    /**
+     * Focuses the pane, its textbox becomes the active one. The keyboard stays with the textboxes if it was there.
+     *
+     * Returns false if the pane is already focused or doesn't exist.
+     */
+    pub fn focus(&mut self, pane: pane_grid::Pane, active: &mut Option<Textbox>) -> bool {
+        if pane == self.focused || !self.state.panes.contains_key(&pane) {
+            return false
+        }
+        let has_keyboard = active.as_ref().is_some_and(Textbox::is_focused);
+        if let Some(text_box) = active.as_mut() {
+            text_box.set_focus(false);
+        }
+        let previous = std::mem::replace(active, self.state.panes.get_mut(&pane).and_then(|pane| pane.text_box.take()));
+        if let Some(pane) = self.state.panes.get_mut(&self.focused) {
+            pane.text_box = previous;
+        }
+        if let Some(text_box) = active.as_mut() {
+            text_box.set_focus(has_keyboard);
+        }
+        self.focused = pane;
+        true
+    }
+
+    /**
+     * Returns the pane at the index, counting the panes from the top left corner as they are read.
+     */
+    pub fn nth(&self, index: usize) -> Option<pane_grid::Pane> {
+        let regions = self.state.layout().pane_regions(0.0, Size::new(1.0, 1.0));
+        let mut panes: Vec<_> = regions.into_iter().collect();
+        panes.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+        panes.get(index).map(|&(pane, _)| pane)
+    }
+
+    /**
+     * Closes the focused pane, the pane next to it takes the focus. The last pane isn't closed.
+     */
+    pub fn close(&mut self, active: &mut Option<Textbox>) -> bool {
+        if self.state.len() < 2 {
+            return false
+        }
+        let Some((_, sibling)) = self.state.close(&self.focused) else {
+            return false
+        };
+        let has_keyboard = active.as_ref().is_some_and(Textbox::is_focused);
+        *active = self.state.panes.get_mut(&sibling).and_then(|pane| pane.text_box.take());
+        if let Some(text_box) = active.as_mut() {
+            text_box.set_focus(has_keyboard);
+        }
+        self.focused = sibling;
+        true
+    }
+
+    pub fn resize(&mut self, event: pane_grid::ResizeEvent) {
+        self.state.resize(&event.split, event.ratio);
+    }
+
+    pub fn get(&self, pane: pane_grid::Pane) -> Option<&EditorPane> {
+        self.state.get(&pane)
+    }
+
+    /**
+     * Returns the textbox of the pane, unless it's the focused pane.
+     */
+    pub fn unfocused_mut(&mut self, pane: pane_grid::Pane) -> Option<&mut Textbox> {
+        self.state.get_mut(&pane)?.text_box.as_mut()
+    }
+
+    /**
+     * Brings the textboxes of every pane up to date with the edits made in the other panes.
+     */
+    pub fn sync(&mut self, active: &mut Option<Textbox>) {
+        for text_box in self.unfocused().chain(active.as_mut()) {
+            text_box.sync();
+        }
+    }
+
+    /**
+     * Returns the textboxes of the panes that aren't focused.
+     */
+    pub fn unfocused(&mut self) -> impl Iterator<Item = &mut Textbox> {
+        self.state.panes.values_mut().filter_map(|pane| pane.text_box.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod panes_tests {
+    use iced::widget::pane_grid::Axis;
+    use pretty_assertions::assert_eq;
+
+    use crate::core::{buffer::Buffer, document::Document, position::Position};
+    use crate::widgets::textbox::Textbox;
+
+    use super::Panes;
+
+    fn text(text_box: &Option<Textbox>) -> String {
+        text_box.as_ref().map(|text_box| text_box.buffer().get_string()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_split_panes_share_the_document() {
+        let mut panes = Panes::default();
+        let mut active = Some(Textbox::new(Buffer::new(Document::recovered(None, "one\ntwo\nthree"), None)));
+        active.as_mut().unwrap().set_focus(true);
+        let first = panes.focused();
+
+        assert!(panes.split(Axis::Vertical, &mut active));
+        assert_eq!(panes.len(), 2);
+        let second = panes.focused();
+        assert!(active.as_ref().unwrap().is_focused());
+
+        // An edit in the second pane shows in the first, which keeps its own cursor
+        let text_box = active.as_mut().unwrap();
+        text_box.set_curor(Position::new(2, 5)).set_selection(Position::new(2, 5));
+        text_box.insert("!".to_owned());
+        text_box.seal_history();
+        assert_eq!(panes.nth(0), Some(first));
+        assert_eq!(panes.nth(1), Some(second));
+        assert!(panes.focus(first, &mut active));
+        panes.sync(&mut active);
+        assert_eq!(text(&active), "one\ntwo\nthree!");
+        assert_eq!(active.as_ref().unwrap().buffer().cursor.0, Position::new(0, 0));
+
+        // The history is shared too, the edit is undone from the first pane
+        active.as_mut().unwrap().undo();
+        assert!(panes.focus(second, &mut active));
+        assert_eq!(text(&active), "one\ntwo\nthree");
+
+        assert!(panes.close(&mut active));
+        assert_eq!(panes.len(), 1);
+        assert_eq!(panes.focused(), first);
+        assert!(!panes.close(&mut active));
+        assert_eq!(text(&active), "one\ntwo\nthree");
+    }
+}
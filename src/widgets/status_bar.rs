@@ -0,0 +1,76 @@
+use encoding_rs::Encoding;
+use iced::{widget::{text, container, button, horizontal_space, Row}, Element, Length, Padding, Pixels};
+use iced_style::theme;
+
+use crate::{core::{file_format::FileFormat, position::Position, session::Settings}, styles::{button::MenuButton, container::MenuContainer}, Message};
+
+/**
+ * The changes the status bar makes to how the open file is saved and shown.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatAction {
+    PickEncoding,
+    /// Opens the file again, reading it with the encoding
+    ReopenWith(&'static Encoding),
+    /// Saves the file with the encoding from now on
+    SaveWith(&'static Encoding),
+    ToggleBom,
+    ToggleLineEnding,
+    ToggleFinalNewline,
+    ToggleTrimWhitespace,
+    ToggleInsertFinalNewline,
+    /// Wraps long lines at the window, at a column, or not at all
+    CycleWrap,
+    /// Colors the brackets by how deep they are nested
+    ToggleRainbowBrackets,
+    /// Switches between the default keys, Vim and Emacs
+    CycleKeymap,
+}
+
+fn status_button(label: String, action: Option<FormatAction>) -> Element<'static, Message> {
+    let mut button = button(text(label).size(Pixels::from(12.0)))
+        .style(theme::Button::Custom(Box::new(MenuButton)))
+        .padding(Padding::from([3, 8]));
+    if let Some(action) = action {
+        button = button.on_press(Message::Format(action));
+    }
+    button.into()
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "On" } else { "Off" }
+}
+
+/**
+ * Shows the cursor, the format of the open file and the settings. Clicking an entry changes it. The state of the
+ * keymap, like the mode of Vim, is shown first.
+ */
+pub fn status_bar(cursor: Position, format: FileFormat, has_final_newline: bool, settings: Settings, keymap_status: String) -> Element<'static, Message> {
+    let options = settings.save_options;
+    let bom_action = format.can_have_bom().then_some(FormatAction::ToggleBom);
+    let bom = if format.has_bom { "BOM" } else { "No BOM" };
+    let final_newline = if has_final_newline { "Final newline" } else { "No final newline" };
+    let mut content = Row::new();
+    if !keymap_status.is_empty() {
+        content = content.push(status_button(keymap_status, None));
+    }
+    let content = content
+        .push(status_button(format!("Ln {}, Col {}", cursor.line() + 1, cursor.character() + 1), None))
+        .push(horizontal_space(Length::Fill))
+        .push(status_button(settings.keymap.label().to_owned(), Some(FormatAction::CycleKeymap)))
+        .push(status_button(settings.wrap.label(), Some(FormatAction::CycleWrap)))
+        .push(status_button(format!("Rainbow brackets: {}", on_off(settings.rainbow_brackets)), Some(FormatAction::ToggleRainbowBrackets)))
+        .push(status_button(format!("Trim on save: {}", on_off(options.trim_trailing_whitespace)), Some(FormatAction::ToggleTrimWhitespace)))
+        .push(status_button(format!("Newline on save: {}", on_off(options.insert_final_newline)), Some(FormatAction::ToggleInsertFinalNewline)))
+        .push(status_button(final_newline.to_owned(), Some(FormatAction::ToggleFinalNewline)))
+        .push(status_button(format.line_ending.label().to_owned(), Some(FormatAction::ToggleLineEnding)))
+        .push(status_button(bom.to_owned(), bom_action))
+        .push(status_button(format.encoding.name().to_owned(), Some(FormatAction::PickEncoding)))
+    .padding(Padding::from([0, 10]));
+
+    let theme = Box::new(MenuContainer);
+    container(content)
+    .style(theme::Container::Custom(theme))
+    .width(Length::Fill)
+    .into()
+}
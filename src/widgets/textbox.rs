@@ -7,7 +7,7 @@ use iced::advanced::text::Renderer as _;
 use iced::mouse;
 use iced::widget::canvas;
 use iced::widget::canvas::Cache;
-use iced::widget::canvas::Text;
+use iced::widget::canvas::{Path, Stroke, Text};
 use iced::widget::text::Shaping;
 use iced::widget::Canvas;
 use iced::Font;
@@ -16,9 +16,21 @@ use iced::Size;
 
 use iced::{Color, Element, Length, Point, Rectangle, Renderer, Theme};
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::core::buffer::Buffer;
 use crate::core::buffer::TextInfo;
+use crate::core::clipboard::Clip;
+use crate::core::display_map::{VisualRow, WrapMode};
+use crate::core::document::{grapheme_width, SaveJob};
 use crate::core::document_change::DocumentChange;
+use crate::core::folding::FoldRange;
+use crate::core::motion::WordKind;
+use crate::core::emacs::{Emacs, EmacsKey};
+use crate::core::keymap::KeymapRequest;
+use crate::core::vim::{Vim, VimKey};
+use crate::core::error::DocumentResult;
+use crate::core::file_format::{FileFormat, SaveOptions};
 use crate::core::position::Cursor;
 use crate::core::position::Position;
 use crate::highlighter::HighlightItem;
@@ -30,21 +42,28 @@ use crate::VirtualWindow;
 use crate::highlighter::color_selector::ColorSelector;
 
 use crate::lsp::response::LspResponse;
+use crate::lsp::encoding::PositionEncoding;
 
 use super::floating_text::floating_text_element::FloatingElement;
 use super::floating_text::floating_text_element::FloatingText;
 
 
+/**
+ * The narrowest rows lines are wrapped in, so a narrow window doesn't break the text in a column of graphemes.
+ */
+const MIN_WRAP_WIDTH: usize = 20;
+
 pub struct Textbox {
     buffer: Buffer,
     line_height: f32,
     font_size: f32,
     text_cache: Cache,
     font: Font,
-    longest_line: Cell<usize>,
     text_width: Cell<f32>,
     floating_element: Option<FloatingElement>,
     is_focused: bool,
+    /// Colors the brackets by how deep they are nested.
+    rainbow_brackets: bool,
 }
 
 impl Textbox {
@@ -55,15 +74,38 @@ impl Textbox {
             line_height: height.0,
             font_size: 14.0,
             font: Font::MONOSPACE,
-            longest_line: Cell::new(0),
             text_width: Cell::new(0.0),
             buffer: document,
             is_focused: false,
+            rainbow_brackets: false,
             // floating_element: Some(FloatingText::Diagnostic("Something\n".repeat(20).to_owned()))
             floating_element: None
         }
     }
 
+    /**
+     * Returns a textbox for a new pane, showing the same document from the same place.
+     */
+    pub fn split(&self) -> Self {
+        Self {
+            buffer: self.buffer.split(),
+            text_cache: Cache::new(),
+            text_width: Cell::new(self.text_width.get()),
+            floating_element: None,
+            is_focused: false,
+            ..*self
+        }
+    }
+
+    /**
+     * Catches up with the edits made to the document from another pane.
+     */
+    pub fn sync(&mut self) {
+        if self.buffer.sync() {
+            self.clear();
+        }
+    }
+
     pub fn is_focused(&self) -> bool {
         self.is_focused
     }
@@ -72,6 +114,16 @@ impl Textbox {
         self.is_focused = focus
     }
 
+    /**
+     * Turns the coloring of the brackets by their depth on or off.
+     */
+    pub fn set_rainbow_brackets(&mut self, rainbow_brackets: bool) {
+        if self.rainbow_brackets != rainbow_brackets {
+            self.rainbow_brackets = rainbow_brackets;
+            self.clear();
+        }
+    }
+
     pub fn move_selection_with_shift(&mut self, modifier: Modifiers) {
         if modifier.shift {
             self.set_selection_end(self.buffer.cursor.0);
@@ -105,13 +157,152 @@ impl Textbox {
         self.clear_floating_elements();
     }
 
-    pub fn move_start(&mut self) {
-        self.buffer.cursor.0.set_character(0);
+    pub fn move_start(&mut self, modifier: Modifiers) {
+        if modifier.ctrl {
+            self.buffer.move_to_document_start();
+        } else {
+            self.buffer.move_to_row_start();
+        }
+        self.move_selection_with_shift(modifier);
+        self.clear_floating_elements();
+    }
+
+    pub fn move_end(&mut self, modifier: Modifiers) {
+        if modifier.ctrl {
+            self.buffer.move_to_document_end();
+        } else {
+            self.buffer.move_to_row_end();
+        }
+        self.move_selection_with_shift(modifier);
+        self.clear_floating_elements();
+    }
+
+    /**
+     * Moves the cursor by a word, the selection follows if shift is held.
+     */
+    pub fn move_word(&mut self, forward: bool, kind: WordKind, modifier: Modifiers) {
+        self.buffer.move_word(forward, kind);
+        self.move_selection_with_shift(modifier);
+        self.clear_floating_elements();
+    }
+
+    pub fn delete_word(&mut self, forward: bool, kind: WordKind) {
+        self.buffer.delete_word(forward, kind);
+        self.clear_floating_elements();
+    }
+
+    /**
+     * Moves the cursor to the blank line after or before the paragraph, the selection follows if shift is held.
+     */
+    pub fn move_paragraph(&mut self, forward: bool, modifier: Modifiers) {
+        self.buffer.move_paragraph(forward);
+        self.move_selection_with_shift(modifier);
+        self.clear_floating_elements();
+    }
+
+    /**
+     * Selects the word at the position, from a double click.
+     */
+    pub fn select_word(&mut self, position: Position) {
+        self.buffer.select_word(position);
+        self.clear();
+    }
+
+    /**
+     * Selects the line at the position, from a triple click.
+     */
+    pub fn select_line(&mut self, position: Position) {
+        self.buffer.select_line(position.line());
+        self.clear();
+    }
+
+    /**
+     * Wraps the lines the way the mode asks for. The width of the window is only known once the text was drawn.
+     */
+    pub fn set_wrap(&mut self, mode: WrapMode) {
+        let width = match mode {
+            WrapMode::Off => None,
+            WrapMode::Column(column) => Some(column.max(MIN_WRAP_WIDTH)),
+            WrapMode::Window if self.text_width.get() > 0.0 && self.buffer.window.width() > 0.0 => {
+                let cells = (self.buffer.window.width() / self.text_width.get()).floor() as usize;
+                // A cell is left for the cursor after the last grapheme
+                Some(cells.saturating_sub(1).max(MIN_WRAP_WIDTH))
+            }
+            WrapMode::Window => None,
+        };
+        if self.buffer.set_wrap_width(width) {
+            self.clear();
+        }
+    }
+
+    /**
+     * Grows the selection to the next node of the syntax tree. Returns false if the tree can't grow it.
+     */
+    pub fn expand_selection(&mut self) -> bool {
+        let expanded = self.buffer.expand_selection();
+        self.clear();
+        expanded
+    }
+
+    /**
+     * Grows the selection to one of the ranges sent by a language server, when the syntax tree couldn't.
+     */
+    pub fn expand_to_server_ranges(&mut self, ranges: Vec<lsp_types::SelectionRange>, encoding: PositionEncoding) {
+        self.buffer.expand_to_server_ranges(ranges, encoding);
+        self.clear();
+    }
+
+    pub fn shrink_selection(&mut self) {
+        self.buffer.shrink_selection();
+        self.clear();
+    }
+
+    pub fn jump_to_matching_bracket(&mut self) {
+        self.buffer.jump_to_matching_bracket();
+        self.clear();
     }
 
-    pub fn move_end(&mut self) {
-        let line_len = self.buffer.line_len(self.buffer.cursor.0.line());
-        self.buffer.cursor.0.set_character(line_len);
+    /**
+     * Folds the range that starts on the line, or unfolds it, from the toggles of the gutter.
+     */
+    pub fn toggle_fold(&mut self, line: usize) {
+        self.buffer.toggle_fold(line);
+        self.clear();
+    }
+
+    pub fn fold(&mut self) {
+        self.buffer.fold_at_cursor();
+        self.clear();
+    }
+
+    pub fn unfold(&mut self) {
+        self.buffer.unfold_at_cursor();
+        self.clear();
+    }
+
+    pub fn fold_all(&mut self) {
+        self.buffer.fold_all();
+        self.clear();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.buffer.unfold_all();
+        self.clear();
+    }
+
+    /**
+     * Unfolds the ranges that hide the cursor.
+     */
+    pub fn reveal_cursor(&mut self) {
+        self.buffer.reveal_cursor();
+    }
+
+    /**
+     * Folds the ranges a language server sent instead of the ones of the syntax tree.
+     */
+    pub fn set_server_folds(&mut self, ranges: Vec<FoldRange>) {
+        self.buffer.set_server_folds(ranges);
+        self.clear();
     }
 
     pub fn page_up(&mut self) {
@@ -128,36 +319,131 @@ impl Textbox {
     }
 
     pub fn backspace(&mut self) {
-        if self.buffer.cursor.0.character() == 0 && self.buffer.cursor.0.line() == 0 && self.buffer.selection.is_empty() {
-            return
-        }
-        self.buffer.move_previous(1, &self.text_info());
-        self.buffer.delete();
+        self.buffer.backspace();
         self.clear_floating_elements()
     }
 
     pub fn new_line(&mut self) {
-        self.buffer.get_position();
+        self.buffer.new_line();
+        self.clear_floating_elements()
+    }
 
-        self.buffer.insert('\n'.to_string());
-        self.buffer.move_next(2, &self.text_info());
+    /**
+     * Indents the selected lines, or the cursor up to the next level. Outdents them if shift is held.
+     */
+    pub fn indent(&mut self, modifiers: Modifiers) {
+        if modifiers.shift {
+            self.buffer.outdent();
+        } else {
+            self.buffer.indent();
+        }
         self.clear_floating_elements()
     }
 
-    pub fn save(&mut self, workspace: Option<String>) {
-        self.buffer.save(workspace);
+    /**
+     * Ends the command that made the last edits, they're undone together.
+     */
+    pub fn seal_history(&mut self) {
+        self.buffer.seal_history();
     }
 
-    pub fn copy(&mut self, commands: &mut Vec<Command<Message>>) {
-        self.buffer.copy(commands);
+    pub fn undo(&mut self) {
+        if self.buffer.undo() {
+            self.clear();
+        }
+        self.clear_floating_elements()
+    }
+
+    pub fn redo(&mut self) {
+        if self.buffer.redo() {
+            self.clear();
+        }
+        self.clear_floating_elements()
+    }
+
+    /**
+     * Runs one of the commands that edit whole lines.
+     */
+    pub fn edit_lines(&mut self, command: LineCommand) {
+        match command {
+            LineCommand::MoveUp => self.buffer.move_lines(true),
+            LineCommand::MoveDown => self.buffer.move_lines(false),
+            LineCommand::Duplicate => self.buffer.duplicate_lines(),
+            LineCommand::Delete => self.buffer.delete_lines(),
+            LineCommand::Join => self.buffer.join_lines(),
+            LineCommand::Sort => self.buffer.sort_lines(),
+            LineCommand::Reverse => self.buffer.reverse_lines(),
+            LineCommand::ToggleLineComment => self.buffer.toggle_line_comment(),
+            LineCommand::ToggleBlockComment => self.buffer.toggle_block_comment(),
+        }
+        self.clear();
+        self.clear_floating_elements()
+    }
+
+    pub fn save_job(&mut self, workspace: Option<String>, options: SaveOptions) -> DocumentResult<SaveJob> {
+        let job = self.buffer.save_job(workspace, options);
+        self.clear();
+        job
+    }
+
+    pub fn saved(&mut self, filename: &str, version: i32) {
+        self.buffer.saved(filename, version);
+    }
+
+    pub fn set_format(&mut self, format: FileFormat) {
+        self.buffer.set_format(format);
+    }
+
+    pub fn set_final_newline(&mut self, has_final_newline: bool) {
+        self.buffer.set_final_newline(has_final_newline);
+        self.clear();
+    }
+
+    pub fn copy(&mut self, commands: &mut Vec<Command<Message>>) -> Clip {
+        self.buffer.copy(commands)
     }
 
     pub fn paste(&mut self, commands: &mut Vec<Command<Message>>) {
         self.buffer.paste(commands);
     }
 
-    pub fn cut(&mut self, commands: &mut Vec<Command<Message>>) {
-        self.buffer.cut(commands);
+    pub fn cut(&mut self, commands: &mut Vec<Command<Message>>) -> Clip {
+        let clip = self.buffer.cut(commands);
+        self.clear_floating_elements();
+        clip
+    }
+
+    /**
+     * Pastes the text at the cursor, or above its line if it's lines copied without a selection and nothing is
+     * selected.
+     */
+    pub fn paste_text(&mut self, text: String, linewise: bool) {
+        if linewise && self.buffer.selection.is_empty() {
+            self.buffer.paste_lines(text);
+        } else {
+            self.buffer.insert(text);
+        }
+        self.clear_floating_elements();
+    }
+
+    /**
+     * Lets the modal editing handle the key, returns what the editor is asked to do.
+     */
+    pub fn vim(&mut self, vim: &mut Vim, key: VimKey) -> Vec<KeymapRequest> {
+        let requests = vim.handle(&mut self.buffer, key);
+        self.clear();
+        self.clear_floating_elements();
+        requests
+    }
+
+    /**
+     * Lets the bindings of Emacs handle the key, returns what the editor is asked to do.
+     */
+    pub fn emacs(&mut self, emacs: &mut Emacs, key: EmacsKey) -> Vec<KeymapRequest> {
+        let requests = emacs.handle(&mut self.buffer, key);
+        self.clear();
+        self.clear_floating_elements();
+        requests
     }
 
     pub fn select_all(&mut self, _command: &mut [Command<Message>]) {
@@ -199,15 +485,44 @@ impl Textbox {
         document_change
     }
 
-    pub fn process_lsp_response(&mut self, message: LspResponse) {
+    pub fn process_lsp_response(&mut self, server_id: usize, encoding: PositionEncoding, message: LspResponse) {
         match message {
-            LspResponse::Diagnostics(diagnostic) => self.buffer.add_diagnostics(diagnostic),
+            LspResponse::Diagnostics(diagnostic) => self.buffer.add_diagnostics(server_id, diagnostic, encoding),
             LspResponse::Progress => println!("Progress!!"),
             _ => ()
         }
     }
 
-    pub fn file(&self) -> Option<&String> {
+    /**
+     * Applies the edits a server asked for, like the ones made before a file is renamed.
+     */
+    pub fn apply_edits(&mut self, edits: Vec<lsp_types::TextEdit>, encoding: PositionEncoding) {
+        self.buffer.apply_edits(edits, encoding);
+        self.clear();
+    }
+
+    /**
+     * Sets the file the buffer is saved to, once its file was renamed or moved.
+     */
+    pub fn set_file(&mut self, filename: &str) {
+        self.buffer.set_file(filename);
+    }
+
+    /**
+     * Returns the edits that haven't been sent to the language servers yet.
+     */
+    pub fn take_changes(&mut self) -> Vec<DocumentChange> {
+        self.buffer.take_changes()
+    }
+
+    /**
+     * Drops the diagnostics of a server that disconnected.
+     */
+    pub fn remove_diagnostics(&mut self, server_id: usize) {
+        self.buffer.remove_diagnostics(server_id)
+    }
+
+    pub fn file(&self) -> Option<String> {
         self.buffer.filename()
     }
 
@@ -216,7 +531,7 @@ impl Textbox {
     }
 
     pub fn longest_line(&self) -> usize {
-        self.longest_line.get()
+        self.buffer.longest_line()
     }
 
     pub fn get_font_size(&self) -> f32 {
@@ -229,7 +544,7 @@ impl Textbox {
 
     pub fn text_info(&self) -> TextInfo {
         TextInfo { 
-            longest_line: self.longest_line.get(), 
+            longest_line: self.buffer.longest_line(),
             text_width: self.text_width.get()
         }
     }
@@ -256,7 +571,7 @@ impl Textbox {
     }
 
     pub fn height(&self) -> f32 {
-        let text_height = self.line_height * self.buffer.len() as f32;
+        let text_height = self.line_height * self.buffer.row_count() as f32;
         if text_height >= 1980.0 {
             text_height
         } else {
@@ -310,57 +625,63 @@ impl Textbox {
     }
 
     pub fn get_window_point_from_position(&self, position: Position) -> Point{
-        let x = position.character() as f32 * self.text_width.get();
-        let y = position.line() as f32 * self.line_height;
+        let (row, cell) = self.buffer.display().to_display(&self.buffer.buffer(), position);
+        let x = cell as f32 * self.text_width.get();
+        let y = row as f32 * self.line_height;
         Point { x, y }
     }
 
-    fn draw_line(
+    /**
+     * Draws the part of the line on the row as runs of text that share a color, along with the cursor, the
+     * selection and the diagnostics under it.
+     *
+     * Every grapheme sits on the grid of cells the columns are mapped to. Wide graphemes are drawn on their own so
+     * the text after them stays on the grid even if the font draws them a bit narrower or wider than two cells.
+     */
+    fn draw_row(
         &self,
         frame: &mut iced::widget::canvas::Frame,
-        line_number: usize,
-        text_width: f32,
+        row_index: usize,
+        row: VisualRow,
         content: &str,
         highlighter: &mut IntoIter<HighlightItem>,
         highlight_item: &mut Option<HighlightItem>,
     ) {
-        let y = self.line_height * line_number as f32;
+        let y = self.line_height * row_index as f32;
         if !self.buffer.window.within(y) {
             return;
         }
 
-        if self.buffer.cursor.0.line() == line_number && self.buffer.cursor.0.character() == content.len() {
-            let point = Point::new(text_width * content.len() as f32, y);
-            frame.fill_rectangle(
-                point,
-                // Size::new(text_width, self.height),
-                Size::new(2.0, self.line_height),
-                Color::from_rgba(83.0, 83.0, 83.0, 0.2),
-            )
-        }
-        for (c_index, c) in content.chars().enumerate() {
-            let x = text_width * c_index as f32;
-            let point = Point::new(x, y);
+        let line_number = row.line;
+        let text_width = self.text_width.get();
+        let cursor = self.buffer.cursor.0;
+        let selection = self.buffer.selection.correct_position();
+        let color_selector = ColorSelector;
+        let mut run = TextRun::default();
+        let mut cell = row.indent;
+        let mut column = row.start;
+        for (byte, grapheme) in content.grapheme_indices(true).skip(row.start) {
+            if grapheme == "\n" || column >= row.end {
+                break
+            }
+            let width = grapheme_width(grapheme);
+            let point = Point::new(text_width * cell as f32, y);
+            let pos = Position::new(line_number, column);
 
             // Draw Cursor
-            if self.buffer.cursor.0.line() == line_number && self.buffer.cursor.0.character() == c_index {
+            if cursor.line() == line_number && cursor.character() == column {
                 frame.fill_rectangle(
                     point,
-                    // Size::new(text_width, self.height),
                     Size::new(2.0, self.line_height),
                     Color::from_rgba(83.0, 83.0, 83.0, 0.2),
                 )
             }
 
             // Draw selection
-            let pos = Position::new( 
-                line_number,
-                c_index,
-            );
-            if self.buffer.selection.correct_position().is_within(&pos) && !self.buffer.selection.is_empty() {
+            if selection.is_within(&pos) && !self.buffer.selection.is_empty() {
                 frame.fill_rectangle(
                     point,
-                    Size::new(text_width, self.line_height),
+                    Size::new(text_width * width as f32, self.line_height),
                     Color::from_rgba(83.0, 83.0, 83.0, 0.1),
                 )
             }
@@ -369,29 +690,120 @@ impl Textbox {
             if let Some(issue) = self.buffer.diagnostic_are_in_position(pos) {
                 frame.fill_rectangle(
                     Point { x: point.x, y: point.y + self.line_height - 4.0 },
-                    Size::new(text_width, 3.0),
+                    Size::new(text_width * width as f32, 3.0),
                     issue.severity.color(),
                 )
             }
-            
-            let color_selector = ColorSelector;
-
-            let text = Text {
-                position: point,
-                font: self.font,
-                content: String::from(c),
-                size: self.font_size,
-
-                // Check if the highlighted item is in the range of the current function..
-                // If so, set the color of the text to the color.
-                // If not, get the next value and set the current item to another value
-                color: color_selector.select(highlighter, highlight_item, &pos),
-                ..Text::default()
-            };
-
-            frame.fill_text(text);
+
+            // The highlighted items are placed by tree-sitter, which counts the columns in bytes
+            let color = color_selector.select(highlighter, highlight_item, &Position::new(line_number, byte));
+            if !run.continues_with(color, width) {
+                self.fill_run(frame, std::mem::take(&mut run));
+                run = TextRun { content: String::new(), point, color, is_wide: width > 1 };
+            }
+            run.content.push_str(grapheme);
+
+            cell += width;
+            column += 1;
+        }
+
+        // A wrapped row ends where the next one starts, the cursor there is drawn on the next row
+        if cursor.line() == line_number && cursor.character() >= column && column < row.end {
+            frame.fill_rectangle(
+                Point::new(text_width * cell as f32, y),
+                Size::new(2.0, self.line_height),
+                Color::from_rgba(83.0, 83.0, 83.0, 0.2),
+            )
+        }
+        self.fill_run(frame, run);
+
+        // The lines folded away are shown as a placeholder after the line that starts the fold
+        if row.end == usize::MAX && self.buffer.is_folded(line_number) {
+            let x = text_width * (cell + 1) as f32;
+            frame.fill_rectangle(
+                Point::new(x, y + 2.0),
+                Size::new(text_width * 3.0, self.line_height - 4.0),
+                Color::from_rgba8(153, 153, 153, 0.2),
+            );
+            self.fill_run(frame, TextRun {
+                content: "⋯".to_owned(),
+                point: Point::new(x + text_width, y),
+                color: Color::from_rgb8(153, 153, 153),
+                is_wide: false,
+            });
+        }
+    }
+
+    /**
+     * Draws a box around the bracket at the cursor and around the one that matches it.
+     */
+    fn draw_matching_brackets(&self, frame: &mut iced::widget::canvas::Frame) {
+        let Some((bracket, pair)) = self.buffer.matching_brackets() else {
+            return
+        };
+        for position in [bracket, pair] {
+            if self.buffer.display().is_hidden(position.line()) {
+                continue
+            }
+            let point = self.get_window_point_from_position(position);
+            if !self.buffer.window.within(point.y) {
+                continue
+            }
+            frame.stroke(
+                &Path::rectangle(point, Size::new(self.text_width.get(), self.line_height)),
+                Stroke::default().with_width(1.0).with_color(Color::from_rgb8(153, 153, 153)),
+            );
         }
     }
+
+    fn fill_run(&self, frame: &mut iced::widget::canvas::Frame, run: TextRun) {
+        if run.content.is_empty() {
+            return
+        }
+        frame.fill_text(Text {
+            content: run.content,
+            position: run.point,
+            color: run.color,
+            font: self.font,
+            size: self.font_size,
+            // Shapes the whole run, so ligatures and combining marks are drawn the way the font wants them
+            shaping: Shaping::Advanced,
+            ..Text::default()
+        });
+    }
+}
+
+/**
+ * The commands that edit the lines of the selection, or the line of the cursor.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCommand {
+    MoveUp,
+    MoveDown,
+    Duplicate,
+    Delete,
+    Join,
+    Sort,
+    Reverse,
+    ToggleLineComment,
+    ToggleBlockComment,
+}
+
+/**
+ * Graphemes of a line that are drawn with one text.
+ */
+#[derive(Default)]
+struct TextRun {
+    content: String,
+    point: Point,
+    color: Color,
+    is_wide: bool,
+}
+
+impl TextRun {
+    fn continues_with(&self, color: Color, width: usize) -> bool {
+        !self.content.is_empty() && self.color == color && !self.is_wide && width == 1
+    }
 }
 
 
@@ -424,31 +836,30 @@ where
         _cursor: iced::advanced::mouse::Cursor,
     ) -> Vec<<Renderer as canvas::Renderer>::Geometry> {
         let result = self.text_cache.draw(renderer, bounds.size(), |frame| {
-            let mut longest_line = 0;
-            let mut highlighter = self.buffer.get_highlighter().clone().unwrap().captures.into_iter();
+            let captures = self.buffer.get_highlighter(self.rainbow_brackets).map(|highlighter| highlighter.captures).unwrap_or_default();
+            let mut highlighter = captures.into_iter();
             let mut highlight_item = highlighter.next();
-            for (index, line) in self.buffer.lines().enumerate() {
-                let width = if self.text_width.get() == 0.0 {
-                    let width = renderer.measure_width("T", self.font_size, Font::MONOSPACE, Shaping::Basic);
-                    self.text_width.set(width);
-                    width
-                } else {
-                    self.text_width.get()
+            if self.text_width.get() == 0.0 {
+                let width = renderer.measure_width("T", self.font_size, Font::MONOSPACE, Shaping::Basic);
+                self.text_width.set(width);
+            }
+            // Only the rows around the window are read from the document
+            let document = self.buffer.buffer();
+            for index in self.buffer.visible_rows() {
+                let row = self.buffer.display().row(index);
+                let Some(line) = document.get_line(row.line) else {
+                    break
                 };
-
-                if line.len_chars() > longest_line {
-                    longest_line = line.len_chars()
-                }
-                self.draw_line(
+                self.draw_row(
                     frame,
                     index,
-                    width,
+                    row,
                     &line.to_string(),
                     &mut highlighter,
                     &mut highlight_item,
                 )
             }
-            self.longest_line.set(longest_line);
+            self.draw_matching_brackets(frame);
         });
         vec![result]
     }
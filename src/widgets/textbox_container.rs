@@ -7,7 +7,7 @@ use iced::{
     event, touch, Alignment, Element, Event, Font, Padding, Pixels, Point, overlay,
 };
 
-use crate::{core::position::CursorMessage, core::{position::Position, document::Document}};
+use crate::{core::position::CursorMessage, core::{position::Position, display_map::DisplayMap, document::Document}};
 
 
 use super::{textbox::Textbox, floating_text::floating_overlay::FloatingOverlay, view_port::ViewPortMessage};
@@ -53,7 +53,9 @@ where
 }
 #[derive(Default)]
 struct State {
-    dragging: bool
+    dragging: bool,
+    /// The last click, a click that follows it quickly at the same place selects a word or a line.
+    last_click: Option<mouse::Click>,
 }
 
 impl<'a, Message, Renderer> Widget<Message, Renderer> for TextboxContainer<'a, Message, Renderer>
@@ -196,8 +198,14 @@ where
                     
                     let x = cursor.position().unwrap().x - bounds.x;
                     let point = Point::new(x, cursor.position().unwrap().y - bounds.y);
-                    let cursor = line_hit_test(self.textbox.buffer().buffer(), self.line_height, text_width, point);
-                    shell.publish(Message::from_cursor_position(cursor));
+                    let cursor = line_hit_test(self.textbox.buffer().display(), &self.textbox.buffer().buffer(), self.line_height, text_width, point);
+                    let click = mouse::Click::new(point, state.last_click);
+                    state.last_click = Some(click);
+                    match click.kind() {
+                        mouse::click::Kind::Single => shell.publish(Message::from_cursor_position(cursor)),
+                        mouse::click::Kind::Double => shell.publish(Message::from_word_click(cursor)),
+                        mouse::click::Kind::Triple => shell.publish(Message::from_line_click(cursor)),
+                    }
                     shell.publish(Message::set_textbox_focus(true));
                 } else {
                     shell.publish(Message::set_textbox_focus(false));
@@ -223,7 +231,7 @@ where
                             self.font.into(),
                             text::Shaping::Basic,
                         );
-                        let cursor = line_hit_test(self.textbox.buffer().buffer(), self.line_height, text_width, point);
+                        let cursor = line_hit_test(self.textbox.buffer().display(), &self.textbox.buffer().buffer(), self.line_height, text_width, point);
                         shell.publish(Message::from_selection_move(cursor))
                     }
                 }
@@ -283,25 +291,13 @@ where
     }
 }
 
-fn monospace_hit_test(text: &str, glyph_width: f32, point: Point) -> f32 {
-    let result = (point.x / glyph_width).floor();
-    if result > text.len() as f32 {
-        return text.len() as f32;
-    }
-    result
-}
-
-fn line_hit_test(text: &Document, line_height: f32, glyph_width: f32, point: Point) -> Position {
-    let mut line_num = (point.y / line_height).floor() as usize;
-
-
-    let line = text
-        .get_line(line_num)
-        .unwrap_or_else(|| {
-            line_num = text.len();
-            text.last_line().unwrap()
-        });
-    let character = monospace_hit_test(line.as_str().unwrap_or(&line.to_string()), glyph_width, point) as usize;
+/**
+ * Returns the position of the grapheme under the point, mapping the rows and cells of the grid back to lines and
+ * columns so clicks land on what is on screen when lines are wrapped or have wide characters.
+ */
+pub fn line_hit_test(display: &DisplayMap, text: &Document, line_height: f32, glyph_width: f32, point: Point) -> Position {
+    let row = (point.y / line_height).floor().max(0.0) as usize;
+    let cell = (point.x / glyph_width).floor().max(0.0) as usize;
 
-    Position::new(line_num, character)
+    display.from_display(text, row, cell)
 }